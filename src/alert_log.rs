@@ -0,0 +1,239 @@
+use crate::Symbol;
+use chrono::serde::ts_milliseconds;
+use chrono::{DateTime, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Older entries are dropped beyond this many
+const MAX_ENTRIES: usize = 200;
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum AlertKind {
+    PriceTarget,
+    VolumeSpike,
+    PriceSpike,
+    Rule,
+    Disconnection,
+}
+
+impl AlertKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            AlertKind::PriceTarget => "Price target",
+            AlertKind::VolumeSpike => "Volume spike",
+            AlertKind::PriceSpike => "Price spike",
+            AlertKind::Rule => "Rule",
+            AlertKind::Disconnection => "Disconnection",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AlertEntry {
+    pub kind: AlertKind,
+    /// `None` for alerts that aren't about any one symbol, like disconnections
+    pub symbol: Option<Symbol>,
+    pub message: String,
+    #[serde(with = "ts_milliseconds")]
+    pub time: DateTime<Utc>,
+    pub acknowledged: bool,
+}
+
+/// Every alert that fired, newest first
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct AlertLog {
+    entries: VecDeque<AlertEntry>,
+}
+
+impl AlertLog {
+    pub fn push(
+        &mut self,
+        kind: AlertKind,
+        symbol: Option<Symbol>,
+        message: impl Into<String>,
+        time: DateTime<Utc>,
+    ) {
+        self.entries.push_front(AlertEntry {
+            kind,
+            symbol,
+            message: message.into(),
+            time,
+            acknowledged: false,
+        });
+        self.entries.truncate(MAX_ENTRIES);
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &AlertEntry> {
+        self.entries.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn unacknowledged(&self) -> usize {
+        self.entries.iter().filter(|e| !e.acknowledged).count()
+    }
+
+    /// `idx` is as per `entries`
+    pub fn acknowledge(&mut self, idx: usize) {
+        if let Some(entry) = self.entries.get_mut(idx) {
+            entry.acknowledged = true;
+        }
+    }
+
+    pub fn acknowledge_all(&mut self) {
+        for entry in &mut self.entries {
+            entry.acknowledged = true;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Mutes one kind of alert, for one symbol or for none, until a given time
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct Snooze {
+    pub kind: AlertKind,
+    pub symbol: Option<Symbol>,
+    #[serde(with = "ts_milliseconds")]
+    pub until: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct Snoozes(Vec<Snooze>);
+
+impl Snoozes {
+    /// Replaces any snooze of the same alert, and drops the ones that have run out
+    pub fn snooze(
+        &mut self,
+        kind: AlertKind,
+        symbol: Option<Symbol>,
+        until: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) {
+        self.unsnooze(kind, symbol.as_ref());
+        self.0.retain(|s| s.until > now);
+        self.0.push(Snooze {
+            kind,
+            symbol,
+            until,
+        });
+    }
+
+    pub fn unsnooze(&mut self, kind: AlertKind, symbol: Option<&Symbol>) {
+        self.0
+            .retain(|s| !(s.kind == kind && s.symbol.as_ref() == symbol));
+    }
+
+    /// Until when the alert is snoozed, if it still is at `now`
+    pub fn until(
+        &self,
+        kind: AlertKind,
+        symbol: Option<&Symbol>,
+        now: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        self.0
+            .iter()
+            .find(|s| s.kind == kind && s.symbol.as_ref() == symbol && s.until > now)
+            .map(|s| s.until)
+    }
+}
+
+/// When alerts only go in the log, without notifying or making a sound
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum DoNotDisturb {
+    #[default]
+    Off,
+    /// While the alert's market is closed, or every market for alerts without a symbol
+    OutsideMarketHours,
+    /// From one hour of the day to another in the browser's time zone, past midnight
+    /// if `from` is after `to`
+    QuietHours { from: u32, to: u32 },
+}
+
+impl DoNotDisturb {
+    /// `local` is the time in the browser's time zone
+    pub fn is_quiet(self, local: impl Timelike, market_closed: impl FnOnce() -> bool) -> bool {
+        match self {
+            DoNotDisturb::Off => false,
+            DoNotDisturb::OutsideMarketHours => market_closed(),
+            DoNotDisturb::QuietHours { from, to } => {
+                let hour = local.hour();
+                if from <= to {
+                    from <= hour && hour < to
+                } else {
+                    hour >= from || hour < to
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_newest_entries() {
+        let mut log = AlertLog::default();
+        for n in 0..MAX_ENTRIES + 5 {
+            log.push(AlertKind::Disconnection, None, n.to_string(), Utc::now());
+        }
+        assert_eq!(log.entries().count(), MAX_ENTRIES);
+        assert_eq!(
+            log.entries().next().map(|e| e.message.as_str()),
+            Some((MAX_ENTRIES + 4).to_string().as_str())
+        );
+        assert_eq!(log.unacknowledged(), MAX_ENTRIES);
+        log.acknowledge(0);
+        assert_eq!(log.unacknowledged(), MAX_ENTRIES - 1);
+        log.acknowledge_all();
+        assert_eq!(log.unacknowledged(), 0);
+        log.clear();
+        assert!(log.is_empty());
+    }
+
+    #[test]
+    fn snoozes_until_the_time_is_up() {
+        let aapl = Symbol("AAPL".to_string());
+        let now = Utc::now();
+        let mut snoozes = Snoozes::default();
+        snoozes.snooze(
+            AlertKind::PriceTarget,
+            Some(aapl.clone()),
+            now + chrono::Duration::minutes(15),
+            now,
+        );
+        assert!(snoozes
+            .until(AlertKind::PriceTarget, Some(&aapl), now)
+            .is_some());
+        assert!(snoozes.until(AlertKind::Rule, Some(&aapl), now).is_none());
+        assert!(snoozes.until(AlertKind::PriceTarget, None, now).is_none());
+        let later = now + chrono::Duration::minutes(16);
+        assert!(snoozes
+            .until(AlertKind::PriceTarget, Some(&aapl), later)
+            .is_none());
+        snoozes.unsnooze(AlertKind::PriceTarget, Some(&aapl));
+        assert!(snoozes
+            .until(AlertKind::PriceTarget, Some(&aapl), now)
+            .is_none());
+    }
+
+    #[test]
+    fn keeps_quiet_during_the_schedule() {
+        let at = |hour| chrono::NaiveTime::from_hms_opt(hour, 30, 0).unwrap();
+        let overnight = DoNotDisturb::QuietHours { from: 22, to: 7 };
+        assert!(overnight.is_quiet(at(23), || false));
+        assert!(overnight.is_quiet(at(6), || false));
+        assert!(!overnight.is_quiet(at(7), || false));
+        let lunch = DoNotDisturb::QuietHours { from: 12, to: 13 };
+        assert!(lunch.is_quiet(at(12), || false));
+        assert!(!lunch.is_quiet(at(13), || false));
+        assert!(DoNotDisturb::OutsideMarketHours.is_quiet(at(12), || true));
+        assert!(!DoNotDisturb::OutsideMarketHours.is_quiet(at(12), || false));
+        assert!(!DoNotDisturb::Off.is_quiet(at(12), || true));
+    }
+}
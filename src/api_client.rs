@@ -0,0 +1,406 @@
+use crate::finnhub;
+use anyhow::Error;
+use chrono::{DateTime, Duration, Utc};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::{Rc, Weak};
+use yew::format::{Nothing, Text};
+use yew::services::fetch::{FetchTask, Request, Response, StatusCode};
+use yew::services::timeout::TimeoutTask;
+use yew::services::{FetchService, TimeoutService};
+use yew::Callback;
+
+/// Finnhub's free plan allows this many calls a minute
+pub const REQUESTS_PER_MIN: usize = 60;
+/// A request that's been rate limited this many times fails rather than going back
+/// in the queue
+const MAX_ATTEMPTS: u32 = 5;
+/// The longest we wait after being rate limited, however many times it's happened
+const MAX_BACKOFF_SECS: i64 = 60;
+/// What a request fails with when finnhub doesn't accept the token
+pub const INVALID_API_KEY: &str = "Invalid API key";
+/// What a request fails with when the key's plan doesn't cover the endpoint
+pub const NOT_ENTITLED: &str = "Not entitled on your plan";
+
+/// How long a response to `path` can be reused for. Prices go stale too quickly to
+/// cache. Profiles, listings and FX rates are kept by `MetadataCache` instead, which
+/// has to get a fresh answer when it's refreshed
+fn cache_ttl(path: &str) -> Option<Duration> {
+    let endpoint = path.split('?').next().unwrap_or(path);
+    match endpoint {
+        "/calendar/earnings" => Some(Duration::minutes(30)),
+        "/company-news" => Some(Duration::minutes(4)),
+        _ => None,
+    }
+}
+
+/// Spaces requests out to at most `per_min` in any minute, and holds everything off
+/// for a while after finnhub says we've gone over anyway
+#[derive(Debug)]
+pub struct RateLimiter {
+    per_min: usize,
+    /// When the requests of the last minute went out, oldest first
+    sent: VecDeque<DateTime<Utc>>,
+    backoff_until: Option<DateTime<Utc>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_min: usize) -> RateLimiter {
+        RateLimiter {
+            per_min,
+            sent: VecDeque::new(),
+            backoff_until: None,
+        }
+    }
+
+    /// When the next request may go out; `now` if it can go straight away
+    pub fn next_slot(&mut self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let window_start = now - Duration::minutes(1);
+        while self.sent.front().is_some_and(|sent| *sent <= window_start) {
+            self.sent.pop_front();
+        }
+        let by_rate = if self.sent.len() < self.per_min {
+            now
+        } else {
+            self.sent[self.sent.len() - self.per_min] + Duration::minutes(1)
+        };
+        match self.backoff_until {
+            Some(until) if until > by_rate => until,
+            _ => by_rate,
+        }
+    }
+
+    pub fn record(&mut self, now: DateTime<Utc>) {
+        self.sent.push_back(now);
+    }
+
+    /// Backs off for longer the more attempts a request has had, up to a minute
+    pub fn back_off(&mut self, attempts: u32, now: DateTime<Utc>) {
+        let secs = 2i64.pow(attempts.min(6)).min(MAX_BACKOFF_SECS);
+        self.backoff_until = Some(now + Duration::seconds(secs));
+    }
+
+    pub fn sent_in_last_min(&self, now: DateTime<Utc>) -> usize {
+        let window_start = now - Duration::minutes(1);
+        self.sent
+            .iter()
+            .filter(|sent| **sent > window_start)
+            .count()
+    }
+}
+
+/// Response bodies by path, for endpoints whose answers are good for a while
+#[derive(Default, Debug)]
+pub struct ResponseCache {
+    entries: HashMap<String, (DateTime<Utc>, String)>,
+}
+
+impl ResponseCache {
+    pub fn get(&self, path: &str, now: DateTime<Utc>) -> Option<&str> {
+        let ttl = cache_ttl(path)?;
+        self.entries
+            .get(path)
+            .filter(|(fetched_at, _)| now - *fetched_at < ttl)
+            .map(|(_, body)| body.as_str())
+    }
+
+    pub fn insert(&mut self, path: &str, body: String, now: DateTime<Utc>) {
+        if cache_ttl(path).is_some() {
+            self.entries.insert(path.to_string(), (now, body));
+        }
+    }
+}
+
+struct Queued {
+    id: u64,
+    path: String,
+    token: String,
+    attempts: u32,
+    callback: Callback<Result<String, String>>,
+}
+
+struct Inner {
+    fetch_service: FetchService,
+    timeout_service: TimeoutService,
+    limiter: RateLimiter,
+    cache: ResponseCache,
+    queue: VecDeque<Queued>,
+    in_flight: HashMap<u64, (Queued, FetchTask)>,
+    next_id: u64,
+    drain_task: Option<TimeoutTask>,
+}
+
+/// The one way REST endpoints get called, so that between them they stay within
+/// finnhub's rate limit. Requests queue up rather than fail when over it, requests
+/// that get a 429 anyway are retried with backoff, and slow-changing answers are
+/// cached
+pub struct ApiClient {
+    inner: Rc<RefCell<Inner>>,
+}
+
+/// A request that's queued or in flight; dropping it cancels the request, the same as
+/// dropping a `FetchTask`
+pub struct ApiTask {
+    id: u64,
+    inner: Weak<RefCell<Inner>>,
+}
+
+impl Drop for ApiTask {
+    fn drop(&mut self) {
+        let inner = match self.inner.upgrade() {
+            Some(inner) => inner,
+            None => return,
+        };
+        // already done with if the client's busy, e.g. delivering this very response
+        if let Ok(mut inner) = inner.try_borrow_mut() {
+            inner.queue.retain(|queued| queued.id != self.id);
+            inner.in_flight.remove(&self.id);
+        };
+    }
+}
+
+impl ApiClient {
+    pub fn new() -> ApiClient {
+        ApiClient {
+            inner: Rc::new(RefCell::new(Inner {
+                fetch_service: FetchService::new(),
+                timeout_service: TimeoutService::new(),
+                limiter: RateLimiter::new(REQUESTS_PER_MIN),
+                cache: ResponseCache::default(),
+                queue: VecDeque::new(),
+                in_flight: HashMap::new(),
+                next_id: 0,
+                drain_task: None,
+            })),
+        }
+    }
+
+    /// GETs an endpoint; `path_and_query` shouldn't include the token. The callback
+    /// gets the response body. Cached responses are shared whatever the token, since
+    /// they don't depend on whose key fetched them
+    pub fn get(
+        &mut self,
+        path_and_query: &str,
+        token: &str,
+        callback: Callback<Result<String, String>>,
+    ) -> ApiTask {
+        self.enqueue(path_and_query, token, callback, false)
+    }
+
+    /// `get`, but ahead of everything already queued, for when someone's waiting on
+    /// the answer. It still waits for the rate limit
+    pub fn get_first(
+        &mut self,
+        path_and_query: &str,
+        token: &str,
+        callback: Callback<Result<String, String>>,
+    ) -> ApiTask {
+        self.enqueue(path_and_query, token, callback, true)
+    }
+
+    fn enqueue(
+        &mut self,
+        path_and_query: &str,
+        token: &str,
+        callback: Callback<Result<String, String>>,
+        first: bool,
+    ) -> ApiTask {
+        let now = Utc::now();
+        let id = {
+            let mut inner = self.inner.borrow_mut();
+            inner.next_id += 1;
+            inner.next_id
+        };
+        let cached = self
+            .inner
+            .borrow()
+            .cache
+            .get(path_and_query, now)
+            .map(str::to_string);
+        match cached {
+            Some(body) => callback.emit(Ok(body)),
+            None => {
+                let queued = Queued {
+                    id,
+                    path: path_and_query.to_string(),
+                    token: token.to_string(),
+                    attempts: 0,
+                    callback,
+                };
+                {
+                    let queue = &mut self.inner.borrow_mut().queue;
+                    if first {
+                        queue.push_front(queued);
+                    } else {
+                        queue.push_back(queued);
+                    }
+                }
+                drain(&self.inner);
+            }
+        }
+        ApiTask {
+            id,
+            inner: Rc::downgrade(&self.inner),
+        }
+    }
+
+    pub fn queued(&self) -> usize {
+        self.inner.borrow().queue.len()
+    }
+
+    pub fn sent_in_last_min(&self) -> usize {
+        self.inner.borrow().limiter.sent_in_last_min(Utc::now())
+    }
+}
+
+impl Default for ApiClient {
+    fn default() -> ApiClient {
+        ApiClient::new()
+    }
+}
+
+/// Sends as much of the queue as the rate limit allows, and comes back when it
+/// allows more
+fn drain(rc: &Rc<RefCell<Inner>>) {
+    let mut failed = vec![];
+    {
+        let mut inner = rc.borrow_mut();
+        let now = Utc::now();
+        loop {
+            if inner.queue.is_empty() {
+                inner.drain_task = None;
+                break;
+            }
+            let next_slot = inner.limiter.next_slot(now);
+            if next_slot > now {
+                let wait = (next_slot - now).to_std().unwrap_or_default();
+                let weak = Rc::downgrade(rc);
+                let callback = Callback::from(move |_| {
+                    if let Some(rc) = weak.upgrade() {
+                        drain(&rc);
+                    }
+                });
+                inner.drain_task = Some(inner.timeout_service.spawn(wait, callback));
+                break;
+            }
+            let queued = match inner.queue.pop_front() {
+                Some(queued) => queued,
+                None => break,
+            };
+            inner.limiter.record(now);
+            match send(rc, &mut inner, &queued) {
+                Ok(task) => {
+                    inner.in_flight.insert(queued.id, (queued, task));
+                }
+                Err(e) => failed.push((queued.callback, e.to_string())),
+            }
+        }
+    }
+    // once the client's free again, since these may well make more requests
+    for (callback, e) in failed {
+        callback.emit(Err(e));
+    }
+}
+
+fn send(rc: &Rc<RefCell<Inner>>, inner: &mut Inner, queued: &Queued) -> Result<FetchTask, Error> {
+    let request = Request::get(finnhub::rest_url(&queued.path, &queued.token)).body(Nothing)?;
+    let weak = Rc::downgrade(rc);
+    let id = queued.id;
+    let callback = Callback::from(move |response: Response<Text>| {
+        if let Some(rc) = weak.upgrade() {
+            on_response(&rc, id, response);
+        }
+    });
+    inner.fetch_service.fetch(request, callback)
+}
+
+fn on_response(rc: &Rc<RefCell<Inner>>, id: u64, response: Response<Text>) {
+    let (meta, body) = response.into_parts();
+    let delivery = {
+        let mut inner = rc.borrow_mut();
+        let now = Utc::now();
+        let (mut queued, _task) = match inner.in_flight.remove(&id) {
+            Some(in_flight) => in_flight,
+            // cancelled
+            None => return,
+        };
+        queued.attempts += 1;
+        if meta.status == StatusCode::TOO_MANY_REQUESTS && queued.attempts < MAX_ATTEMPTS {
+            inner.limiter.back_off(queued.attempts, now);
+            // ahead of everything else, since it's been waiting longest
+            inner.queue.push_front(queued);
+            None
+        } else {
+            let result = if meta.status.is_success() {
+                body.map_err(|e| e.to_string())
+            } else if meta.status == StatusCode::UNAUTHORIZED {
+                Err(INVALID_API_KEY.to_string())
+            } else if meta.status == StatusCode::FORBIDDEN {
+                Err(NOT_ENTITLED.to_string())
+            } else {
+                Err(format!("HTTP {}", meta.status))
+            };
+            if let Ok(body) = &result {
+                inner.cache.insert(&queued.path, body.clone(), now);
+            }
+            Some((queued.callback, result))
+        }
+    };
+    if let Some((callback, result)) = delivery {
+        callback.emit(result);
+    }
+    drain(rc);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp(secs, 0)
+    }
+
+    #[test]
+    fn spaces_requests_out_and_backs_off() {
+        let mut limiter = RateLimiter::new(2);
+        assert_eq!(limiter.next_slot(at(0)), at(0));
+        limiter.record(at(0));
+        limiter.record(at(10));
+        // the first of the two has to be a minute old first
+        assert_eq!(limiter.next_slot(at(20)), at(60));
+        assert_eq!(limiter.next_slot(at(61)), at(61));
+        assert_eq!(limiter.sent_in_last_min(at(61)), 1);
+
+        limiter.back_off(3, at(61));
+        assert_eq!(limiter.next_slot(at(61)), at(69));
+        limiter.back_off(10, at(61));
+        assert_eq!(limiter.next_slot(at(61)), at(121));
+    }
+
+    #[test]
+    fn caches_only_what_keeps() {
+        let mut cache = ResponseCache::default();
+        let news = "/company-news?symbol=AAPL&from=2020-05-01&to=2020-05-08";
+        cache.insert(news, "[]".to_string(), at(0));
+        cache.insert("/quote?symbol=AAPL", "{}".to_string(), at(0));
+        assert_eq!(cache.get(news, at(60)), Some("[]"));
+        assert_eq!(cache.get("/company-news?symbol=MSFT", at(60)), None);
+        assert_eq!(cache.get("/quote?symbol=AAPL", at(0)), None);
+        assert_eq!(cache.get(news, at(5 * 60)), None);
+    }
+
+    #[test]
+    fn sends_metadata_requests_again_when_refreshed() {
+        let mut cache = ResponseCache::default();
+        for path in &[
+            "/stock/profile2?symbol=AAPL",
+            "/stock/symbol?exchange=US",
+            "/forex/rates?base=USD",
+        ] {
+            cache.insert(path, "{}".to_string(), at(0));
+            // so a refresh a second later goes out rather than getting this back
+            assert_eq!(cache.get(path, at(1)), None);
+        }
+    }
+}
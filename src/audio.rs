@@ -0,0 +1,32 @@
+use wasm_bindgen::JsValue;
+use web_sys::{AudioContext, OscillatorType};
+
+/// Plays short alert tones via the Web Audio API
+pub struct Beeper {
+    context: AudioContext,
+}
+
+impl Beeper {
+    const FREQUENCY_HZ: f32 = 880.0;
+    const GAIN: f32 = 0.1;
+    const DURATION_SECS: f64 = 0.15;
+
+    pub fn new() -> Result<Beeper, JsValue> {
+        Ok(Beeper {
+            context: AudioContext::new()?,
+        })
+    }
+
+    pub fn beep(&self) -> Result<(), JsValue> {
+        let oscillator = self.context.create_oscillator()?;
+        let gain = self.context.create_gain()?;
+        oscillator.set_type(OscillatorType::Sine);
+        oscillator.frequency().set_value(Self::FREQUENCY_HZ);
+        gain.gain().set_value(Self::GAIN);
+        oscillator.connect_with_audio_node(&gain)?;
+        gain.connect_with_audio_node(&self.context.destination())?;
+        oscillator.start()?;
+        oscillator.stop_with_when(self.context.current_time() + Self::DURATION_SECS)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use crate::Symbol;
+
+/// Header cells (lowercased) that name a column of symbols in broker exports
+const SYMBOL_HEADERS: &[&str] = &["symbol", "ticker", "ticker symbol", "instrument", "code"];
+
+const DELIMITERS: [char; 3] = [',', ';', '\t'];
+
+/// Symbols found in a CSV file, in the order they appear and without duplicates.
+/// Files with a header row take the symbol column; otherwise every cell that looks
+/// like a ticker is picked up
+pub fn detect_symbols(content: &str) -> Vec<Symbol> {
+    let content = content.trim_start_matches('\u{feff}');
+    let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+    let first = match lines.next() {
+        Some(first) => first,
+        None => return vec![],
+    };
+    let delimiter = DELIMITERS
+        .iter()
+        .copied()
+        .max_by_key(|d| first.matches(*d).count())
+        .unwrap_or(',');
+    let header = split_row(first, delimiter);
+    let symbol_column = header.iter().position(|cell| {
+        let cell = cell.trim().to_lowercase();
+        SYMBOL_HEADERS.contains(&cell.as_str())
+    });
+    let is_header = symbol_column.is_some() || !header.iter().any(|cell| looks_like_ticker(cell));
+    let first_row = if is_header { None } else { Some(header) };
+    let rows = first_row
+        .into_iter()
+        .chain(lines.map(|line| split_row(line, delimiter)));
+
+    let mut symbols = vec![];
+    for row in rows {
+        let cells: Vec<&String> = match symbol_column {
+            Some(column) => row.get(column).into_iter().collect(),
+            None => row.iter().filter(|cell| looks_like_ticker(cell)).collect(),
+        };
+        for cell in cells {
+            let (parsed, _) = Symbol::parse_list(cell);
+            // a cell is one symbol, so anything with spaces in isn't
+            if let [symbol] = &parsed[..] {
+                if !symbols.contains(symbol) {
+                    symbols.push(symbol.clone());
+                }
+            }
+        }
+    }
+    symbols
+}
+
+/// Uppercase, with at least one letter, so that quantities, prices, dates and
+/// descriptions are left out
+fn looks_like_ticker(cell: &str) -> bool {
+    let cell = cell.trim();
+    !cell.is_empty()
+        && cell.len() <= 24
+        && cell.chars().any(|c| c.is_ascii_uppercase())
+        && !cell.chars().any(|c| c.is_lowercase())
+        && cell
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || ".:-_/^=".contains(c))
+}
+
+/// Splits a line on `delimiter`, minding double quotes (and `""` within them)
+fn split_row(line: &str, delimiter: char) -> Vec<String> {
+    let mut cells = vec![];
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => cells.push(std::mem::take(&mut cell)),
+            c => cell.push(c),
+        }
+    }
+    cells.push(cell);
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbols(list: &[&str]) -> Vec<Symbol> {
+        list.iter().map(|s| Symbol(s.to_string())).collect()
+    }
+
+    #[test]
+    fn takes_the_symbol_column_of_broker_exports() {
+        let csv = "\u{feff}Account;Symbol;Description;Quantity\n\
+                   X123;AAPL;\"Apple Inc.; common\";10\n\
+                   X123;msft;Microsoft;5\n\
+                   X123;AAPL;Apple Inc.;3\n";
+        assert_eq!(detect_symbols(csv), symbols(&["AAPL", "MSFT"]));
+    }
+
+    #[test]
+    fn picks_tickers_out_of_plain_files() {
+        let csv = "AAPL,10,150.25\nBINANCE:BTCUSDT,0.5,9000\n\nVOD.L,100,2020-06-01\n";
+        assert_eq!(
+            detect_symbols(csv),
+            symbols(&["AAPL", "BINANCE:BTCUSDT", "VOD.L"])
+        );
+        assert_eq!(detect_symbols("TSLA\nNFLX"), symbols(&["TSLA", "NFLX"]));
+        // a header without a symbol column is skipped rather than imported
+        assert_eq!(detect_symbols("Name,Qty\nAMZN,1"), symbols(&["AMZN"]));
+        assert!(detect_symbols("").is_empty());
+    }
+
+    #[test]
+    fn splits_quoted_cells() {
+        assert_eq!(
+            split_row(r#"a,"b, ""c""",d"#, ','),
+            vec!["a", r#"b, "c""#, "d"]
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// (ISO code, sign, usual decimal places) of the currencies prices commonly come in.
+/// LSE quotes in pence, which goes by `GBp` or `GBX`
+const CURRENCIES: &[(&str, &str, usize)] = &[
+    ("USD", "$", 2),
+    ("EUR", "€", 2),
+    ("GBP", "£", 2),
+    ("GBp", "p", 2),
+    ("GBX", "p", 2),
+    ("JPY", "¥", 0),
+    ("CNY", "¥", 2),
+    ("HKD", "HK$", 2),
+    ("CAD", "C$", 2),
+    ("AUD", "A$", 2),
+    ("NZD", "NZ$", 2),
+    ("SGD", "S$", 2),
+    ("CHF", "CHF", 2),
+    ("INR", "₹", 2),
+    ("KRW", "₩", 0),
+    ("TWD", "NT$", 2),
+    ("SEK", "kr", 2),
+    ("NOK", "kr", 2),
+    ("DKK", "kr", 2),
+    ("BRL", "R$", 2),
+    ("MXN", "MX$", 2),
+    ("ZAR", "R", 2),
+];
+
+/// A currency as reported by finnhub, which is usually (but not always) an ISO code
+#[derive(Clone, PartialEq, Debug)]
+pub struct Currency(String);
+
+impl Currency {
+    pub fn new(code: &str) -> Currency {
+        Currency(code.trim().to_string())
+    }
+
+    pub fn code(&self) -> &str {
+        &self.0
+    }
+
+    /// The ISO currency this is quoted in, along with how many of this make one of
+    /// those, which is only not 1 for pence
+    fn iso(&self) -> (String, f64) {
+        if self.0 == "GBp" || self.0.eq_ignore_ascii_case("GBX") {
+            ("GBP".to_string(), 100.0)
+        } else {
+            (self.0.to_ascii_uppercase(), 1.0)
+        }
+    }
+
+    // pence and pounds only differ by case, so an exact match wins
+    fn known(&self) -> Option<&'static (&'static str, &'static str, usize)> {
+        CURRENCIES
+            .iter()
+            .find(|(code, _, _)| *code == self.0)
+            .or_else(|| {
+                CURRENCIES
+                    .iter()
+                    .find(|(code, _, _)| code.eq_ignore_ascii_case(&self.0))
+            })
+    }
+
+    /// Falls back to the code for currencies we don't have a sign for
+    pub fn sign(&self) -> &str {
+        self.known().map(|(_, sign, _)| *sign).unwrap_or(&self.0)
+    }
+
+    pub fn decimals(&self) -> Option<usize> {
+        self.known().map(|(_, _, decimals)| *decimals)
+    }
+}
+
+/// Rates from finnhub's `/forex/rates`: how much of each currency one `base` buys
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct FxRates {
+    pub base: String,
+    #[serde(rename = "quote")]
+    rates: HashMap<String, f64>,
+}
+
+impl FxRates {
+    /// `amount` of `from` in the base currency, if we have a rate for it
+    pub fn convert(&self, amount: f64, from: &Currency) -> Option<f64> {
+        let (code, per_unit) = from.iso();
+        let rate = if code.eq_ignore_ascii_case(&self.base) {
+            1.0
+        } else {
+            *self.rates.get(&code).filter(|rate| **rate > 0.0)?
+        };
+        Some(amount / per_unit / rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rates() -> FxRates {
+        serde_json::from_str(r#"{"base":"USD","quote":{"EUR":0.8,"GBP":0.5,"JPY":100.0}}"#).unwrap()
+    }
+
+    #[test]
+    fn converts_to_the_base_currency() {
+        let rates = rates();
+        assert_eq!(rates.convert(8.0, &Currency::new("EUR")), Some(10.0));
+        assert_eq!(rates.convert(3.0, &Currency::new("usd")), Some(3.0));
+        // pence are hundredths of a pound
+        assert_eq!(rates.convert(250.0, &Currency::new("GBp")), Some(5.0));
+        assert_eq!(rates.convert(1.0, &Currency::new("XYZ")), None);
+    }
+
+    #[test]
+    fn tells_pence_from_pounds() {
+        assert_eq!(Currency::new("GBp").sign(), "p");
+        assert_eq!(Currency::new("GBP").sign(), "£");
+        assert_eq!(Currency::new("jpy").decimals(), Some(0));
+        assert_eq!(Currency::new("XYZ").sign(), "XYZ");
+    }
+}
@@ -0,0 +1,225 @@
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// Largest number of decimal places we keep
+const MAX_SCALE: u32 = 18;
+
+/// A fixed-point decimal number: `mantissa * 10^-scale`.
+///
+/// Finnhub sends prices and volumes as JSON numbers; parsing those straight into
+/// floats and printing them back introduces artifacts like `123.45999`. Instead we
+/// keep exactly the digits that were sent.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Decimal {
+    mantissa: i64,
+    scale: u32,
+}
+
+impl Decimal {
+    pub fn new(mantissa: i64, scale: u32) -> Decimal {
+        Decimal { mantissa, scale }
+    }
+
+    /// Number of digits after the decimal point
+    pub fn scale(self) -> u32 {
+        self.scale
+    }
+
+    /// Number of digits after the decimal point, not counting trailing zeros
+    pub fn significant_scale(self) -> u32 {
+        let mut mantissa = self.mantissa;
+        let mut scale = self.scale;
+        while scale > 0 && mantissa % 10 == 0 {
+            mantissa /= 10;
+            scale -= 1;
+        }
+        scale
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.mantissa as f64 / 10f64.powi(self.scale as i32)
+    }
+
+    /// Converts from a float via its shortest round-trip representation, which is what
+    /// the JSON parser handed us
+    pub fn from_f64(f: f64) -> Option<Decimal> {
+        if f.is_finite() {
+            f.to_string().parse().ok()
+        } else {
+            None
+        }
+    }
+
+    fn rescaled(self, scale: u32) -> i128 {
+        self.mantissa as i128 * 10i128.pow(scale - self.scale)
+    }
+
+    /// Rounds half away from zero to the given number of decimal places
+    pub fn round_dp(self, dp: u32) -> Decimal {
+        if dp >= self.scale {
+            return self;
+        }
+        let divisor = 10i64.pow(self.scale - dp);
+        let quotient = self.mantissa / divisor;
+        let remainder = (self.mantissa % divisor).abs();
+        let rounded = if remainder * 2 >= divisor {
+            quotient + self.mantissa.signum()
+        } else {
+            quotient
+        };
+        Decimal::new(rounded, dp)
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Decimal) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Decimal) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Decimal) -> Ordering {
+        let scale = self.scale.max(other.scale);
+        self.rescaled(scale).cmp(&other.rescaled(scale))
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseDecimalError(String);
+
+impl fmt::Display for ParseDecimalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid decimal [{}]", self.0)
+    }
+}
+
+impl std::error::Error for ParseDecimalError {}
+
+impl FromStr for Decimal {
+    type Err = ParseDecimalError;
+
+    fn from_str(s: &str) -> Result<Decimal, ParseDecimalError> {
+        let err = || ParseDecimalError(s.to_string());
+        let (negative, unsigned) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let (int_part, frac_part) = match unsigned.find('.') {
+            Some(idx) => (&unsigned[..idx], &unsigned[idx + 1..]),
+            None => (unsigned, ""),
+        };
+        if int_part.is_empty() && frac_part.is_empty() {
+            return Err(err());
+        }
+        if !int_part
+            .chars()
+            .chain(frac_part.chars())
+            .all(|c| c.is_ascii_digit())
+        {
+            return Err(err());
+        }
+        // drop digits we can't represent rather than failing outright
+        let frac_part = &frac_part[..frac_part.len().min(MAX_SCALE as usize)];
+        let digits = format!("{}{}", int_part, frac_part);
+        let magnitude: i64 = if digits.is_empty() {
+            0
+        } else {
+            digits.parse().map_err(|_| err())?
+        };
+        let mantissa = if negative { -magnitude } else { magnitude };
+        Ok(Decimal::new(mantissa, frac_part.len() as u32))
+    }
+}
+
+impl fmt::Display for Decimal {
+    /// Honours the formatter's precision, rounding or zero-padding as needed
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (mantissa, scale) = match f.precision() {
+            Some(precision) if (precision as u32) < self.scale => {
+                let rounded = self.round_dp(precision as u32);
+                (rounded.mantissa, rounded.scale)
+            }
+            _ => (self.mantissa, self.scale),
+        };
+        let digits = format!("{:0>width$}", mantissa.abs(), width = scale as usize + 1);
+        let (int_part, frac_part) = digits.split_at(digits.len() - scale as usize);
+        let sign = if mantissa < 0 { "-" } else { "" };
+        let padding = f.precision().unwrap_or(0).saturating_sub(scale as usize);
+        if frac_part.is_empty() && padding == 0 {
+            write!(f, "{}{}", sign, int_part)
+        } else {
+            write!(
+                f,
+                "{}{}.{}{}",
+                sign,
+                int_part,
+                frac_part,
+                "0".repeat(padding)
+            )
+        }
+    }
+}
+
+impl Serialize for Decimal {
+    /// Serialised as a plain number so previously persisted state stays readable. Binary
+    /// formats (e.g. messages to and from workers) get the exact parts instead
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_f64(self.to_f64())
+        } else {
+            (self.mantissa, self.scale).serialize(serializer)
+        }
+    }
+}
+
+struct DecimalVisitor;
+
+impl<'de> Visitor<'de> for DecimalVisitor {
+    type Value = Decimal;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a number or a numeric string")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Decimal, E> {
+        Ok(Decimal::new(v, 0))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Decimal, E> {
+        if v > i64::MAX as u64 {
+            Err(E::custom(format!("{} is too large", v)))
+        } else {
+            Ok(Decimal::new(v as i64, 0))
+        }
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Decimal, E> {
+        Decimal::from_f64(v).ok_or_else(|| E::custom(format!("{} is not a valid decimal", v)))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Decimal, E> {
+        v.parse().map_err(E::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for Decimal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Decimal, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(DecimalVisitor)
+        } else {
+            let (mantissa, scale) = <(i64, u32)>::deserialize(deserializer)?;
+            Ok(Decimal::new(mantissa, scale))
+        }
+    }
+}
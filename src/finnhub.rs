@@ -0,0 +1,618 @@
+use crate::decimal::Decimal;
+use chrono::serde::{ts_milliseconds, ts_seconds};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+pub const REST_BASE_URL: &str = "https://finnhub.io/api/v1";
+
+#[derive(Deserialize, Serialize, Hash, PartialEq, Eq, Clone, Debug)]
+pub struct Symbol(pub String);
+
+impl Symbol {
+    /// Symbols are case-insensitive as far as finnhub is concerned, so we
+    /// trim and uppercase them before tracking to avoid duplicates
+    pub fn normalised(&self) -> Symbol {
+        Symbol(self.0.trim().to_uppercase())
+    }
+
+    /// Non-stock symbols are prefixed with their exchange, e.g. `BINANCE:BTCUSDT`
+    pub fn exchange(&self) -> Option<&str> {
+        self.0.find(':').map(|idx| &self.0[..idx])
+    }
+
+    /// Splits a comma, semicolon or whitespace separated list into normalised symbols,
+    /// without duplicates. Entries that can't be symbols are handed back separately
+    pub fn parse_list(input: &str) -> (Vec<Symbol>, Vec<String>) {
+        let mut symbols: Vec<Symbol> = vec![];
+        let mut invalid = vec![];
+        for entry in input.split(|c: char| c == ',' || c == ';' || c.is_whitespace()) {
+            let symbol = Symbol(entry.to_string()).normalised();
+            if symbol.0.is_empty() || symbols.contains(&symbol) {
+                continue;
+            }
+            let valid = symbol
+                .0
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || ".:-_/^=".contains(c));
+            if valid {
+                symbols.push(symbol);
+            } else {
+                invalid.push(symbol.0);
+            }
+        }
+        (symbols, invalid)
+    }
+
+    /// The currency a forex pair like `OANDA:EUR_USD` is quoted in
+    pub fn quote_currency(&self) -> Option<&str> {
+        let pair = self.0.rsplit(':').next()?;
+        let mut parts = pair.split(['_', '/']);
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(_), Some(quote), None) if !quote.is_empty() => Some(quote),
+            _ => None,
+        }
+    }
+
+    pub fn with_exchange(&self, exchange: &str) -> Symbol {
+        if exchange.is_empty() || self.exchange().is_some() {
+            self.clone()
+        } else {
+            Symbol(format!("{}:{}", exchange, self.0))
+        }
+    }
+
+    pub fn kind(&self) -> SymbolKind {
+        self.exchange()
+            .and_then(|exchange| {
+                EXCHANGES
+                    .iter()
+                    .find(|(known, _)| known.eq_ignore_ascii_case(exchange))
+                    .map(|(_, kind)| *kind)
+            })
+            .unwrap_or(SymbolKind::Stock)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SymbolKind {
+    Stock,
+    Forex,
+    Crypto,
+}
+
+impl SymbolKind {
+    pub const ALL: [SymbolKind; 3] = [SymbolKind::Stock, SymbolKind::Forex, SymbolKind::Crypto];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SymbolKind::Stock => "Stock",
+            SymbolKind::Forex => "Forex",
+            SymbolKind::Crypto => "Crypto",
+        }
+    }
+
+    /// Number of decimals to show prices with
+    pub fn price_precision(self) -> usize {
+        match self {
+            SymbolKind::Stock => 2,
+            SymbolKind::Forex => 5,
+            SymbolKind::Crypto => 8,
+        }
+    }
+}
+
+/// Exchange prefixes supported by finnhub for non-stock symbols
+pub const EXCHANGES: &[(&str, SymbolKind)] = &[
+    ("BINANCE", SymbolKind::Crypto),
+    ("COINBASE", SymbolKind::Crypto),
+    ("KRAKEN", SymbolKind::Crypto),
+    ("BITFINEX", SymbolKind::Crypto),
+    ("BITSTAMP", SymbolKind::Crypto),
+    ("GEMINI", SymbolKind::Crypto),
+    ("HUOBI", SymbolKind::Crypto),
+    ("POLONIEX", SymbolKind::Crypto),
+    ("OANDA", SymbolKind::Forex),
+    ("FXCM", SymbolKind::Forex),
+    ("FXPRO", SymbolKind::Forex),
+    ("FOREX.COM", SymbolKind::Forex),
+    ("IC MARKETS", SymbolKind::Forex),
+];
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct Price(pub Decimal);
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct Volume(pub Decimal);
+
+/// This is a single Stock info payload that comes from the FinnPub API
+#[derive(Deserialize, Serialize, Debug)]
+pub struct TickerInfo {
+    #[serde(rename = "s")]
+    pub symbol: Symbol,
+    #[serde(rename = "p")]
+    pub price: Price,
+    #[serde(rename = "v")]
+    pub volume: Volume,
+    #[serde(with = "ts_milliseconds", rename = "t")]
+    pub time: DateTime<Utc>,
+    /// Synthesised from a polled REST quote while the websocket was down, so the
+    /// volume is unknown
+    #[serde(default)]
+    pub delayed: bool,
+}
+
+impl TickerInfo {
+    /// Price and volume, as they go into `RunningStats`
+    pub fn sample(&self) -> (f64, f64) {
+        (self.price.0.to_f64(), self.volume.0.to_f64())
+    }
+
+    /// Price × volume; unknown for delayed quotes, which have no volume
+    pub fn notional(&self) -> Option<f64> {
+        if self.delayed {
+            None
+        } else {
+            Some(self.price.0.to_f64() * self.volume.0.to_f64())
+        }
+    }
+}
+
+/// Company details from finnhub's `/stock/profile2` REST endpoint. Every field is
+/// optional because unknown symbols get an empty object back
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CompanyProfile {
+    pub name: Option<String>,
+    pub logo: Option<String>,
+    #[serde(rename = "finnhubIndustry")]
+    pub industry: Option<String>,
+    /// In millions of `currency`
+    pub market_capitalization: Option<f64>,
+    pub currency: Option<String>,
+    pub weburl: Option<String>,
+}
+
+impl CompanyProfile {
+    pub fn formatted_market_capitalization(&self) -> Option<String> {
+        self.market_capitalization.map(|millions| {
+            let currency = self.currency.as_deref().unwrap_or("");
+            if millions >= 1_000_000.0 {
+                format!("{:.2}T {}", millions / 1_000_000.0, currency)
+            } else if millions >= 1_000.0 {
+                format!("{:.2}B {}", millions / 1_000.0, currency)
+            } else {
+                format!("{:.2}M {}", millions, currency)
+            }
+        })
+    }
+}
+
+/// A headline from finnhub's `/company-news` REST endpoint
+#[derive(Deserialize, Clone, Debug)]
+pub struct NewsItem {
+    pub id: u64,
+    pub headline: String,
+    pub source: String,
+    pub url: String,
+    #[serde(with = "ts_seconds")]
+    pub datetime: DateTime<Utc>,
+}
+
+/// A scheduled report from finnhub's `/calendar/earnings` REST endpoint
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct EarningsRelease {
+    pub date: NaiveDate,
+    /// `bmo` (before market open), `amc` (after market close) or `dmh` (during market
+    /// hours), when known
+    #[serde(default)]
+    pub hour: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EarningsCalendar {
+    #[serde(default)]
+    pub earnings_calendar: Vec<EarningsRelease>,
+}
+
+/// Latest quote from finnhub's `/quote` REST endpoint. Unknown symbols come back with
+/// everything zeroed
+#[derive(Deserialize, Debug)]
+pub struct Quote {
+    #[serde(rename = "c")]
+    pub current: Price,
+    #[serde(with = "ts_seconds", rename = "t")]
+    pub time: DateTime<Utc>,
+}
+
+impl Quote {
+    pub fn into_ticker_info(self, symbol: Symbol) -> Option<TickerInfo> {
+        if self.time.timestamp() == 0 {
+            None
+        } else {
+            Some(TickerInfo {
+                symbol,
+                price: self.current,
+                volume: Volume(Decimal::new(0, 0)),
+                time: self.time,
+                delayed: true,
+            })
+        }
+    }
+}
+
+/// One-minute candles from finnhub's `/stock/candle` (or crypto or forex) REST
+/// endpoint, as parallel arrays. Ranges without trades come back as `no_data`
+#[derive(Deserialize, Debug)]
+pub struct Candles {
+    #[serde(rename = "s")]
+    pub status: String,
+    #[serde(rename = "c", default)]
+    pub close: Vec<Price>,
+    #[serde(rename = "v", default)]
+    pub volume: Vec<Volume>,
+    /// When each candle starts, in seconds
+    #[serde(rename = "t", default)]
+    pub time: Vec<i64>,
+}
+
+impl Candles {
+    /// A trade at each candle's close price, for its whole volume, at the start of its
+    /// minute; enough to chart and take stats of until live trades come in
+    pub fn into_trades(self, symbol: &Symbol) -> Vec<TickerInfo> {
+        if self.status != "ok" {
+            return vec![];
+        }
+        self.close
+            .into_iter()
+            .zip(self.volume)
+            .zip(self.time)
+            .filter_map(|((price, volume), secs)| {
+                Some(TickerInfo {
+                    symbol: symbol.clone(),
+                    price,
+                    volume,
+                    time: Utc.timestamp_opt(secs, 0).single()?,
+                    delayed: false,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Top of the book from finnhub's `/stock/bidask` REST endpoint, since `/quote` only
+/// has the last price. Symbols without one come back zeroed
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct BidAsk {
+    #[serde(rename = "b")]
+    pub bid: Price,
+    #[serde(rename = "a")]
+    pub ask: Price,
+}
+
+impl BidAsk {
+    pub fn is_known(&self) -> bool {
+        self.bid.0.to_f64() > 0.0 && self.ask.0.to_f64() > 0.0
+    }
+
+    pub fn spread(&self) -> f64 {
+        self.ask.0.to_f64() - self.bid.0.to_f64()
+    }
+
+    /// Relative to the midpoint
+    pub fn spread_percent(&self) -> f64 {
+        let mid = (self.ask.0.to_f64() + self.bid.0.to_f64()) / 2.0;
+        self.spread() / mid * 100.0
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Request {
+    Subscribe { symbol: Symbol },
+    Unsubscribe { symbol: Symbol },
+}
+
+/// The different messages that we'll get from the websocket connection
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum WsMessage {
+    Error {
+        #[serde(rename = "msg")]
+        message: String,
+    },
+    Ping,
+    Trade {
+        data: Vec<TickerInfo>,
+    },
+    /// A `type` we don't know (yet), kept as it came; only `parse` produces these
+    #[serde(skip)]
+    Unknown {
+        kind: String,
+        payload: String,
+    },
+}
+
+impl WsMessage {
+    const KNOWN_TYPES: [&'static str; 3] = ["error", "ping", "trade"];
+
+    /// Like deserialising, except that messages of unknown types come back as
+    /// `Unknown` rather than as errors
+    pub fn parse(frame: &str) -> serde_json::Result<WsMessage> {
+        #[derive(Deserialize)]
+        struct Tagged {
+            #[serde(rename = "type")]
+            kind: String,
+        }
+        serde_json::from_str(frame).or_else(|e| match serde_json::from_str(frame) {
+            Ok(Tagged { kind }) if !WsMessage::KNOWN_TYPES.contains(&kind.as_str()) => {
+                Ok(WsMessage::Unknown {
+                    kind,
+                    payload: frame.to_string(),
+                })
+            }
+            _ => Err(e),
+        })
+    }
+}
+
+/// Why finnhub turned down a subscription, going by the websocket error it sent
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SymbolError {
+    Invalid,
+    /// The symbol exists, but the plan the key is on doesn't cover it
+    NotEntitled,
+}
+
+impl SymbolError {
+    /// Only the messages finnhub's known to send about a subscription; anything else is
+    /// `None` rather than a guess, since a wrong guess marks a working symbol as broken
+    pub fn from_message(message: &str) -> Option<SymbolError> {
+        let message = message.trim().trim_end_matches('.').to_lowercase();
+        match message.as_str() {
+            "invalid symbol" => Some(SymbolError::Invalid),
+            "you don't have access to this resource" => Some(SymbolError::NotEntitled),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SymbolError::Invalid => "Invalid symbol",
+            SymbolError::NotEntitled => "Not entitled on your plan",
+        }
+    }
+}
+
+/// The full URL of a REST endpoint, with the token tacked on
+pub fn rest_url(path_and_query: &str, token: &str) -> String {
+    let separator = if path_and_query.contains('?') {
+        '&'
+    } else {
+        '?'
+    };
+    format!(
+        "{}{}{}token={}",
+        REST_BASE_URL, path_and_query, separator, token
+    )
+}
+
+/// Paths (and queries) of the REST endpoints, to be given to `rest_url`
+pub mod rest {
+    use super::{Symbol, SymbolKind};
+    use chrono::{DateTime, NaiveDate, Utc};
+
+    fn date(date: NaiveDate) -> String {
+        date.format("%Y-%m-%d").to_string()
+    }
+
+    pub fn profile(symbol: &Symbol) -> String {
+        format!("/stock/profile2?symbol={}", symbol.0)
+    }
+
+    pub fn quote(symbol: &Symbol) -> String {
+        format!("/quote?symbol={}", symbol.0)
+    }
+
+    /// One-minute candles from `from` up to `to`
+    pub fn candles(symbol: &Symbol, from: DateTime<Utc>, to: DateTime<Utc>) -> String {
+        let market = match symbol.kind() {
+            SymbolKind::Stock => "stock",
+            SymbolKind::Crypto => "crypto",
+            SymbolKind::Forex => "forex",
+        };
+        format!(
+            "/{}/candle?symbol={}&resolution=1&from={}&to={}",
+            market,
+            symbol.0,
+            from.timestamp(),
+            to.timestamp()
+        )
+    }
+
+    /// About the cheapest call there is, for finding out whether a key works. It mustn't
+    /// be one that's cached, or the answer could be for some other key
+    pub fn key_check() -> String {
+        quote(&Symbol("AAPL".to_string()))
+    }
+
+    pub fn bid_ask(symbol: &Symbol) -> String {
+        format!("/stock/bidask?symbol={}", symbol.0)
+    }
+
+    pub fn company_news(symbol: &Symbol, from: NaiveDate, to: NaiveDate) -> String {
+        format!(
+            "/company-news?symbol={}&from={}&to={}",
+            symbol.0,
+            date(from),
+            date(to)
+        )
+    }
+
+    pub fn earnings_calendar(symbol: &Symbol, from: NaiveDate, to: NaiveDate) -> String {
+        format!(
+            "/calendar/earnings?symbol={}&from={}&to={}",
+            symbol.0,
+            date(from),
+            date(to)
+        )
+    }
+
+    pub fn symbol_list(exchange: &str) -> String {
+        format!("/stock/symbol?exchange={}", exchange)
+    }
+
+    pub fn fx_rates(base: &str) -> String {
+        format!("/forex/rates?base={}", base)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(s: &str) -> Symbol {
+        Symbol(s.to_string())
+    }
+
+    #[test]
+    fn parses_symbol_lists() {
+        let (symbols, invalid) =
+            Symbol::parse_list(" aapl, MSFT;tsla\nAAPL\n\nbinance:btcusdt <script>");
+        assert_eq!(
+            symbols,
+            vec![
+                symbol("AAPL"),
+                symbol("MSFT"),
+                symbol("TSLA"),
+                symbol("BINANCE:BTCUSDT")
+            ]
+        );
+        assert_eq!(invalid, vec!["<SCRIPT>".to_string()]);
+    }
+
+    #[test]
+    fn measures_the_spread() {
+        let bid_ask: BidAsk =
+            serde_json::from_str(r#"{"a":100.5,"av":300,"b":99.5,"bv":200,"t":1590000000000}"#)
+                .unwrap();
+        assert!(bid_ask.is_known());
+        assert_eq!(bid_ask.spread(), 1.0);
+        assert_eq!(bid_ask.spread_percent(), 1.0);
+        let unknown: BidAsk = serde_json::from_str(r#"{"a":0,"b":0}"#).unwrap();
+        assert!(!unknown.is_known());
+    }
+
+    #[test]
+    fn tells_subscription_errors_apart() {
+        assert_eq!(
+            SymbolError::from_message("Invalid symbol"),
+            Some(SymbolError::Invalid)
+        );
+        assert_eq!(
+            SymbolError::from_message("You don't have access to this resource."),
+            Some(SymbolError::NotEntitled)
+        );
+        assert_eq!(SymbolError::from_message("Too many requests"), None);
+        // merely mentioning access isn't enough
+        assert_eq!(
+            SymbolError::from_message("Access token expired, please reconnect"),
+            None
+        );
+    }
+
+    #[test]
+    fn round_trips_requests() {
+        let request = Request::Subscribe {
+            symbol: symbol("AAPL"),
+        };
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(json, r#"{"type":"subscribe","symbol":"AAPL"}"#);
+        assert_eq!(serde_json::from_str::<Request>(&json).unwrap(), request);
+    }
+
+    #[test]
+    fn parses_messages_including_unknown_ones() {
+        let frame = r#"{"type":"trade","data":[{"s":"AAPL","p":123.45,"v":10,"t":1590000000000}]}"#;
+        match WsMessage::parse(frame).unwrap() {
+            WsMessage::Trade { data } => {
+                assert_eq!(data[0].symbol, symbol("AAPL"));
+                assert_eq!(data[0].price, Price(Decimal::new(12345, 2)));
+                assert_eq!(data[0].time, Utc.timestamp(1_590_000_000, 0));
+                assert!(!data[0].delayed);
+            }
+            other => panic!("expected trades, got {:?}", other),
+        }
+        assert!(matches!(
+            WsMessage::parse(r#"{"type":"ping"}"#).unwrap(),
+            WsMessage::Ping
+        ));
+        match WsMessage::parse(r#"{"type":"news","data":[]}"#).unwrap() {
+            WsMessage::Unknown { kind, .. } => assert_eq!(kind, "news"),
+            other => panic!("expected an unknown message, got {:?}", other),
+        }
+        assert!(WsMessage::parse(r#"{"type":"trade"}"#).is_err());
+    }
+
+    #[test]
+    fn zeroed_quotes_are_not_trades() {
+        let quote: Quote = serde_json::from_str(r#"{"c":0,"t":0}"#).unwrap();
+        assert!(quote.into_ticker_info(symbol("NOPE")).is_none());
+        let quote: Quote = serde_json::from_str(r#"{"c":101.5,"t":1590000000}"#).unwrap();
+        let ticker_info = quote.into_ticker_info(symbol("AAPL")).unwrap();
+        assert!(ticker_info.delayed);
+        assert_eq!(ticker_info.notional(), None);
+    }
+
+    #[test]
+    fn builds_rest_urls() {
+        assert_eq!(
+            rest_url(&rest::quote(&symbol("AAPL")), "abc"),
+            "https://finnhub.io/api/v1/quote?symbol=AAPL&token=abc"
+        );
+        // a quote, since those are never cached
+        assert_eq!(rest::key_check(), rest::quote(&symbol("AAPL")));
+        let day = NaiveDate::from_ymd_opt(2020, 5, 20).unwrap();
+        assert_eq!(
+            rest::company_news(&symbol("AAPL"), day, day),
+            "/company-news?symbol=AAPL&from=2020-05-20&to=2020-05-20"
+        );
+    }
+
+    #[test]
+    fn turns_candles_into_trades() {
+        let candles: Candles = serde_json::from_str(
+            r#"{"c":[217.68,221.03],"h":[218,222],"l":[217,220],"o":[217.5,220.5],"s":"ok","t":[1569297600,1569297660],"v":[1200,1500]}"#,
+        )
+        .unwrap();
+        let trades = candles.into_trades(&symbol("AAPL"));
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[1].price, Price(Decimal::new(22_103, 2)));
+        assert_eq!(trades[1].volume, Volume(Decimal::new(1_500, 0)));
+        assert_eq!(trades[1].time, Utc.timestamp(1_569_297_660, 0));
+        let none: Candles = serde_json::from_str(r#"{"s":"no_data"}"#).unwrap();
+        assert!(none.into_trades(&symbol("AAPL")).is_empty());
+        assert_eq!(
+            rest::candles(
+                &symbol("BINANCE:BTCUSDT"),
+                Utc.timestamp(0, 0),
+                Utc.timestamp(3_600, 0)
+            ),
+            "/crypto/candle?symbol=BINANCE:BTCUSDT&resolution=1&from=0&to=3600"
+        );
+    }
+
+    #[test]
+    fn parses_earnings_without_an_hour() {
+        let calendar: EarningsCalendar = serde_json::from_str(
+            r#"{"earningsCalendar":[{"date":"2020-07-30","hour":null},{"date":"2020-10-29","hour":"amc"}]}"#,
+        )
+        .unwrap();
+        let hours: Vec<Option<&str>> = calendar
+            .earnings_calendar
+            .iter()
+            .map(|release| release.hour.as_deref())
+            .collect();
+        assert_eq!(hours, vec![None, Some("amc")]);
+    }
+}
@@ -0,0 +1,122 @@
+use anyhow::{anyhow, Error};
+use js_sys::{Array, Function, Promise, Reflect, Uint8Array};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use yew::format::{Binary, Text};
+use yew::Callback;
+
+/// A websocket frame as it came in. Finnhub sends text, but binary frames are taken
+/// to be the same JSON, possibly compressed. permessage-deflate needs none of this,
+/// since browsers negotiate it and inflate frames before we see them
+pub enum Frame {
+    Text(Text),
+    Binary(Binary),
+}
+
+impl From<Text> for Frame {
+    fn from(text: Text) -> Frame {
+        Frame::Text(text)
+    }
+}
+
+impl From<Binary> for Frame {
+    fn from(binary: Binary) -> Frame {
+        Frame::Binary(binary)
+    }
+}
+
+/// How a binary frame was compressed, going by its first bytes
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Compression {
+    Gzip,
+    Zlib,
+}
+
+impl Compression {
+    /// `None` for frames that aren't compressed; JSON can't start like either
+    pub fn of(bytes: &[u8]) -> Option<Compression> {
+        match bytes {
+            [0x1f, 0x8b, ..] => Some(Compression::Gzip),
+            // deflate, with the check bits that make the header a multiple of 31
+            [cmf, flg, ..]
+                if cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0 =>
+            {
+                Some(Compression::Zlib)
+            }
+            _ => None,
+        }
+    }
+
+    /// What `DecompressionStream` calls it
+    fn format(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Zlib => "deflate",
+        }
+    }
+}
+
+/// Turns a binary frame into text for the callback; straight away, unless it has to
+/// be decompressed first
+pub fn decode(bytes: Vec<u8>, callback: Callback<Text>) {
+    match Compression::of(&bytes) {
+        None => callback.emit(String::from_utf8(bytes).map_err(Error::from)),
+        Some(compression) => spawn_local(async move {
+            let text = decompress(&bytes, compression).await.map_err(|e| {
+                anyhow!(
+                    "Could not decompress a {} frame [{:?}]",
+                    compression.format(),
+                    e
+                )
+            });
+            callback.emit(text);
+        }),
+    }
+}
+
+/// web-sys doesn't have `DecompressionStream` yet, hence the `Reflect`
+async fn decompress(bytes: &[u8], compression: Compression) -> Result<String, JsValue> {
+    let global = js_sys::global();
+    let construct = |name: &str, args: &Array| -> Result<JsValue, JsValue> {
+        let constructor: Function = Reflect::get(&global, &name.into())?.dyn_into()?;
+        Reflect::construct(&constructor, args)
+    };
+    let call = |target: &JsValue, method: &str, args: &Array| -> Result<JsValue, JsValue> {
+        let method: Function = Reflect::get(target, &method.into())?.dyn_into()?;
+        Reflect::apply(&method, target, args)
+    };
+    let decompression = construct(
+        "DecompressionStream",
+        &Array::of1(&compression.format().into()),
+    )?;
+    let blob = construct("Blob", &Array::of1(&Array::of1(&Uint8Array::from(bytes))))?;
+    let stream = call(&blob, "stream", &Array::new())?;
+    let decompressed = call(&stream, "pipeThrough", &Array::of1(&decompression))?;
+    // the easiest way to read a whole stream
+    let response = construct("Response", &Array::of1(&decompressed))?;
+    let text: Promise = call(&response, "text", &Array::new())?.dyn_into()?;
+    JsFuture::from(text)
+        .await?
+        .as_string()
+        .ok_or_else(|| "Decompressed to something other than text".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_compressed_frames_from_json() {
+        assert_eq!(
+            Compression::of(&[0x1f, 0x8b, 0x08, 0x00]),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::of(&[0x78, 0x9c, 0x01]),
+            Some(Compression::Zlib)
+        );
+        assert_eq!(Compression::of(&[0x78, 0x9d]), None);
+        assert_eq!(Compression::of(br#"{"type":"ping"}"#), None);
+        assert_eq!(Compression::of(b""), None);
+    }
+}
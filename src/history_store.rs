@@ -0,0 +1,196 @@
+use js_sys::Array;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{
+    DomException, Event, IdbDatabase, IdbOpenDbRequest, IdbRequest, IdbTransaction,
+    IdbTransactionMode,
+};
+use yew::Callback;
+
+const DB_NAME: &str = "finnhub-ws-rs";
+const DB_VERSION: u32 = 1;
+const HISTORY_STORE: &str = "history";
+
+/// Key-value storage for trade history in IndexedDB, which has far more room than
+/// LocalStorage and lets us write one symbol at a time. Values are JSON strings.
+pub struct HistoryStore {
+    db: IdbDatabase,
+    _on_abort: Option<Closure<dyn FnMut(Event)>>,
+}
+
+fn js_error(e: JsValue) -> String {
+    format!("{:?}", e)
+}
+
+/// Whether a storage write (LocalStorage or IndexedDB) failed for lack of room.
+/// Firefox has its own name for it
+pub fn is_quota_exceeded(e: &JsValue) -> bool {
+    e.dyn_ref::<DomException>()
+        .map(|e| {
+            let name = e.name();
+            name == "QuotaExceededError" || name == "NS_ERROR_DOM_QUOTA_REACHED"
+        })
+        .unwrap_or(false)
+}
+
+impl HistoryStore {
+    /// Opens (creating if needed) the database; the callback is called once it's ready
+    pub fn open(callback: Callback<Result<HistoryStore, String>>) -> Result<(), String> {
+        let factory = web_sys::window()
+            .ok_or("No window")?
+            .indexed_db()
+            .map_err(js_error)?
+            .ok_or("IndexedDB is not supported")?;
+        let request = factory
+            .open_with_u32(DB_NAME, DB_VERSION)
+            .map_err(js_error)?;
+
+        let upgrade_request = request.clone();
+        let on_upgrade_needed = Closure::once_into_js(move |_: Event| {
+            if let Ok(db) = upgrade_request
+                .result()
+                .and_then(|r| r.dyn_into::<IdbDatabase>())
+            {
+                if !db.object_store_names().contains(HISTORY_STORE) {
+                    let _ = db.create_object_store(HISTORY_STORE);
+                }
+            }
+        });
+        request.set_onupgradeneeded(Some(on_upgrade_needed.unchecked_ref()));
+
+        let success_request = request.clone();
+        let success_callback = callback.clone();
+        let on_success = Closure::once_into_js(move |_: Event| {
+            let result = success_request
+                .result()
+                .and_then(|r| r.dyn_into::<IdbDatabase>())
+                .map(|db| HistoryStore {
+                    db,
+                    _on_abort: None,
+                })
+                .map_err(js_error);
+            success_callback.emit(result);
+        });
+        request.set_onsuccess(Some(on_success.unchecked_ref()));
+
+        let error_request: IdbOpenDbRequest = request.clone();
+        let on_error = Closure::once_into_js(move |_: Event| {
+            let message = error_request
+                .error()
+                .ok()
+                .flatten()
+                .map(|e| e.message())
+                .unwrap_or_else(|| "Unknown IndexedDB error".to_string());
+            callback.emit(Err(message));
+        });
+        request.set_onerror(Some(on_error.unchecked_ref()));
+        Ok(())
+    }
+
+    /// Loads every stored (key, value) pair
+    pub fn load_all(&self, callback: Callback<Result<Vec<(String, String)>, String>>) {
+        let result = self
+            .db
+            .transaction_with_str_and_mode(HISTORY_STORE, IdbTransactionMode::Readonly)
+            .and_then(|transaction| {
+                let store = transaction.object_store(HISTORY_STORE)?;
+                let keys_request = store.get_all_keys()?;
+                let values_request = store.get_all()?;
+                let complete_callback = callback.clone();
+                let on_complete = Closure::once_into_js(move |_: Event| {
+                    complete_callback.emit(Self::zip_results(&keys_request, &values_request));
+                });
+                transaction.set_oncomplete(Some(on_complete.unchecked_ref()));
+                let error_callback = callback.clone();
+                let on_error = Closure::once_into_js(move |_: Event| {
+                    error_callback.emit(Err("Could not load history".to_string()));
+                });
+                transaction.set_onerror(Some(on_error.unchecked_ref()));
+                Ok(())
+            });
+        if let Err(e) = result {
+            callback.emit(Err(js_error(e)));
+        }
+    }
+
+    fn zip_results(
+        keys_request: &IdbRequest,
+        values_request: &IdbRequest,
+    ) -> Result<Vec<(String, String)>, String> {
+        let keys: Array = keys_request.result().map_err(js_error)?.unchecked_into();
+        let values: Array = values_request.result().map_err(js_error)?.unchecked_into();
+        Ok(keys
+            .iter()
+            .zip(values.iter())
+            .filter_map(|(k, v)| Some((k.as_string()?, v.as_string()?)))
+            .collect())
+    }
+
+    /// Writes are fire and forget, so running out of room only shows up later, when
+    /// the transaction aborts. Abort events bubble up to the database, so one listener
+    /// there covers every write
+    pub fn on_quota_exceeded(&mut self, callback: Callback<()>) {
+        let on_abort = Closure::wrap(Box::new(move |event: Event| {
+            let quota_exceeded = event
+                .target()
+                .and_then(|target| target.dyn_into::<IdbTransaction>().ok())
+                .and_then(|transaction| transaction.error())
+                .map(|e| is_quota_exceeded(&e))
+                .unwrap_or(false);
+            if quota_exceeded {
+                callback.emit(());
+            }
+        }) as Box<dyn FnMut(Event)>);
+        self.db.set_onabort(Some(on_abort.as_ref().unchecked_ref()));
+        self._on_abort = Some(on_abort);
+    }
+
+    pub fn put(&self, key: &str, value: &str) -> Result<(), String> {
+        self.db
+            .transaction_with_str_and_mode(HISTORY_STORE, IdbTransactionMode::Readwrite)
+            .and_then(|transaction| transaction.object_store(HISTORY_STORE))
+            .and_then(|store| {
+                store.put_with_key(&JsValue::from_str(value), &JsValue::from_str(key))
+            })
+            .map(|_| ())
+            .map_err(js_error)
+    }
+
+    /// Writes every pair in one transaction, calling back once it's been committed or
+    /// has failed, for when a write has to be known to have happened
+    pub fn put_all(&self, entries: &[(String, String)], callback: Callback<Result<(), String>>) {
+        let result = self
+            .db
+            .transaction_with_str_and_mode(HISTORY_STORE, IdbTransactionMode::Readwrite)
+            .and_then(|transaction| {
+                let store = transaction.object_store(HISTORY_STORE)?;
+                for (key, value) in entries {
+                    store.put_with_key(&JsValue::from_str(value), &JsValue::from_str(key))?;
+                }
+                let complete_callback = callback.clone();
+                let on_complete = Closure::once_into_js(move |_: Event| {
+                    complete_callback.emit(Ok(()));
+                });
+                transaction.set_oncomplete(Some(on_complete.unchecked_ref()));
+                // which failed writes end in, as well as running out of room
+                let abort_callback = callback.clone();
+                let on_abort = Closure::once_into_js(move |_: Event| {
+                    abort_callback.emit(Err("Could not write history".to_string()));
+                });
+                transaction.set_onabort(Some(on_abort.unchecked_ref()));
+                Ok(())
+            });
+        if let Err(e) = result {
+            callback.emit(Err(js_error(e)));
+        }
+    }
+
+    pub fn delete(&self, key: &str) -> Result<(), String> {
+        self.db
+            .transaction_with_str_and_mode(HISTORY_STORE, IdbTransactionMode::Readwrite)
+            .and_then(|transaction| transaction.object_store(HISTORY_STORE))
+            .and_then(|store| store.delete(&JsValue::from_str(key)))
+            .map(|_| ())
+            .map_err(js_error)
+    }
+}
@@ -0,0 +1,250 @@
+use serde::{Deserialize, Serialize};
+
+/// The languages the UI can be shown in
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Language {
+    English,
+    German,
+    French,
+    Spanish,
+}
+
+impl Language {
+    /// The ones offered in the settings. A language is only added once every string
+    /// in the UI has gone through its catalog; until then it'd be a mix with English
+    pub const OFFERED: [Language; 1] = [Language::English];
+
+    pub fn is_offered(self) -> bool {
+        Language::OFFERED.contains(&self)
+    }
+
+    /// The BCP 47 code, which is also what numbers and dates are formatted for
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::German => "de",
+            Language::French => "fr",
+            Language::Spanish => "es",
+        }
+    }
+
+    /// In the language itself, so that it can be found whatever the UI is in
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::German => "Deutsch",
+            Language::French => "Français",
+            Language::Spanish => "Español",
+        }
+    }
+
+    /// The language for a locale like `de-AT`, or English for ones that aren't offered
+    pub fn from_locale(locale: &str) -> Language {
+        let language = locale.split(['-', '_']).next().unwrap_or("");
+        Language::OFFERED
+            .iter()
+            .copied()
+            .find(|l| l.code().eq_ignore_ascii_case(language))
+            .unwrap_or(Language::English)
+    }
+}
+
+/// The locale numbers and dates are formatted for: the chosen language's, or failing
+/// that the browser's
+pub fn locale(language: Option<Language>) -> String {
+    match language {
+        Some(language) => language.code().to_string(),
+        None => crate::number_format::browser_locale(),
+    }
+}
+
+/// A phrase the UI uses, looked up in each language's catalog with `t`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Phrase {
+    Watchlist,
+    Portfolio,
+    Compare,
+    Alerts,
+    Settings,
+    Connect,
+    Disconnect,
+    Demo,
+    DemoTitle,
+    ApiKeyPlaceholder,
+    TickerPlaceholder,
+    NoAlertsYet,
+    Language,
+    BrowserLanguage,
+    JustNow,
+}
+
+/// `text` in `language`. Every catalog is a full match, so a missing translation
+/// doesn't compile
+pub fn t(language: Language, text: Phrase) -> &'static str {
+    match language {
+        Language::English => english(text),
+        Language::German => german(text),
+        Language::French => french(text),
+        Language::Spanish => spanish(text),
+    }
+}
+
+fn english(text: Phrase) -> &'static str {
+    match text {
+        Phrase::Watchlist => "Watchlist",
+        Phrase::Portfolio => "Portfolio",
+        Phrase::Compare => "Compare",
+        Phrase::Alerts => "Alerts",
+        Phrase::Settings => "Settings",
+        Phrase::Connect => "Connect to finnhub.io",
+        Phrase::Disconnect => "Disconnect from finnhub.io",
+        Phrase::Demo => "Demo",
+        Phrase::DemoTitle => "Try it out with simulated trades, no API key needed",
+        Phrase::ApiKeyPlaceholder => "finnhub.io API Key",
+        Phrase::TickerPlaceholder => "Ticker symbol, or several separated by commas",
+        Phrase::NoAlertsYet => "No alerts yet",
+        Phrase::Language => "Language",
+        Phrase::BrowserLanguage => "Browser language",
+        Phrase::JustNow => "just now",
+    }
+}
+
+fn german(text: Phrase) -> &'static str {
+    match text {
+        Phrase::Watchlist => "Beobachtungsliste",
+        Phrase::Portfolio => "Portfolio",
+        Phrase::Compare => "Vergleich",
+        Phrase::Alerts => "Alarme",
+        Phrase::Settings => "Einstellungen",
+        Phrase::Connect => "Mit finnhub.io verbinden",
+        Phrase::Disconnect => "Von finnhub.io trennen",
+        Phrase::Demo => "Demo",
+        Phrase::DemoTitle => "Mit simulierten Trades ausprobieren, ohne API-Schlüssel",
+        Phrase::ApiKeyPlaceholder => "finnhub.io API-Schlüssel",
+        Phrase::TickerPlaceholder => "Tickersymbol, oder mehrere durch Kommas getrennt",
+        Phrase::NoAlertsYet => "Noch keine Alarme",
+        Phrase::Language => "Sprache",
+        Phrase::BrowserLanguage => "Browsersprache",
+        Phrase::JustNow => "gerade eben",
+    }
+}
+
+fn french(text: Phrase) -> &'static str {
+    match text {
+        Phrase::Watchlist => "Liste de suivi",
+        Phrase::Portfolio => "Portefeuille",
+        Phrase::Compare => "Comparer",
+        Phrase::Alerts => "Alertes",
+        Phrase::Settings => "Paramètres",
+        Phrase::Connect => "Se connecter à finnhub.io",
+        Phrase::Disconnect => "Se déconnecter de finnhub.io",
+        Phrase::Demo => "Démo",
+        Phrase::DemoTitle => "Essayer avec des transactions simulées, sans clé d'API",
+        Phrase::ApiKeyPlaceholder => "Clé d'API finnhub.io",
+        Phrase::TickerPlaceholder => "Symbole, ou plusieurs séparés par des virgules",
+        Phrase::NoAlertsYet => "Aucune alerte pour l'instant",
+        Phrase::Language => "Langue",
+        Phrase::BrowserLanguage => "Langue du navigateur",
+        Phrase::JustNow => "à l'instant",
+    }
+}
+
+fn spanish(text: Phrase) -> &'static str {
+    match text {
+        Phrase::Watchlist => "Lista de seguimiento",
+        Phrase::Portfolio => "Cartera",
+        Phrase::Compare => "Comparar",
+        Phrase::Alerts => "Alertas",
+        Phrase::Settings => "Ajustes",
+        Phrase::Connect => "Conectar a finnhub.io",
+        Phrase::Disconnect => "Desconectar de finnhub.io",
+        Phrase::Demo => "Demo",
+        Phrase::DemoTitle => "Pruébalo con operaciones simuladas, sin clave de API",
+        Phrase::ApiKeyPlaceholder => "Clave de API de finnhub.io",
+        Phrase::TickerPlaceholder => "Símbolo, o varios separados por comas",
+        Phrase::NoAlertsYet => "Aún no hay alertas",
+        Phrase::Language => "Idioma",
+        Phrase::BrowserLanguage => "Idioma del navegador",
+        Phrase::JustNow => "justo ahora",
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TimeUnit {
+    Second,
+    Minute,
+    Hour,
+    Day,
+}
+
+/// e.g. "3 minutes ago"; word order and plurals differ too much between languages
+/// for a single catalog entry
+pub fn ago(language: Language, amount: i64, unit: TimeUnit) -> String {
+    let one = amount == 1;
+    let pick = |singular: &'static str, plural: &'static str| if one { singular } else { plural };
+    match language {
+        Language::English => {
+            let unit = match unit {
+                TimeUnit::Second => pick("second", "seconds"),
+                TimeUnit::Minute => pick("minute", "minutes"),
+                TimeUnit::Hour => pick("hour", "hours"),
+                TimeUnit::Day => pick("day", "days"),
+            };
+            format!("{} {} ago", amount, unit)
+        }
+        Language::German => {
+            let unit = match unit {
+                TimeUnit::Second => pick("Sekunde", "Sekunden"),
+                TimeUnit::Minute => pick("Minute", "Minuten"),
+                TimeUnit::Hour => pick("Stunde", "Stunden"),
+                TimeUnit::Day => pick("Tag", "Tagen"),
+            };
+            format!("vor {} {}", amount, unit)
+        }
+        Language::French => {
+            let unit = match unit {
+                TimeUnit::Second => pick("seconde", "secondes"),
+                TimeUnit::Minute => pick("minute", "minutes"),
+                TimeUnit::Hour => pick("heure", "heures"),
+                TimeUnit::Day => pick("jour", "jours"),
+            };
+            format!("il y a {} {}", amount, unit)
+        }
+        Language::Spanish => {
+            let unit = match unit {
+                TimeUnit::Second => pick("segundo", "segundos"),
+                TimeUnit::Minute => pick("minuto", "minutos"),
+                TimeUnit::Hour => pick("hora", "horas"),
+                TimeUnit::Day => pick("día", "días"),
+            };
+            format!("hace {} {}", amount, unit)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_the_language_of_the_locale() {
+        assert_eq!(Language::from_locale("en-GB"), Language::English);
+        assert_eq!(Language::from_locale("EN"), Language::English);
+        // not offered until the UI's fully translated
+        assert_eq!(Language::from_locale("de-AT"), Language::English);
+        assert_eq!(Language::from_locale("ja-JP"), Language::English);
+        assert_eq!(Language::from_locale(""), Language::English);
+        assert_eq!(t(Language::Spanish, Phrase::Settings), "Ajustes");
+    }
+
+    #[test]
+    fn says_how_long_ago_with_plurals() {
+        assert_eq!(ago(Language::English, 1, TimeUnit::Minute), "1 minute ago");
+        assert_eq!(ago(Language::English, 3, TimeUnit::Day), "3 days ago");
+        assert_eq!(ago(Language::German, 2, TimeUnit::Hour), "vor 2 Stunden");
+        assert_eq!(
+            ago(Language::French, 1, TimeUnit::Second),
+            "il y a 1 seconde"
+        );
+    }
+}
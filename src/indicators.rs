@@ -0,0 +1,44 @@
+/// Simple moving average of each price with the `period - 1` before it; `None` until
+/// there are enough prices. Prices are oldest first
+pub fn sma(prices: &[f64], period: usize) -> Vec<Option<f64>> {
+    if period == 0 {
+        return vec![None; prices.len()];
+    }
+    let mut sum = 0.0;
+    prices
+        .iter()
+        .enumerate()
+        .map(|(idx, price)| {
+            sum += price;
+            if idx >= period {
+                sum -= prices[idx - period];
+            }
+            if idx + 1 >= period {
+                Some(sum / period as f64)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Exponential moving average, seeded with the SMA of the first `period` prices.
+/// Prices are oldest first
+pub fn ema(prices: &[f64], period: usize) -> Vec<Option<f64>> {
+    if period == 0 {
+        return vec![None; prices.len()];
+    }
+    let smoothing = 2.0 / (period as f64 + 1.0);
+    let mut previous: Option<f64> = None;
+    sma(prices, period)
+        .into_iter()
+        .zip(prices)
+        .map(|(seed, price)| {
+            previous = match previous {
+                Some(previous) => Some(price * smoothing + previous * (1.0 - smoothing)),
+                None => seed,
+            };
+            previous
+        })
+        .collect()
+}
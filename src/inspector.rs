@@ -0,0 +1,99 @@
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+
+/// Older entries are dropped beyond this many
+const MAX_ENTRIES: usize = 50;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EntryKind {
+    /// A websocket frame, as received
+    Frame,
+    /// Connecting, connected, disconnected and so on
+    Event,
+    /// A frame, or part of one, that couldn't be made sense of
+    ParseFailure,
+}
+
+impl EntryKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            EntryKind::Frame => "frame",
+            EntryKind::Event => "event",
+            EntryKind::ParseFailure => "parse failure",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub kind: EntryKind,
+    pub time: DateTime<Utc>,
+    pub text: String,
+}
+
+/// The latest raw traffic and connection events, newest first, for debugging the
+/// protocol without devtools. Nothing's kept for long, so it isn't persisted
+#[derive(Default, Debug)]
+pub struct Inspector {
+    entries: VecDeque<Entry>,
+}
+
+impl Inspector {
+    pub fn push(&mut self, kind: EntryKind, text: impl Into<String>, time: DateTime<Utc>) {
+        self.entries.push_front(Entry {
+            kind,
+            time,
+            text: text.into(),
+        });
+        self.entries.truncate(MAX_ENTRIES);
+    }
+
+    /// Pretty-printed up front, since a frame is rendered many more times than it's
+    /// received
+    pub fn push_frame(&mut self, frame: &str, time: DateTime<Utc>) {
+        self.push(EntryKind::Frame, pretty_print(frame), time);
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Indented JSON, or the frame as it came if it isn't JSON
+fn pretty_print(frame: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(frame)
+        .and_then(|value| serde_json::to_string_pretty(&value))
+        .unwrap_or_else(|_| frame.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_newest_entries_pretty_printed() {
+        let mut inspector = Inspector::default();
+        for n in 0..MAX_ENTRIES + 5 {
+            inspector.push(EntryKind::Event, n.to_string(), Utc::now());
+        }
+        inspector.push_frame(r#"{"type":"ping"}"#, Utc::now());
+        inspector.push_frame("not json", Utc::now());
+        assert_eq!(inspector.entries().count(), MAX_ENTRIES);
+        let texts: Vec<&str> = inspector
+            .entries()
+            .map(|e| e.text.as_str())
+            .take(2)
+            .collect();
+        assert_eq!(texts, vec!["not json", "{\n  \"type\": \"ping\"\n}"]);
+        inspector.clear();
+        assert!(inspector.is_empty());
+    }
+}
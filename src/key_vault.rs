@@ -0,0 +1,153 @@
+use js_sys::{Array, Uint8Array};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{AesDerivedKeyParams, AesGcmParams, CryptoKey, Pbkdf2Params, SubtleCrypto};
+use yew::Callback;
+
+/// Slows down guessing the passphrase; OWASP's recommendation for PBKDF2-SHA256
+const PBKDF2_ITERATIONS: u32 = 600_000;
+const SALT_LEN: usize = 16;
+/// AES-GCM's recommended nonce length
+const IV_LEN: usize = 12;
+
+/// Something encrypted with a key derived from a passphrase (PBKDF2, then AES-GCM),
+/// along with what's needed to derive the key again. None of it is secret
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct Sealed {
+    salt: Vec<u8>,
+    iv: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+fn js_error(e: JsValue) -> String {
+    e.as_string()
+        .or_else(|| {
+            e.dyn_ref::<js_sys::Error>()
+                .map(|e| String::from(e.message()))
+        })
+        .unwrap_or_else(|| format!("{:?}", e))
+}
+
+fn subtle() -> Result<SubtleCrypto, String> {
+    let crypto = web_sys::window()
+        .ok_or("No window")?
+        .crypto()
+        .map_err(js_error)?;
+    Ok(crypto.subtle())
+}
+
+fn random_bytes(len: usize) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![0; len];
+    web_sys::window()
+        .ok_or("No window")?
+        .crypto()
+        .map_err(js_error)?
+        .get_random_values_with_u8_array(&mut bytes)
+        .map_err(js_error)?;
+    Ok(bytes)
+}
+
+async fn derive_key(
+    subtle: &SubtleCrypto,
+    passphrase: &str,
+    salt: &[u8],
+) -> Result<CryptoKey, JsValue> {
+    let usages = |usages: &[&str]| -> JsValue {
+        usages
+            .iter()
+            .map(|usage| JsValue::from_str(usage))
+            .collect::<Array>()
+            .into()
+    };
+    let passphrase = Uint8Array::from(passphrase.as_bytes());
+    let base_key = JsFuture::from(subtle.import_key_with_str(
+        "raw",
+        &passphrase,
+        "PBKDF2",
+        false,
+        &usages(&["deriveKey"]),
+    )?)
+    .await?;
+    let params = Pbkdf2Params::new(
+        "PBKDF2",
+        &JsValue::from_str("SHA-256"),
+        PBKDF2_ITERATIONS,
+        &Uint8Array::from(salt),
+    );
+    let key = JsFuture::from(subtle.derive_key_with_object_and_object(
+        &params,
+        &base_key.dyn_into()?,
+        &AesDerivedKeyParams::new("AES-GCM", 256),
+        false,
+        &usages(&["encrypt", "decrypt"]),
+    )?)
+    .await?;
+    key.dyn_into()
+}
+
+/// The bytes of the `ArrayBuffer` a promise resolved to
+fn to_vec(buffer: JsValue) -> Vec<u8> {
+    Uint8Array::new(&buffer).to_vec()
+}
+
+async fn seal_async(plaintext: String, passphrase: String) -> Result<Sealed, String> {
+    let subtle = subtle()?;
+    let salt = random_bytes(SALT_LEN)?;
+    let iv = random_bytes(IV_LEN)?;
+    let key = derive_key(&subtle, &passphrase, &salt)
+        .await
+        .map_err(js_error)?;
+    let mut data = plaintext.into_bytes();
+    let promise = subtle
+        .encrypt_with_object_and_u8_array(
+            &AesGcmParams::new("AES-GCM", &Uint8Array::from(iv.as_slice())),
+            &key,
+            &mut data,
+        )
+        .map_err(js_error)?;
+    let ciphertext = JsFuture::from(promise)
+        .await
+        .map(to_vec)
+        .map_err(js_error)?;
+    Ok(Sealed {
+        salt,
+        iv,
+        ciphertext,
+    })
+}
+
+async fn open_async(sealed: Sealed, passphrase: String) -> Result<String, String> {
+    let subtle = subtle()?;
+    let key = derive_key(&subtle, &passphrase, &sealed.salt)
+        .await
+        .map_err(js_error)?;
+    let mut data = sealed.ciphertext;
+    let promise = subtle
+        .decrypt_with_object_and_u8_array(
+            &AesGcmParams::new("AES-GCM", &Uint8Array::from(sealed.iv.as_slice())),
+            &key,
+            &mut data,
+        )
+        .map_err(js_error)?;
+    // a wrong passphrase makes for a key that fails GCM's authentication check
+    let plaintext = JsFuture::from(promise)
+        .await
+        .map(to_vec)
+        .map_err(|_| "Wrong passphrase".to_string())?;
+    String::from_utf8(plaintext).map_err(|e| e.to_string())
+}
+
+/// Encrypts `plaintext` with `passphrase`, handing the result to the callback
+pub fn seal(plaintext: String, passphrase: String, callback: Callback<Result<Sealed, String>>) {
+    spawn_local(async move {
+        callback.emit(seal_async(plaintext, passphrase).await);
+    });
+}
+
+/// Decrypts what `seal` produced, failing if the passphrase is wrong
+pub fn open(sealed: Sealed, passphrase: String, callback: Callback<Result<String, String>>) {
+    spawn_local(async move {
+        callback.emit(open_async(sealed, passphrase).await);
+    });
+}
@@ -1,130 +1,1063 @@
-#![recursion_limit = "256"]
+#![recursion_limit = "512"]
+
+mod alert_log;
+mod api_client;
+mod audio;
+mod csv_import;
+mod currency;
+mod decimal;
+mod finnhub;
+mod frame_codec;
+mod history_store;
+mod i18n;
+mod indicators;
+mod inspector;
+mod key_vault;
+mod logger;
+mod market_hours;
+mod metadata_cache;
+mod metrics;
+mod migrations;
+mod notifications;
+mod number_format;
+mod replay;
+mod rules;
+mod share;
+mod shortcuts;
+mod simulator;
+mod stats;
+mod symbol_card;
+mod symbol_list;
+mod tab_sync;
+mod time_axis;
+mod time_format;
+mod trade_worker;
+mod ws;
 
 use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
 use yew::prelude::*;
 
-use anyhow::Error;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::{Ordering, Reverse};
 use std::collections::hash_map::Entry;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::ops::Deref;
+use std::rc::Rc;
 use yew::format::Json;
-use yew::services::websocket::{WebSocketStatus, WebSocketTask};
-use yew::services::{ConsoleService, DialogService, StorageService, WebSocketService};
+use yew::format::Text;
+use yew::services::interval::IntervalTask;
+use yew::services::reader::{File, FileData, ReaderService, ReaderTask};
+use yew::services::timeout::TimeoutTask;
+use yew::services::websocket::WebSocketStatus;
+use yew::services::{IntervalService, StorageService, TimeoutService, WebSocketService};
 
-use chrono::serde::ts_milliseconds;
-use chrono::{DateTime, Utc};
+use chrono::serde::ts_seconds;
+use chrono::{DateTime, Duration, Local, Utc};
 use yew::services::storage::Area;
 
-#[derive(Deserialize, Serialize)]
+use web_sys::{
+    DataTransfer, Element, Event, HtmlElement, MediaQueryList, MediaQueryListEvent, UiEvent,
+};
+
+use alert_log::{AlertKind, AlertLog, DoNotDisturb, Snoozes};
+use api_client::{ApiClient, ApiTask};
+use audio::Beeper;
+use currency::{Currency, FxRates};
+use decimal::Decimal;
+use finnhub::{
+    rest, BidAsk, Candles, CompanyProfile, EarningsCalendar, EarningsRelease, NewsItem, Price,
+    Quote, Request, Symbol, SymbolError, SymbolKind, TickerInfo, Volume, WsMessage, EXCHANGES,
+};
+use frame_codec::Frame;
+use history_store::{is_quota_exceeded, HistoryStore};
+use i18n::{Language, Phrase};
+use inspector::{EntryKind, Inspector};
+use key_vault::Sealed;
+use logger::{LogLevel, Logger};
+use metadata_cache::MetadataCache;
+use metrics::Metrics;
+use migrations::Restored;
+use notifications::{Level, NotificationList, Notifications};
+use number_format::{NumberFormat, NumberStyle};
+use replay::{Replay, ReplaySpeed};
+use rules::{Condition, RuleEngine};
+use shortcuts::{KeyboardShortcuts, Shortcut};
+use simulator::Simulator;
+use stats::{RunningStats, SpikeThreshold};
+use symbol_card::SymbolCard;
+use symbol_list::{ListedSymbol, SymbolList};
+use tab_sync::{Election, TabMessage, TabSync};
+use time_axis::{AxisSpacing, TimeAxis};
+use time_format::{TimeFormatter, TimeZoneSetting};
+use trade_worker::{TradeDelta, TradeFilter, TradeTotals, TradeWorker, WorkerInput, WorkerOutput};
+use ws::{Session, SharedConnector, WsAuth, WsTarget};
+
+#[derive(Deserialize, Serialize, Clone, PartialEq, Default)]
 struct ApiKey(String);
 
-#[derive(Deserialize, Serialize, Hash, PartialEq, Eq, Clone, Debug)]
-struct Symbol(String);
+/// Named API keys (e.g. sandbox vs production) to switch between. Like the plain API
+/// key, these are kept out of `State` and stored according to `ApiKeyStorage`
+#[derive(Deserialize, Serialize, Default)]
+struct ApiKeyProfiles {
+    active: Option<String>,
+    /// By name, so the dropdown comes out sorted
+    profiles: BTreeMap<String, ApiKey>,
+}
+
+/// What came of checking the API key against the REST API before connecting with it
+#[derive(Clone, PartialEq, Debug)]
+enum KeyCheck {
+    Unchecked,
+    Checking,
+    Valid,
+    Invalid,
+    /// The check itself failed, e.g. for being offline, so the key might be fine
+    Inconclusive,
+}
+
+/// REST data that only some finnhub plans come with. Found out about by asking, since
+/// there's no endpoint for what a key's plan covers
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum PlanFeature {
+    BidAsk,
+    FxRates,
+    News,
+    Earnings,
+    Candles,
+}
+
+impl PlanFeature {
+    fn label(self) -> &'static str {
+        match self {
+            PlanFeature::BidAsk => "Bid/ask",
+            PlanFeature::FxRates => "Currency conversion",
+            PlanFeature::News => "Company news",
+            PlanFeature::Earnings => "The earnings calendar",
+            PlanFeature::Candles => "Backfilling history with candles",
+        }
+    }
+}
+
+/// Where the API key is kept between visits
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug, Default)]
+enum ApiKeyStorage {
+    #[default]
+    Local,
+    Session,
+    Memory,
+}
+
+/// How much of `State` is saved between visits
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug, Default)]
+enum PersistencePolicy {
+    /// Just the tracked symbols
+    Watchlist,
+    WatchlistAndSettings,
+    /// Including trade history and everything set per symbol
+    #[default]
+    Everything,
+}
+
+impl Symbol {
+    /// Made up by the demo's simulator rather than known to finnhub
+    fn is_demo(&self) -> bool {
+        self.exchange() == Some(simulator::EXCHANGE)
+    }
+}
+
+/// How a symbol's prices are shown, which depends on the kind of symbol and, once we
+/// know it, the currency it trades in
+#[derive(Clone, PartialEq, Debug)]
+struct PriceFormat {
+    precision: usize,
+    /// Chosen in the settings, so prices are rounded to it rather than only padded
+    fixed_precision: bool,
+    currency: Option<Currency>,
+    number_format: NumberFormat,
+}
+
+impl PriceFormat {
+    /// Pads to the usual precision, but never drops digits that were actually sent
+    /// unless the precision was chosen in the settings
+    fn format(&self, price: Price) -> String {
+        let precision = if self.fixed_precision {
+            self.precision
+        } else {
+            self.precision.max(price.0.scale() as usize)
+        };
+        self.number_format.decimal(price.0, precision)
+    }
 
-#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, PartialOrd)]
-struct Price(f32);
+    fn format_f64(&self, value: f64) -> String {
+        self.number_format.fixed(value, self.precision)
+    }
 
-#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, PartialOrd)]
-struct Volume(f32);
+    fn header(&self) -> String {
+        match &self.currency {
+            Some(currency) => format!("Price ({})", currency.sign()),
+            None => "Price".to_string(),
+        }
+    }
+}
 
-/// This is a single Stock info payload that comes from the FinnPub API
+/// A trade as it's kept and shown: what finnhub sent, along with what was made of it
+/// on the way in. Only the former is saved
 #[derive(Deserialize, Serialize, Debug)]
-struct TickerInfo {
-    #[serde(rename = "s")]
+#[serde(transparent)]
+struct Trade {
+    info: TickerInfo,
+    /// How the price moved since the previous trade; only known for trades we saw live
+    #[serde(skip)]
+    direction: Option<PriceDirection>,
+    /// Whether it set a new session high or low; also only known for live trades
+    #[serde(skip)]
+    session_extreme: Option<SessionExtreme>,
+    /// Whether it was unusually far from the trades before it; also only known for
+    /// live trades
+    #[serde(skip)]
+    spike: bool,
+}
+
+impl From<TickerInfo> for Trade {
+    fn from(info: TickerInfo) -> Trade {
+        Trade {
+            info,
+            direction: None,
+            session_extreme: None,
+            spike: false,
+        }
+    }
+}
+
+impl Deref for Trade {
+    type Target = TickerInfo;
+
+    fn deref(&self) -> &TickerInfo {
+        &self.info
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum PriceDirection {
+    Up,
+    Down,
+    Unchanged,
+}
+
+impl PriceDirection {
+    fn arrow(self) -> &'static str {
+        match self {
+            PriceDirection::Up => "▲",
+            PriceDirection::Down => "▼",
+            PriceDirection::Unchanged => "—",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum SessionExtreme {
+    High,
+    Low,
+}
+
+/// A live trade as shown on the tape, which runs across every tracked symbol
+#[derive(Clone, Debug)]
+struct TapeTrade {
     symbol: Symbol,
-    #[serde(rename = "p")]
     price: Price,
-    #[serde(rename = "v")]
     volume: Volume,
-    #[serde(with = "ts_milliseconds", rename = "t")]
     time: DateTime<Utc>,
+    direction: Option<PriceDirection>,
+}
+
+impl TapeTrade {
+    fn new(ticker_info: &Trade) -> TapeTrade {
+        TapeTrade {
+            symbol: ticker_info.symbol.clone(),
+            price: ticker_info.price,
+            volume: ticker_info.volume,
+            time: ticker_info.time,
+            direction: ticker_info.direction,
+        }
+    }
+}
+
+impl SessionExtreme {
+    fn label(self) -> &'static str {
+        match self {
+            SessionExtreme::High => "HOD",
+            SessionExtreme::Low => "LOD",
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            SessionExtreme::High => "New session high",
+            SessionExtreme::Low => "New session low",
+        }
+    }
+
+    fn badge_class(self) -> &'static str {
+        match self {
+            SessionExtreme::High => "badge badge-success ml-1",
+            SessionExtreme::Low => "badge badge-danger ml-1",
+        }
+    }
+}
+
+/// The highest and lowest live prices since the page was loaded
+#[derive(Clone, Copy, Debug)]
+struct SessionRange {
+    high: Price,
+    low: Price,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(tag = "type", rename_all = "lowercase")]
-enum Request {
-    Subscribe { symbol: Symbol },
-    Unsubscribe { symbol: Symbol },
+impl SessionRange {
+    fn new(price: Price) -> SessionRange {
+        SessionRange {
+            high: price,
+            low: price,
+        }
+    }
+
+    /// Which extreme, if any, `price` is a new one of
+    fn update(&mut self, price: Price) -> Option<SessionExtreme> {
+        if price > self.high {
+            self.high = price;
+            Some(SessionExtreme::High)
+        } else if price < self.low {
+            self.low = price;
+            Some(SessionExtreme::Low)
+        } else {
+            None
+        }
+    }
+}
+
+/// Live trades by which way they moved the price, counted as they come in
+#[derive(Clone, Copy, Default, Debug)]
+struct TickCounts {
+    upticks: u64,
+    downticks: u64,
+}
+
+impl TickCounts {
+    fn record(&mut self, direction: PriceDirection) {
+        match direction {
+            PriceDirection::Up => self.upticks += 1,
+            PriceDirection::Down => self.downticks += 1,
+            PriceDirection::Unchanged => {}
+        }
+    }
+
+    /// Share of the trades that moved the price that moved it up
+    fn uptick_ratio(&self) -> Option<f64> {
+        let moved = self.upticks + self.downticks;
+        if moved > 0 {
+            Some(self.upticks as f64 / moved as f64)
+        } else {
+            None
+        }
+    }
 }
 
-/// The different messages that we'll get from the websocket connection
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(tag = "type", rename_all = "lowercase")]
-enum WsMessage {
-    Error {
-        #[serde(rename = "msg")]
-        message: String,
-    },
-    Ping,
-    Trade {
-        data: Vec<TickerInfo>,
-    },
+/// A symbol's next earnings release as of when we last asked, which is at most daily
+#[derive(Deserialize, Serialize, Clone, Debug)]
+struct EarningsCache {
+    next: Option<EarningsRelease>,
+    #[serde(with = "ts_seconds")]
+    fetched_at: DateTime<Utc>,
+}
+
+impl EarningsCache {
+    fn new(calendar: EarningsCalendar, now: DateTime<Utc>) -> EarningsCache {
+        let today = now.naive_utc().date();
+        let next = calendar
+            .earnings_calendar
+            .into_iter()
+            .filter(|release| release.date >= today)
+            .min_by_key(|release| release.date);
+        EarningsCache {
+            next,
+            fetched_at: now,
+        }
+    }
+
+    fn is_stale(&self, now: DateTime<Utc>) -> bool {
+        now - self.fetched_at >= Duration::hours(EARNINGS_MAX_AGE_HOURS)
+    }
+
+    /// e.g. "Earnings in 3 days", if that's soon enough to mention
+    fn badge_label(&self, now: DateTime<Utc>) -> Option<String> {
+        let release = self.next.as_ref()?;
+        let days = (release.date - now.naive_utc().date()).num_days();
+        match days {
+            0 => Some("Earnings today".to_string()),
+            1 => Some("Earnings tomorrow".to_string()),
+            2..=EARNINGS_SOON_DAYS => Some(format!("Earnings in {} days", days)),
+            _ => None,
+        }
+    }
+}
+
+/// How often a websocket message type we don't know has turned up
+struct UnknownMessages {
+    count: u32,
+    last_payload: String,
 }
 
 #[derive(Deserialize, Serialize)]
 struct TickerHistory {
-    symbol_to_history: HashMap<Symbol, VecDeque<TickerInfo>>,
+    symbol_to_history: HashMap<Symbol, VecDeque<Trade>>,
+    /// Mirrors `Settings::history_retention_mins`
+    #[serde(skip)]
+    retention_mins: Option<u32>,
+    /// Of each symbol's history as it stands, kept up to date trade by trade
+    #[serde(skip)]
+    stats: HashMap<Symbol, RunningStats>,
 }
 
 impl TickerHistory {
     const MAX_HISTORY: usize = 25;
+    /// Still a cap when retaining by time, so that busy symbols can't grow unbounded
+    const MAX_TIMED_HISTORY: usize = 500;
 
     fn new() -> TickerHistory {
         TickerHistory {
             symbol_to_history: HashMap::new(),
+            retention_mins: None,
+            stats: HashMap::new(),
+        }
+    }
+
+    /// How many trades are kept per symbol, given a time-based retention (or not)
+    fn max_len(retention_mins: Option<u32>) -> usize {
+        match retention_mins {
+            Some(_) => Self::MAX_TIMED_HISTORY,
+            None => Self::MAX_HISTORY,
+        }
+    }
+
+    /// Switches between keeping a fixed number of trades and keeping those from the
+    /// last so many minutes, evicting whatever no longer fits
+    fn set_retention(&mut self, retention_mins: Option<u32>) {
+        self.retention_mins = retention_mins;
+        let now = Utc::now();
+        for (symbol, queue) in self.symbol_to_history.iter_mut() {
+            let stats = self.stats.entry(symbol.clone()).or_default();
+            Self::evict(queue, stats, retention_mins, now);
+        }
+    }
+
+    /// Drops trades off the old end of a newest-first queue, taking them out of its
+    /// stats as it goes
+    fn evict(
+        queue: &mut VecDeque<Trade>,
+        stats: &mut RunningStats,
+        retention_mins: Option<u32>,
+        now: DateTime<Utc>,
+    ) {
+        let max_len = Self::max_len(retention_mins);
+        let cutoff = retention_mins.map(|mins| now - Duration::minutes(i64::from(mins)));
+        while let Some(oldest) = queue.back() {
+            if queue.len() <= max_len && cutoff.is_none_or(|cutoff| oldest.time >= cutoff) {
+                break;
+            }
+            let (price, volume) = oldest.sample();
+            stats.remove(price, volume);
+            queue.pop_back();
         }
     }
 
-    fn get(&self, symbol: &Symbol) -> Option<&VecDeque<TickerInfo>> {
+    fn stats(&self, symbol: &Symbol) -> Option<&RunningStats> {
+        self.stats.get(symbol)
+    }
+
+    fn get(&self, symbol: &Symbol) -> Option<&VecDeque<Trade>> {
         self.symbol_to_history.get(symbol)
     }
 
-    fn insert(&mut self, ticker_info: TickerInfo) {
+    /// Where a trade goes in a newest-first queue: ahead of everything no newer than
+    /// it, or `None` if the very same trade is already there
+    fn position(queue: &VecDeque<Trade>, ticker_info: &TickerInfo) -> Option<usize> {
+        let idx = queue.partition_point(|t| t.time > ticker_info.time);
+        let duplicate = queue
+            .range(idx..)
+            .take_while(|t| t.time == ticker_info.time)
+            .any(|t| t.price == ticker_info.price && t.volume == ticker_info.volume);
+        if duplicate {
+            None
+        } else {
+            Some(idx)
+        }
+    }
+
+    /// Whether this very trade is already in the history, as happens when finnhub
+    /// sends trades again after a reconnect
+    fn contains(&self, ticker_info: &TickerInfo) -> bool {
+        self.get(&ticker_info.symbol)
+            .is_some_and(|queue| Self::position(queue, ticker_info).is_none())
+    }
+
+    /// Goes in by time, so that trades arriving out of order still end up where they
+    /// belong. Returns false, leaving the history be, for a trade it already has
+    fn insert(&mut self, ticker_info: Trade) -> bool {
         let symbol = ticker_info.symbol.clone();
-        match self.symbol_to_history.entry(symbol) {
-            Entry::Occupied(mut existing) => {
-                let queue = existing.get_mut();
-                queue.push_front(ticker_info);
-                if queue.len() > Self::MAX_HISTORY {
-                    queue.pop_back();
-                }
-            }
-            Entry::Vacant(vacant) => {
-                let mut new_queue = VecDeque::new();
-                new_queue.push_front(ticker_info);
-                vacant.insert(new_queue);
-            }
+        let queue = self.symbol_to_history.entry(symbol.clone()).or_default();
+        let idx = match Self::position(queue, &ticker_info) {
+            Some(idx) => idx,
+            None => return false,
+        };
+        let (price, volume) = ticker_info.sample();
+        let stats = self.stats.entry(symbol).or_default();
+        stats.add(price, volume);
+        queue.insert(idx, ticker_info);
+        Self::evict(queue, stats, self.retention_mins, Utc::now());
+        true
+    }
+
+    fn remove(&mut self, symbol: &Symbol) -> Option<VecDeque<Trade>> {
+        self.stats.remove(symbol);
+        self.symbol_to_history.remove(symbol)
+    }
+
+    fn clear(&mut self) {
+        self.symbol_to_history.clear();
+        self.stats.clear();
+    }
+
+    /// Drops the oldest quarter of every symbol's history to make room in storage,
+    /// returning whether there was anything to drop
+    fn trim_oldest(&mut self) -> bool {
+        let mut trimmed = false;
+        for (symbol, queue) in self.symbol_to_history.iter_mut() {
+            let keep = queue.len() - queue.len().div_ceil(4);
+            trimmed |= keep < queue.len();
+            queue.truncate(keep);
+            self.stats.insert(
+                symbol.clone(),
+                RunningStats::of(queue.iter().map(|t| t.sample())),
+            );
         }
+        trimmed
     }
 
-    fn remove(&mut self, symbol: &Symbol) {
-        self.symbol_to_history.remove(symbol);
+    /// Merges in candles that closed before the symbol's oldest trade; the rest would
+    /// overlap with live trades. Returns how many went in
+    fn backfill(&mut self, symbol: Symbol, trades: Vec<TickerInfo>) -> usize {
+        let oldest = self
+            .get(&symbol)
+            .and_then(|queue| queue.back())
+            .map(|t| t.time);
+        let older: Vec<Trade> = trades
+            .into_iter()
+            // stamped with when their minute starts, so one from less than a minute
+            // before would still cover some of the live trades
+            .filter(|t| oldest.is_none_or(|oldest| t.time + Duration::minutes(1) <= oldest))
+            .map(Trade::from)
+            .collect();
+        let count = older.len();
+        if count > 0 {
+            self.merge(symbol, older);
+        }
+        count
+    }
+
+    /// Merges trades into a symbol's existing history
+    fn merge(&mut self, symbol: Symbol, history: impl IntoIterator<Item = Trade>) {
+        let mut merged: Vec<Trade> = self
+            .symbol_to_history
+            .remove(&symbol)
+            .unwrap_or_default()
+            .into_iter()
+            .chain(history)
+            .collect();
+        // newest first, same as insert
+        merged.sort_by_key(|t| Reverse(t.time));
+        let mut queue: VecDeque<Trade> = merged
+            .into_iter()
+            .map(|mut trade| {
+                trade.info.symbol = symbol.clone();
+                trade
+            })
+            .collect();
+        let mut stats = RunningStats::of(queue.iter().map(|t| t.sample()));
+        Self::evict(&mut queue, &mut stats, self.retention_mins, Utc::now());
+        self.stats.insert(symbol.clone(), stats);
+        self.symbol_to_history.insert(symbol, queue);
+    }
+
+    /// Re-keys history under normalised symbols, merging the histories of symbols
+    /// that only differed by case or whitespace
+    fn normalise(&mut self) {
+        let old = std::mem::take(&mut self.symbol_to_history);
+        self.stats.clear();
+        for (symbol, history) in old {
+            self.merge(symbol.normalised(), history);
+        }
     }
 }
 
-#[derive(Deserialize, Serialize)]
+impl Default for TickerHistory {
+    fn default() -> TickerHistory {
+        TickerHistory::new()
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug, Default)]
+enum Theme {
+    Light,
+    Dark,
+    /// Follows the browser's `prefers-color-scheme`
+    #[default]
+    Auto,
+}
+
+/// How the watchlist is laid out
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug, Default)]
+enum ViewMode {
+    #[default]
+    Cards,
+    /// One row per symbol, for when there are a lot of them
+    Grid,
+    /// Cards side by side across the whole width, each as big as it's been made
+    Dashboard,
+}
+
+/// How many of the dashboard's columns and rows a card spans
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+struct CardSize {
+    cols: u32,
+    rows: u32,
+}
+
+impl CardSize {
+    const MAX_ROWS: u32 = 3;
+
+    /// Cycles through the widths that fit, back to one column after the widest
+    fn wider(self, columns: u32) -> CardSize {
+        let cols = if self.cols >= columns {
+            1
+        } else {
+            self.cols + 1
+        };
+        CardSize { cols, ..self }
+    }
+
+    fn taller(self) -> CardSize {
+        let rows = if self.rows >= CardSize::MAX_ROWS {
+            1
+        } else {
+            self.rows + 1
+        };
+        CardSize { rows, ..self }
+    }
+
+    /// Inline style for a card in a dashboard this many columns wide
+    fn grid_style(self, columns: u32) -> String {
+        format!(
+            "grid-column: span {}; grid-row: span {};",
+            self.cols.clamp(1, columns.max(1)),
+            self.rows.clamp(1, CardSize::MAX_ROWS)
+        )
+    }
+}
+
+impl Default for CardSize {
+    fn default() -> CardSize {
+        CardSize { cols: 1, rows: 1 }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
+struct Settings {
+    /// How long we wait without any message (ping or trade) before deciding the
+    /// connection is stale and reconnecting
+    heartbeat_timeout_secs: u32,
+    api_key_storage: ApiKeyStorage,
+    /// Silences all volume alerts
+    sound_muted: bool,
+    /// Alerts muted for a while from the alert log
+    alert_snoozes: Snoozes,
+    do_not_disturb: DoNotDisturb,
+    theme: Theme,
+    /// Trades below this volume are dropped, unless overridden per symbol
+    min_volume: Option<Volume>,
+    time_zone: TimeZoneSetting,
+    /// Symbols without a trade for this long get flagged as stale
+    stale_after_mins: u32,
+    view_mode: ViewMode,
+    /// Periods of the moving average columns in trade tables; `None` hides them
+    sma_period: Option<u32>,
+    ema_period: Option<u32>,
+    persistence: PersistencePolicy,
+    /// Keep the trades from this many minutes back rather than a fixed number of them
+    history_retention_mins: Option<u32>,
+    /// Trade table columns, in order; moving averages come after these
+    trade_columns: Vec<TradeColumn>,
+    /// Trades with at least this notional are highlighted as block trades
+    block_trade_notional: Option<Decimal>,
+    /// Prices and P&L are also shown converted into this currency; `None` turns it off
+    base_currency: Option<String>,
+    /// Where to connect instead of finnhub, e.g. a proxy or a local mock server
+    ws_base_url: Option<String>,
+    /// How the API key is given to the websocket server
+    ws_auth: WsAuth,
+    /// Logs every message received to the console
+    debug_logging: bool,
+    /// Shows the latest raw frames and connection events on the settings page
+    show_inspector: bool,
+    /// Shows the latest trades across all symbols next to the watchlist
+    show_tape: bool,
+    /// Polls each stock's bid and ask to show alongside the last trade
+    show_bid_ask: bool,
+    number_style: NumberStyle,
+    price_decimals: PriceDecimals,
+    /// Shows large volumes as e.g. 1.2M
+    abbreviate_volumes: bool,
+    /// Connects as soon as the page loads, if there's a key and something to track
+    auto_connect: bool,
+    /// Unsubscribes from everything once the tab's been hidden this long; `None` never does
+    pause_when_hidden_mins: Option<u32>,
+    /// Most symbols the plan lets us subscribe to at once; any more wait for a slot
+    subscription_limit: u32,
+    /// Flags trades this far from the recent mean; `None` doesn't look for spikes
+    spike_threshold: Option<SpikeThreshold>,
+    /// Also alerts on spikes, rather than only marking the trade
+    spike_alerts: bool,
+    /// How many cards fit side by side in the dashboard layout
+    dashboard_columns: u32,
+    /// What the UI's in, and numbers and dates are formatted for; `None` follows the
+    /// browser
+    language: Option<Language>,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            heartbeat_timeout_secs: 60,
+            api_key_storage: ApiKeyStorage::default(),
+            sound_muted: false,
+            alert_snoozes: Snoozes::default(),
+            do_not_disturb: DoNotDisturb::default(),
+            theme: Theme::default(),
+            min_volume: None,
+            time_zone: TimeZoneSetting::default(),
+            stale_after_mins: 5,
+            view_mode: ViewMode::default(),
+            sma_period: None,
+            ema_period: None,
+            persistence: PersistencePolicy::default(),
+            history_retention_mins: None,
+            trade_columns: TradeColumn::defaults(),
+            block_trade_notional: None,
+            base_currency: None,
+            ws_base_url: None,
+            ws_auth: WsAuth::default(),
+            debug_logging: false,
+            show_inspector: false,
+            show_tape: false,
+            show_bid_ask: false,
+            number_style: NumberStyle::default(),
+            price_decimals: PriceDecimals::default(),
+            abbreviate_volumes: true,
+            auto_connect: false,
+            pause_when_hidden_mins: None,
+            subscription_limit: DEFAULT_SUBSCRIPTION_LIMIT,
+            spike_threshold: None,
+            spike_alerts: false,
+            dashboard_columns: 3,
+            language: None,
+        }
+    }
+}
+
+impl Settings {
+    fn log_level(&self) -> LogLevel {
+        if self.debug_logging {
+            LogLevel::Debug
+        } else {
+            LogLevel::default()
+        }
+    }
+}
+
+/// How many of a symbol's latest trades its tick size is judged from
+const TICK_SIZE_SAMPLE: usize = 100;
+
+/// Decimal places prices are shown with per kind of symbol; `None` leaves it to the
+/// symbol's currency, or failing that its kind
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(default)]
+struct PriceDecimals {
+    stock: Option<usize>,
+    forex: Option<usize>,
+    crypto: Option<usize>,
+    /// Where decimals aren't set, pads each symbol's prices to its tick size as seen
+    /// in its recent trades rather than to the usual precision for its kind
+    tick_size: bool,
+}
+
+impl PriceDecimals {
+    fn get(&self, kind: SymbolKind) -> Option<usize> {
+        match kind {
+            SymbolKind::Stock => self.stock,
+            SymbolKind::Forex => self.forex,
+            SymbolKind::Crypto => self.crypto,
+        }
+    }
+
+    fn set(&mut self, kind: SymbolKind, decimals: Option<usize>) {
+        match kind {
+            SymbolKind::Stock => self.stock = decimals,
+            SymbolKind::Forex => self.forex = decimals,
+            SymbolKind::Crypto => self.crypto = decimals,
+        }
+    }
+}
+
+/// Shares held in a symbol, and the average price paid for each
+#[derive(Deserialize, Serialize, Clone, Copy, Default, Debug)]
+struct Position {
+    shares: Decimal,
+    cost_basis: Price,
+}
+
+impl Position {
+    fn is_empty(&self) -> bool {
+        self.shares == Decimal::default() && self.cost_basis == Price::default()
+    }
+
+    fn market_value(&self, price: Price) -> f64 {
+        self.shares.to_f64() * price.0.to_f64()
+    }
+
+    fn cost(&self) -> f64 {
+        self.shares.to_f64() * self.cost_basis.0.to_f64()
+    }
+
+    fn unrealized_pnl(&self, price: Price) -> f64 {
+        self.market_value(price) - self.cost()
+    }
+
+    /// `None` when nothing was paid, i.e. no cost basis entered
+    fn unrealized_pnl_percent(&self, price: Price) -> Option<f64> {
+        let cost = self.cost();
+        if cost == 0.0 {
+            None
+        } else {
+            Some(self.unrealized_pnl(price) / cost.abs() * 100.0)
+        }
+    }
+}
+
+/// A price a symbol is hoped to reach, measured from where we started
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+struct PriceTarget {
+    target: Price,
+    /// The price when the target was set (or the first one after, if we had none);
+    /// the position's cost basis takes precedence
+    reference: Option<Price>,
+}
+
+impl PriceTarget {
+    /// How far along `price` is from the reference to the target, clamped to 0-100.
+    /// Works for targets below the reference too
+    fn progress_percent(&self, reference: Price, price: Price) -> f64 {
+        let distance = self.target.0.to_f64() - reference.0.to_f64();
+        let progress = if distance == 0.0 {
+            1.0
+        } else {
+            (price.0.to_f64() - reference.0.to_f64()) / distance
+        };
+        (progress * 100.0).clamp(0.0, 100.0)
+    }
+
+    /// Whether going from `previous` to `latest` got to the target, from either side
+    fn is_reached(&self, previous: Option<Price>, latest: Price) -> bool {
+        match previous {
+            Some(previous) => {
+                (previous < self.target && latest >= self.target)
+                    || (previous > self.target && latest <= self.target)
+            }
+            None => false,
+        }
+    }
+}
+
+/// The API key is kept out of `State` so that it can be stored according to
+/// `ApiKeyStorage`, independently of everything else
+#[derive(Deserialize, Serialize, Default)]
 struct State {
-    api_key: ApiKey,
     tracked: Vec<Symbol>,
+    /// Kept in IndexedDB rather than with the rest of the state; older versions
+    /// persisted it here, so it's still read back in order to migrate it
+    #[serde(default, skip_serializing)]
     history: TickerHistory,
+    #[serde(default)]
+    settings: Settings,
+    /// Symbols that are still tracked, but that we've unsubscribed from for now
+    #[serde(default)]
+    paused: HashSet<Symbol>,
+    /// Trades at or above these volumes play a sound
+    #[serde(default)]
+    volume_alerts: HashMap<Symbol, Volume>,
+    /// Per-symbol overrides of `Settings::min_volume`
+    #[serde(default)]
+    min_volumes: HashMap<Symbol, Volume>,
+    /// Profiles, symbol lists and FX rates; saved under its own key, and only ever
+    /// read from here for the profiles of saves from before it had one
+    #[serde(default, skip_serializing)]
+    metadata: MetadataCache,
+    /// When each symbol's news was last looked at, so we can highlight what's new
+    #[serde(default)]
+    news_seen_at: HashMap<Symbol, DateTime<Utc>>,
+    #[serde(default)]
+    positions: HashMap<Symbol, Position>,
+    #[serde(default)]
+    price_targets: HashMap<Symbol, PriceTarget>,
+    /// Only for the dashboard layout; where cards go is the order they're tracked in
+    #[serde(default)]
+    card_sizes: HashMap<Symbol, CardSize>,
+    /// Alert whenever one of these starts to hold
+    #[serde(default)]
+    alert_rules: HashMap<Symbol, Vec<Condition>>,
+    /// Which named group, if any, each symbol's card is shown under
+    #[serde(default)]
+    symbol_groups: HashMap<Symbol, String>,
+    #[serde(default)]
+    collapsed_groups: HashSet<String>,
+    /// Free text shown on each symbol's card
+    #[serde(default)]
+    notes: HashMap<Symbol, String>,
+    /// Labels like "swing trade", which cards can be filtered by
+    #[serde(default)]
+    tags: HashMap<Symbol, Vec<String>>,
+    /// Price targets reached, volume alerts and disconnections, newest first
+    #[serde(default)]
+    alert_log: AlertLog,
+    /// Cached so that the earnings calendar is only asked once a day
+    #[serde(default)]
+    earnings: HashMap<Symbol, EarningsCache>,
+    /// The chosen language's, or the browser's, which numbers are formatted for by
+    /// default
+    #[serde(skip)]
+    locale: String,
+}
+
+/// What's saved of `State` under a policy short of `PersistencePolicy::Everything`;
+/// it reads back as a `State` with everything else defaulted
+#[derive(Serialize)]
+struct PartialState<'a> {
+    tracked: &'a [Symbol],
+    settings: Cow<'a, Settings>,
+}
+
+/// `State` along with its history, as older versions saved it, for until that history
+/// has been moved into IndexedDB
+#[derive(Serialize)]
+struct LegacyHistoryState<'a> {
+    #[serde(flatten)]
+    state: &'a State,
+    history: &'a TickerHistory,
+}
+
+/// Older versions persisted the API key inside `State`
+#[derive(Deserialize)]
+struct LegacyState {
+    api_key: Option<ApiKey>,
 }
 
 struct UntrackResult {
     is_last: bool,
     symbol: Symbol,
+    /// Only when the last entry for the symbol went, since that's when its data goes too
+    tombstone: Option<Tombstone>,
+}
+
+/// Everything that goes when a symbol is untracked, kept around briefly so that it
+/// can be undone
+struct Tombstone {
+    idx: usize,
+    symbol: Symbol,
+    history: Option<VecDeque<Trade>>,
+    paused: bool,
+    volume_alert: Option<Volume>,
+    min_volume: Option<Volume>,
+    earnings: Option<EarningsCache>,
+    news_seen_at: Option<DateTime<Utc>>,
+    position: Option<Position>,
+    price_target: Option<PriceTarget>,
+    card_size: Option<CardSize>,
+    group: Option<String>,
+    note: Option<String>,
+    tags: Option<Vec<String>>,
+    alert_rules: Option<Vec<Condition>>,
 }
 
 impl State {
-    fn add_symbol(&mut self, symbol: Symbol) {
-        self.tracked.push(symbol);
+    /// Returns false if the symbol is already tracked
+    fn add_symbol(&mut self, symbol: Symbol) -> bool {
+        if self.tracked.contains(&symbol) {
+            false
+        } else {
+            self.tracked.push(symbol);
+            true
+        }
+    }
+
+    /// What can be made out of a saved state that doesn't read as a whole: the
+    /// watchlist, less any symbols that don't read, and the settings if they do
+    fn salvage(saved: &serde_json::Value) -> State {
+        let tracked = saved
+            .get("tracked")
+            .and_then(|tracked| tracked.as_array())
+            .map(|tracked| {
+                tracked
+                    .iter()
+                    .filter_map(|symbol| serde_json::from_value(symbol.clone()).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let settings = saved
+            .get("settings")
+            .and_then(|settings| serde_json::from_value(settings.clone()).ok())
+            .unwrap_or_default();
+        State {
+            tracked,
+            settings,
+            ..State::default()
+        }
     }
 
-    fn last_added(&self) -> Option<&Symbol> {
-        self.tracked.last()
+    /// Migrates previously persisted state, which may contain un-normalised or
+    /// duplicate symbols
+    fn normalise(&mut self) {
+        let mut tracked = Vec::with_capacity(self.tracked.len());
+        for symbol in self.tracked.drain(..) {
+            let symbol = symbol.normalised();
+            if !tracked.contains(&symbol) {
+                tracked.push(symbol);
+            }
+        }
+        self.tracked = tracked;
+        self.history.normalise();
+        self.settings.heartbeat_timeout_secs = self
+            .settings
+            .heartbeat_timeout_secs
+            .max(MIN_HEARTBEAT_TIMEOUT_SECS);
     }
 
-    fn remove_last_added(&mut self) {
-        self.tracked.pop();
+    /// Moves the tracked symbol at `from` so that it ends up at `to`
+    fn move_symbol(&mut self, from: usize, to: usize) {
+        if from < self.tracked.len() && to < self.tracked.len() {
+            let symbol = self.tracked.remove(from);
+            self.tracked.insert(to, symbol);
+        }
     }
 
     fn untrack_symbol(&mut self, idx: usize) -> UntrackResult {
@@ -134,398 +1067,8020 @@ impl State {
             .iter()
             .find(|t| t == &&removed_symbol)
             .is_none();
-        if last_for_symbol {
-            self.history.remove(&removed_symbol);
-        }
+        let tombstone = if last_for_symbol {
+            Some(Tombstone {
+                idx,
+                symbol: removed_symbol.clone(),
+                history: self.history.remove(&removed_symbol),
+                paused: self.paused.remove(&removed_symbol),
+                volume_alert: self.volume_alerts.remove(&removed_symbol),
+                min_volume: self.min_volumes.remove(&removed_symbol),
+                earnings: self.earnings.remove(&removed_symbol),
+                news_seen_at: self.news_seen_at.remove(&removed_symbol),
+                position: self.positions.remove(&removed_symbol),
+                price_target: self.price_targets.remove(&removed_symbol),
+                card_size: self.card_sizes.remove(&removed_symbol),
+                group: self.remove_from_group(&removed_symbol),
+                note: self.notes.remove(&removed_symbol),
+                tags: self.tags.remove(&removed_symbol),
+                alert_rules: self.alert_rules.remove(&removed_symbol),
+            })
+        } else {
+            None
+        };
         UntrackResult {
             is_last: last_for_symbol,
             symbol: removed_symbol,
+            tombstone,
         }
     }
 
-    fn add_history(&mut self, ticker_info: TickerInfo) {
-        self.history.insert(ticker_info);
+    /// Puts an untracked symbol back where it was, along with its data. Returns false
+    /// if it has been tracked again in the meantime
+    fn restore(&mut self, tombstone: Tombstone) -> bool {
+        let symbol = tombstone.symbol;
+        if self.tracked.contains(&symbol) {
+            return false;
+        }
+        let idx = tombstone.idx.min(self.tracked.len());
+        self.tracked.insert(idx, symbol.clone());
+        if let Some(history) = tombstone.history {
+            self.history.merge(symbol.clone(), history);
+        }
+        if tombstone.paused {
+            self.paused.insert(symbol.clone());
+        }
+        if let Some(volume) = tombstone.volume_alert {
+            self.volume_alerts.insert(symbol.clone(), volume);
+        }
+        if let Some(volume) = tombstone.min_volume {
+            self.min_volumes.insert(symbol.clone(), volume);
+        }
+        if let Some(earnings) = tombstone.earnings {
+            self.earnings.insert(symbol.clone(), earnings);
+        }
+        if let Some(seen_at) = tombstone.news_seen_at {
+            self.news_seen_at.insert(symbol.clone(), seen_at);
+        }
+        if let Some(position) = tombstone.position {
+            self.positions.insert(symbol.clone(), position);
+        }
+        if let Some(size) = tombstone.card_size {
+            self.card_sizes.insert(symbol.clone(), size);
+        }
+        if let Some(target) = tombstone.price_target {
+            self.price_targets.insert(symbol.clone(), target);
+        }
+        if let Some(group) = tombstone.group {
+            self.symbol_groups.insert(symbol.clone(), group);
+        }
+        if let Some(note) = tombstone.note {
+            self.notes.insert(symbol.clone(), note);
+        }
+        if let Some(tags) = tombstone.tags {
+            self.tags.insert(symbol.clone(), tags);
+        }
+        if let Some(rules) = tombstone.alert_rules {
+            self.alert_rules.insert(symbol.clone(), rules);
+        }
+        true
     }
-}
 
-struct Model {
-    websocket_service: WebSocketService,
-    dialog_service: DialogService,
-    console_service: ConsoleService,
-    // optional because might not be supported
-    storage_service: Option<StorageService>,
-    symbol_to_add: Symbol,
-    state: State,
-    link: ComponentLink<Self>,
-    websocket_task: Option<WebSocketTask>,
-}
+    /// Undoes untracking several symbols at once, putting each back where it was.
+    /// Returns the ones that hadn't been tracked again in the meantime
+    fn restore_all(&mut self, mut tombstones: Vec<Tombstone>) -> Vec<Symbol> {
+        // each index is from before the ones after it were untracked
+        tombstones.sort_by_key(|tombstone| tombstone.idx);
+        let mut restored = vec![];
+        for tombstone in tombstones {
+            let symbol = tombstone.symbol.clone();
+            if self.restore(tombstone) {
+                restored.push(symbol);
+            }
+        }
+        restored
+    }
 
-enum Msg {
-    ApiKeyUpdate(ApiKey),
-    UpdateSymbolToTrack(Symbol),
-    TrackSymbol,
-    ApiKeyConnect,
-    ApiKeyDisconnect,
-    UnTrackSymbolAtIdx(usize),
-    WsIncoming(Result<WsMessage, Error>),
-    WsOpened,
+    /// Every tag in use, alphabetically
+    fn all_tags(&self) -> BTreeSet<&String> {
+        self.tags.values().flatten().collect()
+    }
+
+    /// Case-insensitively matches part of the symbol or of any of its tags
+    fn matches_query(&self, symbol: &Symbol, query: &str) -> bool {
+        let query = query.trim().to_lowercase();
+        query.is_empty()
+            || symbol.0.to_lowercase().contains(&query)
+            || self
+                .tags
+                .get(symbol)
+                .into_iter()
+                .flatten()
+                .any(|tag| tag.to_lowercase().contains(&query))
+    }
+
+    fn has_tag(&self, symbol: &Symbol, tag: &str) -> bool {
+        self.tags
+            .get(symbol)
+            .is_some_and(|tags| tags.iter().any(|t| t == tag))
+    }
+
+    /// Takes a symbol out of its group, forgetting the group's collapsed state once
+    /// nothing is left in it
+    fn remove_from_group(&mut self, symbol: &Symbol) -> Option<String> {
+        let group = self.symbol_groups.remove(symbol)?;
+        if !self.symbol_groups.values().any(|g| *g == group) {
+            self.collapsed_groups.remove(&group);
+        }
+        Some(group)
+    }
+
+    /// Stocks get their currency from their profile, forex pairs from their name.
+    /// Crypto is left as is, since it trades against all sorts
+    fn currency(&self, symbol: &Symbol) -> Option<Currency> {
+        match symbol.kind() {
+            SymbolKind::Stock => self
+                .metadata
+                .profile(symbol)
+                .and_then(|p| p.currency.as_deref())
+                .map(Currency::new),
+            SymbolKind::Forex => symbol.quote_currency().map(Currency::new),
+            SymbolKind::Crypto => None,
+        }
+    }
+
+    fn price_format(&self, symbol: &Symbol) -> PriceFormat {
+        let kind = symbol.kind();
+        let currency = self.currency(symbol);
+        let chosen = self.settings.price_decimals.get(kind);
+        let tick_decimals = if self.settings.price_decimals.tick_size {
+            self.tick_decimals(symbol)
+        } else {
+            None
+        };
+        let precision = match (
+            chosen.or(tick_decimals),
+            kind,
+            currency.as_ref().and_then(Currency::decimals),
+        ) {
+            (Some(decimals), _, _) => decimals,
+            (None, SymbolKind::Stock, Some(decimals)) => decimals,
+            _ => kind.price_precision(),
+        };
+        PriceFormat {
+            precision,
+            fixed_precision: chosen.is_some(),
+            currency,
+            number_format: self.number_format(),
+        }
+    }
+
+    /// The most decimals any of the symbol's recent trades needed
+    fn tick_decimals(&self, symbol: &Symbol) -> Option<usize> {
+        self.history
+            .get(symbol)?
+            .iter()
+            .take(TICK_SIZE_SAMPLE)
+            .map(|t| t.price.0.significant_scale() as usize)
+            .max()
+    }
+
+    fn number_format(&self) -> NumberFormat {
+        NumberFormat::new(
+            self.settings.number_style,
+            &self.locale,
+            self.settings.abbreviate_volumes,
+        )
+    }
+
+    fn format_volume(&self, volume: Volume) -> String {
+        self.number_format()
+            .volume(volume.0.to_f64(), volume.0.scale() as usize)
+    }
+
+    /// Up or down depending on how the latest trade compares to the one before it
+    fn ticker_health(&self, symbol: &Symbol) -> TickerHealth {
+        let mut history = self.history.get(symbol).into_iter().flatten();
+        match (history.next(), history.next()) {
+            (Some(last), Some(second_last)) if last.price > second_last.price => TickerHealth::Good,
+            (Some(last), Some(second_last)) if last.price < second_last.price => TickerHealth::Bad,
+            _ => TickerHealth::Normal,
+        }
+    }
+
+    fn add_history(&mut self, ticker_info: Trade) -> bool {
+        self.history.insert(ticker_info)
+    }
+
+    /// Works out which way the price moved compared to the trade before it, which is
+    /// the newest we have unless it came in out of order
+    fn annotate_direction(&self, ticker_info: &mut Trade) {
+        ticker_info.direction = self
+            .history
+            .get(&ticker_info.symbol)
+            .and_then(|h| h.iter().find(|t| t.time <= ticker_info.time))
+            .map(|previous| {
+                if ticker_info.price > previous.price {
+                    PriceDirection::Up
+                } else if ticker_info.price < previous.price {
+                    PriceDirection::Down
+                } else {
+                    PriceDirection::Unchanged
+                }
+            });
+    }
+
+    fn trade_filter(&self) -> TradeFilter {
+        TradeFilter {
+            paused: self.paused.clone(),
+            min_volume: self.settings.min_volume,
+            min_volumes: self.min_volumes.clone(),
+            volume_alerts: self.volume_alerts.clone(),
+            history_retention_mins: self.settings.history_retention_mins,
+        }
+    }
+
+    fn is_paused(&self, symbol: &Symbol) -> bool {
+        self.paused.contains(symbol)
+    }
+
+    /// How far the price has moved since the oldest trade in the symbol's history
+    fn change_percent(&self, symbol: &Symbol) -> Option<f64> {
+        let history = self.history.get(symbol)?;
+        let oldest = history.back()?.price.0.to_f64();
+        let newest = history.front()?.price.0.to_f64();
+        if oldest == 0.0 {
+            None
+        } else {
+            Some((newest - oldest) / oldest * 100.0)
+        }
+    }
+
+    fn latest_price(&self, symbol: &Symbol) -> Option<Price> {
+        self.history
+            .get(symbol)
+            .and_then(|h| h.front())
+            .map(|t| t.price)
+    }
+
+    /// Market value and unrealized P&L across every position we have a price for
+    fn portfolio_totals(&self) -> Option<(f64, f64)> {
+        self.portfolio_totals_converted(|_, amount| Some(amount))
+    }
+
+    /// Totals with each position's value and P&L passed through `convert` first, or
+    /// `None` if any of them can't be
+    fn portfolio_totals_converted<F>(&self, convert: F) -> Option<(f64, f64)>
+    where
+        F: Fn(&Symbol, f64) -> Option<f64>,
+    {
+        self.positions
+            .iter()
+            .filter_map(|(symbol, position)| {
+                self.latest_price(symbol).map(|price| {
+                    Some((
+                        convert(symbol, position.market_value(price))?,
+                        convert(symbol, position.unrealized_pnl(price))?,
+                    ))
+                })
+            })
+            .try_fold(None, |acc, converted| {
+                let (value, pnl) = converted?;
+                let (total_value, total_pnl) = acc.unwrap_or((0.0, 0.0));
+                Some(Some((total_value + value, total_pnl + pnl)))
+            })
+            .flatten()
+    }
+
+    fn price_target_reference(&self, symbol: &Symbol, target: &PriceTarget) -> Option<Price> {
+        self.positions
+            .get(symbol)
+            .map(|p| p.cost_basis)
+            .filter(|cost_basis| *cost_basis != Price::default())
+            .or(target.reference)
+    }
+
+    fn update_position<F: FnOnce(&mut Position)>(&mut self, symbol: Symbol, f: F) {
+        let position = self.positions.entry(symbol.clone()).or_default();
+        f(position);
+        if position.is_empty() {
+            self.positions.remove(&symbol);
+        }
+    }
+
+    /// Tracked symbols we should be subscribed to
+    fn streaming(&self) -> impl Iterator<Item = &Symbol> {
+        self.tracked.iter().filter(move |s| !self.is_paused(s))
+    }
+
+    /// What a session should be subscribed to: what's streaming and hasn't been
+    /// rejected, or nothing while the tab has been hidden for too long
+    fn wanted_subscriptions<'a>(
+        &'a self,
+        hidden_pause: bool,
+        symbol_errors: &'a HashMap<Symbol, SymbolError>,
+    ) -> impl Iterator<Item = &'a Symbol> {
+        self.streaming()
+            .filter(move |s| !hidden_pause && !symbol_errors.contains_key(s))
+    }
+}
+
+struct Model {
+    ws_connector: SharedConnector,
+    interval_service: IntervalService,
+    timeout_service: TimeoutService,
+    api_client: ApiClient,
+    notifications: Notifications<NotificationAction>,
+    // the "connection failed" notification, which goes away once we're reconnected
+    reconnect_notification: Option<usize>,
+    // connections in a row that failed without ever opening
+    ws_failures: u32,
+    // unsubscribed from everything while the tab's hidden, to save battery
+    hidden_pause: bool,
+    hidden_pause_task: Option<TimeoutTask>,
+    // websockets look to be blocked, so quotes are being polled in the meantime
+    ws_fallback: bool,
+    ws_retry_task: Option<TimeoutTask>,
+    // the "storage is full" warning, replaced rather than repeated
+    storage_full_notification: Option<usize>,
+    // the "nearly out of subscriptions" warning and what it said, up for as long as
+    // it's true and only replaced when that changes
+    subscription_limit_notification: Option<(usize, String)>,
+    // dismiss the "Undo" notifications for untracked symbols once they've been up a while
+    undo_timeouts: HashMap<usize, TimeoutTask>,
+    // for talking to other tabs of the app; unsupported in some browsers
+    tab_sync: Option<TabSync>,
+    // which tab holds the websocket, while the others mirror it
+    election: Election,
+    tab_sync_task: Option<IntervalTask>,
+    logger: Logger,
+    history_store: Option<HistoryStore>,
+    // symbols whose profile section is open
+    expanded_profiles: HashSet<Symbol>,
+    profile_tasks: HashMap<Symbol, ApiTask>,
+    earnings_tasks: HashMap<Symbol, ApiTask>,
+    _earnings_refresh_task: IntervalTask,
+    // per type, for the diagnostics on the settings page
+    unknown_messages: BTreeMap<String, UnknownMessages>,
+    // only filled while `Settings::show_inspector` is on
+    inspector: Inspector,
+    card_tabs: HashMap<Symbol, CardTab>,
+    news: HashMap<Symbol, Vec<NewsItem>>,
+    // news newer than this is highlighted; it's when news was seen before the current view
+    news_highlight_after: HashMap<Symbol, DateTime<Utc>>,
+    news_tasks: HashMap<Symbol, ApiTask>,
+    news_refresh_task: Option<IntervalTask>,
+    // only running while there's no websocket
+    quote_poll_task: Option<IntervalTask>,
+    quote_tasks: HashMap<Symbol, ApiTask>,
+    candle_tasks: HashMap<Symbol, ApiTask>,
+    // only running while there's a base currency to convert to
+    fx_refresh_task: Option<IntervalTask>,
+    reader_service: ReaderService,
+    csv_reader_task: Option<ReaderTask>,
+    csv_import: Option<CsvImport>,
+    fx_task: Option<ApiTask>,
+    // only running while bid/ask is turned on
+    bid_ask_refresh_task: Option<IntervalTask>,
+    bid_ask_tasks: HashMap<Symbol, ApiTask>,
+    bid_asks: HashMap<Symbol, BidAsk>,
+    // the symbol list is fetched the first time a symbol is typed in, if the cached one
+    // is stale
+    symbol_list_requested: bool,
+    symbol_list_task: Option<ApiTask>,
+    // shown under the ticker input for symbols that weren't tracked
+    symbol_input_error: Option<String>,
+    // we hold off writing history until what was stored has been loaded and merged
+    history_loaded: bool,
+    // whether the saved state still has the history older versions kept in it, which
+    // stays there until it's been written to IndexedDB
+    legacy_history: bool,
+    // created on first use; browsers may not allow audio before user interaction
+    beeper: Option<Beeper>,
+    // whether the browser prefers a dark colour scheme, kept up to date by the listener
+    prefers_dark: bool,
+    _prefers_dark_listener: Option<MediaQueryListener>,
+    _visibility_listener: Option<Closure<dyn FnMut()>>,
+    // while offline the app only shows what was saved, and connects once back online
+    online: bool,
+    _online_listeners: Option<OnlineListeners>,
+    _paste_listener: Option<Closure<dyn FnMut(Event)>>,
+    route: Route,
+    _hash_change_listener: Option<Closure<dyn FnMut()>>,
+    _before_unload_listener: Option<Closure<dyn FnMut()>>,
+    _keyboard_shortcuts: Option<KeyboardShortcuts>,
+    show_shortcuts_help: bool,
+    // only cards with this tag are shown
+    tag_filter: Option<String>,
+    // narrows the cards shown, without touching subscriptions
+    card_query: String,
+    health_filter: Option<TickerHealth>,
+    // the one card shown, full width, while the rest are hidden
+    maximized: Option<Symbol>,
+    // charted together on the compare page, in the order they were picked
+    compared: Vec<Symbol>,
+    chart_spacings: HashMap<Chart, AxisSpacing>,
+    symbol_input_ref: NodeRef,
+    main_content_ref: NodeRef,
+    // read out by screen readers; trades come too thick and fast to announce each one
+    announcement: String,
+    announced_prices: HashMap<Symbol, Price>,
+    _announce_task: IntervalTask,
+    // everything accepted since the page loaded, for the trade table footers
+    session_totals: HashMap<Symbol, TradeTotals>,
+    tick_counts: HashMap<Symbol, TickCounts>,
+    session_ranges: HashMap<Symbol, SessionRange>,
+    // newest first, live only; it's a glance at overall flow rather than history
+    tape: VecDeque<TapeTrade>,
+    rule_engine: RuleEngine,
+    // alert rules being typed in, and why the last one entered wasn't understood
+    alert_rule_inputs: HashMap<Symbol, String>,
+    alert_rule_errors: HashMap<Symbol, String>,
+    // flipped on every trade batch so that the price flash animation restarts
+    flash_parity: HashMap<Symbol, bool>,
+    // the symbol cards to render again, rather than leave as they were; every one of
+    // them after any message that could change what's on them all
+    redraw_all_cards: bool,
+    redraw_cards: HashSet<Symbol>,
+    // the cards on the page as of the last render, since new ones need rendering
+    // whatever else has changed
+    mounted_cards: HashSet<Symbol>,
+    // filled in by `view`, as each card is either rendered or kept
+    cards_in_view: RefCell<Vec<(Symbol, bool)>>,
+    table_sorts: HashMap<Symbol, TableSort>,
+    table_scroll_tops: HashMap<Symbol, f64>,
+    // how many of the newest trades tables show, for those showing more than a page
+    table_depths: HashMap<Symbol, usize>,
+    time_formatter: TimeFormatter,
+    // re-renders every second so that relative timestamps stay current
+    relative_time_task: Option<IntervalTask>,
+    // whether the user wants to be connected, as opposed to whether we actually are
+    wants_connection: bool,
+    // optional because might not be supported
+    storage_service: Option<StorageService>,
+    session_storage_service: Option<StorageService>,
+    api_key: ApiKey,
+    api_key_profiles: ApiKeyProfiles,
+    // what the current key will be saved as
+    profile_name_to_save: String,
+    key_check: KeyCheck,
+    key_check_task: Option<ApiTask>,
+    // what finnhub has turned down for the key's plan, so that it isn't asked for again
+    not_entitled: HashSet<PlanFeature>,
+    // what it's turned down for only some symbols; news and earnings can be covered
+    // for one exchange's and not another's
+    not_entitled_symbols: HashSet<(PlanFeature, Symbol)>,
+    // the keys are encrypted with this before being stored, if set; never stored itself
+    passphrase: Option<String>,
+    // stored keys that can't be used until the passphrase is entered
+    locked_api_keys: Option<Sealed>,
+    passphrase_input: String,
+    passphrase_error: Option<String>,
+    unlocking: bool,
+    // encrypting is async, so only the latest result is stored
+    seal_seq: u64,
+    // whether to connect once the keys are unlocked, as we would have on load
+    auto_connect_on_unlock: bool,
+    symbol_to_add: Symbol,
+    // index of the card currently being dragged
+    dragging: Option<usize>,
+    // empty for plain stock symbols
+    exchange_to_add: String,
+    state: State,
+    link: ComponentLink<Self>,
+    session: Option<Session>,
+    connection_state: ConnectionState,
+    heartbeat_task: Option<IntervalTask>,
+    // the simulator and what ticks it, while the demo is running
+    demo: Option<(Simulator, IntervalTask)>,
+    // a symbol's history being played back, and what ticks it
+    replay: Option<(Replay, IntervalTask)>,
+    // symbols Finnhub has rejected and why, flagged on their cards and left unsubscribed
+    // until retried or untracked
+    symbol_errors: HashMap<Symbol, SymbolError>,
+    // time of the last message of any kind received over the websocket
+    last_message_at: Option<DateTime<Utc>>,
+    metrics: Metrics<Symbol>,
+    _stale_check_task: IntervalTask,
+    // changed by trades since last saved; written out by `_persist_task`
+    state_dirty: bool,
+    dirty_histories: HashSet<Symbol>,
+    _persist_task: IntervalTask,
+    // streaming symbols whose newest trade is older than `Settings::stale_after_mins`
+    stale_symbols: HashSet<Symbol>,
+    trade_worker: Box<dyn Bridge<TradeWorker>>,
+    // what the worker was last told, so it's only resent on changes
+    trade_filter: TradeFilter,
+}
+
+enum Msg {
+    ApiKeyUpdate(ApiKey),
+    ApiKeyProfileSelect(String),
+    ApiKeyProfileNameUpdate(String),
+    ApiKeyProfileSave,
+    ApiKeyProfileDelete,
+    ApiKeyChecked(Result<serde_json::Value, String>),
+    PassphraseInput(String),
+    /// Encrypts the stored keys with the passphrase entered
+    SetPassphrase,
+    RemovePassphrase,
+    UnlockApiKey,
+    /// Gives up on keys whose passphrase has been forgotten
+    ForgetLockedApiKey,
+    ApiKeySealed(u64, Result<Sealed, String>),
+    ApiKeyUnsealed(Result<String, String>),
+    UpdateSymbolToTrack(Symbol),
+    UpdateExchangeToTrack(String),
+    TrackSymbol,
+    /// A list of symbols pasted into the ticker input
+    PasteSymbols(String),
+    ApiKeyConnect,
+    ApiKeyDisconnect,
+    StartDemo,
+    DemoTick,
+    StartReplay(Symbol),
+    ReplaySpeedUpdate(ReplaySpeed),
+    ReplayTick,
+    StopReplay,
+    /// Subscribes again to a symbol finnhub rejected
+    RetrySymbol(Symbol),
+    UnTrackSymbolAtIdx(usize),
+    UnTrackAll,
+    /// Empties a symbol's history, while staying subscribed to it
+    ClearSymbolHistory(Symbol),
+    DragStart(usize),
+    DragOver,
+    DropAtIdx(usize),
+    DragEnd,
+    VolumeAlertUpdate(Symbol, Option<Volume>),
+    SoundMutedToggle,
+    MinVolumeUpdate(Symbol, Option<Volume>),
+    GlobalMinVolumeUpdate(Option<Volume>),
+    SharesUpdate(Symbol, Option<Decimal>),
+    CostBasisUpdate(Symbol, Option<Price>),
+    PriceTargetUpdate(Symbol, Option<Price>),
+    AlertRuleInput(Symbol, String),
+    AddAlertRule(Symbol),
+    RemoveAlertRule(Symbol, usize),
+    SymbolGroupUpdate(Symbol, Option<String>),
+    NoteUpdate(Symbol, Option<String>),
+    TagsUpdate(Symbol, Vec<String>),
+    TagFilterToggle(String),
+    ChartSpacingUpdate(Chart, AxisSpacing),
+    CardQueryUpdate(String),
+    HealthFilterUpdate(Option<TickerHealth>),
+    ToggleGroupCollapsed(String),
+    HistoryStoreOpened(Result<HistoryStore, String>),
+    HistoryLoaded(Result<Vec<(String, String)>, String>),
+    LegacyHistoryWritten(Result<(), String>),
+    HistoryQuotaExceeded,
+    ToggleProfile(Symbol),
+    ProfileFetched(Symbol, Result<CompanyProfile, String>),
+    /// Fetches profiles, symbol lists and FX rates again, however fresh they are
+    RefreshMetadata,
+    CandlesFetched(Symbol, Result<Candles, String>),
+    SelectCardTab(Symbol, CardTab),
+    SortTable(Symbol, SortColumn),
+    ShowMoreTrades(Symbol),
+    ShowFewerTrades(Symbol),
+    TableScroll(Symbol, f64),
+    NewsFetched(Symbol, Result<Vec<NewsItem>, String>),
+    NewsRefresh,
+    QuotePoll,
+    ShareWatchlist,
+    CsvFileChosen(File),
+    CsvFileLoaded(FileData),
+    CsvImportToggle(Symbol),
+    CsvImportConfirm,
+    CsvImportCancel,
+    Maximize(Option<Symbol>),
+    CompareToggle(Symbol),
+    QuoteFetched(Symbol, Result<Quote, String>),
+    FxRefresh,
+    FxRatesFetched(Result<FxRates, String>),
+    EarningsRefresh,
+    EarningsFetched(Symbol, Result<EarningsCalendar, String>),
+    BidAskRefresh,
+    BidAskFetched(Symbol, Result<BidAsk, String>),
+    SymbolListFetched(Result<Vec<ListedSymbol>, String>),
+    ShowBidAskUpdate(bool),
+    AutoConnectUpdate(bool),
+    PauseWhenHiddenUpdate(Option<u32>),
+    CardSizeUpdate(Symbol, CardSize),
+    DashboardColumnsUpdate(u32),
+    SubscriptionLimitUpdate(u32),
+    SpikeThresholdUpdate(Option<SpikeThreshold>),
+    SpikeAlertsUpdate(bool),
+    HiddenTooLong,
+    BeforeUnload,
+    VisibilityChange(bool),
+    OnlineChange(bool),
+    RouteChanged(Route),
+    Shortcut(Shortcut),
+    DismissNotification(usize),
+    UndoExpired(usize),
+    NotificationActionTaken(usize),
+    ThemeUpdate(Theme),
+    ViewModeUpdate(ViewMode),
+    SmaPeriodUpdate(Option<u32>),
+    EmaPeriodUpdate(Option<u32>),
+    HistoryRetentionUpdate(Option<u32>),
+    TradeColumnsUpdate(Vec<TradeColumn>),
+    BlockTradeNotionalUpdate(Option<Decimal>),
+    BaseCurrencyUpdate(Option<String>),
+    WsBaseUrlUpdate(Option<String>),
+    WsAuthUpdate(WsAuth),
+    DebugLoggingUpdate(bool),
+    ShowInspectorUpdate(bool),
+    ClearInspector,
+    ShowTapeUpdate(bool),
+    NumberStyleUpdate(NumberStyle),
+    LanguageUpdate(Option<Language>),
+    PriceDecimalsUpdate(SymbolKind, Option<usize>),
+    TickSizeDecimalsUpdate(bool),
+    AbbreviateVolumesUpdate(bool),
+    AcknowledgeAlert(usize),
+    /// Mutes the alert at this index in the log, and any like it, for so many minutes
+    SnoozeAlert(usize, i64),
+    UnsnoozeAlert(usize),
+    DoNotDisturbUpdate(DoNotDisturb),
+    AcknowledgeAllAlerts,
+    ClearAlertLog,
+    TimeZoneUpdate(TimeZoneSetting),
+    RelativeTimeTick,
+    PrefersDarkUpdate(bool),
+    PauseSymbol(Symbol),
+    ResumeSymbol(Symbol),
+    WsFrame(Text),
+    /// Decoded into a `WsFrame`
+    WsBinaryFrame(Vec<u8>),
+    WorkerOutput(WorkerOutput),
+    WsOpened,
     WsDead,
+    WsRetry,
+    HeartbeatCheck,
+    HeartbeatTimeoutUpdate(u32),
+    StaleCheck,
+    PersistDirty,
+    Announce,
+    SkipToContent,
+    StaleAfterUpdate(u32),
+    ApiKeyStorageUpdate(ApiKeyStorage),
+    PersistenceUpdate(PersistencePolicy),
+    TabMessage(TabMessage),
+    TabSyncTick,
     Nope,
 }
 
+/// Which symbol cards a message could change what's shown on
+#[derive(PartialEq, Debug)]
+enum CardsChanged<'a> {
+    None,
+    One(&'a Symbol),
+    All,
+}
+
+impl Msg {
+    /// Anything not known to leave cards alone is taken to change all of them. Trades
+    /// mark the cards they're for as they're applied, and the few timers that do
+    /// change cards now and then mark them all when they do
+    fn cards_changed(&self) -> CardsChanged<'_> {
+        match self {
+            Msg::WsFrame(_)
+            | Msg::WsBinaryFrame(_)
+            | Msg::TabMessage(TabMessage::Frame(_))
+            | Msg::TabMessage(TabMessage::Leader { .. })
+            | Msg::TabMessage(TabMessage::Resigned { .. })
+            | Msg::TabMessage(TabMessage::Hello)
+            | Msg::WorkerOutput(WorkerOutput::Trades(_))
+            | Msg::WorkerOutput(WorkerOutput::Ping)
+            | Msg::WorkerOutput(WorkerOutput::Unknown { .. })
+            | Msg::WorkerOutput(WorkerOutput::Unparseable(_))
+            | Msg::TabSyncTick
+            | Msg::HeartbeatCheck
+            | Msg::PersistDirty
+            | Msg::Announce
+            | Msg::SkipToContent
+            | Msg::BeforeUnload
+            | Msg::DragStart(_)
+            | Msg::DragOver
+            | Msg::QuotePoll
+            | Msg::NewsRefresh
+            | Msg::FxRefresh
+            | Msg::EarningsRefresh
+            | Msg::BidAskRefresh
+            | Msg::Nope => CardsChanged::None,
+            Msg::ToggleProfile(symbol)
+            | Msg::ProfileFetched(symbol, _)
+            | Msg::SelectCardTab(symbol, _)
+            | Msg::SortTable(symbol, _)
+            | Msg::ShowMoreTrades(symbol)
+            | Msg::ShowFewerTrades(symbol)
+            | Msg::TableScroll(symbol, _)
+            | Msg::NewsFetched(symbol, _)
+            | Msg::BidAskFetched(symbol, _)
+            | Msg::EarningsFetched(symbol, _)
+            | Msg::CandlesFetched(symbol, _) => CardsChanged::One(symbol),
+            _ => CardsChanged::All,
+        }
+    }
+}
+
+/// Symbols picked out of an imported file, waiting on which of them to track
+struct CsvImport {
+    file_name: String,
+    symbols: Vec<Symbol>,
+    selected: HashSet<Symbol>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ConnectionState {
+    Disconnected,
+    /// We have a websocket task, but it hasn't opened yet
+    Connecting,
+    Connected,
+}
+
+/// A media query along with the closure listening to its changes, which has to be kept
+/// alive for as long as we want to hear about them
+type MediaQueryListener = (MediaQueryList, Closure<dyn FnMut(MediaQueryListEvent)>);
+/// `online` and `offline` window event listeners
+type OnlineListeners = (Closure<dyn FnMut()>, Closure<dyn FnMut()>);
+
+/// Percentage change at each trade's time
+type ChangeSeries = Vec<(DateTime<Utc>, f64)>;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum SortColumn {
+    Time,
+    Volume,
+    Price,
+    Notional,
+}
+
+/// How a symbol's trade table is sorted; purely a view concern, the history itself
+/// is always kept newest first
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct TableSort {
+    column: SortColumn,
+    ascending: bool,
+}
+
+impl Default for TableSort {
+    fn default() -> TableSort {
+        TableSort {
+            column: SortColumn::Time,
+            ascending: false,
+        }
+    }
+}
+
+impl TableSort {
+    /// Clicking the current column flips the direction, other columns start descending
+    fn toggled(self, column: SortColumn) -> TableSort {
+        if self.column == column {
+            TableSort {
+                column,
+                ascending: !self.ascending,
+            }
+        } else {
+            TableSort {
+                column,
+                ascending: false,
+            }
+        }
+    }
+
+    /// Pairs each of the newest `depth` trades with its index in the history (0 being
+    /// the newest)
+    fn sorted(self, history: &VecDeque<Trade>, depth: usize) -> Vec<(usize, &Trade)> {
+        let mut rows: Vec<(usize, &Trade)> = history.iter().take(depth).enumerate().collect();
+        if self == TableSort::default() {
+            return rows;
+        }
+        rows.sort_by(|(_, a), (_, b)| {
+            let ordering = match self.column {
+                SortColumn::Time => a.time.cmp(&b.time),
+                SortColumn::Volume => a.volume.0.cmp(&b.volume.0),
+                SortColumn::Price => a.price.0.cmp(&b.price.0),
+                SortColumn::Notional => match (a.notional(), b.notional()) {
+                    (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+                    // delayed quotes have no notional, and go last whichever way it's sorted
+                    (Some(_), None) => return Ordering::Less,
+                    (None, Some(_)) => return Ordering::Greater,
+                    (None, None) => Ordering::Equal,
+                },
+            };
+            if self.ascending {
+                ordering
+            } else {
+                ordering.reverse()
+            }
+        });
+        rows
+    }
+}
+
+/// Columns the trade table can show
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+enum TradeColumn {
+    Time,
+    Price,
+    Volume,
+    /// Price × volume
+    Notional,
+    /// Volume of this and every older trade in the history
+    CumulativeVolume,
+    /// Price change since the trade before
+    Delta,
+    /// ▲/▼/— for whether the trade was above, below or at the price before
+    Tick,
+}
+
+impl TradeColumn {
+    const ALL: [TradeColumn; 7] = [
+        TradeColumn::Time,
+        TradeColumn::Price,
+        TradeColumn::Volume,
+        TradeColumn::Notional,
+        TradeColumn::CumulativeVolume,
+        TradeColumn::Delta,
+        TradeColumn::Tick,
+    ];
+
+    fn defaults() -> Vec<TradeColumn> {
+        vec![TradeColumn::Time, TradeColumn::Volume, TradeColumn::Price]
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            TradeColumn::Time => "Time",
+            TradeColumn::Price => "Price",
+            TradeColumn::Volume => "Volume",
+            TradeColumn::Notional => "Notional",
+            TradeColumn::CumulativeVolume => "Cum. volume",
+            TradeColumn::Delta => "Δ",
+            TradeColumn::Tick => "Tick",
+        }
+    }
+
+    fn sort_column(self) -> Option<SortColumn> {
+        match self {
+            TradeColumn::Time => Some(SortColumn::Time),
+            TradeColumn::Price => Some(SortColumn::Price),
+            TradeColumn::Volume => Some(SortColumn::Volume),
+            TradeColumn::Notional => Some(SortColumn::Notional),
+            _ => None,
+        }
+    }
+}
+
+/// Percentage change of each history from a common baseline, which is the latest of
+/// their oldest trades so that every line starts at the same moment. Each series is
+/// oldest first and starts with the baseline itself at 0%
+fn percent_change_series(
+    histories: &[&VecDeque<Trade>],
+) -> Option<(DateTime<Utc>, Vec<ChangeSeries>)> {
+    let baseline = histories
+        .iter()
+        .map(|history| history.back().map(|oldest| oldest.time))
+        .collect::<Option<Vec<_>>>()?
+        .into_iter()
+        .max()?;
+    let series = histories
+        .iter()
+        .map(|history| {
+            // the price as it stood at the baseline
+            let base = history
+                .iter()
+                .find(|t| t.time <= baseline)
+                .map(|t| t.price.0.to_f64())
+                .filter(|price| *price != 0.0)?;
+            let mut points = vec![(baseline, 0.0)];
+            points.extend(
+                history
+                    .iter()
+                    .rev()
+                    .filter(|t| t.time > baseline)
+                    .map(|t| (t.time, (t.price.0.to_f64() / base - 1.0) * 100.0)),
+            );
+            Some(points)
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some((baseline, series))
+}
+
+/// Running volume totals for a history, summed from the oldest trade but in the
+/// history's (newest first) order. Each comes with the decimal places it's good to
+fn cumulative_volumes(history: &VecDeque<Trade>) -> Vec<(f64, usize)> {
+    let mut total = 0.0;
+    let mut scale = 0;
+    let mut totals: Vec<(f64, usize)> = history
+        .iter()
+        .rev()
+        .map(|t| {
+            if !t.delayed {
+                total += t.volume.0.to_f64();
+                scale = scale.max(t.volume.0.scale() as usize);
+            }
+            (total, scale)
+        })
+        .collect();
+    totals.reverse();
+    totals
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct MovingAverages {
+    sma: Option<f64>,
+    ema: Option<f64>,
+}
+
+/// Which view a symbol card is showing
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+enum CardTab {
+    #[default]
+    Trades,
+    News,
+}
+
+/// The app's pages, addressed by the URL hash so they can be bookmarked and navigated
+/// with the back button. This is a hash router of our own rather than yew-router, and
+/// each page is a `view_*_page` on `Model` over the whole of `State`; splitting them out
+/// into components of their own is left until yew-router can be brought in
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+enum Route {
+    #[default]
+    Watchlist,
+    Portfolio,
+    Compare,
+    Alerts,
+    Settings,
+}
+
+impl Route {
+    const ALL: [Route; 5] = [
+        Route::Watchlist,
+        Route::Portfolio,
+        Route::Compare,
+        Route::Alerts,
+        Route::Settings,
+    ];
+
+    fn hash(self) -> &'static str {
+        match self {
+            Route::Watchlist => "#/watchlist",
+            Route::Portfolio => "#/portfolio",
+            Route::Compare => "#/compare",
+            Route::Alerts => "#/alerts",
+            Route::Settings => "#/settings",
+        }
+    }
+
+    fn phrase(self) -> Phrase {
+        match self {
+            Route::Watchlist => Phrase::Watchlist,
+            Route::Portfolio => Phrase::Portfolio,
+            Route::Compare => Phrase::Compare,
+            Route::Alerts => Phrase::Alerts,
+            Route::Settings => Phrase::Settings,
+        }
+    }
+
+    /// Anything unrecognised, including no hash at all, goes to the watchlist
+    fn from_hash(hash: &str) -> Route {
+        Route::ALL
+            .iter()
+            .copied()
+            .find(|route| route.hash() == hash)
+            .unwrap_or_default()
+    }
+
+    fn current() -> Route {
+        web_sys::window()
+            .and_then(|w| w.location().hash().ok())
+            .map(|hash| Route::from_hash(&hash))
+            .unwrap_or_default()
+    }
+}
+
+/// Things the user can do from a notification
+enum NotificationAction {
+    Reconnect,
+    UndoUntrack(Box<Tombstone>),
+    UndoUntrackAll(Vec<Tombstone>),
+    ClearHistory,
+    ImportSymbols(Vec<Symbol>),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
 enum TickerHealth {
     Good,
     Normal,
     Bad,
 }
 
+impl TickerHealth {
+    const ALL: [TickerHealth; 3] = [TickerHealth::Good, TickerHealth::Normal, TickerHealth::Bad];
+
+    fn label(self) -> &'static str {
+        match self {
+            TickerHealth::Good => "Up",
+            TickerHealth::Normal => "Flat",
+            TickerHealth::Bad => "Down",
+        }
+    }
+
+    /// Whichever of up or down the majority is, or normal if neither
+    fn aggregate(healths: impl IntoIterator<Item = TickerHealth>) -> TickerHealth {
+        let (mut good, mut bad) = (0, 0);
+        for health in healths {
+            match health {
+                TickerHealth::Good => good += 1,
+                TickerHealth::Bad => bad += 1,
+                TickerHealth::Normal => (),
+            }
+        }
+        if good > bad {
+            TickerHealth::Good
+        } else if bad > good {
+            TickerHealth::Bad
+        } else {
+            TickerHealth::Normal
+        }
+    }
+
+    fn border_class(self) -> &'static str {
+        match self {
+            TickerHealth::Good => "border-success",
+            TickerHealth::Bad => "border-danger",
+            TickerHealth::Normal => "border-primary",
+        }
+    }
+}
+
 const STATE_STORAGE_KEY: &str = "state";
+/// Where a saved state that couldn't be read in full is kept, rather than being lost
+/// once the salvaged one is saved over it
+const STATE_BACKUP_STORAGE_KEY: &str = "state_backup";
+const METADATA_STORAGE_KEY: &str = "metadata";
+
+const TICKER_INPUT_ID: &str = "ticker-symbol";
+
+/// `StorageService::store` panics when a write fails, which is exactly what happens
+/// once LocalStorage is full
+fn store_local(key: &str, value: &str) -> Result<(), JsValue> {
+    web_sys::window()
+        .ok_or("No window")?
+        .local_storage()?
+        .ok_or("LocalStorage is not supported")?
+        .set_item(key, value)
+}
+
+/// Serialises something to go in `store_local` or `store_session`
+fn to_json(value: &impl Serialize) -> Result<String, JsValue> {
+    serde_json::to_string(value).map_err(|e| JsValue::from(e.to_string()))
+}
+
+/// `store_local` for SessionStorage
+fn store_session(key: &str, value: &str) -> Result<(), JsValue> {
+    web_sys::window()
+        .ok_or("No window")?
+        .session_storage()?
+        .ok_or("SessionStorage is not supported")?
+        .set_item(key, value)
+}
+
+/// Fire and forget, since there's nothing to be done if it's refused. `Clipboard` is
+/// another of web_sys's unstable APIs, hence the `Reflect`
+fn copy_to_clipboard(text: &str) -> bool {
+    web_sys::window()
+        .and_then(|w| js_sys::Reflect::get(&w.navigator(), &"clipboard".into()).ok())
+        .filter(|clipboard| clipboard.is_object())
+        .and_then(|clipboard| {
+            let write_text = js_sys::Reflect::get(&clipboard, &"writeText".into()).ok()?;
+            let write_text = write_text.dyn_into::<js_sys::Function>().ok()?;
+            write_text.call1(&clipboard, &text.into()).ok()
+        })
+        .is_some()
+}
+
+fn card_element_id(idx: usize) -> String {
+    format!("symbol-card-{}", idx)
+}
+
+const NEWS_LOOKBACK_DAYS: i64 = 7;
+/// How far back newly tracked symbols' history is filled in from candles
+const CANDLE_BACKFILL_MINS: i64 = 60;
+const NEWS_REFRESH_INTERVAL_SECS: u64 = 5 * 60;
+/// How often quotes are polled over REST while the websocket is down
+const QUOTE_POLL_INTERVAL_SECS: u64 = 30;
+const FX_REFRESH_INTERVAL_SECS: u64 = 10 * 60;
+const BID_ASK_REFRESH_INTERVAL_SECS: u64 = 15;
+const EARNINGS_REFRESH_INTERVAL_SECS: u64 = 60 * 60;
+const EARNINGS_MAX_AGE_HOURS: i64 = 24;
+const EARNINGS_LOOKAHEAD_DAYS: i64 = 30;
+/// Earnings further out than this don't get a badge
+const EARNINGS_SOON_DAYS: i64 = 14;
+const API_KEY_STORAGE_KEY: &str = "api_key";
+const API_KEY_PROFILES_STORAGE_KEY: &str = "api_key_profiles";
+/// Both of the above, encrypted, once the user has set a passphrase
+const SEALED_API_KEYS_STORAGE_KEY: &str = "api_keys_sealed";
+/// Whether we wanted to be connected when the page was last unloaded. Kept in
+/// SessionStorage, so that it survives reloads but not closing the tab
+const WANTS_CONNECTION_STORAGE_KEY: &str = "wants_connection";
+
+/// Must match the row height in `app.css`
+const TABLE_ROW_HEIGHT_PX: f64 = 36.0;
+/// Rows rendered beyond each edge of the visible ones, so fast scrolling doesn't flash blank
+const TABLE_OVERSCAN_ROWS: usize = 5;
+const TABLE_VISIBLE_ROWS: usize = 10;
+/// Must match `.virtual-table.maximized` in `app.css`
+const TABLE_MAXIMIZED_VISIBLE_ROWS: usize = 25;
+/// Trades a table starts out with, however deep the history; each "show more" adds as
+/// many older ones again
+const TABLE_PAGE_ROWS: usize = 25;
+
+/// Which rows of a virtualised table that fits `shown` rows need to be in the DOM at
+/// the given scroll offset
+fn visible_rows(scroll_top: f64, total: usize, shown: usize) -> std::ops::Range<usize> {
+    let first_visible = (scroll_top / TABLE_ROW_HEIGHT_PX).floor().max(0.0) as usize;
+    let start = first_visible.saturating_sub(TABLE_OVERSCAN_ROWS).min(total);
+    let end = (first_visible + shown + TABLE_OVERSCAN_ROWS).min(total);
+    start..end
+}
+
+/// How often cards are checked for symbols that have gone quiet
+const STALE_CHECK_INTERVAL_SECS: u64 = 15;
+
+/// Trades only mark what they change as unsaved, which is written out at most this often
+const PERSIST_INTERVAL_SECS: u64 = 2;
+
+/// How often price changes are read out to screen readers, and how many at a time
+const ANNOUNCE_INTERVAL_SECS: u64 = 30;
+const MAX_ANNOUNCED_SYMBOLS: usize = 5;
+
+/// How many symbols the compare page charts at once, at most
+const MAX_COMPARED: usize = 5;
+const COMPARE_COLORS: [&str; MAX_COMPARED] =
+    ["#007bff", "#fd7e14", "#28a745", "#e83e8c", "#6f42c1"];
+const COMPARE_CHART_WIDTH: f64 = 800.0;
+const COMPARE_CHART_HEIGHT: f64 = 300.0;
+/// Gaps between trades longer than this get marked when squeezed out of a chart
+const CHART_GAP_MINS: i64 = 15;
+
+/// Charts whose x axis spacing can be picked
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+enum Chart {
+    /// The price chart of a maximized card
+    Symbol(Symbol),
+    Compare,
+}
+
+impl Chart {
+    /// Whatever each chart did before the spacing could be picked
+    fn default_spacing(&self) -> AxisSpacing {
+        match self {
+            Chart::Symbol(_) => AxisSpacing::Sequence,
+            Chart::Compare => AxisSpacing::RealTime,
+        }
+    }
+}
+
+/// Trades kept on the tape
+const TAPE_LENGTH: usize = 100;
+
+/// How long an untracked symbol can be brought back for
+const UNDO_UNTRACK_SECS: u64 = 10;
+
+/// Connections that fail before ever opening this many times in a row suggest the
+/// network blocks websockets, so we settle for polling quotes instead
+const WS_FAILURES_BEFORE_FALLBACK: u32 = 3;
+/// Between quick retries while it could still be a blip
+const WS_RETRY_SECS: u64 = 5;
+/// Finnhub's free plan allows this many symbols at once
+const DEFAULT_SUBSCRIPTION_LIMIT: u32 = 50;
+/// Warns once this share of the subscription limit is in use
+const SUBSCRIPTION_WARN_PERCENT: usize = 90;
+/// Between quiet retries once we've fallen back to polling
+const WS_FALLBACK_RETRY_SECS: u64 = 5 * 60;
+
+/// How often we check whether the connection has gone stale
+const HEARTBEAT_CHECK_INTERVAL_SECS: u64 = 5;
+
+/// Any shorter and normal gaps between messages would be taken for a dead connection
+const MIN_HEARTBEAT_TIMEOUT_SECS: u32 = 10;
+
+/// How often the demo's simulator sends a frame of trades
+const DEMO_TICK_MILLIS: u64 = 500;
+
+/// How often a replay moves on
+const REPLAY_TICK_MILLIS: u64 = 100;
+
+/// What the app can be mounted with. Everything left out is the real thing
+#[derive(Clone, Default, Properties)]
+pub struct Props {
+    /// Opens the websocket, so a fake one can stand in for finnhub
+    #[prop_or_default]
+    pub ws_connector: Option<SharedConnector>,
+}
+
+impl Component for Model {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let mut logger = Logger::new(LogLevel::default());
+        let maybe_storage_service = StorageService::new(Area::Local).ok();
+        if maybe_storage_service.is_none() {
+            logger.warn("Local storage is disabled, nothing will be saved.");
+        }
+        let mut salvage_error = None;
+        let mut state = maybe_storage_service
+            .as_ref()
+            .and_then(|s| {
+                let saved: Text = s.restore(STATE_STORAGE_KEY);
+                let saved = saved.ok()?;
+                match migrations::restore(&saved, State::salvage) {
+                    Ok(Restored::Upgraded(restored)) => Some(restored),
+                    Ok(Restored::Salvaged { state, error }) => {
+                        if let Err(e) = store_local(STATE_BACKUP_STORAGE_KEY, &saved) {
+                            logger.error(format!("Could not back up the saved state [{:?}]", e));
+                        }
+                        salvage_error = Some(error);
+                        Some(state)
+                    }
+                    Err(e) => {
+                        logger.error(format!("Saved state isn't JSON [{}]", e));
+                        None
+                    }
+                }
+            })
+            .map(|mut restored: State| {
+                restored.normalise();
+                restored
+            })
+            .unwrap_or_default();
+        state
+            .history
+            .set_retention(state.settings.history_retention_mins);
+        state.locale = i18n::locale(state.settings.language);
+        if let Some(Json(Ok(metadata))) = maybe_storage_service
+            .as_ref()
+            .map(|s| s.restore(METADATA_STORAGE_KEY))
+        {
+            state.metadata = metadata;
+        }
+        // left over from a demo that was running when the page was closed
+        state.tracked.retain(|symbol| !symbol.is_demo());
+        let maybe_session_storage_service = StorageService::new(Area::Session).ok();
+        let api_key_storage_service = match state.settings.api_key_storage {
+            ApiKeyStorage::Local => maybe_storage_service.as_ref(),
+            ApiKeyStorage::Session => maybe_session_storage_service.as_ref(),
+            ApiKeyStorage::Memory => None,
+        };
+        let locked_api_keys: Option<Sealed> = api_key_storage_service.and_then(|s| {
+            if let Json(Ok(restored)) = s.restore(SEALED_API_KEYS_STORAGE_KEY) {
+                Some(restored)
+            } else {
+                None
+            }
+        });
+        let api_key = api_key_storage_service
+            .filter(|_| locked_api_keys.is_none())
+            .and_then(|s| {
+                if let Json(Ok(restored)) = s.restore(API_KEY_STORAGE_KEY) {
+                    Some(restored)
+                } else {
+                    None
+                }
+            })
+            .or_else(|| {
+                maybe_storage_service.as_ref().and_then(|s| {
+                    if let Json(Ok(LegacyState {
+                        api_key: Some(legacy),
+                    })) = s.restore(STATE_STORAGE_KEY)
+                    {
+                        Some(legacy)
+                    } else {
+                        None
+                    }
+                })
+            })
+            .unwrap_or_default();
+        let api_key_profiles = api_key_storage_service
+            .and_then(|s| {
+                if let Json(Ok(restored)) = s.restore(API_KEY_PROFILES_STORAGE_KEY) {
+                    Some(restored)
+                } else {
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        let prefers_dark_listener = web_sys::window()
+            .and_then(|w| w.match_media("(prefers-color-scheme: dark)").ok())
+            .flatten()
+            .map(|media_query_list| {
+                let callback = link.callback(Msg::PrefersDarkUpdate);
+                let closure =
+                    Closure::wrap(
+                        Box::new(move |e: MediaQueryListEvent| callback.emit(e.matches()))
+                            as Box<dyn FnMut(MediaQueryListEvent)>,
+                    );
+                media_query_list.set_onchange(Some(closure.as_ref().unchecked_ref()));
+                (media_query_list, closure)
+            });
+        let prefers_dark = prefers_dark_listener
+            .as_ref()
+            .map(|(media_query_list, _)| media_query_list.matches())
+            .unwrap_or(false);
+
+        let visibility_listener = web_sys::window()
+            .and_then(|w| w.document())
+            .map(|document| {
+                let callback = link.callback(Msg::VisibilityChange);
+                let listener_document = document.clone();
+                let closure =
+                    Closure::wrap(Box::new(move || callback.emit(!listener_document.hidden()))
+                        as Box<dyn FnMut()>);
+                document.set_onvisibilitychange(Some(closure.as_ref().unchecked_ref()));
+                closure
+            });
+
+        let online = web_sys::window().is_none_or(|w| w.navigator().on_line());
+        let online_listeners = web_sys::window().map(|window| {
+            let listener = |online: bool| {
+                let callback = link.callback(move |_| Msg::OnlineChange(online));
+                Closure::wrap(Box::new(move || callback.emit(())) as Box<dyn FnMut()>)
+            };
+            let (on_online, on_offline) = (listener(true), listener(false));
+            window.set_ononline(Some(on_online.as_ref().unchecked_ref()));
+            window.set_onoffline(Some(on_offline.as_ref().unchecked_ref()));
+            (on_online, on_offline)
+        });
+
+        // yew has no paste events, and text inputs drop the newlines from pasted lists,
+        // so lists pasted into the ticker input are picked up here instead. web-sys only
+        // has `ClipboardEvent` behind its unstable APIs, hence the `Reflect`
+        let paste_listener = web_sys::window()
+            .and_then(|w| w.document())
+            .map(|document| {
+                let callback = link.callback(Msg::PasteSymbols);
+                let closure = Closure::wrap(Box::new(move |e: Event| {
+                    let into_ticker_input = e
+                        .target()
+                        .and_then(|t| t.dyn_into::<Element>().ok())
+                        .map(|el| el.id() == TICKER_INPUT_ID)
+                        .unwrap_or(false);
+                    let text = js_sys::Reflect::get(&e, &"clipboardData".into())
+                        .ok()
+                        .and_then(|data| data.dyn_into::<DataTransfer>().ok())
+                        .and_then(|data| data.get_data("text").ok())
+                        .unwrap_or_default();
+                    if into_ticker_input && Symbol::parse_list(&text).0.len() > 1 {
+                        e.prevent_default();
+                        callback.emit(text);
+                    }
+                }) as Box<dyn FnMut(Event)>);
+                let _ = document
+                    .add_event_listener_with_callback("paste", closure.as_ref().unchecked_ref());
+                closure
+            });
+
+        let hash_change_listener = web_sys::window().map(|window| {
+            let callback = link.callback(|_| Msg::RouteChanged(Route::current()));
+            let closure = Closure::wrap(Box::new(move || callback.emit(())) as Box<dyn FnMut()>);
+            window.set_onhashchange(Some(closure.as_ref().unchecked_ref()));
+            closure
+        });
+
+        // callbacks are handled synchronously, so this is stored before the page goes
+        let before_unload_listener = web_sys::window().map(|window| {
+            let callback = link.callback(|_| Msg::BeforeUnload);
+            let closure = Closure::wrap(Box::new(move || callback.emit(())) as Box<dyn FnMut()>);
+            window.set_onbeforeunload(Some(closure.as_ref().unchecked_ref()));
+            closure
+        });
+        let was_connected = maybe_session_storage_service.as_ref().and_then(|s| {
+            if let Json(Ok(restored)) = s.restore(WANTS_CONNECTION_STORAGE_KEY) {
+                Some(restored)
+            } else {
+                None
+            }
+        });
+
+        let legacy_history = state.tracked.iter().any(|s| state.history.get(s).is_some());
+        let language = Language::from_locale(&state.locale);
+        let time_formatter = TimeFormatter::new(&state.settings.time_zone, language)
+            .or_else(|_| TimeFormatter::new(&TimeZoneSetting::Local, language))
+            .expect("local time formatting can't fail");
+
+        let trade_worker = TradeWorker::bridge(link.callback(Msg::WorkerOutput));
+        let mut interval_service = IntervalService::new();
+        let stale_check_task = interval_service.spawn(
+            std::time::Duration::from_secs(STALE_CHECK_INTERVAL_SECS),
+            link.callback(|_| Msg::StaleCheck),
+        );
+        let persist_task = interval_service.spawn(
+            std::time::Duration::from_secs(PERSIST_INTERVAL_SECS),
+            link.callback(|_| Msg::PersistDirty),
+        );
+        let announce_task = interval_service.spawn(
+            std::time::Duration::from_secs(ANNOUNCE_INTERVAL_SECS),
+            link.callback(|_| Msg::Announce),
+        );
+        // only ones older than a day actually get fetched again
+        let earnings_refresh_task = interval_service.spawn(
+            std::time::Duration::from_secs(EARNINGS_REFRESH_INTERVAL_SECS),
+            link.callback(|_| Msg::EarningsRefresh),
+        );
+
+        // connecting has to wait until the key's unlocked, if it's encrypted
+        let auto_connect_on_unlock =
+            locked_api_keys.is_some() && was_connected.unwrap_or(state.settings.auto_connect);
+        let mut model = Model {
+            symbol_to_add: Symbol("".into()),
+            exchange_to_add: "".into(),
+            dragging: None,
+            state,
+            api_key,
+            api_key_profiles,
+            profile_name_to_save: "".into(),
+            key_check: KeyCheck::Unchecked,
+            key_check_task: None,
+            not_entitled: HashSet::new(),
+            not_entitled_symbols: HashSet::new(),
+            auto_connect_on_unlock,
+            passphrase: None,
+            locked_api_keys,
+            passphrase_input: "".into(),
+            passphrase_error: None,
+            unlocking: false,
+            seal_seq: 0,
+            storage_service: maybe_storage_service,
+            session_storage_service: maybe_session_storage_service,
+            ws_connector: props
+                .ws_connector
+                .unwrap_or_else(|| Rc::new(RefCell::new(WebSocketService::new()))),
+            interval_service,
+            reader_service: ReaderService::new(),
+            csv_reader_task: None,
+            csv_import: None,
+            timeout_service: TimeoutService::new(),
+            api_client: ApiClient::new(),
+            notifications: Notifications::new(),
+            reconnect_notification: None,
+            ws_failures: 0,
+            ws_fallback: false,
+            hidden_pause: false,
+            hidden_pause_task: None,
+            ws_retry_task: None,
+            storage_full_notification: None,
+            subscription_limit_notification: None,
+            undo_timeouts: HashMap::new(),
+            tab_sync: TabSync::new(link.callback(Msg::TabMessage)).ok(),
+            election: Election::new(tab_sync::new_tab_id(), Utc::now()),
+            tab_sync_task: None,
+            logger,
+            history_store: None,
+            history_loaded: false,
+            legacy_history,
+            expanded_profiles: HashSet::new(),
+            profile_tasks: HashMap::new(),
+            earnings_tasks: HashMap::new(),
+            _earnings_refresh_task: earnings_refresh_task,
+            unknown_messages: BTreeMap::new(),
+            inspector: Inspector::default(),
+            card_tabs: HashMap::new(),
+            news: HashMap::new(),
+            news_highlight_after: HashMap::new(),
+            news_tasks: HashMap::new(),
+            news_refresh_task: None,
+            quote_poll_task: None,
+            quote_tasks: HashMap::new(),
+            candle_tasks: HashMap::new(),
+            fx_refresh_task: None,
+            fx_task: None,
+            bid_ask_refresh_task: None,
+            bid_ask_tasks: HashMap::new(),
+            bid_asks: HashMap::new(),
+            symbol_list_requested: false,
+            symbol_list_task: None,
+            symbol_input_error: None,
+            beeper: None,
+            prefers_dark,
+            _prefers_dark_listener: prefers_dark_listener,
+            _visibility_listener: visibility_listener,
+            online,
+            _online_listeners: online_listeners,
+            _paste_listener: paste_listener,
+            route: Route::current(),
+            _hash_change_listener: hash_change_listener,
+            _before_unload_listener: before_unload_listener,
+            _keyboard_shortcuts: KeyboardShortcuts::listen(link.callback(Msg::Shortcut)),
+            show_shortcuts_help: false,
+            tag_filter: None,
+            card_query: String::new(),
+            health_filter: None,
+            maximized: None,
+            compared: vec![],
+            chart_spacings: HashMap::new(),
+            symbol_input_ref: NodeRef::default(),
+            main_content_ref: NodeRef::default(),
+            announcement: String::new(),
+            announced_prices: HashMap::new(),
+            _announce_task: announce_task,
+            flash_parity: HashMap::new(),
+            redraw_all_cards: true,
+            redraw_cards: HashSet::new(),
+            mounted_cards: HashSet::new(),
+            cards_in_view: RefCell::new(Vec::new()),
+            table_sorts: HashMap::new(),
+            table_scroll_tops: HashMap::new(),
+            table_depths: HashMap::new(),
+            session_totals: HashMap::new(),
+            tick_counts: HashMap::new(),
+            session_ranges: HashMap::new(),
+            tape: VecDeque::new(),
+            rule_engine: RuleEngine::default(),
+            alert_rule_inputs: HashMap::new(),
+            alert_rule_errors: HashMap::new(),
+            time_formatter,
+            relative_time_task: None,
+            wants_connection: false,
+            link,
+            session: None,
+            connection_state: ConnectionState::Disconnected,
+            heartbeat_task: None,
+            demo: None,
+            replay: None,
+            symbol_errors: HashMap::new(),
+            last_message_at: None,
+            metrics: Metrics::new(),
+            _stale_check_task: stale_check_task,
+            state_dirty: false,
+            dirty_histories: HashSet::new(),
+            _persist_task: persist_task,
+            stale_symbols: HashSet::new(),
+            trade_worker,
+            trade_filter: TradeFilter::default(),
+        };
+        if let Err(e) = HistoryStore::open(model.link.callback(Msg::HistoryStoreOpened)) {
+            model.logger.warn(format!(
+                "IndexedDB is unavailable, history won't be saved [{}]",
+                e
+            ));
+        }
+        model.logger.set_level(model.state.settings.log_level());
+        model.logger.set_secret(&model.api_key.0);
+        if let Some(error) = salvage_error {
+            model.logger.warn(format!(
+                "Saved state could only be partly read, a backup is under [{}] [{}]",
+                STATE_BACKUP_STORAGE_KEY, error
+            ));
+            model.notifications.push(
+                Level::Warning,
+                "Your saved data couldn't be read in full, so only your watchlist and what settings could be were kept",
+            );
+        }
+        // moves a legacy API key and company profiles out of the state blob: into
+        // their own keys, then saving the state over the blob without them. Legacy
+        // history stays in it until it's been written to IndexedDB
+        model.persist_api_key();
+        model.persist_metadata();
+        model.persist_state();
+        model.update_relative_time_task();
+        model.update_quote_polling();
+        model.sync_trade_filter();
+        model.fetch_stale_profiles();
+        model.fetch_stale_earnings();
+        model.update_fx_refresh();
+        model.update_bid_ask_refresh();
+        model.offer_shared_import();
+        model.start_tab_sync();
+        // a reload picks up where it left off, while a fresh visit goes by the setting
+        if was_connected.unwrap_or(model.state.settings.auto_connect)
+            && !model.api_key.0.is_empty()
+            && !model.state.tracked.is_empty()
+        {
+            model.logger.info("Connecting automatically");
+            model.update(Msg::ApiKeyConnect);
+        }
+        model
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        // trade rates on cards without trades of their own catch up on the next
+        // render of every card
+        match msg.cards_changed() {
+            CardsChanged::None => (),
+            CardsChanged::One(symbol) => {
+                self.redraw_cards.insert(symbol.clone());
+            }
+            CardsChanged::All => self.redraw_all_cards = true,
+        }
+        match msg {
+            Msg::ApiKeyUpdate(key) => {
+                self.api_key = key;
+                self.logger.set_secret(&self.api_key.0);
+                self.reset_key_check();
+                // an edited key no longer matches the profile it was picked from
+                let active_key = self
+                    .api_key_profiles
+                    .active
+                    .as_ref()
+                    .and_then(|name| self.api_key_profiles.profiles.get(name));
+                if active_key != Some(&self.api_key) {
+                    self.api_key_profiles.active = None;
+                }
+                self.persist_api_key();
+            }
+            Msg::ApiKeyProfileSelect(name) => {
+                let key = match self.api_key_profiles.profiles.get(&name) {
+                    Some(key) => key.clone(),
+                    None => return false,
+                };
+                self.api_key = key;
+                self.logger.set_secret(&self.api_key.0);
+                self.reset_key_check();
+                self.api_key_profiles.active = Some(name);
+                self.persist_api_key();
+                // the websocket is tied to the key it was opened with
+                if self.connection_state != ConnectionState::Disconnected {
+                    self.disconnect();
+                    return self.update(Msg::ApiKeyConnect);
+                }
+            }
+            Msg::ApiKeyProfileNameUpdate(name) => self.profile_name_to_save = name,
+            Msg::ApiKeyProfileSave => {
+                let name = self.profile_name_to_save.trim().to_string();
+                if name.is_empty() || self.api_key.0.is_empty() {
+                    return false;
+                }
+                self.api_key_profiles
+                    .profiles
+                    .insert(name.clone(), self.api_key.clone());
+                self.api_key_profiles.active = Some(name);
+                self.profile_name_to_save.clear();
+                self.persist_api_key();
+            }
+            Msg::ApiKeyProfileDelete => {
+                if let Some(name) = self.api_key_profiles.active.take() {
+                    self.api_key_profiles.profiles.remove(&name);
+                    self.persist_api_key();
+                }
+            }
+            Msg::PassphraseInput(passphrase) => {
+                self.passphrase_input = passphrase;
+                self.passphrase_error = None;
+            }
+            Msg::SetPassphrase => {
+                if self.passphrase_input.is_empty() {
+                    return false;
+                }
+                self.passphrase = Some(std::mem::take(&mut self.passphrase_input));
+                self.persist_api_key();
+                self.notifications
+                    .push(Level::Info, "The API key will be stored encrypted");
+            }
+            Msg::RemovePassphrase => {
+                self.passphrase = None;
+                self.persist_api_key();
+            }
+            Msg::UnlockApiKey => {
+                let sealed = match &self.locked_api_keys {
+                    Some(sealed) if !self.passphrase_input.is_empty() && !self.unlocking => {
+                        sealed.clone()
+                    }
+                    _ => return false,
+                };
+                self.unlocking = true;
+                self.passphrase_error = None;
+                key_vault::open(
+                    sealed,
+                    self.passphrase_input.clone(),
+                    self.link.callback(Msg::ApiKeyUnsealed),
+                );
+            }
+            Msg::ForgetLockedApiKey => {
+                self.locked_api_keys = None;
+                self.passphrase = None;
+                self.passphrase_input.clear();
+                self.passphrase_error = None;
+                self.auto_connect_on_unlock = false;
+                self.persist_api_key();
+            }
+            Msg::ApiKeySealed(seq, result) => {
+                if seq != self.seal_seq {
+                    return false;
+                }
+                match result {
+                    Ok(sealed) => self.store_api_keys(Some(&sealed)),
+                    Err(e) => {
+                        self.logger
+                            .error(format!("Couldn't encrypt the API key [{}]", e));
+                        self.notifications.push(
+                            Level::Error,
+                            format!("Couldn't encrypt the API key, so it wasn't saved: {}", e),
+                        );
+                    }
+                }
+            }
+            Msg::ApiKeyUnsealed(result) => {
+                self.unlocking = false;
+                let unsealed = result.and_then(|json| {
+                    serde_json::from_str::<(ApiKey, ApiKeyProfiles)>(&json)
+                        .map_err(|e| e.to_string())
+                });
+                match unsealed {
+                    Ok((api_key, api_key_profiles)) => {
+                        self.api_key = api_key;
+                        self.api_key_profiles = api_key_profiles;
+                        self.logger.set_secret(&self.api_key.0);
+                        self.reset_key_check();
+                        self.locked_api_keys = None;
+                        self.passphrase = Some(std::mem::take(&mut self.passphrase_input));
+                        if std::mem::take(&mut self.auto_connect_on_unlock)
+                            && !self.api_key.0.is_empty()
+                            && !self.state.tracked.is_empty()
+                        {
+                            self.logger.info("Connecting automatically");
+                            self.update(Msg::ApiKeyConnect);
+                        }
+                    }
+                    Err(e) => self.passphrase_error = Some(e),
+                }
+            }
+            Msg::ApiKeyConnect if !self.online => {
+                self.wants_connection = true;
+                self.notifications.push(
+                    Level::Info,
+                    "You're offline, so this will connect once the network is back",
+                );
+            }
+            // rather than leave a bad key to the websocket, whose failures don't say why
+            Msg::ApiKeyConnect
+                if matches!(
+                    self.key_check,
+                    KeyCheck::Unchecked | KeyCheck::Checking | KeyCheck::Invalid
+                ) =>
+            {
+                self.wants_connection = true;
+                if self.key_check != KeyCheck::Checking {
+                    self.key_check = KeyCheck::Checking;
+                    self.key_check_task =
+                        Some(self.fetch_json_first(&rest::key_check(), Msg::ApiKeyChecked));
+                }
+            }
+            Msg::ApiKeyChecked(result) => {
+                self.key_check_task = None;
+                match result {
+                    Ok(_) => self.key_check = KeyCheck::Valid,
+                    Err(e) if e == api_client::INVALID_API_KEY => {
+                        self.key_check = KeyCheck::Invalid;
+                        self.wants_connection = false;
+                        return true;
+                    }
+                    Err(e) => {
+                        self.logger.warn(format!(
+                            "Could not check the API key, connecting anyway [{}]",
+                            e
+                        ));
+                        self.key_check = KeyCheck::Inconclusive;
+                    }
+                }
+                if self.wants_connection {
+                    return self.update(Msg::ApiKeyConnect);
+                }
+            }
+            Msg::ApiKeyConnect => {
+                self.wants_connection = true;
+                self.fetch_stale_profiles();
+                self.fetch_stale_earnings();
+                self.update_fx_refresh();
+                self.update_bid_ask_refresh();
+                return self.connect_to_api();
+            }
+            Msg::ApiKeyDisconnect => {
+                self.wants_connection = false;
+                self.ws_failures = 0;
+                self.ws_fallback = false;
+                self.ws_retry_task = None;
+                self.disconnect();
+            }
+            Msg::StartDemo => self.start_demo(),
+            Msg::StartReplay(symbol) => {
+                self.stop_replay();
+                let history = match self.state.history.remove(&symbol) {
+                    Some(history) => history,
+                    None => return false,
+                };
+                let task = self.interval_service.spawn(
+                    std::time::Duration::from_millis(REPLAY_TICK_MILLIS),
+                    self.link.callback(|_| Msg::ReplayTick),
+                );
+                let history = history.into_iter().map(|trade| trade.info).collect();
+                let replay = Replay::new(symbol, history, ReplaySpeed::RealTime);
+                self.replay = Some((replay, task));
+            }
+            Msg::ReplaySpeedUpdate(speed) => {
+                if let Some((replay, _)) = &mut self.replay {
+                    replay.speed = speed;
+                }
+            }
+            Msg::ReplayTick => {
+                let (replay, _) = match &mut self.replay {
+                    Some(replay) => replay,
+                    None => return false,
+                };
+                let due = replay.step(Duration::milliseconds(REPLAY_TICK_MILLIS as i64));
+                let symbol = replay.symbol.clone();
+                let done = replay.is_done();
+                if !due.is_empty() {
+                    // the same way live trades go in, bar the alerts
+                    for ticker_info in due {
+                        let mut trade = Trade::from(ticker_info);
+                        self.state.annotate_direction(&mut trade);
+                        self.state.add_history(trade);
+                    }
+                    let parity = self.flash_parity.entry(symbol).or_default();
+                    *parity = !*parity;
+                }
+                if done {
+                    self.stop_replay();
+                }
+            }
+            Msg::StopReplay => self.stop_replay(),
+            Msg::DemoTick => {
+                if let Some((simulator, _)) = &mut self.demo {
+                    let symbols = self.state.streaming().filter(|s| s.is_demo());
+                    let frame = simulator.frame(symbols, Utc::now());
+                    return self.update(Msg::WsFrame(Ok(frame)));
+                }
+                return false;
+            }
+            Msg::UpdateSymbolToTrack(symbol) => {
+                self.symbol_to_add = symbol;
+                self.symbol_input_error = None;
+                self.fetch_symbol_list();
+            }
+            Msg::UpdateExchangeToTrack(exchange) => self.exchange_to_add = exchange,
+            Msg::TrackSymbol => {
+                let input = std::mem::take(&mut self.symbol_to_add.0);
+                return self.track_symbols(&input);
+            }
+            Msg::PasteSymbols(text) => return self.track_symbols(&text),
+            Msg::RetrySymbol(symbol) => {
+                if self.symbol_errors.remove(&symbol).is_some() {
+                    self.reconcile_subscriptions();
+                }
+            }
+            Msg::UnTrackSymbolAtIdx(idx) => {
+                if let Some(tombstone) = self.untrack(idx) {
+                    self.offer_undo(tombstone);
+                }
+                self.persist_state();
+            }
+            Msg::UnTrackAll => {
+                // from the end, so the indices left to go stay put
+                let tombstones: Vec<Tombstone> = (0..self.state.tracked.len())
+                    .rev()
+                    .filter_map(|idx| self.untrack(idx))
+                    .collect();
+                if !tombstones.is_empty() {
+                    self.offer_undo_all(tombstones);
+                }
+                self.persist_state();
+            }
+            Msg::ClearSymbolHistory(symbol) => {
+                if self.is_replaying(&symbol) {
+                    self.stop_replay();
+                }
+                if self.state.history.remove(&symbol).is_none() {
+                    return false;
+                }
+                self.delete_history(&symbol);
+                self.tape.retain(|trade| trade.symbol != symbol);
+            }
+            Msg::DragStart(idx) => self.dragging = Some(idx),
+            Msg::DragOver => return false,
+            Msg::DropAtIdx(idx) => {
+                if let Some(from) = self.dragging.take() {
+                    self.state.move_symbol(from, idx);
+                    self.persist_state();
+                }
+            }
+            Msg::DragEnd => self.dragging = None,
+            Msg::VolumeAlertUpdate(symbol, maybe_volume) => {
+                match maybe_volume {
+                    Some(volume) => self.state.volume_alerts.insert(symbol, volume),
+                    None => self.state.volume_alerts.remove(&symbol),
+                };
+                self.persist_state();
+            }
+            Msg::MinVolumeUpdate(symbol, maybe_volume) => {
+                match maybe_volume {
+                    Some(volume) => self.state.min_volumes.insert(symbol, volume),
+                    None => self.state.min_volumes.remove(&symbol),
+                };
+                self.persist_state();
+            }
+            Msg::SharesUpdate(symbol, maybe_shares) => {
+                self.state.update_position(symbol, |position| {
+                    position.shares = maybe_shares.unwrap_or_default()
+                });
+                self.persist_state();
+            }
+            Msg::CostBasisUpdate(symbol, maybe_cost_basis) => {
+                self.state.update_position(symbol, |position| {
+                    position.cost_basis = maybe_cost_basis.unwrap_or_default()
+                });
+                self.persist_state();
+            }
+            Msg::AlertRuleInput(symbol, input) => {
+                self.alert_rule_errors.remove(&symbol);
+                self.alert_rule_inputs.insert(symbol, input);
+            }
+            Msg::AddAlertRule(symbol) => {
+                let input = self.alert_rule_inputs.get(&symbol).map(String::as_str);
+                match Condition::parse(input.unwrap_or_default()) {
+                    Ok(condition) => {
+                        self.alert_rule_inputs.remove(&symbol);
+                        self.rule_engine.forget(&symbol);
+                        self.state
+                            .alert_rules
+                            .entry(symbol)
+                            .or_default()
+                            .push(condition);
+                        self.persist_state();
+                    }
+                    Err(e) => {
+                        self.alert_rule_errors.insert(symbol, e);
+                    }
+                }
+            }
+            Msg::RemoveAlertRule(symbol, idx) => {
+                if let Entry::Occupied(mut rules) = self.state.alert_rules.entry(symbol.clone()) {
+                    if idx < rules.get().len() {
+                        rules.get_mut().remove(idx);
+                    }
+                    if rules.get().is_empty() {
+                        rules.remove();
+                    }
+                }
+                self.rule_engine.forget(&symbol);
+                self.persist_state();
+            }
+            Msg::PriceTargetUpdate(symbol, maybe_target) => {
+                match maybe_target {
+                    Some(target) => {
+                        let reference = self
+                            .state
+                            .price_targets
+                            .get(&symbol)
+                            .and_then(|t| t.reference)
+                            .or_else(|| self.state.latest_price(&symbol));
+                        self.state
+                            .price_targets
+                            .insert(symbol, PriceTarget { target, reference });
+                    }
+                    None => {
+                        self.state.price_targets.remove(&symbol);
+                    }
+                }
+                self.persist_state();
+            }
+            Msg::SymbolGroupUpdate(symbol, maybe_group) => {
+                self.state.remove_from_group(&symbol);
+                if let Some(group) = maybe_group {
+                    self.state.symbol_groups.insert(symbol, group);
+                }
+                self.persist_state();
+            }
+            Msg::NoteUpdate(symbol, maybe_note) => {
+                match maybe_note {
+                    Some(note) => self.state.notes.insert(symbol, note),
+                    None => self.state.notes.remove(&symbol),
+                };
+                self.persist_state();
+            }
+            Msg::TagsUpdate(symbol, tags) => {
+                if tags.is_empty() {
+                    self.state.tags.remove(&symbol);
+                } else {
+                    self.state.tags.insert(symbol, tags);
+                }
+                // filtering by a tag nothing has any more would hide every card
+                let orphaned = self
+                    .tag_filter
+                    .as_ref()
+                    .is_some_and(|tag| !self.state.all_tags().contains(tag));
+                if orphaned {
+                    self.tag_filter = None;
+                }
+                self.persist_state();
+            }
+            Msg::CardQueryUpdate(query) => self.card_query = query,
+            Msg::HealthFilterUpdate(health) => self.health_filter = health,
+            Msg::ChartSpacingUpdate(chart, spacing) => {
+                self.chart_spacings.insert(chart, spacing);
+            }
+            Msg::TagFilterToggle(tag) => {
+                self.tag_filter = if self.tag_filter.as_ref() == Some(&tag) {
+                    None
+                } else {
+                    Some(tag)
+                };
+            }
+            Msg::ToggleGroupCollapsed(group) => {
+                if !self.state.collapsed_groups.remove(&group) {
+                    self.state.collapsed_groups.insert(group);
+                }
+                self.persist_state();
+            }
+            Msg::GlobalMinVolumeUpdate(maybe_volume) => {
+                self.state.settings.min_volume = maybe_volume;
+                self.persist_state();
+            }
+            Msg::SoundMutedToggle => {
+                self.state.settings.sound_muted = !self.state.settings.sound_muted;
+                self.persist_state();
+            }
+            Msg::HistoryStoreOpened(Ok(mut history_store)) => {
+                history_store.on_quota_exceeded(self.link.callback(|_| Msg::HistoryQuotaExceeded));
+                history_store.load_all(self.link.callback(Msg::HistoryLoaded));
+                self.history_store = Some(history_store);
+                return false;
+            }
+            Msg::HistoryStoreOpened(Err(e)) => {
+                self.logger.warn(format!(
+                    "Could not open IndexedDB, history won't be saved [{}]",
+                    e
+                ));
+                return false;
+            }
+            Msg::HistoryLoaded(Ok(stored)) => {
+                for (key, value) in stored {
+                    let symbol = Symbol(key);
+                    if !self.state.tracked.contains(&symbol) {
+                        self.delete_history(&symbol);
+                        continue;
+                    }
+                    match serde_json::from_str::<VecDeque<Trade>>(&value) {
+                        Ok(history) => self.state.history.merge(symbol, history),
+                        Err(e) => self.logger.error(format!(
+                            "Could not read stored history for [{}]: {}",
+                            symbol.0, e
+                        )),
+                    }
+                }
+                self.history_loaded = true;
+                // writes back anything merged in
+                if self.legacy_history {
+                    self.write_back_legacy_history();
+                } else {
+                    for symbol in self.state.tracked.clone() {
+                        self.persist_history(&symbol);
+                    }
+                }
+            }
+            Msg::LegacyHistoryWritten(Ok(())) => {
+                self.legacy_history = false;
+                self.persist_state();
+                return false;
+            }
+            Msg::LegacyHistoryWritten(Err(e)) => {
+                self.logger.warn(format!(
+                    "Could not move history into IndexedDB, so it's kept where it was [{}]",
+                    e
+                ));
+                return false;
+            }
+            Msg::HistoryQuotaExceeded => {
+                // the retry may well fail again, in which case we end up back here and
+                // trim some more
+                if self.state.history.trim_oldest() {
+                    for symbol in self.state.tracked.clone() {
+                        self.persist_history(&symbol);
+                    }
+                }
+                self.warn_storage_full(
+                    "Browser storage is full, so the oldest trade history was dropped",
+                    true,
+                );
+            }
+            Msg::HistoryLoaded(Err(e)) => {
+                self.logger
+                    .error(format!("Could not load stored history [{}]", e));
+                return false;
+            }
+            Msg::ToggleProfile(symbol) => {
+                if !self.expanded_profiles.remove(&symbol) {
+                    if self.state.metadata.profile(&symbol).is_none() {
+                        self.fetch_profile(&symbol);
+                    }
+                    self.expanded_profiles.insert(symbol);
+                }
+            }
+            Msg::ProfileFetched(symbol, result) => {
+                self.profile_tasks.remove(&symbol);
+                match result {
+                    Ok(profile) => {
+                        self.state.metadata.set_profile(symbol, profile, Utc::now());
+                        self.persist_metadata();
+                    }
+                    Err(e) => {
+                        // a stale profile is still better than none
+                        if self.state.metadata.profile(&symbol).is_none() {
+                            self.expanded_profiles.remove(&symbol);
+                        }
+                        self.logger
+                            .error(format!("Could not fetch profile for [{}]: {}", symbol.0, e));
+                    }
+                }
+            }
+            Msg::SortTable(symbol, column) => {
+                let sort = self.table_sort(&symbol).toggled(column);
+                self.table_sorts.insert(symbol, sort);
+            }
+            Msg::TableScroll(symbol, scroll_top) => {
+                let depth = self.table_depth(&symbol);
+                let total = self
+                    .state
+                    .history
+                    .get(&symbol)
+                    .map_or(0, |h| h.len().min(depth));
+                let shown = self.table_shown_rows(&symbol);
+                let previous = self.table_scroll_tops.insert(symbol, scroll_top);
+                // most scroll events don't move the window of rendered rows
+                return previous.map(|top| visible_rows(top, total, shown))
+                    != Some(visible_rows(scroll_top, total, shown));
+            }
+            Msg::ShowMoreTrades(symbol) => {
+                let depth = self.table_depth(&symbol) + TABLE_PAGE_ROWS;
+                self.table_depths.insert(symbol, depth);
+            }
+            Msg::ShowFewerTrades(symbol) => {
+                self.table_depths.remove(&symbol);
+                self.table_scroll_tops.remove(&symbol);
+            }
+            Msg::SelectCardTab(symbol, tab) => {
+                if tab == CardTab::News && self.card_tab(&symbol) != CardTab::News {
+                    let seen_at = self
+                        .state
+                        .news_seen_at
+                        .insert(symbol.clone(), Utc::now())
+                        .unwrap_or_else(|| Utc::now() - Duration::days(NEWS_LOOKBACK_DAYS));
+                    self.news_highlight_after.insert(symbol.clone(), seen_at);
+                    self.persist_state();
+                    self.fetch_news(&symbol);
+                }
+                self.card_tabs.insert(symbol, tab);
+                self.update_news_refresh();
+            }
+            Msg::NewsFetched(symbol, result) => {
+                self.news_tasks.remove(&symbol);
+                match result {
+                    Ok(mut news) => {
+                        news.sort_by_key(|n| Reverse(n.datetime));
+                        self.news.insert(symbol, news);
+                    }
+                    Err(e) if e == api_client::NOT_ENTITLED => {
+                        self.note_not_entitled_for(PlanFeature::News, symbol);
+                        self.update_news_refresh();
+                    }
+                    Err(e) => {
+                        self.logger
+                            .error(format!("Could not fetch news for [{}]: {}", symbol.0, e));
+                        return false;
+                    }
+                }
+            }
+            Msg::NewsRefresh => {
+                let showing_news: Vec<Symbol> = self
+                    .card_tabs
+                    .iter()
+                    .filter(|(_, tab)| **tab == CardTab::News)
+                    .map(|(symbol, _)| symbol.clone())
+                    .collect();
+                for symbol in showing_news {
+                    self.state.news_seen_at.insert(symbol.clone(), Utc::now());
+                    self.fetch_news(&symbol);
+                }
+                self.persist_state();
+                return false;
+            }
+            Msg::ShareWatchlist => self.share_watchlist(),
+            Msg::CsvFileChosen(file) => {
+                let callback = self.link.callback(Msg::CsvFileLoaded);
+                match self.reader_service.read_file(file, callback) {
+                    Ok(task) => self.csv_reader_task = Some(task),
+                    Err(e) => {
+                        self.logger.error(format!("Could not read file: {}", e));
+                        self.notifications
+                            .push(Level::Error, "Could not read that file");
+                    }
+                }
+            }
+            Msg::CsvFileLoaded(data) => {
+                self.csv_reader_task = None;
+                let symbols = csv_import::detect_symbols(&String::from_utf8_lossy(&data.content));
+                if symbols.is_empty() {
+                    self.csv_import = None;
+                    self.notifications.push(
+                        Level::Warning,
+                        format!("Found no symbols in [{}]", data.name),
+                    );
+                } else {
+                    let selected = symbols
+                        .iter()
+                        .filter(|s| !self.state.tracked.contains(s))
+                        .cloned()
+                        .collect();
+                    self.csv_import = Some(CsvImport {
+                        file_name: data.name,
+                        symbols,
+                        selected,
+                    });
+                }
+            }
+            Msg::CsvImportToggle(symbol) => {
+                if let Some(import) = &mut self.csv_import {
+                    if !import.selected.remove(&symbol) {
+                        import.selected.insert(symbol);
+                    }
+                }
+            }
+            Msg::CsvImportConfirm => {
+                if let Some(import) = self.csv_import.take() {
+                    // in file order rather than however the set has them
+                    let CsvImport {
+                        symbols, selected, ..
+                    } = import;
+                    let selected = symbols
+                        .into_iter()
+                        .filter(|s| selected.contains(s))
+                        .collect();
+                    self.import_symbols(selected);
+                }
+            }
+            Msg::CsvImportCancel => self.csv_import = None,
+            Msg::CompareToggle(symbol) => match self.compared.iter().position(|s| *s == symbol) {
+                Some(idx) => {
+                    self.compared.remove(idx);
+                }
+                None if self.compared.len() < MAX_COMPARED => self.compared.push(symbol),
+                None => return false,
+            },
+            Msg::Maximize(symbol) => {
+                self.maximized = symbol;
+                if let Some(symbol) = &self.maximized {
+                    // the window of rendered rows is worked out afresh for the taller table
+                    self.table_scroll_tops.remove(symbol);
+                }
+            }
+            Msg::QuotePoll => {
+                self.poll_quotes();
+                return false;
+            }
+            Msg::QuoteFetched(symbol, result) => {
+                self.quote_tasks.remove(&symbol);
+                match result.map(|quote| quote.into_ticker_info(symbol.clone())) {
+                    Ok(Some(ticker_info)) => {
+                        let is_new = self
+                            .state
+                            .history
+                            .get(&symbol)
+                            .and_then(|h| h.front())
+                            .map(|newest| ticker_info.time > newest.time)
+                            .unwrap_or(true);
+                        // the websocket may have come back while this was in flight
+                        if !is_new
+                            || self.session.is_some()
+                            || self.state.is_paused(&symbol)
+                            || self.is_replaying(&symbol)
+                        {
+                            return false;
+                        }
+                        let mut trade = Trade::from(ticker_info);
+                        self.state.annotate_direction(&mut trade);
+                        self.state.add_history(trade);
+                        self.persist_history(&symbol);
+                        let parity = self.flash_parity.entry(symbol).or_default();
+                        *parity = !*parity;
+                    }
+                    Ok(None) => return false,
+                    Err(e) => {
+                        self.logger
+                            .error(format!("Could not fetch quote for [{}]: {}", symbol.0, e));
+                        return false;
+                    }
+                }
+            }
+            Msg::CandlesFetched(symbol, result) => {
+                self.candle_tasks.remove(&symbol);
+                match result {
+                    Ok(candles) => {
+                        let trades = candles.into_trades(&symbol);
+                        let backfilled = self.state.history.backfill(symbol.clone(), trades);
+                        if backfilled == 0 {
+                            return false;
+                        }
+                        self.logger.debug(format!(
+                            "Backfilled [{}] with {} candles",
+                            symbol.0, backfilled
+                        ));
+                        self.persist_history(&symbol);
+                    }
+                    Err(e) if e == api_client::NOT_ENTITLED => {
+                        self.note_not_entitled(PlanFeature::Candles)
+                    }
+                    Err(e) => {
+                        self.logger
+                            .error(format!("Could not fetch candles for [{}]: {}", symbol.0, e));
+                        return false;
+                    }
+                }
+            }
+            Msg::FxRefresh => {
+                self.fetch_fx_rates();
+                return false;
+            }
+            Msg::EarningsRefresh => {
+                self.fetch_stale_earnings();
+                return false;
+            }
+            Msg::EarningsFetched(symbol, result) => {
+                self.earnings_tasks.remove(&symbol);
+                match result {
+                    // it may have been untracked while this was in flight
+                    Ok(calendar) if self.state.tracked.contains(&symbol) => {
+                        let cache = EarningsCache::new(calendar, Utc::now());
+                        self.state.earnings.insert(symbol, cache);
+                        self.persist_state();
+                    }
+                    Ok(_) => return false,
+                    Err(e) if e == api_client::NOT_ENTITLED => {
+                        self.note_not_entitled_for(PlanFeature::Earnings, symbol)
+                    }
+                    Err(e) => {
+                        self.logger.warn(format!(
+                            "Could not fetch earnings for [{}]: {}",
+                            symbol.0, e
+                        ));
+                        return false;
+                    }
+                }
+            }
+            Msg::BidAskRefresh => {
+                self.fetch_bid_asks();
+                return false;
+            }
+            Msg::BidAskFetched(symbol, result) => {
+                self.bid_ask_tasks.remove(&symbol);
+                match result {
+                    Ok(bid_ask) if bid_ask.is_known() => {
+                        self.bid_asks.insert(symbol, bid_ask);
+                    }
+                    Ok(_) => return self.bid_asks.remove(&symbol).is_some(),
+                    Err(e) if e == api_client::NOT_ENTITLED => {
+                        self.note_not_entitled(PlanFeature::BidAsk)
+                    }
+                    Err(e) => {
+                        self.logger
+                            .warn(format!("Could not fetch bid/ask for [{}]: {}", symbol.0, e));
+                        return false;
+                    }
+                }
+            }
+            Msg::SymbolListFetched(result) => {
+                self.symbol_list_task = None;
+                match result {
+                    Ok(listed) => {
+                        self.state.metadata.set_symbol_list(
+                            symbol_list::EXCHANGE,
+                            SymbolList::new(listed),
+                            Utc::now(),
+                        );
+                        self.persist_metadata();
+                    }
+                    Err(e) => self.logger.warn(format!(
+                        "Could not fetch the symbol list, so symbols are only checked by subscribing: {}",
+                        e
+                    )),
+                }
+                return false;
+            }
+            Msg::ShowBidAskUpdate(show_bid_ask) => {
+                self.state.settings.show_bid_ask = show_bid_ask;
+                self.update_bid_ask_refresh();
+                self.persist_state();
+            }
+            Msg::PauseWhenHiddenUpdate(mins) => {
+                self.state.settings.pause_when_hidden_mins = mins;
+                self.persist_state();
+            }
+            Msg::CardSizeUpdate(symbol, size) => {
+                if size == CardSize::default() {
+                    self.state.card_sizes.remove(&symbol);
+                } else {
+                    self.state.card_sizes.insert(symbol, size);
+                }
+                self.persist_state();
+            }
+            Msg::DashboardColumnsUpdate(columns) => {
+                self.state.settings.dashboard_columns = columns;
+                self.persist_state();
+            }
+            Msg::SubscriptionLimitUpdate(limit) => {
+                self.state.settings.subscription_limit = limit;
+                if let Some(session) = &mut self.session {
+                    session.set_limit(limit as usize, Utc::now());
+                }
+                self.check_subscription_limit();
+                self.persist_state();
+            }
+            Msg::SpikeThresholdUpdate(threshold) => {
+                self.state.settings.spike_threshold = threshold;
+                self.persist_state();
+            }
+            Msg::SpikeAlertsUpdate(spike_alerts) => {
+                self.state.settings.spike_alerts = spike_alerts;
+                self.persist_state();
+            }
+            Msg::AutoConnectUpdate(auto_connect) => {
+                self.state.settings.auto_connect = auto_connect;
+                self.persist_state();
+            }
+            Msg::BeforeUnload => {
+                self.persist_dirty();
+                if self.election.is_leader() {
+                    let tab = self.election.tab();
+                    self.post_to_tabs(&TabMessage::Resigned { tab });
+                }
+                if self.session_storage_service.is_some() {
+                    let wants_connection = self.wants_connection.to_string();
+                    // the tab's going, so all there's left to do is say so
+                    if let Err(e) = store_session(WANTS_CONNECTION_STORAGE_KEY, &wants_connection) {
+                        self.logger
+                            .warn(format!("Could not save the connection state [{:?}]", e));
+                    }
+                }
+                return false;
+            }
+            Msg::FxRatesFetched(result) => {
+                self.fx_task = None;
+                match result {
+                    Ok(rates) => {
+                        self.state.metadata.set_fx_rates(rates, Utc::now());
+                        self.persist_metadata();
+                    }
+                    Err(e) if e == api_client::NOT_ENTITLED => {
+                        self.note_not_entitled(PlanFeature::FxRates)
+                    }
+                    Err(e) => {
+                        // the last rates we got are still better than nothing
+                        self.logger
+                            .error(format!("Could not fetch FX rates: {}", e));
+                        return false;
+                    }
+                }
+            }
+            Msg::RouteChanged(route) => {
+                let import_offered = self.offer_shared_import();
+                if self.route == route {
+                    return import_offered;
+                }
+                self.route = route;
+            }
+            Msg::OnlineChange(online) => {
+                if self.online == online {
+                    return false;
+                }
+                self.online = online;
+                if !online {
+                    // the socket is as good as dead, and there's nothing to fall back to
+                    self.disconnect();
+                } else if self.wants_connection {
+                    self.logger.info("Back online, reconnecting");
+                    self.connect_to_api();
+                } else {
+                    self.update_quote_polling();
+                }
+            }
+            Msg::VisibilityChange(visible) => {
+                // mobile browsers often kill hidden tabs without an unload
+                if !visible {
+                    self.persist_dirty();
+                }
+                if let (false, Some(mins)) = (visible, self.state.settings.pause_when_hidden_mins) {
+                    let callback = self.link.callback(|_| Msg::HiddenTooLong);
+                    self.hidden_pause_task = Some(self.timeout_service.spawn(
+                        std::time::Duration::from_secs(u64::from(mins) * 60),
+                        callback,
+                    ));
+                }
+                if visible {
+                    self.hidden_pause_task = None;
+                    if self.hidden_pause {
+                        self.hidden_pause = false;
+                        self.logger.info("Tab visible again, resubscribing");
+                        self.reconcile_subscriptions();
+                    }
+                }
+                // background tabs often get their websockets throttled or killed, so we
+                // quietly pick up where we left off
+                if visible
+                    && self.online
+                    && self.wants_connection
+                    && self.connection_state == ConnectionState::Disconnected
+                {
+                    self.logger
+                        .info("Tab visible again with a dead connection, reconnecting");
+                    return self.connect_to_api();
+                }
+                return false;
+            }
+            Msg::HiddenTooLong => {
+                self.hidden_pause_task = None;
+                self.hidden_pause = true;
+                self.logger
+                    .info("Tab hidden for a while, unsubscribing until it's visible again");
+                self.reconcile_subscriptions();
+                return false;
+            }
+            Msg::Shortcut(shortcut) => return self.handle_shortcut(shortcut),
+            Msg::DismissNotification(id) => {
+                self.notifications.dismiss(id);
+                self.undo_timeouts.remove(&id);
+            }
+            Msg::UndoExpired(id) => {
+                self.notifications.dismiss(id);
+                self.undo_timeouts.remove(&id);
+            }
+            Msg::NotificationActionTaken(id) => match self.notifications.dismiss(id) {
+                Some(NotificationAction::Reconnect) => {
+                    self.wants_connection = true;
+                    self.disconnect();
+                    return self.connect_to_api();
+                }
+                Some(NotificationAction::UndoUntrack(tombstone)) => {
+                    self.undo_timeouts.remove(&id);
+                    self.undo_untrack(*tombstone);
+                }
+                Some(NotificationAction::UndoUntrackAll(tombstones)) => {
+                    self.undo_timeouts.remove(&id);
+                    self.undo_untrack_all(tombstones);
+                }
+                Some(NotificationAction::ImportSymbols(symbols)) => {
+                    return self.import_symbols(symbols);
+                }
+                Some(NotificationAction::ClearHistory) => {
+                    self.storage_full_notification = None;
+                    for symbol in self.state.tracked.clone() {
+                        self.delete_history(&symbol);
+                    }
+                    self.state.history.clear();
+                }
+                None => (),
+            },
+            Msg::SmaPeriodUpdate(period) => {
+                self.state.settings.sma_period = period;
+                self.persist_state();
+            }
+            Msg::EmaPeriodUpdate(period) => {
+                self.state.settings.ema_period = period;
+                self.persist_state();
+            }
+            Msg::HistoryRetentionUpdate(retention_mins) => {
+                self.state.settings.history_retention_mins = retention_mins;
+                self.state.history.set_retention(retention_mins);
+                for symbol in self.state.tracked.clone() {
+                    self.persist_history(&symbol);
+                }
+                self.persist_state();
+            }
+            Msg::TradeColumnsUpdate(columns) => {
+                if columns.is_empty() {
+                    return false;
+                }
+                self.state.settings.trade_columns = columns;
+                self.persist_state();
+            }
+            Msg::BlockTradeNotionalUpdate(notional) => {
+                self.state.settings.block_trade_notional = notional;
+                self.persist_state();
+            }
+            Msg::AcknowledgeAlert(idx) => {
+                self.state.alert_log.acknowledge(idx);
+                self.persist_state();
+            }
+            Msg::SnoozeAlert(idx, mins) => {
+                let alert = self.state.alert_log.entries().nth(idx);
+                if let Some((kind, symbol)) = alert.map(|e| (e.kind, e.symbol.clone())) {
+                    let now = Utc::now();
+                    self.state.settings.alert_snoozes.snooze(
+                        kind,
+                        symbol,
+                        now + Duration::minutes(mins),
+                        now,
+                    );
+                    self.persist_state();
+                }
+            }
+            Msg::UnsnoozeAlert(idx) => {
+                let alert = self.state.alert_log.entries().nth(idx);
+                if let Some((kind, symbol)) = alert.map(|e| (e.kind, e.symbol.clone())) {
+                    self.state
+                        .settings
+                        .alert_snoozes
+                        .unsnooze(kind, symbol.as_ref());
+                    self.persist_state();
+                }
+            }
+            Msg::DoNotDisturbUpdate(do_not_disturb) => {
+                self.state.settings.do_not_disturb = do_not_disturb;
+                self.persist_state();
+            }
+            Msg::AcknowledgeAllAlerts => {
+                self.state.alert_log.acknowledge_all();
+                self.persist_state();
+            }
+            Msg::ClearAlertLog => {
+                self.state.alert_log.clear();
+                self.persist_state();
+            }
+            Msg::NumberStyleUpdate(number_style) => {
+                self.state.settings.number_style = number_style;
+                self.persist_state();
+            }
+            Msg::LanguageUpdate(language) => {
+                self.state.settings.language = language;
+                self.state.locale = i18n::locale(language);
+                if let Ok(time_formatter) =
+                    TimeFormatter::new(&self.state.settings.time_zone, self.language())
+                {
+                    self.time_formatter = time_formatter;
+                }
+                self.persist_state();
+            }
+            Msg::PriceDecimalsUpdate(kind, decimals) => {
+                self.state.settings.price_decimals.set(kind, decimals);
+                self.persist_state();
+            }
+            Msg::TickSizeDecimalsUpdate(tick_size) => {
+                self.state.settings.price_decimals.tick_size = tick_size;
+                self.persist_state();
+            }
+            Msg::AbbreviateVolumesUpdate(abbreviate_volumes) => {
+                self.state.settings.abbreviate_volumes = abbreviate_volumes;
+                self.persist_state();
+            }
+            Msg::ShowTapeUpdate(show_tape) => {
+                self.state.settings.show_tape = show_tape;
+                self.persist_state();
+            }
+            Msg::DebugLoggingUpdate(debug_logging) => {
+                self.state.settings.debug_logging = debug_logging;
+                self.logger.set_level(self.state.settings.log_level());
+                self.persist_state();
+            }
+            Msg::ShowInspectorUpdate(show_inspector) => {
+                self.state.settings.show_inspector = show_inspector;
+                if !show_inspector {
+                    self.inspector.clear();
+                }
+                self.persist_state();
+            }
+            Msg::ClearInspector => self.inspector.clear(),
+            Msg::WsAuthUpdate(ws_auth) => {
+                self.state.settings.ws_auth = ws_auth;
+                self.persist_state();
+                if self.session.is_some() {
+                    self.disconnect();
+                    return self.connect_to_api();
+                }
+            }
+            Msg::WsBaseUrlUpdate(base_url) => {
+                if let Some(invalid) = base_url.as_ref().filter(|u| !ws::is_valid_base_url(u)) {
+                    self.notifications.push(
+                        Level::Warning,
+                        format!(
+                            "[{}] isn't a websocket URL; it should start with wss:// or ws://",
+                            invalid
+                        ),
+                    );
+                    return true;
+                }
+                self.state.settings.ws_base_url = base_url;
+                self.persist_state();
+                if self.session.is_some() {
+                    self.disconnect();
+                    return self.connect_to_api();
+                }
+            }
+            Msg::BaseCurrencyUpdate(base_currency) => {
+                self.state.settings.base_currency = base_currency;
+                self.fx_refresh_task = None;
+                self.update_fx_refresh();
+                self.persist_state();
+            }
+            Msg::ViewModeUpdate(view_mode) => {
+                self.state.settings.view_mode = view_mode;
+                self.persist_state();
+            }
+            Msg::ThemeUpdate(theme) => {
+                self.state.settings.theme = theme;
+                self.persist_state();
+            }
+            Msg::TimeZoneUpdate(time_zone) => match TimeFormatter::new(&time_zone, self.language())
+            {
+                Ok(time_formatter) => {
+                    self.time_formatter = time_formatter;
+                    self.state.settings.time_zone = time_zone;
+                    self.update_relative_time_task();
+                    self.persist_state();
+                }
+                Err(_) => {
+                    if let TimeZoneSetting::Named(zone) = time_zone {
+                        self.notifications
+                            .push(Level::Warning, format!("Unknown time zone [{}]", zone));
+                    }
+                }
+            },
+            Msg::RelativeTimeTick => (),
+            Msg::PrefersDarkUpdate(prefers_dark) => {
+                self.prefers_dark = prefers_dark;
+                return self.state.settings.theme == Theme::Auto;
+            }
+            Msg::PauseSymbol(symbol) => {
+                if self.state.paused.insert(symbol) {
+                    self.reconcile_subscriptions();
+                    self.persist_state();
+                }
+            }
+            Msg::ResumeSymbol(symbol) => {
+                // not if finnhub's rejected it, or the tab's hidden
+                if self.state.paused.remove(&symbol) {
+                    self.reconcile_subscriptions();
+                    self.persist_state();
+                }
+            }
+            Msg::WsBinaryFrame(bytes) => {
+                frame_codec::decode(bytes, self.link.callback(Msg::WsFrame));
+                return false;
+            }
+            Msg::WsFrame(data) => {
+                let now = Utc::now();
+                self.last_message_at = Some(now);
+                self.metrics.record_message(now);
+                match data {
+                    Ok(frame) => {
+                        if self.state.settings.show_inspector {
+                            self.inspector.push_frame(&frame, now);
+                        }
+                        if self.election.is_leader() {
+                            self.post_to_tabs(&TabMessage::Frame(frame.clone()));
+                        }
+                        self.trade_worker.send(WorkerInput::Frame(frame));
+                    }
+                    Err(sucks) => {
+                        self.inspect(EntryKind::ParseFailure, sucks.to_string());
+                        self.logger
+                            .error(format!("Got an unreadable frame [{}]", sucks));
+                    }
+                }
+                // otherwise frames only show once their trades cause a render
+                return self.state.settings.show_inspector && self.route == Route::Settings;
+            }
+            Msg::WorkerOutput(output) => {
+                self.logger
+                    .debug(format!("Received message [{:?}]", output));
+                match output {
+                    WorkerOutput::Error(message) => match SymbolError::from_message(&message) {
+                        Some(error) => match self.take_rejected_subscription(&message) {
+                            Some(rejected) => {
+                                self.logger.warn(format!(
+                                    "Finnhub rejected [{}]: {}",
+                                    rejected.0, message
+                                ));
+                                self.symbol_errors.insert(rejected, error);
+                                // frees up its slot, and keeps it from being resubscribed
+                                // to on every reconcile
+                                self.reconcile_subscriptions();
+                            }
+                            None => {
+                                self.notifications.push(
+                                    Level::Error,
+                                    format!(
+                                        "Finnhub rejected a subscription [{}], but it isn't clear which",
+                                        message
+                                    ),
+                                );
+                            }
+                        },
+                        None => self
+                            .logger
+                            .warn(format!("Got an error from finnhub [{}]", message)),
+                    },
+                    WorkerOutput::Trades(delta) => self.apply_trades(delta),
+                    WorkerOutput::Ping => return false,
+                    WorkerOutput::Unknown { kind, payload } => {
+                        let seen =
+                            self.unknown_messages
+                                .entry(kind.clone())
+                                .or_insert(UnknownMessages {
+                                    count: 0,
+                                    last_payload: String::new(),
+                                });
+                        // once is informative, every time is spam
+                        if seen.count == 0 {
+                            self.logger.warn(format!(
+                                "Got a websocket message of unknown type [{}]: {}",
+                                kind, payload
+                            ));
+                        }
+                        seen.count += 1;
+                        seen.last_payload = payload;
+                        return self.route == Route::Settings;
+                    }
+                    WorkerOutput::Unparseable(sucks) => {
+                        self.inspect(EntryKind::ParseFailure, sucks.as_str());
+                        self.logger
+                            .error(format!("Got some undeserialisable data [{}]", sucks));
+                        return false;
+                    }
+                }
+            }
+            Msg::WsOpened => {
+                self.inspect(EntryKind::Event, "Connected");
+                self.connection_state = ConnectionState::Connected;
+                self.ws_failures = 0;
+                self.ws_retry_task = None;
+                if self.ws_fallback {
+                    self.ws_fallback = false;
+                    self.notifications.push(
+                        Level::Info,
+                        "The websocket is back, so trades are live again",
+                    );
+                }
+                if let Some(id) = self.reconnect_notification.take() {
+                    self.notifications.dismiss(id);
+                }
+                self.start_heartbeat();
+                if self.session.is_some() {
+                    self.session_opened();
+                } else {
+                    // impossible,
+                    self.notifications.push(
+                        Level::Error,
+                        "The no websocket connection despite it being open, wtf?",
+                    );
+                }
+                return true;
+            }
+            Msg::WsDead if !self.online => self.disconnect(),
+            Msg::WsDead => {
+                self.inspect(EntryKind::Event, "Connection closed or failed");
+                let never_opened = self.connection_state == ConnectionState::Connecting;
+                self.disconnect();
+                if never_opened && self.wants_connection {
+                    self.ws_failures += 1;
+                    if self.ws_fallback {
+                        // already explained by the banner
+                        self.schedule_ws_retry(WS_FALLBACK_RETRY_SECS);
+                        return true;
+                    }
+                    if self.ws_failures >= WS_FAILURES_BEFORE_FALLBACK {
+                        self.fall_back_to_polling();
+                        return true;
+                    }
+                    self.schedule_ws_retry(WS_RETRY_SECS);
+                }
+                self.dispatch_alert(
+                    AlertKind::Disconnection,
+                    None,
+                    "The websocket connection failed",
+                    None,
+                );
+                self.persist_state();
+                if let Some(id) = self.reconnect_notification.take() {
+                    self.notifications.dismiss(id);
+                }
+                self.reconnect_notification = Some(self.notifications.push_with_action(
+                    Level::Error,
+                    "The Websocket connection failed 😞\n\nThis might be because our API key is wrong, but if you were previously connected, you might want to try reconnecting?",
+                    "Reconnect",
+                    NotificationAction::Reconnect,
+                ));
+            }
+            Msg::WsRetry => {
+                self.ws_retry_task = None;
+                if !self.wants_connection || self.session.is_some() || !self.online {
+                    return false;
+                }
+                self.logger.info("Retrying the websocket connection");
+                return self.connect_to_api();
+            }
+            Msg::TabMessage(message) => match message {
+                TabMessage::Hello => {
+                    if self.election.is_leader() {
+                        let tab = self.election.tab();
+                        self.post_to_tabs(&TabMessage::Leader { tab });
+                        self.post_to_tabs(&TabMessage::Tracked(self.state.tracked.clone()));
+                    }
+                    return false;
+                }
+                TabMessage::Leader { tab } => {
+                    if self.election.on_leader(tab, Utc::now()) {
+                        self.on_leadership_change();
+                        self.redraw_all_cards = true;
+                        return true;
+                    }
+                    return false;
+                }
+                TabMessage::Resigned { tab } => {
+                    if self.election.on_resigned(tab) {
+                        self.on_leadership_change();
+                        self.redraw_all_cards = true;
+                        return true;
+                    }
+                    return false;
+                }
+                TabMessage::Frame(frame) if !self.election.is_leader() => {
+                    return self.update(Msg::WsFrame(Ok(frame)));
+                }
+                TabMessage::Frame(_) => return false,
+                TabMessage::Tracked(tracked) if !self.election.is_leader() => {
+                    self.state.tracked = tracked;
+                }
+                TabMessage::Tracked(_) => return false,
+            },
+            Msg::TabSyncTick => {
+                if self.election.is_leader() {
+                    let tab = self.election.tab();
+                    self.post_to_tabs(&TabMessage::Leader { tab });
+                } else if self.election.tick(Utc::now()) {
+                    self.on_leadership_change();
+                    self.redraw_all_cards = true;
+                    return true;
+                }
+                return false;
+            }
+            Msg::HeartbeatCheck => {
+                if self.connection_state != ConnectionState::Connected {
+                    return false;
+                }
+                if !self.is_connection_stale() {
+                    // re-render to keep the last message age fresh
+                    return true;
+                }
+                let message = format!(
+                    "No messages received for over {} seconds, reconnecting",
+                    self.state.settings.heartbeat_timeout_secs
+                );
+                self.logger.warn(message.as_str());
+                self.inspect(EntryKind::Event, message.as_str());
+                // silence is to be expected overnight, so there's no need to make a fuss
+                if !self.all_markets_closed() {
+                    self.dispatch_alert(AlertKind::Disconnection, None, message, Some(Level::Info));
+                    self.persist_state();
+                }
+                self.disconnect();
+                self.redraw_all_cards = true;
+                return self.connect_to_api();
+            }
+            Msg::Announce => return self.announce_prices(),
+            Msg::SkipToContent => {
+                if let Some(main) = self.main_content_ref.cast::<HtmlElement>() {
+                    let _ = main.focus();
+                }
+                return false;
+            }
+            Msg::PersistDirty => {
+                self.persist_dirty();
+                return false;
+            }
+            Msg::StaleCheck => {
+                let stale_symbols = self.find_stale_symbols();
+                let changed = stale_symbols != self.stale_symbols;
+                self.stale_symbols = stale_symbols;
+                // stale badges show an age, so keep re-rendering while there are any
+                return changed || !self.stale_symbols.is_empty();
+            }
+            Msg::StaleAfterUpdate(mins) => {
+                self.state.settings.stale_after_mins = mins;
+                self.stale_symbols = self.find_stale_symbols();
+                self.persist_state();
+            }
+            Msg::HeartbeatTimeoutUpdate(secs) => {
+                self.state.settings.heartbeat_timeout_secs = secs.max(MIN_HEARTBEAT_TIMEOUT_SECS);
+                self.persist_state();
+            }
+            Msg::ApiKeyStorageUpdate(api_key_storage) => {
+                self.state.settings.api_key_storage = api_key_storage;
+                self.persist_api_key();
+                self.persist_state();
+            }
+            Msg::PersistenceUpdate(persistence) => {
+                self.state.settings.persistence = persistence;
+                // what was saved under the old policy shouldn't outlive it
+                for symbol in self.state.tracked.clone() {
+                    if persistence == PersistencePolicy::Everything {
+                        self.persist_history(&symbol);
+                    } else {
+                        self.delete_history(&symbol);
+                    }
+                }
+                self.persist_metadata();
+                self.persist_state();
+            }
+            Msg::RefreshMetadata => {
+                self.state.metadata.expire();
+                self.symbol_list_requested = false;
+                self.fetch_stale_profiles();
+                if self
+                    .state
+                    .metadata
+                    .symbol_list(symbol_list::EXCHANGE)
+                    .is_some()
+                {
+                    self.fetch_symbol_list();
+                }
+                self.fetch_fx_rates();
+                self.persist_metadata();
+            }
+            Msg::Nope => (),
+        }
+        true
+    }
+
+    fn rendered(&mut self, _first_render: bool) {
+        self.redraw_all_cards = false;
+        self.redraw_cards.clear();
+        let cards_in_view = self.cards_in_view.replace(Vec::new());
+        let rendered = cards_in_view
+            .iter()
+            .filter(|(_, rendered)| *rendered)
+            .count();
+        self.metrics.record_cards(
+            rendered as u32,
+            (cards_in_view.len() - rendered) as u32,
+            Utc::now(),
+        );
+        self.mounted_cards = cards_in_view
+            .into_iter()
+            .map(|(symbol, _)| symbol)
+            .collect();
+        // we're mounted to the body, so it needs the theme applied to it directly
+        if let Some(body) = web_sys::window()
+            .and_then(|w| w.document())
+            .and_then(|d| d.body())
+        {
+            let class = if self.is_dark() {
+                "bg-dark text-light"
+            } else {
+                ""
+            };
+            if body.class_name() != class {
+                body.set_class_name(class);
+            }
+        }
+        // for screen readers, hyphenation and the like
+        if let Some(root) = web_sys::window()
+            .and_then(|w| w.document())
+            .and_then(|d| d.document_element())
+        {
+            let code = self.language().code();
+            if root.get_attribute("lang").as_deref() != Some(code) {
+                let _ = root.set_attribute("lang", code);
+            }
+        }
+    }
+
+    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
+        // Should only return "true" if new properties are different to
+        // previously received properties.
+        // This component has no properties so we will always return "false".
+        false
+    }
+
+    fn view(&self) -> Html {
+        html! {
+        < div class = "container-fluid text-center" >
+            <a class="sr-only sr-only-focusable" href="#"
+              onclick=self.link.callback( | e: MouseEvent | {
+                  // the hash is for routing
+                  e.prevent_default();
+                  Msg::SkipToContent
+              })>
+              { "Skip to content" }
+            </a>
+            <div class="sr-only" aria-live="polite" aria-atomic="true">{ &self.announcement }</div>
+            <NotificationList
+                notifications=self.notifications.to_vec()
+                on_dismiss=self.link.callback(Msg::DismissNotification)
+                on_action=self.link.callback(Msg::NotificationActionTaken) />
+            { self.view_shortcuts_help() }
+            < div class ="row" >
+                < div class ="col text-center" >
+                    < h1 class = "display-3">{ "finnhub trades" }< / h1 >
+                    { self.view_connection_status() }
+                < /div >
+            < /div>
+            <div class = "row" >
+                < div class ="col text-center" >
+                    <p>{ "WASM app written in " }<a href={"https://www.rust-lang.org"}>{ "Rust" }</a>{ " using "}<a href={"https://yew.rs"}>{ "Yew" }</a>< / p >
+                    <p>{ "Connects to the " }<a href={"https://finnhub.io"}>{ "finnhub.io" }</a>{ " Websocket Trades API and persists to LocalStorage and IndexedDB"}< / p >
+                    <p class="text-muted">
+                        { "Github" }
+                        <a class={"p-2"} href={ "https://github.com/lloydmeta/finnhub-ws-rs"}>
+                            <img src={ "https://img.shields.io/github/stars/lloydmeta/finnhub-ws-rs?style=social" } alt={"github"}/>
+                        </a>
+                    < / p >
+                    <p class="text-muted small">{ "Press " }<kbd>{ "?" }</kbd>{ " for keyboard shortcuts" }</p>
+                < /div >
+            </div>
+            < div class ="row" >
+                < div class ="offset-md-4 col-md-4" >
+                    { self.view_follower_notice() }
+                    { self.view_unlock_api_key() }
+                    { self.view_api_key_input() }
+                    { self.view_api_key_profiles() }
+                < /div >
+            < /div>
+            <main id="main-content" tabindex="-1" ref=self.main_content_ref.clone()>
+            { self.view_summary_bar() }
+            { self.view_route_tabs() }
+            // a follower's edits wouldn't be saved, so it doesn't take any
+            <fieldset disabled=self.is_follower()>
+            {
+                match self.route {
+                    Route::Watchlist => self.view_watchlist_page(),
+                    Route::Portfolio => self.view_portfolio_page(),
+                    Route::Compare => self.view_compare_page(),
+                    Route::Alerts => self.view_alerts_page(),
+                    Route::Settings => self.view_settings_page(),
+                }
+            }
+            </fieldset>
+            </main>
+        < / div >
+        }
+    }
+}
+
+impl Model {
+    fn persist_state(&mut self) {
+        self.state_dirty = false;
+        if self.is_follower() {
+            return;
+        }
+        if self.tab_sync.is_some() {
+            self.post_to_tabs(&TabMessage::Tracked(self.state.tracked.clone()));
+        }
+        if self.storage_service.is_some() {
+            let settings = &self.state.settings;
+            let partial_settings = match settings.persistence {
+                PersistencePolicy::Everything => None,
+                PersistencePolicy::WatchlistAndSettings => Some(Cow::Borrowed(settings)),
+                // where things get saved still has to be remembered, or the next visit
+                // wouldn't know where to look
+                PersistencePolicy::Watchlist => Some(Cow::Owned(Settings {
+                    api_key_storage: settings.api_key_storage,
+                    persistence: settings.persistence,
+                    ..Settings::default()
+                })),
+            };
+            let json = match partial_settings {
+                None if self.legacy_history => migrations::save(&LegacyHistoryState {
+                    state: &self.state,
+                    history: &self.state.history,
+                }),
+                None => migrations::save(&self.state),
+                Some(settings) => migrations::save(&PartialState {
+                    tracked: &self.state.tracked,
+                    settings,
+                }),
+            };
+            match json
+                .map_err(|e| JsValue::from(e.to_string()))
+                .and_then(|json| store_local(STATE_STORAGE_KEY, &json))
+            {
+                Ok(()) => (),
+                Err(e) if is_quota_exceeded(&e) => {
+                    self.warn_storage_full(
+                        "Browser storage is full, so your watchlist and settings couldn't be saved",
+                        false,
+                    );
+                }
+                Err(e) => self.logger.error(format!("Could not save state [{:?}]", e)),
+            }
+        }
+        self.sync_trade_filter();
+    }
+
+    /// The metadata cache only changes when something's been fetched, so it's saved
+    /// apart from the state, and only kept when trade history would be
+    fn persist_metadata(&mut self) {
+        if self.is_follower() || self.storage_service.is_none() {
+            return;
+        }
+        let tracked = &self.state.tracked;
+        self.state
+            .metadata
+            .prune_profiles(|symbol| tracked.contains(symbol), Utc::now());
+        if self.state.settings.persistence != PersistencePolicy::Everything {
+            if let Some(storage_service) = &mut self.storage_service {
+                storage_service.remove(METADATA_STORAGE_KEY);
+            }
+            return;
+        }
+        match to_json(&self.state.metadata)
+            .and_then(|json| store_local(METADATA_STORAGE_KEY, &json))
+        {
+            Ok(()) => (),
+            Err(e) if is_quota_exceeded(&e) => self.warn_storage_full(
+                "Browser storage is full, so company profiles and symbol lists couldn't be saved",
+                false,
+            ),
+            Err(e) => self
+                .logger
+                .error(format!("Could not save metadata [{:?}]", e)),
+        }
+    }
+
+    /// Replaces any earlier warning, since storage tends to stay full for a while
+    fn warn_storage_full(&mut self, message: &str, offer_clear: bool) {
+        if let Some(id) = self.storage_full_notification.take() {
+            self.notifications.dismiss(id);
+        }
+        let id = if offer_clear {
+            self.notifications.push_with_action(
+                Level::Warning,
+                message,
+                "Clear history",
+                NotificationAction::ClearHistory,
+            )
+        } else {
+            self.notifications.push(Level::Warning, message)
+        };
+        self.storage_full_notification = Some(id);
+    }
+
+    fn sync_trade_filter(&mut self) {
+        let trade_filter = self.state.trade_filter();
+        if trade_filter != self.trade_filter {
+            self.trade_worker
+                .send(WorkerInput::Filter(trade_filter.clone()));
+            self.trade_filter = trade_filter;
+        }
+    }
+
+    /// Adds what the trade worker kept of a frame to the history
+    fn apply_trades(&mut self, delta: TradeDelta) {
+        let now = Utc::now();
+        for (symbol, count) in delta.counts {
+            self.metrics.record_trades(symbol, count, now);
+        }
+        for (symbol, totals) in &delta.totals {
+            self.session_totals
+                .entry(symbol.clone())
+                .or_default()
+                .add(totals);
+        }
+        let mut updated = HashSet::new();
+        let previous_prices: HashMap<Symbol, Option<Price>> = delta
+            .trades
+            .iter()
+            .map(|t| (t.symbol.clone(), self.state.latest_price(&t.symbol)))
+            .collect();
+        let mut spikes = vec![];
+        let mut fired_rules = vec![];
+        for ticker_info in delta.trades {
+            if let Some((replay, _)) = &mut self.replay {
+                if replay.symbol == ticker_info.symbol {
+                    replay.stash_live(ticker_info);
+                    continue;
+                }
+            }
+            if self.state.history.contains(&ticker_info) {
+                continue;
+            }
+            updated.insert(ticker_info.symbol.clone());
+            if let Some(rules) = self.state.alert_rules.get(&ticker_info.symbol) {
+                let history = self.state.history.get(&ticker_info.symbol);
+                let history = history.into_iter().flatten().map(Deref::deref);
+                for rule in self.rule_engine.observe(rules, history, &ticker_info) {
+                    let fired = (ticker_info.symbol.clone(), rule.to_string());
+                    // crossing back and forth within one frame is still just the one alert
+                    if !fired_rules.contains(&fired) {
+                        fired_rules.push(fired);
+                    }
+                }
+            }
+            let mut ticker_info = Trade::from(ticker_info);
+            self.state.annotate_direction(&mut ticker_info);
+            if let Some(direction) = ticker_info.direction {
+                self.tick_counts
+                    .entry(ticker_info.symbol.clone())
+                    .or_default()
+                    .record(direction);
+            }
+            // the first trade seen is the range, rather than a new extreme of it
+            ticker_info.session_extreme =
+                match self.session_ranges.entry(ticker_info.symbol.clone()) {
+                    Entry::Occupied(mut range) => range.get_mut().update(ticker_info.price),
+                    Entry::Vacant(vacant) => {
+                        vacant.insert(SessionRange::new(ticker_info.price));
+                        None
+                    }
+                };
+            // against the history before this trade goes into it
+            ticker_info.spike = match self.state.settings.spike_threshold {
+                Some(threshold) => self
+                    .state
+                    .history
+                    .stats(&ticker_info.symbol)
+                    .is_some_and(|stats| threshold.is_spike(stats, ticker_info.price.0.to_f64())),
+                None => false,
+            };
+            if ticker_info.spike {
+                spikes.push((ticker_info.symbol.clone(), ticker_info.price));
+            }
+            self.tape.push_front(TapeTrade::new(&ticker_info));
+            self.state.add_history(ticker_info);
+        }
+        self.tape.truncate(TAPE_LENGTH);
+        if let Some(session) = &mut self.session {
+            session.confirm(&updated);
+        }
+        for symbol in &updated {
+            self.redraw_cards.insert(symbol.clone());
+            let latest_price = self.state.latest_price(symbol);
+            if let Some(target) = self.state.price_targets.get_mut(symbol) {
+                target.reference = target.reference.or(latest_price);
+            }
+            let previous_price = previous_prices.get(symbol).copied().flatten();
+            let reached_target = match (self.state.price_targets.get(symbol), latest_price) {
+                (Some(target), Some(latest)) if target.is_reached(previous_price, latest) => {
+                    Some(target.target)
+                }
+                _ => None,
+            };
+            if let Some(target) = reached_target {
+                let message = format!(
+                    "{} reached its target of {}",
+                    symbol.0,
+                    self.state.price_format(symbol).format(target)
+                );
+                self.dispatch_alert(
+                    AlertKind::PriceTarget,
+                    Some(symbol.clone()),
+                    message,
+                    Some(Level::Info),
+                );
+            }
+            self.symbol_errors.remove(symbol);
+            self.stale_symbols.remove(symbol);
+            self.dirty_histories.insert(symbol.clone());
+            let parity = self.flash_parity.entry(symbol.clone()).or_default();
+            *parity = !*parity;
+        }
+        for (symbol, rule) in fired_rules {
+            let message = format!("{} matched \"{}\"", symbol.0, rule);
+            self.dispatch_alert(AlertKind::Rule, Some(symbol), message, Some(Level::Info));
+        }
+        let mut beep = false;
+        for (symbol, volume) in &delta.volume_alerts {
+            beep |= self.dispatch_alert(
+                AlertKind::VolumeSpike,
+                Some(symbol.clone()),
+                format!(
+                    "{} traded {} in one go",
+                    symbol.0,
+                    self.state.format_volume(*volume)
+                ),
+                None,
+            );
+        }
+        if self.state.settings.spike_alerts {
+            for (symbol, price) in spikes {
+                let message = format!(
+                    "{} traded at {}, well away from its recent mean",
+                    symbol.0,
+                    self.state.price_format(&symbol).format(price)
+                );
+                self.dispatch_alert(
+                    AlertKind::PriceSpike,
+                    Some(symbol),
+                    message,
+                    Some(Level::Warning),
+                );
+            }
+        }
+        if beep && !self.state.settings.sound_muted {
+            self.play_alert();
+        }
+        self.state_dirty = true;
+    }
+
+    /// Saves whatever trades have changed since last time; each symbol's history is
+    /// stored under its own key, so only the symbols that traded get written
+    fn persist_dirty(&mut self) {
+        if self.state_dirty {
+            self.persist_state();
+        }
+        for symbol in std::mem::take(&mut self.dirty_histories) {
+            self.persist_history(&symbol);
+        }
+    }
+
+    /// Every alert goes in the alert log, which is persisted along with the rest of the
+    /// state, but is only announced (as a notification at `level`, if any) when it isn't
+    /// snoozed or due to keep quiet. Whether it was announced
+    fn dispatch_alert(
+        &mut self,
+        kind: AlertKind,
+        symbol: Option<Symbol>,
+        message: impl Into<String>,
+        level: Option<Level>,
+    ) -> bool {
+        let now = Utc::now();
+        let message = message.into();
+        let settings = &self.state.settings;
+        let silenced = settings
+            .alert_snoozes
+            .until(kind, symbol.as_ref(), now)
+            .is_some()
+            || settings
+                .do_not_disturb
+                .is_quiet(Local::now(), || match &symbol {
+                    Some(symbol) => market_hours::is_closed(symbol, now),
+                    None => self.all_markets_closed(),
+                });
+        if let (false, Some(level)) = (silenced, level) {
+            self.notifications.push(level, message.as_str());
+        }
+        self.state.alert_log.push(kind, symbol, message, now);
+        !silenced
+    }
+
+    /// GETs a finnhub REST endpoint through the shared, rate limited client;
+    /// `path_and_query` shouldn't include the token
+    fn fetch_json<T, F>(&mut self, path_and_query: &str, to_msg: F) -> ApiTask
+    where
+        T: for<'de> Deserialize<'de> + 'static,
+        F: Fn(Result<T, String>) -> Msg + 'static,
+    {
+        let callback = self.json_callback(to_msg);
+        self.api_client
+            .get(path_and_query, &self.api_key.0, callback)
+    }
+
+    /// `fetch_json`, but jumping the queue
+    fn fetch_json_first<T, F>(&mut self, path_and_query: &str, to_msg: F) -> ApiTask
+    where
+        T: for<'de> Deserialize<'de> + 'static,
+        F: Fn(Result<T, String>) -> Msg + 'static,
+    {
+        let callback = self.json_callback(to_msg);
+        self.api_client
+            .get_first(path_and_query, &self.api_key.0, callback)
+    }
+
+    fn json_callback<T, F>(&self, to_msg: F) -> Callback<Result<String, String>>
+    where
+        T: for<'de> Deserialize<'de> + 'static,
+        F: Fn(Result<T, String>) -> Msg + 'static,
+    {
+        self.link.callback(move |body: Result<String, String>| {
+            to_msg(body.and_then(|body| serde_json::from_str(&body).map_err(|e| e.to_string())))
+        })
+    }
+
+    fn handle_shortcut(&mut self, shortcut: Shortcut) -> ShouldRender {
+        let document = web_sys::window().and_then(|w| w.document());
+        match shortcut {
+            Shortcut::FocusSymbolInput => {
+                if let Some(input) = self.symbol_input_ref.cast::<HtmlElement>() {
+                    let _ = input.focus();
+                }
+                false
+            }
+            Shortcut::ToggleConnection => {
+                if self.connection_state == ConnectionState::Disconnected {
+                    self.update(Msg::ApiKeyConnect)
+                } else {
+                    self.update(Msg::ApiKeyDisconnect)
+                }
+            }
+            Shortcut::JumpToCard(idx) => {
+                if let Some(card) = document
+                    .and_then(|d| d.get_element_by_id(&card_element_id(idx)))
+                    .and_then(|e| e.dyn_into::<HtmlElement>().ok())
+                {
+                    card.scroll_into_view();
+                    let _ = card.focus();
+                }
+                false
+            }
+            Shortcut::UntrackFocusedCard => {
+                let focused_idx = document
+                    .and_then(|d| d.active_element())
+                    .and_then(|e| e.closest("[data-card-idx]").ok().flatten())
+                    .and_then(|card| card.get_attribute("data-card-idx"))
+                    .and_then(|idx| idx.parse().ok());
+                match focused_idx {
+                    Some(idx) => self.update(Msg::UnTrackSymbolAtIdx(idx)),
+                    None => false,
+                }
+            }
+            Shortcut::ToggleHelp => {
+                self.show_shortcuts_help = !self.show_shortcuts_help;
+                true
+            }
+            Shortcut::CloseHelp => {
+                if self.show_shortcuts_help {
+                    self.show_shortcuts_help = false;
+                    true
+                } else {
+                    self.maximized.take().is_some()
+                }
+            }
+        }
+    }
+
+    /// Stocks need their profile for their currency, so it's fetched up front rather
+    /// than when first opened, and again once the cached one is stale
+    fn fetch_stale_profiles(&mut self) {
+        if self.api_key.0.is_empty() {
+            return;
+        }
+        let now = Utc::now();
+        let stale: Vec<Symbol> = self
+            .state
+            .tracked
+            .iter()
+            .filter(|s| s.kind() == SymbolKind::Stock && !s.is_demo())
+            .filter(|s| self.state.metadata.profile_is_stale(s, now))
+            .cloned()
+            .collect();
+        for symbol in stale {
+            self.fetch_profile(&symbol);
+        }
+    }
+
+    /// Only stocks report earnings
+    fn fetch_stale_earnings(&mut self) {
+        if self.api_key.0.is_empty() || !self.online {
+            return;
+        }
+        let now = Utc::now();
+        let stale: Vec<Symbol> = self
+            .state
+            .tracked
+            .iter()
+            .filter(|s| s.kind() == SymbolKind::Stock && !s.is_demo())
+            .filter(|s| self.is_entitled_for(PlanFeature::Earnings, s))
+            .filter(|s| self.state.earnings.get(s).is_none_or(|e| e.is_stale(now)))
+            .cloned()
+            .collect();
+        for symbol in stale {
+            if self.earnings_tasks.contains_key(&symbol) {
+                continue;
+            }
+            let from = now.naive_utc().date();
+            let to = from + Duration::days(EARNINGS_LOOKAHEAD_DAYS);
+            let callback_symbol = symbol.clone();
+            let task = self
+                .fetch_json(&rest::earnings_calendar(&symbol, from, to), move |result| {
+                    Msg::EarningsFetched(callback_symbol.clone(), result)
+                });
+            self.earnings_tasks.insert(symbol, task);
+        }
+    }
+
+    fn fetch_profile(&mut self, symbol: &Symbol) {
+        if self.profile_tasks.contains_key(symbol) {
+            return;
+        }
+        let callback_symbol = symbol.clone();
+        let task = self.fetch_json(&rest::profile(symbol), move |result| {
+            Msg::ProfileFetched(callback_symbol.clone(), result)
+        });
+        self.profile_tasks.insert(symbol.clone(), task);
+    }
+
+    /// Fills in the last while of a newly tracked symbol's history, so that there's
+    /// something to show before live trades come in
+    fn fetch_candles(&mut self, symbol: &Symbol) {
+        if self.api_key.0.is_empty()
+            || symbol.is_demo()
+            || self.candle_tasks.contains_key(symbol)
+            || !self.is_entitled(PlanFeature::Candles)
+        {
+            return;
+        }
+        let to = Utc::now();
+        let from = to - Duration::minutes(CANDLE_BACKFILL_MINS);
+        let callback_symbol = symbol.clone();
+        let task = self.fetch_json(&rest::candles(symbol, from, to), move |result| {
+            Msg::CandlesFetched(callback_symbol.clone(), result)
+        });
+        self.candle_tasks.insert(symbol.clone(), task);
+    }
+
+    fn fetch_news(&mut self, symbol: &Symbol) {
+        if self.news_tasks.contains_key(symbol) || !self.is_entitled_for(PlanFeature::News, symbol)
+        {
+            return;
+        }
+        let to = Utc::now();
+        let from = to - Duration::days(NEWS_LOOKBACK_DAYS);
+        let callback_symbol = symbol.clone();
+        let task = self.fetch_json(
+            &rest::company_news(symbol, from.naive_utc().date(), to.naive_utc().date()),
+            move |result| Msg::NewsFetched(callback_symbol.clone(), result),
+        );
+        self.news_tasks.insert(symbol.clone(), task);
+    }
+
+    /// Puts a link to the watchlist (but nothing else, least of all the API key) on the
+    /// clipboard
+    fn share_watchlist(&mut self) {
+        let location = match web_sys::window().map(|w| w.location()) {
+            Some(location) => location,
+            None => return,
+        };
+        let url = format!(
+            "{}{}{}{}",
+            location.origin().unwrap_or_default(),
+            location.pathname().unwrap_or_default(),
+            share::HASH_PREFIX,
+            share::encode(&self.state.tracked)
+        );
+        let message = if copy_to_clipboard(&url) {
+            format!("Copied a link to this watchlist:\n{}", url)
+        } else {
+            format!("Share this watchlist with this link:\n{}", url)
+        };
+        self.notifications.push(Level::Info, message);
+    }
+
+    /// Offers to track the symbols in a shared watchlist link, if that's how we got
+    /// here. Returns whether there's anything new to show
+    fn offer_shared_import(&mut self) -> bool {
+        let location = match web_sys::window().map(|w| w.location()) {
+            Some(location) => location,
+            None => return false,
+        };
+        let hash = location.hash().unwrap_or_default();
+        if !hash.starts_with(share::HASH_PREFIX) {
+            return false;
+        }
+        // so a reload doesn't offer it all over again
+        let _ = location.set_hash(Route::Watchlist.hash());
+        let symbols = match share::from_hash(&hash) {
+            Some(symbols) => symbols,
+            None => {
+                self.notifications
+                    .push(Level::Warning, "The shared watchlist link is broken");
+                return true;
+            }
+        };
+        let new: Vec<Symbol> = symbols
+            .into_iter()
+            .filter(|s| !self.state.tracked.contains(s))
+            .collect();
+        if new.is_empty() {
+            self.notifications.push(
+                Level::Info,
+                "You're already tracking everything in the shared watchlist",
+            );
+        } else {
+            let list: Vec<String> = new.iter().map(|s| format!("[{}]", s.0)).collect();
+            self.notifications.push_with_action(
+                Level::Info,
+                format!("Someone shared a watchlist with {}", list.join(", ")),
+                "Track them",
+                NotificationAction::ImportSymbols(new),
+            );
+        }
+        true
+    }
+
+    /// Tracks everything in a typed list of symbols (or just the one) and subscribes to
+    /// them. Those without an exchange get the one picked next to the input
+    fn track_symbols(&mut self, input: &str) -> ShouldRender {
+        let (symbols, invalid) = Symbol::parse_list(input);
+        let symbols = symbols
+            .iter()
+            .map(|symbol| symbol.with_exchange(&self.exchange_to_add))
+            .collect();
+        self.track_all(symbols, invalid)
+    }
+
+    /// Tracks symbols that came from elsewhere, e.g. a shared link, exactly as they are
+    fn import_symbols(&mut self, symbols: Vec<Symbol>) -> ShouldRender {
+        self.track_all(symbols, vec![])
+    }
+
+    fn track_all(&mut self, symbols: Vec<Symbol>, invalid: Vec<String>) -> ShouldRender {
+        if self.is_follower() {
+            return false;
+        }
+        if symbols.is_empty() && invalid.is_empty() {
+            return false;
+        }
+        let mut already_tracked = vec![];
+        let mut unknown = vec![];
+        for symbol in symbols {
+            let is_unknown = self
+                .state
+                .metadata
+                .symbol_list(symbol_list::EXCHANGE)
+                .is_some_and(|list| list.is_unknown(&symbol));
+            if is_unknown {
+                unknown.push(symbol.0);
+            } else if self.state.add_symbol(symbol.clone()) {
+                self.fetch_candles(&symbol);
+            } else {
+                already_tracked.push(format!("[{}]", symbol.0));
+            }
+        }
+        if !already_tracked.is_empty() {
+            let verb = if already_tracked.len() == 1 {
+                "is"
+            } else {
+                "are"
+            };
+            self.notifications.push(
+                Level::Warning,
+                format!(
+                    "{} {} already being tracked",
+                    already_tracked.join(", "),
+                    verb
+                ),
+            );
+        }
+        if !unknown.is_empty() {
+            let verb = if unknown.len() == 1 {
+                "isn't"
+            } else {
+                "aren't"
+            };
+            self.symbol_input_error =
+                Some(format!("{} {} listed on Finnhub", unknown.join(", "), verb));
+            // left in the input to be corrected
+            let typed = self.symbol_to_add.0.trim();
+            if !typed.is_empty() {
+                unknown.insert(0, typed.to_string());
+            }
+            self.symbol_to_add = Symbol(unknown.join(", "));
+        }
+        if !invalid.is_empty() {
+            let invalid: Vec<String> = invalid.iter().map(|s| format!("[{}]", s)).collect();
+            self.notifications.push(
+                Level::Warning,
+                format!("Skipped {}, which can't be symbols", invalid.join(", ")),
+            );
+        }
+        self.fetch_stale_profiles();
+        self.reconcile_subscriptions();
+        self.fetch_stale_earnings();
+        self.persist_state();
+        true
+    }
+
+    /// Untracks the symbol at `idx`, cleaning up after it if that was its last entry
+    fn untrack(&mut self, idx: usize) -> Option<Tombstone> {
+        // so that the whole of the history goes in the tombstone
+        if self.is_replaying(&self.state.tracked[idx]) {
+            self.stop_replay();
+        }
+        let result = self.state.untrack_symbol(idx);
+        if result.is_last {
+            if self.is_maximized(&result.symbol) {
+                self.maximized = None;
+            }
+            self.delete_history(&result.symbol);
+            self.card_tabs.remove(&result.symbol);
+            self.flash_parity.remove(&result.symbol);
+            self.announced_prices.remove(&result.symbol);
+            self.tape.retain(|trade| trade.symbol != result.symbol);
+            self.compared.retain(|symbol| *symbol != result.symbol);
+            self.table_sorts.remove(&result.symbol);
+            self.table_scroll_tops.remove(&result.symbol);
+            self.table_depths.remove(&result.symbol);
+            self.news.remove(&result.symbol);
+            self.candle_tasks.remove(&result.symbol);
+            self.symbol_errors.remove(&result.symbol);
+            self.not_entitled_symbols
+                .retain(|(_, symbol)| *symbol != result.symbol);
+            self.metrics.remove(&result.symbol);
+            self.bid_asks.remove(&result.symbol);
+            self.session_totals.remove(&result.symbol);
+            self.tick_counts.remove(&result.symbol);
+            self.session_ranges.remove(&result.symbol);
+            self.rule_engine.forget(&result.symbol);
+            self.alert_rule_inputs.remove(&result.symbol);
+            self.alert_rule_errors.remove(&result.symbol);
+            self.update_news_refresh();
+            self.reconcile_subscriptions();
+        }
+        result.tombstone
+    }
+
+    /// Asks whether another tab is already leading, and keeps checking in with it
+    fn start_tab_sync(&mut self) {
+        if self.tab_sync.is_none() {
+            return;
+        }
+        self.post_to_tabs(&TabMessage::Hello);
+        self.tab_sync_task = Some(self.interval_service.spawn(
+            std::time::Duration::from_secs(tab_sync::HEARTBEAT_SECS),
+            self.link.callback(|_| Msg::TabSyncTick),
+        ));
+    }
+
+    fn post_to_tabs(&self, message: &TabMessage) {
+        if let Some(tab_sync) = &self.tab_sync {
+            tab_sync.post(message);
+        }
+    }
+
+    /// Only one tab holds the websocket and writes to storage; the others mirror it
+    fn is_follower(&self) -> bool {
+        self.tab_sync.is_some() && !self.election.is_leader()
+    }
+
+    /// Picks up what the leader saved, keeping the trades mirrored from it
+    fn reload_state(&mut self) {
+        let saved: Text = match &self.storage_service {
+            Some(storage_service) => storage_service.restore(STATE_STORAGE_KEY),
+            None => return,
+        };
+        // anything short of a clean read and this tab's copy is the better bet
+        let mut state = match saved.map(|saved| migrations::restore(&saved, State::salvage)) {
+            Ok(Ok(Restored::Upgraded(state))) => state,
+            _ => return,
+        };
+        state.normalise();
+        state.history = std::mem::take(&mut self.state.history);
+        state
+            .history
+            .set_retention(state.settings.history_retention_mins);
+        state.metadata = std::mem::take(&mut self.state.metadata);
+        state.locale = i18n::locale(state.settings.language);
+        self.state = state;
+        if let Ok(time_formatter) =
+            TimeFormatter::new(&self.state.settings.time_zone, self.language())
+        {
+            self.time_formatter = time_formatter;
+        }
+        self.sync_trade_filter();
+    }
+
+    fn on_leadership_change(&mut self) {
+        if self.election.is_leader() {
+            self.logger.info("This tab now holds the connection");
+            let tab = self.election.tab();
+            self.post_to_tabs(&TabMessage::Leader { tab });
+            // the old leader may have saved changes this tab never saw
+            self.reload_state();
+            self.persist_state();
+            if self.wants_connection && self.connection_state == ConnectionState::Disconnected {
+                self.connect_to_api();
+            }
+        } else {
+            self.logger
+                .info("Another tab holds the connection, so this one mirrors it");
+            // still wanted, should this tab have to take over again
+            if self.session.is_some() {
+                self.disconnect();
+            }
+        }
+        self.update_quote_polling();
+    }
+
+    /// Lets the user take back an untrack for a little while
+    fn offer_undo(&mut self, tombstone: Tombstone) {
+        let message = format!("Untracked [{}]", tombstone.symbol.0);
+        self.push_undo(
+            message,
+            NotificationAction::UndoUntrack(Box::new(tombstone)),
+        );
+    }
+
+    /// One toast for the lot, rather than one per symbol
+    fn offer_undo_all(&mut self, tombstones: Vec<Tombstone>) {
+        let message = format!("Untracked {} symbols", tombstones.len());
+        self.push_undo(message, NotificationAction::UndoUntrackAll(tombstones));
+    }
+
+    fn push_undo(&mut self, message: String, action: NotificationAction) {
+        let id = self
+            .notifications
+            .push_with_action(Level::Info, message, "Undo", action);
+        let callback = self.link.callback(move |_| Msg::UndoExpired(id));
+        let task = self
+            .timeout_service
+            .spawn(std::time::Duration::from_secs(UNDO_UNTRACK_SECS), callback);
+        self.undo_timeouts.insert(id, task);
+    }
+
+    fn undo_untrack_all(&mut self, tombstones: Vec<Tombstone>) {
+        for symbol in self.state.restore_all(tombstones) {
+            self.persist_history(&symbol);
+        }
+        self.reconcile_subscriptions();
+        self.persist_state();
+    }
+
+    fn undo_untrack(&mut self, tombstone: Tombstone) {
+        let symbol = tombstone.symbol.clone();
+        if !self.state.restore(tombstone) {
+            return;
+        }
+        self.persist_history(&symbol);
+        self.reconcile_subscriptions();
+        self.persist_state();
+    }
+
+    /// Copies the history read from where older versions saved it into IndexedDB, which
+    /// it's only dropped from the saved state once that's done
+    fn write_back_legacy_history(&mut self) {
+        let history_store = match &self.history_store {
+            Some(history_store) if !self.is_follower() => history_store,
+            _ => return,
+        };
+        let entries: Result<Vec<(String, String)>, String> = self
+            .state
+            .tracked
+            .iter()
+            .filter_map(|symbol| {
+                let history = self.state.history.get(symbol)?;
+                Some(
+                    serde_json::to_string(history)
+                        .map(|json| (symbol.0.clone(), json))
+                        .map_err(|e| e.to_string()),
+                )
+            })
+            .collect();
+        match entries {
+            Ok(entries) => {
+                history_store.put_all(&entries, self.link.callback(Msg::LegacyHistoryWritten))
+            }
+            Err(e) => self
+                .logger
+                .error(format!("Could not save the history read back [{}]", e)),
+        }
+    }
+
+    /// Refreshes news periodically, but only while some card is showing it
+    fn update_news_refresh(&mut self) {
+        let showing_news = self.card_tabs.iter().any(|(symbol, tab)| {
+            *tab == CardTab::News && self.is_entitled_for(PlanFeature::News, symbol)
+        });
+        if showing_news && self.news_refresh_task.is_none() {
+            let callback = self.link.callback(|_| Msg::NewsRefresh);
+            self.news_refresh_task = Some(self.interval_service.spawn(
+                std::time::Duration::from_secs(NEWS_REFRESH_INTERVAL_SECS),
+                callback,
+            ));
+        } else if !showing_news {
+            self.news_refresh_task = None;
+        }
+    }
+
+    /// Falls back to polling REST quotes whenever there's no websocket
+    fn update_quote_polling(&mut self) {
+        if self.session.is_some() || self.demo.is_some() || self.is_follower() {
+            self.quote_poll_task = None;
+            self.quote_tasks.clear();
+        } else if self.quote_poll_task.is_none() {
+            let callback = self.link.callback(|_| Msg::QuotePoll);
+            self.quote_poll_task = Some(self.interval_service.spawn(
+                std::time::Duration::from_secs(QUOTE_POLL_INTERVAL_SECS),
+                callback,
+            ));
+            self.poll_quotes();
+        }
+    }
+
+    fn poll_quotes(&mut self) {
+        if self.api_key.0.is_empty() || !self.online {
+            return;
+        }
+        let streaming: Vec<Symbol> = self.state.streaming().cloned().collect();
+        for symbol in streaming {
+            if self.quote_tasks.contains_key(&symbol) {
+                continue;
+            }
+            let callback_symbol = symbol.clone();
+            let task = self.fetch_json(&rest::quote(&symbol), move |result| {
+                Msg::QuoteFetched(callback_symbol.clone(), result)
+            });
+            self.quote_tasks.insert(symbol, task);
+        }
+    }
+
+    /// Keeps FX rates fresh for as long as there's a base currency to convert to
+    fn update_fx_refresh(&mut self) {
+        if self.state.settings.base_currency.is_none() {
+            self.fx_refresh_task = None;
+            self.fx_task = None;
+        } else if self.fx_refresh_task.is_none()
+            && !self.api_key.0.is_empty()
+            && self.is_entitled(PlanFeature::FxRates)
+        {
+            let callback = self.link.callback(|_| Msg::FxRefresh);
+            self.fx_refresh_task = Some(self.interval_service.spawn(
+                std::time::Duration::from_secs(FX_REFRESH_INTERVAL_SECS),
+                callback,
+            ));
+            self.fetch_fx_rates();
+        }
+    }
+
+    /// Keeps bid/ask fresh for as long as it's turned on
+    fn update_bid_ask_refresh(&mut self) {
+        if !self.state.settings.show_bid_ask
+            || self.api_key.0.is_empty()
+            || !self.is_entitled(PlanFeature::BidAsk)
+        {
+            self.bid_ask_refresh_task = None;
+            self.bid_ask_tasks.clear();
+            self.bid_asks.clear();
+        } else if self.bid_ask_refresh_task.is_none() {
+            let callback = self.link.callback(|_| Msg::BidAskRefresh);
+            self.bid_ask_refresh_task = Some(self.interval_service.spawn(
+                std::time::Duration::from_secs(BID_ASK_REFRESH_INTERVAL_SECS),
+                callback,
+            ));
+            self.fetch_bid_asks();
+        }
+    }
+
+    /// finnhub only has bid/ask for stocks
+    fn fetch_bid_asks(&mut self) {
+        if !self.online {
+            return;
+        }
+        let stocks: Vec<Symbol> = self
+            .state
+            .streaming()
+            .filter(|s| s.kind() == SymbolKind::Stock && !s.is_demo())
+            .cloned()
+            .collect();
+        for symbol in stocks {
+            if self.bid_ask_tasks.contains_key(&symbol) {
+                continue;
+            }
+            let callback_symbol = symbol.clone();
+            let task = self.fetch_json(&rest::bid_ask(&symbol), move |result| {
+                Msg::BidAskFetched(callback_symbol.clone(), result)
+            });
+            self.bid_ask_tasks.insert(symbol, task);
+        }
+    }
+
+    fn fetch_symbol_list(&mut self) {
+        if self.symbol_list_requested
+            || self.api_key.0.is_empty()
+            || !self.online
+            || !self
+                .state
+                .metadata
+                .symbol_list_is_stale(symbol_list::EXCHANGE, Utc::now())
+        {
+            return;
+        }
+        self.symbol_list_requested = true;
+        let task = self.fetch_json(
+            &rest::symbol_list(symbol_list::EXCHANGE),
+            Msg::SymbolListFetched,
+        );
+        self.symbol_list_task = Some(task);
+    }
+
+    fn fetch_fx_rates(&mut self) {
+        let base = match &self.state.settings.base_currency {
+            Some(base)
+                if self.fx_task.is_none()
+                    && !self.api_key.0.is_empty()
+                    && self.state.metadata.fx_rates_are_stale(base, Utc::now()) =>
+            {
+                base.clone()
+            }
+            _ => return,
+        };
+        let task = self.fetch_json(&rest::fx_rates(&base), Msg::FxRatesFetched);
+        self.fx_task = Some(task);
+    }
+
+    /// `amount` of the symbol's currency in the base currency, when that's somewhere
+    /// else and we have the rates for it
+    fn to_base_currency(&self, symbol: &Symbol, amount: f64) -> Option<f64> {
+        let base = self.state.settings.base_currency.as_ref()?;
+        let rates = self.state.metadata.fx_rates(base)?;
+        let currency = self.state.currency(symbol)?;
+        if currency.code() == base {
+            return None;
+        }
+        rates.convert(amount, &currency)
+    }
+
+    fn format_base_currency(&self, amount: f64) -> String {
+        let base = Currency::new(self.state.settings.base_currency.as_deref().unwrap_or(""));
+        format!(
+            "≈ {}{}",
+            base.sign(),
+            self.state.number_format().fixed(amount, 2)
+        )
+    }
+
+    fn view_in_base_currency(&self, symbol: &Symbol, amount: f64) -> Html {
+        match self.to_base_currency(symbol, amount) {
+            Some(converted) => html! {
+                <div class="small text-muted">{ self.format_base_currency(converted) }</div>
+            },
+            None => html! {},
+        }
+    }
+
+    /// Sums up the prices that moved since they were last announced. Returns whether
+    /// there's anything new to read out
+    fn announce_prices(&mut self) -> bool {
+        if self.connection_state != ConnectionState::Connected {
+            return false;
+        }
+        let mut changes = vec![];
+        for symbol in self.state.streaming() {
+            let price = match self.state.latest_price(symbol) {
+                Some(price) => price,
+                None => continue,
+            };
+            let previous = self.announced_prices.get(symbol).copied();
+            if previous == Some(price) {
+                continue;
+            }
+            let direction = match previous {
+                Some(previous) if price > previous => " up",
+                Some(_) => " down",
+                None => "",
+            };
+            let price_format = self.state.price_format(symbol);
+            changes.push((
+                symbol.clone(),
+                price,
+                format!(
+                    "{}{} at {}",
+                    symbol.0,
+                    direction,
+                    price_format.format(price)
+                ),
+            ));
+        }
+        if changes.is_empty() {
+            return false;
+        }
+        let more = changes.len().saturating_sub(MAX_ANNOUNCED_SYMBOLS);
+        let mut parts = vec![];
+        for (symbol, price, text) in changes {
+            if parts.len() < MAX_ANNOUNCED_SYMBOLS {
+                parts.push(text);
+            }
+            self.announced_prices.insert(symbol, price);
+        }
+        if more > 0 {
+            parts.push(format!("{} more moved", more));
+        }
+        self.announcement = parts.join(". ");
+        true
+    }
+
+    fn is_maximized(&self, symbol: &Symbol) -> bool {
+        self.maximized.as_ref() == Some(symbol)
+    }
+
+    fn table_shown_rows(&self, symbol: &Symbol) -> usize {
+        if self.is_maximized(symbol) {
+            TABLE_MAXIMIZED_VISIBLE_ROWS
+        } else {
+            TABLE_VISIBLE_ROWS
+        }
+    }
+
+    fn table_depth(&self, symbol: &Symbol) -> usize {
+        self.table_depths
+            .get(symbol)
+            .copied()
+            .unwrap_or(TABLE_PAGE_ROWS)
+    }
+
+    fn table_sort(&self, symbol: &Symbol) -> TableSort {
+        self.table_sorts.get(symbol).copied().unwrap_or_default()
+    }
+
+    fn card_tab(&self, symbol: &Symbol) -> CardTab {
+        self.card_tabs.get(symbol).copied().unwrap_or_default()
+    }
+
+    fn persist_history(&mut self, symbol: &Symbol) {
+        if !self.history_loaded
+            || self.state.settings.persistence != PersistencePolicy::Everything
+            || self.is_follower()
+            || self.is_replaying(symbol)
+        {
+            return;
+        }
+        if let (Some(history_store), Some(history)) =
+            (&self.history_store, self.state.history.get(symbol))
+        {
+            let result = serde_json::to_string(history)
+                .map_err(|e| e.to_string())
+                .and_then(|json| history_store.put(&symbol.0, &json));
+            if let Err(e) = result {
+                self.logger
+                    .error(format!("Could not save history for [{}]: {}", symbol.0, e));
+            }
+        }
+    }
+
+    fn delete_history(&mut self, symbol: &Symbol) {
+        if let Some(history_store) = &self.history_store {
+            if let Err(e) = history_store.delete(&symbol.0) {
+                self.logger.error(format!(
+                    "Could not delete history for [{}]: {}",
+                    symbol.0, e
+                ));
+            }
+        }
+    }
+
+    /// A different key has to be checked again
+    fn reset_key_check(&mut self) {
+        self.key_check = KeyCheck::Unchecked;
+        self.key_check_task = None;
+        // and its plan may cover more
+        self.not_entitled.clear();
+        self.not_entitled_symbols.clear();
+    }
+
+    fn is_entitled(&self, feature: PlanFeature) -> bool {
+        !self.not_entitled.contains(&feature)
+    }
+
+    fn is_entitled_for(&self, feature: PlanFeature, symbol: &Symbol) -> bool {
+        self.is_entitled(feature)
+            && !self
+                .not_entitled_symbols
+                .contains(&(feature, symbol.clone()))
+    }
+
+    /// Stops asking for what the plan doesn't cover for this symbol, saying so the first
+    /// time. Other symbols are still asked for
+    fn note_not_entitled_for(&mut self, feature: PlanFeature, symbol: Symbol) {
+        let message = format!(
+            "{} for {} requires a paid finnhub plan",
+            feature.label(),
+            symbol.0
+        );
+        if !self.not_entitled_symbols.insert((feature, symbol)) {
+            return;
+        }
+        self.logger.warn(&message);
+        self.notifications.push(Level::Info, message);
+    }
+
+    /// Stops asking for what the plan doesn't cover, saying so the first time
+    fn note_not_entitled(&mut self, feature: PlanFeature) {
+        if !self.not_entitled.insert(feature) {
+            return;
+        }
+        let message = format!("{} requires a paid finnhub plan", feature.label());
+        self.logger.warn(&message);
+        self.notifications.push(Level::Info, message);
+        match feature {
+            PlanFeature::BidAsk => {
+                self.bid_ask_refresh_task = None;
+                self.bid_ask_tasks.clear();
+            }
+            PlanFeature::FxRates => self.fx_refresh_task = None,
+            PlanFeature::Candles => self.candle_tasks.clear(),
+            // turned down per symbol, with `note_not_entitled_for`
+            PlanFeature::News | PlanFeature::Earnings => (),
+        }
+    }
+
+    /// Stores the API key in the area chosen in settings, encrypted first if there's a
+    /// passphrase
+    fn persist_api_key(&mut self) {
+        if let Some(sealed) = self.locked_api_keys.clone() {
+            // still locked, so there's nothing newer to store; it may have to move though
+            self.store_api_keys(Some(&sealed));
+            return;
+        }
+        match &self.passphrase {
+            Some(passphrase) if self.state.settings.api_key_storage != ApiKeyStorage::Memory => {
+                let plaintext = serde_json::to_string(&(&self.api_key, &self.api_key_profiles))
+                    .unwrap_or_default();
+                self.seal_seq += 1;
+                let seq = self.seal_seq;
+                key_vault::seal(
+                    plaintext,
+                    passphrase.clone(),
+                    self.link
+                        .callback(move |result| Msg::ApiKeySealed(seq, result)),
+                );
+            }
+            _ => self.store_api_keys(None),
+        }
+    }
+
+    /// Stores the API key, either as is or `sealed`, in the area chosen in settings,
+    /// and makes sure it isn't left behind anywhere else
+    fn store_api_keys(&mut self, sealed: Option<&Sealed>) {
+        let api_key_storage = self.state.settings.api_key_storage;
+        let api_key = &self.api_key;
+        let api_key_profiles = &self.api_key_profiles;
+        let mut stored = Ok(());
+        for (area, maybe_storage_service, store) in [
+            (
+                ApiKeyStorage::Local,
+                &mut self.storage_service,
+                store_local as fn(&str, &str) -> Result<(), JsValue>,
+            ),
+            (
+                ApiKeyStorage::Session,
+                &mut self.session_storage_service,
+                store_session,
+            ),
+        ] {
+            if let Some(storage_service) = maybe_storage_service {
+                match sealed {
+                    Some(sealed) if area == api_key_storage => {
+                        stored = to_json(sealed)
+                            .and_then(|json| store(SEALED_API_KEYS_STORAGE_KEY, &json));
+                        storage_service.remove(API_KEY_STORAGE_KEY);
+                        storage_service.remove(API_KEY_PROFILES_STORAGE_KEY);
+                    }
+                    None if area == api_key_storage => {
+                        stored = to_json(api_key)
+                            .and_then(|json| store(API_KEY_STORAGE_KEY, &json))
+                            .and_then(|()| to_json(api_key_profiles))
+                            .and_then(|json| store(API_KEY_PROFILES_STORAGE_KEY, &json));
+                        storage_service.remove(SEALED_API_KEYS_STORAGE_KEY);
+                    }
+                    _ => {
+                        storage_service.remove(API_KEY_STORAGE_KEY);
+                        storage_service.remove(API_KEY_PROFILES_STORAGE_KEY);
+                        storage_service.remove(SEALED_API_KEYS_STORAGE_KEY);
+                    }
+                }
+            }
+        }
+        match stored {
+            Ok(()) => (),
+            Err(e) if is_quota_exceeded(&e) => self.warn_storage_full(
+                "Browser storage is full, so the API key couldn't be saved",
+                false,
+            ),
+            Err(e) => self
+                .logger
+                .error(format!("Could not save the API key [{:?}]", e)),
+        }
+    }
+
+    /// Warns while close to the plan's limit on subscriptions, and says how many
+    /// symbols are waiting for a slot once past it
+    fn check_subscription_limit(&mut self) {
+        let (count, limit, waiting) = match &self.session {
+            Some(session) => (
+                session.subscribed_count(),
+                session.limit(),
+                session.waiting_count(),
+            ),
+            None => (0, 1, 0),
+        };
+        let message = if count * 100 < limit * SUBSCRIPTION_WARN_PERCENT {
+            None
+        } else if waiting > 0 {
+            Some(format!(
+                "Subscribed to {} of {} symbols allowed; {} more waiting for a slot",
+                count, limit, waiting
+            ))
+        } else {
+            Some(format!(
+                "Subscribed to {} of {} symbols allowed",
+                count, limit
+            ))
+        };
+        let shown = self
+            .subscription_limit_notification
+            .as_ref()
+            .map(|(_, m)| m);
+        if shown == message.as_ref() {
+            return;
+        }
+        if let Some((id, _)) = self.subscription_limit_notification.take() {
+            self.notifications.dismiss(id);
+        }
+        self.subscription_limit_notification = message.map(|message| {
+            (
+                self.notifications.push(Level::Warning, message.as_str()),
+                message,
+            )
+        });
+    }
+
+    fn is_waiting_for_slot(&self, symbol: &Symbol) -> bool {
+        self.session
+            .as_ref()
+            .is_some_and(|session| session.is_waiting(symbol))
+    }
+
+    /// Flushes anything asked of the session while it was connecting, then brings its
+    /// subscriptions in line with what's currently tracked and not paused
+    fn session_opened(&mut self) {
+        if let Some(session) = &mut self.session {
+            session.opened(Utc::now());
+        }
+        self.reconcile_subscriptions();
+    }
+
+    /// Subscribes to what's tracked, not paused and not rejected, or to nothing while
+    /// the tab has been hidden for too long
+    fn reconcile_subscriptions(&mut self) {
+        if let Some(session) = &mut self.session {
+            session.reconcile(
+                self.state
+                    .wanted_subscriptions(self.hidden_pause, &self.symbol_errors),
+                Utc::now(),
+            );
+        }
+        self.check_subscription_limit();
+    }
+
+    /// The subscription a rejection refers to, if that's certain
+    fn take_rejected_subscription(&mut self, message: &str) -> Option<Symbol> {
+        self.session
+            .as_mut()
+            .and_then(|session| session.take_rejected_subscription(message, Utc::now()))
+    }
+
+    fn play_alert(&mut self) {
+        if self.beeper.is_none() {
+            match Beeper::new() {
+                Ok(beeper) => self.beeper = Some(beeper),
+                Err(e) => {
+                    self.logger
+                        .error(format!("Could not set up audio [{:?}]", e));
+                    return;
+                }
+            }
+        }
+        if let Some(beeper) = &self.beeper {
+            if let Err(e) = beeper.beep() {
+                self.logger.error(format!("Could not play alert [{:?}]", e));
+            }
+        }
+    }
+
+    /// What the UI's shown in, following the browser unless chosen in the settings
+    fn language(&self) -> Language {
+        Language::from_locale(&self.state.locale)
+    }
+
+    fn is_dark(&self) -> bool {
+        match self.state.settings.theme {
+            Theme::Light => false,
+            Theme::Dark => true,
+            Theme::Auto => self.prefers_dark,
+        }
+    }
+
+    fn disconnect(&mut self) {
+        if self.demo.take().is_some() {
+            self.remove_demo_symbols();
+        }
+        self.session = None;
+        self.check_subscription_limit();
+        self.connection_state = ConnectionState::Disconnected;
+        self.heartbeat_task = None;
+        self.last_message_at = None;
+        self.update_quote_polling();
+    }
+
+    fn is_replaying(&self, symbol: &Symbol) -> bool {
+        matches!(&self.replay, Some((replay, _)) if &replay.symbol == symbol)
+    }
+
+    /// Puts back whatever the replay hadn't got to yet, along with trades that came in
+    /// live while it was playing
+    fn stop_replay(&mut self) {
+        if let Some((replay, _)) = self.replay.take() {
+            let symbol = replay.symbol.clone();
+            self.state
+                .history
+                .merge(symbol.clone(), replay.finish().map(Trade::from));
+            self.persist_history(&symbol);
+        }
+    }
+
+    /// Feeds made up trades through the same pipeline as the websocket, so the app can
+    /// be tried out without an API key
+    fn start_demo(&mut self) {
+        self.wants_connection = false;
+        self.disconnect();
+        for symbol in simulator::SYMBOLS {
+            let symbol = Symbol(symbol.to_string());
+            if !self.state.tracked.contains(&symbol) {
+                self.state.tracked.push(symbol);
+            }
+        }
+        let tick_task = self.interval_service.spawn(
+            std::time::Duration::from_millis(DEMO_TICK_MILLIS),
+            self.link.callback(|_| Msg::DemoTick),
+        );
+        self.demo = Some((Simulator::new(), tick_task));
+        self.connection_state = ConnectionState::Connected;
+        self.last_message_at = Some(Utc::now());
+        self.update_quote_polling();
+        self.persist_state();
+    }
+
+    fn schedule_ws_retry(&mut self, secs: u64) {
+        let callback = self.link.callback(|_| Msg::WsRetry);
+        self.ws_retry_task = Some(
+            self.timeout_service
+                .spawn(std::time::Duration::from_secs(secs), callback),
+        );
+    }
+
+    /// Quotes are already polled whenever there's no websocket; this is about saying
+    /// so, rather than going on about failed connections, and trying again now and then
+    fn fall_back_to_polling(&mut self) {
+        self.ws_fallback = true;
+        if let Some(id) = self.reconnect_notification.take() {
+            self.notifications.dismiss(id);
+        }
+        self.logger.warn(format!(
+            "The websocket failed to open {} times in a row, falling back to polling quotes",
+            self.ws_failures
+        ));
+        self.dispatch_alert(
+            AlertKind::Disconnection,
+            None,
+            "Websockets look to be blocked, so quotes are being polled instead",
+            None,
+        );
+        self.persist_state();
+        self.schedule_ws_retry(WS_FALLBACK_RETRY_SECS);
+    }
+
+    /// Records an entry in the inspector, if it's shown
+    fn inspect(&mut self, kind: EntryKind, text: impl Into<String>) {
+        if self.state.settings.show_inspector {
+            self.inspector.push(kind, text, Utc::now());
+        }
+    }
+
+    /// Demo symbols are only any use while the demo is running
+    fn remove_demo_symbols(&mut self) {
+        while let Some(idx) = self.state.tracked.iter().position(Symbol::is_demo) {
+            self.untrack(idx);
+        }
+        self.persist_state();
+    }
+
+    /// Only streaming symbols with at least one trade can be stale, and only while
+    /// subscribed; the rest either aren't expected to have trades or are covered by
+    /// other warnings
+    fn find_stale_symbols(&self) -> HashSet<Symbol> {
+        if self.connection_state != ConnectionState::Connected || self.hidden_pause {
+            return HashSet::new();
+        }
+        let cutoff =
+            Utc::now() - Duration::minutes(i64::from(self.state.settings.stale_after_mins));
+        self.state
+            .streaming()
+            .filter(|symbol| {
+                self.state
+                    .history
+                    .get(symbol)
+                    .and_then(|h| h.front())
+                    .map(|newest| newest.time < cutoff)
+                    .unwrap_or(false)
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn update_relative_time_task(&mut self) {
+        self.relative_time_task = if self.time_formatter.is_relative() {
+            Some(self.interval_service.spawn(
+                std::time::Duration::from_secs(1),
+                self.link.callback(|_| Msg::RelativeTimeTick),
+            ))
+        } else {
+            None
+        };
+    }
+
+    /// Finnhub pings us periodically, but there is no client-side keepalive message in
+    /// its protocol (and browsers don't expose websocket ping frames), so the best we can
+    /// do is watch for silence.
+    fn start_heartbeat(&mut self) {
+        self.last_message_at = Some(Utc::now());
+        let callback = self.link.callback(|_| Msg::HeartbeatCheck);
+        self.heartbeat_task = Some(self.interval_service.spawn(
+            std::time::Duration::from_secs(HEARTBEAT_CHECK_INTERVAL_SECS),
+            callback,
+        ));
+    }
+
+    fn all_markets_closed(&self) -> bool {
+        let now = Utc::now();
+        let mut streaming = self.state.streaming().peekable();
+        streaming.peek().is_some() && streaming.all(|symbol| market_hours::is_closed(symbol, now))
+    }
+
+    fn is_connection_stale(&self) -> bool {
+        let timeout = Duration::seconds(self.state.settings.heartbeat_timeout_secs as i64);
+        match self.last_message_at {
+            Some(last_message_at) => Utc::now() - last_message_at > timeout,
+            None => false,
+        }
+    }
+
+    fn connect_to_api(&mut self) -> bool {
+        // the leading tab has the connection, and this one mirrors it
+        if self.is_follower() {
+            return true;
+        }
+        let callback = self.link.callback(|frame: Frame| match frame {
+            Frame::Text(text) => Msg::WsFrame(text),
+            Frame::Binary(Ok(bytes)) => Msg::WsBinaryFrame(bytes),
+            Frame::Binary(Err(e)) => Msg::WsFrame(Err(e)),
+        });
+
+        let notification = self.link.callback(|status| match status {
+            WebSocketStatus::Opened => Msg::WsOpened,
+            WebSocketStatus::Closed | WebSocketStatus::Error => Msg::WsDead,
+        });
+
+        let settings = &self.state.settings;
+        let target = WsTarget::new(
+            settings
+                .ws_base_url
+                .as_deref()
+                .unwrap_or(ws::DEFAULT_BASE_URL),
+            &self.api_key.0,
+            settings.ws_auth,
+        );
+        self.inspect(
+            EntryKind::Event,
+            format!(
+                "Connecting to {} ({})",
+                logger::redact(&target.url, &self.api_key.0),
+                self.state.settings.ws_auth.label()
+            ),
+        );
+        let connection_result = self.ws_connector.borrow_mut().connect(
+            target.url.as_str(),
+            target.protocol.as_deref(),
+            callback,
+            notification,
+        );
+        match connection_result {
+            Ok(connection) => {
+                let mut session =
+                    Session::new(connection, self.state.settings.subscription_limit as usize);
+                if let Some(message) = target.first_message {
+                    session.send_first(message);
+                }
+                self.session = Some(session);
+                self.connection_state = ConnectionState::Connecting;
+                self.update_quote_polling();
+                true
+            }
+            Err(yikes) => {
+                // the error may well include the URL, token and all
+                self.notifications
+                    .push(Level::Error, logger::redact(&yikes, &self.api_key.0));
+                true
+            }
+        }
+    }
+
+    fn view_route_tabs(&self) -> Html {
+        let language = self.language();
+        let tab = |route: Route| {
+            let class = if route == self.route {
+                "nav-link active"
+            } else {
+                "nav-link"
+            };
+            let unacknowledged = self.state.alert_log.unacknowledged();
+            let badge = if route == Route::Alerts && unacknowledged > 0 {
+                html! {
+                <span class="badge badge-pill badge-danger ml-1" title="Unacknowledged alerts">
+                    { unacknowledged }
+                </span>
+                }
+            } else {
+                html! {}
+            };
+            html! {
+            <li class="nav-item">
+                <a class=class href=route.hash()>{ i18n::t(language, route.phrase()) }{ badge }</a>
+            </li>
+            }
+        };
+        html! {
+        <div class="row mb-3">
+            <div class="offset-md-2 col-md-8">
+                <ul class="nav nav-tabs justify-content-center">
+                    { for Route::ALL.iter().map(|route| tab(*route)) }
+                </ul>
+            </div>
+        </div>
+        }
+    }
+
+    fn view_watchlist_page(&self) -> Html {
+        let maximized = self.maximized.as_ref().and_then(|symbol| {
+            self.state
+                .tracked
+                .iter()
+                .position(|s| s == symbol)
+                .map(|idx| (idx, symbol))
+        });
+        if let Some(card) = maximized {
+            return html! {
+            <div class = "row" >
+                < div class ="col-12" >
+                    { self.view_symbol(card) }
+                < /div>
+            < /div>
+            };
+        }
+        let symbols = match self.state.settings.view_mode {
+            ViewMode::Cards => self.view_symbol_cards(),
+            ViewMode::Grid => self.view_symbol_grid(),
+            ViewMode::Dashboard => html! {
+                <div class="dashboard" style=format!("grid-template-columns: repeat({}, minmax(0, 1fr));", self.state.settings.dashboard_columns.max(1))>
+                    { self.view_symbol_cards() }
+                </div>
+            },
+        };
+        // the dashboard makes use of all the width there is
+        let symbols_class = match (self.state.settings.view_mode, self.state.settings.show_tape) {
+            (ViewMode::Dashboard, true) => "col-md-10",
+            (ViewMode::Dashboard, false) => "col-12",
+            _ => "offset-md-2 col-md-8",
+        };
+        html! {
+        <>
+        < div class ="row" >
+            < div class ="offset-md-4 col-md-4" >
+                { self.view_ticker_input() }
+            < /div >
+            <div class="col-md-2 text-left">
+                { self.view_view_mode_toggle() }
+                <button type="button" class="btn btn-outline-secondary mb-3 ml-2" aria-label="Share watchlist" title="Share watchlist"
+                  disabled=self.state.tracked.is_empty()
+                  onclick = self.link.callback( | _ | Msg::ShareWatchlist)>
+                  <i class="fas fa-share-alt"></i>
+                </button>
+                <button type="button" class="btn btn-outline-secondary mb-3 ml-2" aria-label="Untrack all" title="Untrack all"
+                  disabled=self.state.tracked.is_empty()
+                  onclick = self.link.callback( | _ | Msg::UnTrackAll)>
+                  <i class="fas fa-trash-alt"></i>
+                </button>
+                { self.view_csv_import_button() }
+                { self.view_tape_toggle() }
+            </div>
+        < /div>
+        { self.view_csv_import_preview() }
+        <div class = "row" >
+            < div class=symbols_class >
+                { self.view_card_filter() }
+                { self.view_tag_filter() }
+                { symbols }
+            < /div>
+            { self.view_tape() }
+        < /div>
+        </>
+        }
+    }
+
+    fn view_csv_import_button(&self) -> Html {
+        html! {
+        <>
+        <input type="file" id="csv-import" class="sr-only" accept=".csv,.txt,text/csv,text/plain"
+          onchange = self.link.callback( | e: ChangeData | match e {
+              ChangeData::Files(files) => files.get(0).map_or(Msg::Nope, Msg::CsvFileChosen),
+              _ => Msg::Nope,
+          })
+          />
+        <label for="csv-import" class="btn btn-outline-secondary mb-3 ml-2" title="Import symbols from a CSV file">
+          <i class="fas fa-file-import"></i>
+          <span class="sr-only">{ "Import symbols from a CSV file" }</span>
+        </label>
+        </>
+        }
+    }
+
+    /// Detected symbols to pick from; ones already tracked can't be picked again
+    fn view_csv_import_preview(&self) -> Html {
+        let import = match &self.csv_import {
+            Some(import) => import,
+            None => return html! {},
+        };
+        let option = |(idx, symbol): (usize, &Symbol)| {
+            let id = format!("csv-import-{}", idx);
+            let tracked = self.state.tracked.contains(symbol);
+            let toggled = symbol.clone();
+            html! {
+            <div class="custom-control custom-checkbox custom-control-inline">
+              <input
+                type="checkbox"
+                class="custom-control-input"
+                id=id.clone()
+                disabled=tracked
+                checked=import.selected.contains(symbol)
+                onclick = self.link.callback( move | _ | Msg::CsvImportToggle(toggled.clone()))
+                />
+              <label class="custom-control-label" for=id>
+                { &symbol.0 }
+                { if tracked { html! { <span class="text-muted">{ " (tracked)" }</span> } } else { html! {} } }
+              </label>
+            </div>
+            }
+        };
+        html! {
+        <div class="row">
+            <div class="offset-md-2 col-md-8">
+                <div class="alert alert-secondary text-left" role="region" aria-label="Import preview">
+                    <p>{ format!("Found {} symbols in {}", import.symbols.len(), import.file_name) }</p>
+                    <div class="mb-2">
+                        { for import.symbols.iter().enumerate().map(option) }
+                    </div>
+                    <button type="button" class="btn btn-sm btn-primary"
+                      disabled=import.selected.is_empty()
+                      onclick = self.link.callback( | _ | Msg::CsvImportConfirm)>
+                      { format!("Track {} selected", import.selected.len()) }
+                    </button>
+                    <button type="button" class="btn btn-sm btn-outline-secondary ml-2"
+                      onclick = self.link.callback( | _ | Msg::CsvImportCancel)>
+                      { "Cancel" }
+                    </button>
+                </div>
+            </div>
+        </div>
+        }
+    }
+
+    fn view_tape_toggle(&self) -> Html {
+        let show_tape = self.state.settings.show_tape;
+        let (class, label) = if show_tape {
+            ("btn btn-secondary active mb-3 ml-2", "Hide trade tape")
+        } else {
+            ("btn btn-outline-secondary mb-3 ml-2", "Show trade tape")
+        };
+        html! {
+        <button type="button" class=class aria-label=label title=label aria-pressed=show_tape.to_string()
+          onclick = self.link.callback( move | _ | Msg::ShowTapeUpdate(!show_tape))>
+          <i class="fas fa-stream"></i>
+        </button>
+        }
+    }
+
+    /// The latest trades from every symbol in one column, newest at the top
+    fn view_tape(&self) -> Html {
+        if !self.state.settings.show_tape {
+            return html! {};
+        }
+        let now = Utc::now();
+        let row = |trade: &TapeTrade| {
+            let price_class = match trade.direction {
+                Some(PriceDirection::Up) => "text-right text-success",
+                Some(PriceDirection::Down) => "text-right text-danger",
+                _ => "text-right",
+            };
+            html! {
+            <tr>
+                <td>{ &trade.symbol.0 }</td>
+                <td class=price_class>{ self.state.price_format(&trade.symbol).format(trade.price) }</td>
+                <td class="text-right">{ self.state.format_volume(trade.volume) }</td>
+                <td>{ self.time_formatter.format(trade.time, now) }</td>
+            </tr>
+            }
+        };
+        html! {
+        <div class="col-md-2">
+            <h6>{ "Tape" }</h6>
+            <div class="tape">
+              <table class=self.page_table_class()>
+                <caption class="sr-only">{ "Latest trades across all symbols" }</caption>
+                <thead>
+                  <tr>
+                    <th>{ "Symbol" }</th>
+                    <th class="text-right">{ "Price" }</th>
+                    <th class="text-right">{ "Size" }</th>
+                    <th>{ "Time" }</th>
+                  </tr>
+                </thead>
+                <tbody>
+                  { for self.tape.iter().map(row) }
+                </tbody>
+              </table>
+              { if self.tape.is_empty() {
+                  html! { <p class="text-muted small">{ "No trades yet" }</p> }
+              } else {
+                  html! {}
+              } }
+            </div>
+        </div>
+        }
+    }
+
+    /// Grouped cards go under their group's header, which sits where the group's
+    /// first card would be
+    fn view_symbol_cards(&self) -> Html {
+        let mut shown_groups = HashSet::new();
+        let cards = self
+            .state
+            .tracked
+            .iter()
+            .enumerate()
+            .filter(|(_, symbol)| self.is_card_shown(symbol))
+            .filter_map(|(idx, symbol)| match self.state.symbol_groups.get(symbol) {
+                None => Some(self.view_symbol((idx, symbol))),
+                Some(group) if shown_groups.insert(group) => Some(self.view_symbol_group(group)),
+                Some(_) => None,
+            })
+            .collect::<Vec<_>>();
+        html! {
+            { for cards }
+        }
+    }
+
+    fn view_symbol_group(&self, group: &str) -> Html {
+        let members: Vec<(usize, &Symbol)> = self
+            .state
+            .tracked
+            .iter()
+            .enumerate()
+            .filter(|(_, symbol)| {
+                self.state.symbol_groups.get(*symbol).map(String::as_str) == Some(group)
+                    && self.is_card_shown(symbol)
+            })
+            .collect();
+        let healths: Vec<TickerHealth> = members
+            .iter()
+            .map(|(_, symbol)| self.state.ticker_health(symbol))
+            .collect();
+        let up = healths.iter().filter(|h| **h == TickerHealth::Good).count();
+        let down = healths.iter().filter(|h| **h == TickerHealth::Bad).count();
+        let health = TickerHealth::aggregate(healths);
+        let collapsed = self.state.collapsed_groups.contains(group);
+        let theme_class = if self.is_dark() {
+            "bg-dark text-light"
+        } else {
+            ""
+        };
+        let class = format!("card m-2 {} {}", health.border_class(), theme_class);
+        let toggle_group = group.to_string();
+        let (chevron, toggle_label) = if collapsed {
+            ("fas fa-chevron-right", "Expand group")
+        } else {
+            ("fas fa-chevron-down", "Collapse group")
+        };
+        let cards = if collapsed {
+            html! {}
+        } else {
+            html! {
+            <div class="card-body p-0">
+                { for members.iter().map( | (idx, symbol) | self.view_symbol((*idx, symbol))) }
+            </div>
+            }
+        };
+        // groups take up a whole row of the dashboard
+        let style = if self.state.settings.view_mode == ViewMode::Dashboard {
+            "grid-column: 1 / -1;"
+        } else {
+            ""
+        };
+        html! {
+        <div class=class style=style>
+          <div class="card-header d-flex justify-content-between align-items-center">
+            <button type="button" class="btn btn-link p-0 text-left" aria-expanded=(!collapsed).to_string() aria-label=toggle_label
+              onclick = self.link.callback( move | _ | Msg::ToggleGroupCollapsed(toggle_group.clone()))>
+              <i class=chevron></i>
+              <strong class="ml-2">{ group }</strong>
+            </button>
+            <small class="text-muted">
+              { format!("{} symbols · {} up · {} down", members.len(), up, down) }
+            </small>
+          </div>
+          { cards }
+        </div>
+        }
+    }
+
+    fn chart_spacing(&self, chart: &Chart) -> AxisSpacing {
+        self.chart_spacings
+            .get(chart)
+            .copied()
+            .unwrap_or_else(|| chart.default_spacing())
+    }
+
+    fn view_chart_spacing_toggle(&self, chart: Chart) -> Html {
+        let current = self.chart_spacing(&chart);
+        let button = |spacing: AxisSpacing| {
+            let class = if spacing == current {
+                "btn btn-secondary active"
+            } else {
+                "btn btn-outline-secondary"
+            };
+            let chart = chart.clone();
+            html! {
+            <button type="button" class=class aria-pressed=(spacing == current).to_string()
+              title=format!("Space by {}", spacing.label().to_lowercase())
+              onclick = self.link.callback( move | _ | Msg::ChartSpacingUpdate(chart.clone(), spacing))>
+              <i class=spacing.icon()></i>{ " " }{ spacing.label() }
+            </button>
+            }
+        };
+        html! {
+        <div class="btn-group btn-group-sm mb-2" role="group" aria-label="Chart x axis spacing">
+            { for AxisSpacing::ALL.iter().map(|s| button(*s)) }
+        </div>
+        }
+    }
+
+    /// Whether a symbol gets through the filters above the watchlist
+    fn is_card_shown(&self, symbol: &Symbol) -> bool {
+        self.tag_filter
+            .as_ref()
+            .is_none_or(|tag| self.state.has_tag(symbol, tag))
+            && self
+                .health_filter
+                .is_none_or(|health| self.state.ticker_health(symbol) == health)
+            && self.state.matches_query(symbol, &self.card_query)
+    }
+
+    fn view_card_filter(&self) -> Html {
+        if self.state.tracked.is_empty() {
+            return html! {};
+        }
+        let shown = self
+            .state
+            .tracked
+            .iter()
+            .filter(|s| self.is_card_shown(s))
+            .count();
+        let hint = if shown < self.state.tracked.len() {
+            format!("{} of {} shown", shown, self.state.tracked.len())
+        } else {
+            String::new()
+        };
+        let health_option = |health: TickerHealth| {
+            html! {
+            <option value=health.label() selected=self.health_filter == Some(health)>{ health.label() }</option>
+            }
+        };
+        html! {
+        <div class="input-group input-group-sm mb-2">
+          <div class="input-group-prepend">
+            <span class="input-group-text"><i class="fas fa-search"></i></span>
+          </div>
+          <input
+            type="search"
+            class="form-control"
+            placeholder="Filter by symbol or tag"
+            aria-label="Filter cards by symbol or tag"
+            value=&self.card_query
+            oninput = self.link.callback( | e: InputData | Msg::CardQueryUpdate(e.value))
+            />
+          <select
+            class="custom-select"
+            aria-label="Filter cards by health"
+            onchange = self.link.callback( | e: ChangeData | match e {
+                ChangeData::Select(select) => Msg::HealthFilterUpdate(
+                    TickerHealth::ALL.iter().copied().find(|h| h.label() == select.value())
+                ),
+                _ => Msg::Nope,
+            })>
+            <option value="" selected=self.health_filter.is_none()>{ "Any direction" }</option>
+            { for TickerHealth::ALL.iter().map(|h| health_option(*h)) }
+          </select>
+          <div class="input-group-append">
+            <span class="input-group-text" aria-live="polite">{ hint }</span>
+          </div>
+        </div>
+        }
+    }
+
+    fn view_tag_filter(&self) -> Html {
+        let tags = self.state.all_tags();
+        if tags.is_empty() {
+            return html! {};
+        }
+        let tag_button = |tag: &String| {
+            let active = self.tag_filter.as_ref() == Some(tag);
+            let class = if active {
+                format!("badge badge-pill {} border-0 mr-1", tag_badge_class(tag))
+            } else {
+                "badge badge-pill badge-light border mr-1".to_string()
+            };
+            let toggled = tag.clone();
+            html! {
+            <button type="button" class=class aria-pressed=active.to_string()
+              onclick = self.link.callback( move | _ | Msg::TagFilterToggle(toggled.clone()))>
+              { tag }
+            </button>
+            }
+        };
+        html! {
+        <div class="mb-2 text-left" role="group" aria-label="Filter by tag">
+            <small class="text-muted mr-2">{ "Tags" }</small>
+            { for tags.into_iter().map(tag_button) }
+        </div>
+        }
+    }
+
+    fn view_tags(&self, symbol: &Symbol) -> Html {
+        match self.state.tags.get(symbol) {
+            Some(tags) => html! {
+                { for tags.iter().map(|tag| html! {
+                    <span class=format!("badge badge-pill {} ml-1", tag_badge_class(tag))>{ tag }</span>
+                }) }
+            },
+            None => html! {},
+        }
+    }
+
+    fn view_note(&self, symbol: &Symbol) -> Html {
+        match self.state.notes.get(symbol) {
+            Some(note) => html! { <p class="small text-muted mb-0 text-break">{ note }</p> },
+            None => html! {},
+        }
+    }
+
+    fn view_notes_inputs(&self, symbol: &Symbol) -> Html {
+        let tags = self
+            .state
+            .tags
+            .get(symbol)
+            .map(|tags| tags.join(", "))
+            .unwrap_or_default();
+        let note = self.state.notes.get(symbol).cloned().unwrap_or_default();
+        let tags_symbol = symbol.clone();
+        let note_symbol = symbol.clone();
+        html! {
+        <div class="input-group input-group-sm mb-2">
+          <div class="input-group-prepend">
+            <span class="input-group-text"><i class="fas fa-tags"></i></span>
+          </div>
+          <input
+            type="text"
+            class="form-control"
+            placeholder="Tags, comma separated"
+            aria-label="Tags"
+            title="Tags"
+            list="symbol-tags"
+            value=tags
+            onchange = self.link.callback( move | e: ChangeData | match e {
+                ChangeData::Value(v) => Msg::TagsUpdate(tags_symbol.clone(), parse_tags(&v)),
+                _ => Msg::Nope,
+            })
+            />
+          <datalist id="symbol-tags">
+            { for self.state.all_tags().iter().map( | tag | html! { <option value=tag /> }) }
+          </datalist>
+          <input
+            type="text"
+            class="form-control"
+            placeholder="Note"
+            aria-label="Note"
+            title="Note"
+            value=note
+            onchange = self.link.callback( move | e: ChangeData | match e {
+                ChangeData::Value(v) if v.trim().is_empty() => Msg::NoteUpdate(note_symbol.clone(), None),
+                ChangeData::Value(v) => Msg::NoteUpdate(note_symbol.clone(), Some(v.trim().to_string())),
+                _ => Msg::Nope,
+            })
+            />
+        </div>
+        }
+    }
+
+    fn view_group_input(&self, symbol: &Symbol) -> Html {
+        let current = self
+            .state
+            .symbol_groups
+            .get(symbol)
+            .cloned()
+            .unwrap_or_default();
+        let groups: BTreeSet<&String> = self.state.symbol_groups.values().collect();
+        let group_symbol = symbol.clone();
+        html! {
+        <div class="input-group input-group-sm mb-2">
+          <div class="input-group-prepend">
+            <span class="input-group-text"><i class="fas fa-layer-group"></i></span>
+          </div>
+          <input
+            type="text"
+            class="form-control"
+            placeholder="Group"
+            aria-label="Group"
+            title="Group"
+            list="symbol-groups"
+            value=current
+            onchange = self.link.callback( move | e: ChangeData | match e {
+                ChangeData::Value(v) if v.trim().is_empty() => Msg::SymbolGroupUpdate(group_symbol.clone(), None),
+                ChangeData::Value(v) => Msg::SymbolGroupUpdate(group_symbol.clone(), Some(v.trim().to_string())),
+                _ => Msg::Nope,
+            })
+            />
+          <datalist id="symbol-groups">
+            { for groups.iter().map( | group | html! { <option value=group /> }) }
+          </datalist>
+        </div>
+        }
+    }
+
+    fn view_view_mode_toggle(&self) -> Html {
+        let button = |view_mode: ViewMode, icon_class: &str, label: &str| {
+            let class = if view_mode == self.state.settings.view_mode {
+                "btn btn-secondary active"
+            } else {
+                "btn btn-outline-secondary"
+            };
+            html! {
+            <button type="button" class=class aria-label=label title=label
+              onclick = self.link.callback( move | _ | Msg::ViewModeUpdate(view_mode))>
+              <i class=icon_class></i>
+            </button>
+            }
+        };
+        html! {
+        <div class="btn-group mb-3" role="group" aria-label="Layout">
+            { button(ViewMode::Cards, "fas fa-th-large", "Cards") }
+            { button(ViewMode::Grid, "fas fa-list", "Grid") }
+            { button(ViewMode::Dashboard, "fas fa-th", "Dashboard") }
+        </div>
+        }
+    }
+
+    fn view_symbol_grid(&self) -> Html {
+        html! {
+        <div class="table-responsive">
+          <table class=self.page_table_class()>
+            <thead>
+              <tr>
+                <th>{ "Symbol" }</th>
+                <th class="text-right">{ "Last" }</th>
+                <th class="text-right" title="Since the oldest trade in the history">{ "Change" }</th>
+                <th class="text-right">{ "Volume" }</th>
+                <th>{ "Trend" }</th>
+                <th></th>
+              </tr>
+            </thead>
+            <tbody>
+              { for self.state.tracked.iter().enumerate().filter(|(_, s)| self.is_card_shown(s)).map(|e| self.view_symbol_grid_row(e)) }
+            </tbody>
+          </table>
+        </div>
+        }
+    }
+
+    fn view_symbol_grid_row(&self, (idx, symbol): (usize, &Symbol)) -> Html {
+        let price_format = self.state.price_format(symbol);
+        let history = self.state.history.get(symbol);
+        let newest = history.and_then(|h| h.front());
+        let change = self.state.change_percent(symbol);
+        let (change_text, change_class) = match change {
+            Some(change) if change > 0.0 => (format!("{:+.2}%", change), "text-right text-success"),
+            Some(change) if change < 0.0 => (format!("{:+.2}%", change), "text-right text-danger"),
+            Some(change) => (format!("{:.2}%", change), "text-right"),
+            None => ("".to_string(), "text-right"),
+        };
+        let status = if self.state.is_paused(symbol) {
+            html! { <span class="badge badge-secondary ml-1">{ "Paused" }</span> }
+        } else if let Some(error) = self.symbol_errors.get(symbol) {
+            html! { <span class="badge badge-danger ml-1">{ error.label() }</span> }
+        } else if self.is_waiting_for_slot(symbol) {
+            html! { <span class="badge badge-info ml-1">{ "Waiting" }</span> }
+        } else if self.stale_symbols.contains(symbol) {
+            if market_hours::is_closed(symbol, Utc::now()) {
+                html! { <span class="badge badge-secondary ml-1">{ "Closed" }</span> }
+            } else {
+                html! { <span class="badge badge-warning ml-1">{ "Stale" }</span> }
+            }
+        } else {
+            html! {}
+        };
+        html! {
+        <tr>
+          <td>{ &symbol.0 }{ status }</td>
+          <td class="text-right">
+            { newest.map(|t| price_format.format(t.price)).unwrap_or_default() }
+            { newest.map(|t| self.view_in_base_currency(symbol, t.price.0.to_f64())).unwrap_or_else(|| html! {}) }
+          </td>
+          <td class=change_class>{ change_text }</td>
+          <td class="text-right">{ newest.filter(|t| !t.delayed).map(|t| self.state.format_volume(t.volume)).unwrap_or_default() }</td>
+          <td>{ history.map(|h| view_sparkline(h, change.unwrap_or(0.0), 100.0, 24.0, AxisSpacing::Sequence, false)).unwrap_or_else(|| html! {}) }</td>
+          <td class="text-right">
+            <button type="button" class="close" aria-label=format!("Untrack {}", symbol.0) onclick = self.link.callback( move | _ | Msg::UnTrackSymbolAtIdx(idx)) >
+              <i class="fas fa-times"></i>
+            </button>
+          </td>
+        </tr>
+        }
+    }
+
+    fn view_portfolio_page(&self) -> Html {
+        html! {
+        <div class = "row" >
+            < div class ="offset-md-2 col-md-8" >
+                { self.view_portfolio_summary() }
+                <div class="table-responsive">
+                  <table class=self.page_table_class()>
+                    <thead>
+                      <tr>
+                        <th>{ "Symbol" }</th>
+                        <th>{ "Shares" }</th>
+                        <th>{ "Cost basis" }</th>
+                        <th>{ "Last" }</th>
+                        <th>{ "Value / P&L" }</th>
+                      </tr>
+                    </thead>
+                    <tbody>
+                      { for self.state.tracked.iter().map(|symbol| self.view_portfolio_row(symbol)) }
+                    </tbody>
+                  </table>
+                </div>
+            < /div>
+        < /div>
+        }
+    }
+
+    fn view_portfolio_row(&self, symbol: &Symbol) -> Html {
+        let position = self.state.positions.get(symbol).copied();
+        let shares_symbol = symbol.clone();
+        let cost_basis_symbol = symbol.clone();
+        let latest_price = self.state.latest_price(symbol);
+        let last = latest_price
+            .map(|price| self.state.price_format(symbol).format(price))
+            .unwrap_or_default();
+        html! {
+        <tr>
+          <td class="align-middle">{ &symbol.0 }</td>
+          <td>
+            {
+                self.view_decimal_input(
+                    "fas fa-briefcase",
+                    "Shares held",
+                    position.map(|p| p.shares),
+                    move |shares| Msg::SharesUpdate(shares_symbol.clone(), shares),
+                )
+            }
+          </td>
+          <td>
+            {
+                self.view_decimal_input(
+                    "fas fa-tag",
+                    "Cost basis per share",
+                    position.map(|p| p.cost_basis.0),
+                    move |cost_basis| {
+                        Msg::CostBasisUpdate(cost_basis_symbol.clone(), cost_basis.map(Price))
+                    },
+                )
+            }
+          </td>
+          <td class="align-middle text-right">
+            { last }
+            { latest_price.map(|p| self.view_in_base_currency(symbol, p.0.to_f64())).unwrap_or_else(|| html! {}) }
+          </td>
+          <td class="align-middle">{ self.view_position_summary(symbol) }</td>
+        </tr>
+        }
+    }
+
+    fn view_compare_page(&self) -> Html {
+        // a symbol can be tracked more than once
+        let mut seen = HashSet::new();
+        let tracked: Vec<&Symbol> = self
+            .state
+            .tracked
+            .iter()
+            .filter(|s| seen.insert(*s))
+            .collect();
+        let option = |(idx, symbol): (usize, &&Symbol)| {
+            let id = format!("compare-{}", idx);
+            let checked = self.compared.contains(symbol);
+            let disabled = !checked && self.compared.len() >= MAX_COMPARED;
+            let toggled = (*symbol).clone();
+            html! {
+            <div class="custom-control custom-checkbox custom-control-inline">
+              <input
+                type="checkbox"
+                class="custom-control-input"
+                id=id.clone()
+                checked=checked
+                disabled=disabled
+                onclick = self.link.callback( move | _ | Msg::CompareToggle(toggled.clone()))
+                />
+              <label class="custom-control-label" for=id>{ &symbol.0 }</label>
+            </div>
+            }
+        };
+        html! {
+        <div class = "row" >
+            < div class ="offset-md-2 col-md-8 text-left" >
+                <p class="text-muted small">
+                    { format!("Pick 2 to {} symbols to chart their change since a common starting point", MAX_COMPARED) }
+                </p>
+                <div class="mb-3">
+                    { for tracked.iter().enumerate().map(option) }
+                </div>
+                { self.view_chart_spacing_toggle(Chart::Compare) }
+                { self.view_comparison_chart() }
+            < /div>
+        < /div>
+        }
+    }
+
+    fn view_comparison_chart(&self) -> Html {
+        if self.compared.len() < 2 {
+            return html! {};
+        }
+        let histories: Option<Vec<&VecDeque<Trade>>> = self
+            .compared
+            .iter()
+            .map(|symbol| self.state.history.get(symbol))
+            .collect();
+        let (baseline, series) = match histories.as_deref().and_then(percent_change_series) {
+            Some(chart) => chart,
+            None => {
+                return html! {
+                <p class="text-muted">{ "Waiting for trades in every compared symbol" }</p>
+                }
+            }
+        };
+        let end = series
+            .iter()
+            .filter_map(|points| points.last().map(|(time, _)| *time))
+            .max()
+            .unwrap_or(baseline);
+        let spacing = self.chart_spacing(&Chart::Compare);
+        let axis = TimeAxis::new(
+            series.iter().flatten().map(|(time, _)| *time),
+            spacing,
+            COMPARE_CHART_WIDTH,
+        );
+        let changes = series.iter().flatten().map(|(_, change)| *change);
+        let low = changes.clone().fold(0.0, f64::min);
+        let high = changes.fold(0.0, f64::max);
+        let (low, high) = if high - low < 0.01 {
+            (low - 0.5, high + 0.5)
+        } else {
+            (low, high)
+        };
+        let y = |change: f64| {
+            COMPARE_CHART_HEIGHT - (change - low) / (high - low) * COMPARE_CHART_HEIGHT
+        };
+        let line = |(points, color): (&ChangeSeries, &&str)| {
+            let points = points
+                .iter()
+                .map(|(time, change)| format!("{:.1},{:.1}", axis.x(*time), y(*change)))
+                .collect::<Vec<_>>()
+                .join(" ");
+            html! {
+            <polyline points=points fill="none" stroke=*color stroke-width="1.5" />
+            }
+        };
+        let legend = |((symbol, points), color): ((&Symbol, &ChangeSeries), &&str)| {
+            let latest = points.last().map_or(0.0, |(_, change)| *change);
+            html! {
+            <li class="list-inline-item">
+                <span style=format!("color: {};", color) aria-hidden="true">{ "■ " }</span>
+                { format!("{} {:+.2}%", symbol.0, latest) }
+            </li>
+            }
+        };
+        let now = Utc::now();
+        html! {
+        <>
+        <svg class="w-100" viewBox=format!("0 0 {} {}", COMPARE_CHART_WIDTH, COMPARE_CHART_HEIGHT) preserveAspectRatio="none"
+          height=COMPARE_CHART_HEIGHT.to_string() role="img"
+          aria-label=format!("Percentage change of {} since {}", self.compared.iter().map(|s| s.0.as_str()).collect::<Vec<_>>().join(", "), self.time_formatter.format(baseline, now))>
+            <line x1="0" x2=COMPARE_CHART_WIDTH.to_string() y1=y(0.0).to_string() y2=y(0.0).to_string()
+              stroke="currentColor" stroke-opacity="0.3" stroke-dasharray="4 4" />
+            { for series.iter().zip(COMPARE_COLORS.iter()).map(line) }
+            { view_gap_markers(&axis, COMPARE_CHART_HEIGHT) }
+        </svg>
+        <div class="d-flex justify-content-between small text-muted">
+            <span>{ self.time_formatter.format(baseline, now) }</span>
+            <span>{ format!("{:+.2}% to {:+.2}%", low, high) }</span>
+            <span>{ self.time_formatter.format(end, now) }</span>
+        </div>
+        <ul class="list-inline mt-2">
+            { for self.compared.iter().zip(series.iter()).zip(COMPARE_COLORS.iter()).map(legend) }
+        </ul>
+        </>
+        }
+    }
+
+    fn view_alerts_page(&self) -> Html {
+        html! {
+        <div class = "row" >
+            < div class ="offset-md-2 col-md-8" >
+                <div class="table-responsive">
+                  <table class=self.page_table_class()>
+                    <thead>
+                      <tr>
+                        <th>{ "Symbol" }</th>
+                        <th>{ "Volume alert" }</th>
+                        <th>{ "Minimum volume" }</th>
+                        <th>{ "Price target" }</th>
+                        <th>{ "Rules" }</th>
+                      </tr>
+                    </thead>
+                    <tbody>
+                      { for self.state.tracked.iter().map(|symbol| self.view_alerts_row(symbol)) }
+                    </tbody>
+                  </table>
+                </div>
+                { self.view_alert_log() }
+            < /div>
+        < /div>
+        }
+    }
+
+    fn view_alert_log(&self) -> Html {
+        let log = &self.state.alert_log;
+        let now = Utc::now();
+        let row = |(idx, entry): (usize, &alert_log::AlertEntry)| {
+            let (class, acknowledge) = if entry.acknowledged {
+                ("text-muted", html! {})
+            } else {
+                (
+                    "font-weight-bold",
+                    html! {
+                    <button type="button" class="btn btn-sm btn-outline-secondary" aria-label="Acknowledge" title="Acknowledge"
+                      onclick = self.link.callback( move | _ | Msg::AcknowledgeAlert(idx))>
+                      <i class="fas fa-check"></i>
+                    </button>
+                    },
+                )
+            };
+            let snoozed_until =
+                self.state
+                    .settings
+                    .alert_snoozes
+                    .until(entry.kind, entry.symbol.as_ref(), now);
+            let snooze = match snoozed_until {
+                Some(until) => html! {
+                <button type="button" class="btn btn-sm btn-outline-secondary mr-1"
+                  title=format!("Snoozed until {}, click to unsnooze", self.time_formatter.format(until, now))
+                  onclick = self.link.callback( move | _ | Msg::UnsnoozeAlert(idx))>
+                  <i class="fas fa-bell-slash"></i>
+                </button>
+                },
+                None => html! {
+                <>
+                <button type="button" class="btn btn-sm btn-outline-secondary mr-1" title="Mute alerts like this for 15 minutes"
+                  onclick = self.link.callback( move | _ | Msg::SnoozeAlert(idx, 15))>
+                  { "15m" }
+                </button>
+                <button type="button" class="btn btn-sm btn-outline-secondary mr-1" title="Mute alerts like this for an hour"
+                  onclick = self.link.callback( move | _ | Msg::SnoozeAlert(idx, 60))>
+                  { "1h" }
+                </button>
+                </>
+                },
+            };
+            html! {
+            <tr class=class>
+              <td class="align-middle">{ self.time_formatter.format(entry.time, now) }</td>
+              <td class="align-middle">{ entry.kind.label() }</td>
+              <td class="align-middle">{ &entry.message }</td>
+              <td class="text-right text-nowrap">{ snooze }{ acknowledge }</td>
+            </tr>
+            }
+        };
+        html! {
+        <>
+        <div class="d-flex justify-content-between align-items-center mt-3 mb-2">
+            <h5 class="mb-0">{ "Alert history" }</h5>
+            <div>
+                <button type="button" class="btn btn-sm btn-outline-secondary"
+                  disabled=log.unacknowledged() == 0
+                  onclick = self.link.callback( | _ | Msg::AcknowledgeAllAlerts)>
+                  { "Acknowledge all" }
+                </button>
+                <button type="button" class="btn btn-sm btn-outline-danger ml-2"
+                  disabled=log.is_empty()
+                  onclick = self.link.callback( | _ | Msg::ClearAlertLog)>
+                  { "Clear" }
+                </button>
+            </div>
+        </div>
+        {
+            if log.is_empty() {
+                html! { <p class="text-muted">{ i18n::t(self.language(), Phrase::NoAlertsYet) }</p> }
+            } else {
+                html! {
+                <div class="table-responsive">
+                  <table class=self.page_table_class()>
+                    <thead>
+                      <tr>
+                        <th>{ "Time" }</th>
+                        <th>{ "Alert" }</th>
+                        <th>{ "Details" }</th>
+                        <th></th>
+                      </tr>
+                    </thead>
+                    <tbody>
+                      { for log.entries().enumerate().map(row) }
+                    </tbody>
+                  </table>
+                </div>
+                }
+            }
+        }
+        </>
+        }
+    }
+
+    fn view_alerts_row(&self, symbol: &Symbol) -> Html {
+        let target_symbol = symbol.clone();
+        html! {
+        <tr>
+          <td class="align-middle">{ &symbol.0 }</td>
+          <td>{ self.view_volume_alert_input(symbol) }</td>
+          <td>{ self.view_min_volume_input(symbol) }</td>
+          <td>
+            {
+                self.view_decimal_input(
+                    "fas fa-bullseye",
+                    "Target price",
+                    self.state.price_targets.get(symbol).map(|t| t.target.0),
+                    move |target| Msg::PriceTargetUpdate(target_symbol.clone(), target.map(Price)),
+                )
+            }
+          </td>
+          <td>{ self.view_alert_rules(symbol) }</td>
+        </tr>
+        }
+    }
+
+    fn view_replay_controls(&self, symbol: &Symbol) -> Html {
+        let replay = match &self.replay {
+            Some((replay, _)) if &replay.symbol == symbol => replay,
+            _ => return html! {},
+        };
+        let speed_button = |speed: ReplaySpeed| {
+            let class = if speed == replay.speed {
+                "btn btn-sm btn-secondary active"
+            } else {
+                "btn btn-sm btn-outline-secondary"
+            };
+            html! {
+            <button type="button" class=class aria-pressed=(speed == replay.speed).to_string()
+              onclick = self.link.callback( move | _ | Msg::ReplaySpeedUpdate(speed))>
+              { speed.label() }
+            </button>
+            }
+        };
+        let clock = replay
+            .clock()
+            .map(|clock| format!(" · {}", self.time_formatter.format(clock, Utc::now())))
+            .unwrap_or_default();
+        html! {
+        <div class="d-flex justify-content-between align-items-center alert alert-secondary py-1 px-2 mb-2" role="status">
+          <small>
+            <i class="fas fa-history mr-1"></i>
+            { format!("Replaying {} of {} trades{}", replay.played(), replay.total(), clock) }
+          </small>
+          <div>
+            <div class="btn-group mr-2" role="group" aria-label="Replay speed">
+              { for ReplaySpeed::ALL.iter().map(|speed| speed_button(*speed)) }
+            </div>
+            <button type="button" class="btn btn-sm btn-outline-secondary" aria-label="Stop replaying" title="Stop replaying"
+              onclick = self.link.callback( | _ | Msg::StopReplay)>
+              <i class="fas fa-stop"></i>
+            </button>
+          </div>
+        </div>
+        }
+    }
+
+    fn view_alert_rules(&self, symbol: &Symbol) -> Html {
+        let rule = |(idx, condition): (usize, &Condition)| {
+            let symbol = symbol.clone();
+            html! {
+            <li class="d-flex justify-content-between align-items-center">
+              <code>{ condition.to_string() }</code>
+              <button type="button" class="close ml-2" aria-label=format!("Remove rule {}", condition) title="Remove"
+                onclick = self.link.callback( move | _ | Msg::RemoveAlertRule(symbol.clone(), idx))>
+                <i class="fas fa-times"></i>
+              </button>
+            </li>
+            }
+        };
+        let (input_class, error) = match self.alert_rule_errors.get(symbol) {
+            Some(e) => (
+                "form-control is-invalid",
+                html! { <div class="invalid-feedback">{ e }</div> },
+            ),
+            None => ("form-control", html! {}),
+        };
+        let input_symbol = symbol.clone();
+        let enter_symbol = symbol.clone();
+        let add_symbol = symbol.clone();
+        html! {
+        <>
+        <ul class="list-unstyled mb-1 small">
+          { for self.state.alert_rules.get(symbol).into_iter().flatten().enumerate().map(rule) }
+        </ul>
+        <div class="input-group input-group-sm mb-2">
+          <input
+            type="text"
+            class=input_class
+            placeholder="e.g. move 2% in 5m"
+            aria-label=format!("New alert rule for {}", symbol.0)
+            title="move 2% in 5m, volume > 50000 in 1m or crosses sma 20"
+            value=self.alert_rule_inputs.get(symbol).cloned().unwrap_or_default()
+            oninput = self.link.callback( move | e: InputData | Msg::AlertRuleInput(input_symbol.clone(), e.value))
+            onkeypress = self.link.callback( move | e: KeyboardEvent | {
+                if e.key() == "Enter" { Msg::AddAlertRule(enter_symbol.clone()) } else { Msg::Nope }
+            })
+            />
+          <div class="input-group-append">
+            <button class="btn btn-outline-secondary" type="button" aria-label="Add rule" title="Add rule"
+              onclick = self.link.callback( move | _ | Msg::AddAlertRule(add_symbol.clone()))>
+              <i class="fas fa-plus"></i>
+            </button>
+          </div>
+          { error }
+        </div>
+        </>
+        }
+    }
+
+    fn view_settings_page(&self) -> Html {
+        html! {
+        < div class ="row" >
+            < div class ="offset-md-4 col-md-4" >
+                { self.view_settings() }
+                { self.view_diagnostics() }
+                { self.view_inspector() }
+            < /div >
+        < /div>
+        }
+    }
+
+    fn metadata_summary(&self) -> String {
+        let metadata = &self.state.metadata;
+        match metadata.oldest() {
+            Some(oldest) => format!(
+                "Metadata: {} cached, the oldest fetched {}",
+                metadata.len(),
+                self.time_formatter.format(oldest, Utc::now())
+            ),
+            None => "Metadata: nothing cached".to_string(),
+        }
+    }
+
+    /// Websocket message types we got but don't handle, in case finnhub adds some
+    fn view_diagnostics(&self) -> Html {
+        let row = |(kind, seen): (&String, &UnknownMessages)| {
+            html! {
+            <tr>
+              <td>{ kind }</td>
+              <td class="text-right">{ seen.count }</td>
+              <td><code class="text-break">{ &seen.last_payload }</code></td>
+            </tr>
+            }
+        };
+        html! {
+        <div class="text-left mb-3">
+            <h5>{ "Diagnostics" }</h5>
+            <p class="text-muted small">
+                { format!(
+                    "REST: {} of {} calls a minute used · {} queued",
+                    self.api_client.sent_in_last_min(),
+                    api_client::REQUESTS_PER_MIN,
+                    self.api_client.queued()
+                ) }
+            </p>
+            <p class="text-muted small d-flex justify-content-between align-items-center">
+                <span>{ self.metadata_summary() }</span>
+                <button type="button" class="btn btn-sm btn-outline-secondary ml-2"
+                  title="Fetch company profiles, the symbol list and FX rates again"
+                  disabled=self.api_key.0.is_empty()
+                  onclick = self.link.callback( | _ | Msg::RefreshMetadata)>
+                  <i class="fas fa-sync-alt mr-1"></i>{ "Refresh metadata" }
+                </button>
+            </p>
+            {
+                if self.unknown_messages.is_empty() {
+                    html! { <p class="text-muted small">{ "No unknown websocket messages received" }</p> }
+                } else {
+                    html! {
+                    <div class="table-responsive">
+                      <table class=self.page_table_class()>
+                        <caption>{ "Unknown websocket message types this session" }</caption>
+                        <thead>
+                          <tr>
+                            <th>{ "Type" }</th>
+                            <th class="text-right">{ "Count" }</th>
+                            <th>{ "Latest" }</th>
+                          </tr>
+                        </thead>
+                        <tbody>
+                          { for self.unknown_messages.iter().map(row) }
+                        </tbody>
+                      </table>
+                    </div>
+                    }
+                }
+            }
+        </div>
+        }
+    }
+
+    /// The latest raw traffic, for debugging without devtools
+    fn view_inspector(&self) -> Html {
+        if !self.state.settings.show_inspector {
+            return html! {};
+        }
+        let now = Utc::now();
+        let entry = |entry: &inspector::Entry| {
+            let badge = match entry.kind {
+                EntryKind::Frame => "badge badge-secondary",
+                EntryKind::Event => "badge badge-info",
+                EntryKind::ParseFailure => "badge badge-danger",
+            };
+            html! {
+            <li class="list-group-item px-2 py-1">
+              <div class="small text-muted">
+                <span class=badge>{ entry.kind.label() }</span>
+                { " " }{ self.time_formatter.format(entry.time, now) }
+              </div>
+              <pre class="small mb-0 text-break">{ &entry.text }</pre>
+            </li>
+            }
+        };
+        html! {
+        <div class="text-left mb-3">
+            <div class="d-flex justify-content-between align-items-center">
+                <h5>{ "Message inspector" }</h5>
+                <button class="btn btn-sm btn-outline-secondary" type="button"
+                  disabled=self.inspector.is_empty()
+                  onclick = self.link.callback( | _ | Msg::ClearInspector)>
+                  { "Clear" }
+                </button>
+            </div>
+            {
+                if self.inspector.is_empty() {
+                    html! { <p class="text-muted small">{ "Nothing received since the inspector was turned on" }</p> }
+                } else {
+                    html! {
+                    <ul class="list-group inspector">
+                      { for self.inspector.entries().map(entry) }
+                    </ul>
+                    }
+                }
+            }
+        </div>
+        }
+    }
+
+    fn page_table_class(&self) -> &'static str {
+        if self.is_dark() {
+            "table table-sm table-dark text-left"
+        } else {
+            "table table-sm text-left"
+        }
+    }
+
+    fn view_shortcuts_help(&self) -> Html {
+        if !self.show_shortcuts_help {
+            return html! {};
+        }
+        html! {
+        <>
+        <div class="modal d-block text-left" tabindex="-1" role="dialog" aria-labelledby="shortcuts-title">
+          <div class="modal-dialog" role="document">
+            <div class="modal-content text-dark">
+              <div class="modal-header">
+                <h5 class="modal-title" id="shortcuts-title">{ "Keyboard shortcuts" }</h5>
+                <button type="button" class="close" aria-label="Close" onclick = self.link.callback( | _ | Msg::Shortcut(Shortcut::CloseHelp))>
+                  <span aria-hidden="true">{ "×" }</span>
+                </button>
+              </div>
+              <div class="modal-body">
+                <table class="table table-sm mb-0">
+                  <tbody>
+                    { for shortcuts::BINDINGS.iter().map(|(key, description)| html! {
+                        <tr><td><kbd>{ key }</kbd></td><td>{ description }</td></tr>
+                    }) }
+                  </tbody>
+                </table>
+              </div>
+            </div>
+          </div>
+        </div>
+        <div class="modal-backdrop show"></div>
+        </>
+        }
+    }
+
+    /// The badge class and text for where the connection's at
+    fn connection_badge(&self) -> (&'static str, &'static str) {
+        match self.connection_state {
+            ConnectionState::Disconnected if !self.online => {
+                ("badge badge-dark", "Offline · showing saved history")
+            }
+            ConnectionState::Disconnected if self.ws_fallback => {
+                ("badge badge-warning", "Polling · websocket unavailable")
+            }
+            ConnectionState::Disconnected => ("badge badge-secondary", "Disconnected"),
+            ConnectionState::Connecting => ("badge badge-warning", "Connecting"),
+            ConnectionState::Connected if self.demo.is_some() => {
+                ("badge badge-info", "Demo · simulated trades")
+            }
+            ConnectionState::Connected => ("badge badge-success", "Connected"),
+        }
+    }
+
+    fn view_connection_status(&self) -> Html {
+        let (badge_class, text) = self.connection_badge();
+        // the age ticks over every few seconds, which would be a lot to have read out
+        let age = match self.connection_state {
+            ConnectionState::Connected if self.demo.is_none() => self
+                .last_message_at
+                .map(|at| format!(" · last message {}s ago", (Utc::now() - at).num_seconds())),
+            _ => None,
+        };
+        let stats = if self.connection_state == ConnectionState::Connected {
+            let now = Utc::now();
+            let subscriptions = self
+                .session
+                .as_ref()
+                .map(|session| {
+                    format!(
+                        " · {} of {} subscriptions",
+                        session.subscribed_count(),
+                        session.limit()
+                    )
+                })
+                .unwrap_or_default();
+            html! {
+            <p class="small text-muted">
+                { format!(
+                    "{:.1} messages/s · {:.1} trades/s · {:.1} cards/s rendered, {:.1} kept{}",
+                    self.metrics.messages_per_sec(now),
+                    self.metrics.trades_per_sec(now),
+                    self.metrics.cards_rendered_per_sec(now),
+                    self.metrics.cards_kept_per_sec(now),
+                    subscriptions
+                ) }
+            </p>
+            }
+        } else {
+            html! {}
+        };
+        html! {
+        <>
+        <p>
+            <span class={ badge_class }>
+                <span role="status">{ text }</span>
+                <span aria-hidden="true">{ age.unwrap_or_default() }</span>
+            </span>
+        </p>
+        { stats }
+        { self.view_fallback_banner() }
+        </>
+        }
+    }
+
+    /// Stays at the top of the page while scrolling through the cards
+    fn view_summary_bar(&self) -> Html {
+        if self.state.tracked.is_empty() {
+            return html! {};
+        }
+        let healths: Vec<TickerHealth> = self
+            .state
+            .tracked
+            .iter()
+            .map(|symbol| self.state.ticker_health(symbol))
+            .collect();
+        let up = healths.iter().filter(|h| **h == TickerHealth::Good).count();
+        let down = healths.iter().filter(|h| **h == TickerHealth::Bad).count();
+        let mover = self
+            .state
+            .tracked
+            .iter()
+            .filter_map(|symbol| Some((symbol, self.state.change_percent(symbol)?)))
+            .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()));
+        let mover = match mover {
+            Some((symbol, change)) => {
+                let class = if change > 0.0 {
+                    "text-success"
+                } else if change < 0.0 {
+                    "text-danger"
+                } else {
+                    ""
+                };
+                html! {
+                <span class="mx-2" title="Biggest move across the history kept">
+                    { "Top mover " }<strong>{ &symbol.0 }</strong>{ " " }
+                    <span class=class>{ format!("{:+.2}%", change) }</span>
+                </span>
+                }
+            }
+            None => html! {},
+        };
+        let volume: f64 = self
+            .session_totals
+            .values()
+            .map(|totals| totals.volume)
+            .sum();
+        let (badge_class, text) = self.connection_badge();
+        let theme_class = if self.is_dark() {
+            "bg-dark text-light border-secondary"
+        } else {
+            "bg-light"
+        };
+        html! {
+        <div class=format!("summary-bar d-flex flex-wrap justify-content-center align-items-center small border-bottom py-1 mb-2 {}", theme_class)
+          aria-label="Summary">
+            <span class=badge_class>{ text }</span>
+            <span class="mx-2">
+                <span class="text-success">{ format!("▲ {} up", up) }</span>{ " · " }
+                <span class="text-danger">{ format!("▼ {} down", down) }</span>
+            </span>
+            { mover }
+            <span class="mx-2" title="Traded across all symbols since connecting">
+                { "Volume " }{ self.state.number_format().volume(volume, 0) }
+            </span>
+        </div>
+        }
+    }
+
+    fn view_fallback_banner(&self) -> Html {
+        if !self.ws_fallback || self.session.is_some() {
+            return html! {};
+        }
+        html! {
+        <div class="row">
+          <div class="offset-md-3 col-md-6">
+            <div class="alert alert-warning small" role="alert">
+              { format!(
+                  "Websocket connections keep failing, which some networks do on purpose. Until one works, \
+                   prices are polled every {}s, so there are no volumes or individual trades. \
+                   The websocket is retried every {} minutes.",
+                  QUOTE_POLL_INTERVAL_SECS,
+                  WS_FALLBACK_RETRY_SECS / 60
+              ) }
+              <button type="button" class="btn btn-sm btn-outline-dark ml-2"
+                onclick = self.link.callback( | _ | Msg::WsRetry)>
+                { "Retry now" }
+              </button>
+            </div>
+          </div>
+        </div>
+        }
+    }
+
+    fn view_api_key_input(&self) -> Html {
+        let ws_connected = self.connection_state != ConnectionState::Disconnected;
+        let button_class = if ws_connected {
+            "btn btn-secondary"
+        } else {
+            "btn btn-primary"
+        };
+        let language = self.language();
+        let button_text = if ws_connected {
+            i18n::t(language, Phrase::Disconnect)
+        } else {
+            i18n::t(language, Phrase::Connect)
+        };
+        let button_onclick = if ws_connected {
+            self.link.callback(|_| Msg::ApiKeyDisconnect)
+        } else {
+            self.link.callback(|_| Msg::ApiKeyConnect)
+        };
+
+        let button_icon = if ws_connected {
+            html! {
+            <i class="fas fa-unlink" style="color:red;"></i>
+            }
+        } else {
+            html! {
+            <i class="fas fa-link"></i>
+            }
+        };
+
+        let demo_button = if ws_connected {
+            html! {}
+        } else {
+            html! {
+            <button class="btn btn-outline-secondary"
+             type="button"
+             title=i18n::t(language, Phrase::DemoTitle)
+             onclick=self.link.callback(|_| Msg::StartDemo)>
+                 { i18n::t(language, Phrase::Demo) }
+            </button>
+            }
+        };
+
+        let (input_class, feedback) = match self.key_check {
+            KeyCheck::Checking => (
+                "form-control",
+                html! { <small class="form-text text-muted w-100 text-left">{ "Checking the key…" }</small> },
+            ),
+            KeyCheck::Valid => (
+                "form-control is-valid",
+                html! { <div class="valid-feedback text-left">{ "Key valid" }</div> },
+            ),
+            KeyCheck::Invalid => (
+                "form-control is-invalid",
+                html! { <div class="invalid-feedback text-left">{ "Key invalid: finnhub.io didn't accept it" }</div> },
+            ),
+            KeyCheck::Unchecked | KeyCheck::Inconclusive => ("form-control", html! {}),
+        };
+
+        html! {
+        <div class="input-group mb-3">
+          <input
+            type="text"
+            class=input_class
+            placeholder=i18n::t(language, Phrase::ApiKeyPlaceholder)
+            aria-label="API Key from finnhub.io"
+            aria-describedby="api-key-connect"
+            value =& self.api_key.0
+            oninput = self.link.callback( | e: InputData | Msg::ApiKeyUpdate(ApiKey(e.value)))
+            onkeypress = self.link.callback( |e: KeyboardEvent | {
+                if e.key() == "Enter" { Msg::ApiKeyConnect } else { Msg::Nope }
+            })
+            disabled=ws_connected
+            />
+          <div class="input-group-append">
+            <button class=button_class
+             type="button"
+             id="api-key-connect"
+             aria-label={ button_text }
+             title={ button_text }
+             onclick=button_onclick>
+                 { button_icon }
+            </button>
+            { demo_button }
+          </div>
+          { feedback }
+        </div>
+        }
+    }
+
+    fn view_follower_notice(&self) -> Html {
+        if !self.is_follower() {
+            return html! {};
+        }
+        html! {
+        <div class="alert alert-info small" role="status">
+          <i class="fas fa-clone mr-1"></i>
+          { "The app is open in another tab, which holds the connection. This tab mirrors it, read only" }
+        </div>
+        }
+    }
+
+    /// Asks for the passphrase when the stored API key is encrypted
+    fn view_unlock_api_key(&self) -> Html {
+        if self.locked_api_keys.is_none() {
+            return html! {};
+        }
+        let input_class = if self.passphrase_error.is_some() {
+            "form-control is-invalid"
+        } else {
+            "form-control"
+        };
+        let error = match &self.passphrase_error {
+            Some(e) => html! { <div class="invalid-feedback">{ e }</div> },
+            None => html! {},
+        };
+        html! {
+        <div class="input-group mb-3">
+          <input
+            type="password"
+            class=input_class
+            placeholder="Passphrase for the saved API key"
+            aria-label="Passphrase for the saved API key"
+            autocomplete="current-password"
+            value=&self.passphrase_input
+            disabled=self.unlocking
+            oninput=self.link.callback(|e: InputData| Msg::PassphraseInput(e.value))
+            onkeypress=self.link.callback(|e: KeyboardEvent| {
+                if e.key() == "Enter" { Msg::UnlockApiKey } else { Msg::Nope }
+            })
+            />
+          <div class="input-group-append">
+            <button class="btn btn-primary"
+             type="button"
+             title="Unlock the saved API key"
+             disabled=self.unlocking
+             onclick=self.link.callback(|_| Msg::UnlockApiKey)>
+              <i class="fas fa-unlock"></i>
+            </button>
+            <button class="btn btn-outline-secondary"
+             type="button"
+             title="Forget the saved API key and enter one again"
+             onclick=self.link.callback(|_| Msg::ForgetLockedApiKey)>
+              { "Forget" }
+            </button>
+          </div>
+          { error }
+        </div>
+        }
+    }
+
+    fn view_api_key_profiles(&self) -> Html {
+        let active = self.api_key_profiles.active.as_deref();
+        let profile_picker = if self.api_key_profiles.profiles.is_empty() {
+            html! {}
+        } else {
+            html! {
+            <>
+            <select
+              class="custom-select"
+              aria-label="API key profile"
+              onchange = self.link.callback( | e: ChangeData | match e {
+                  ChangeData::Select(select) => Msg::ApiKeyProfileSelect(select.value()),
+                  _ => Msg::Nope,
+              })>
+              <option value="" disabled=true selected=active.is_none()>{ "Switch profile" }</option>
+              {
+                  for self.api_key_profiles.profiles.keys().map(|name| html! {
+                      <option value=name selected=active == Some(name.as_str())>{ name }</option>
+                  })
+              }
+            </select>
+            <div class="input-group-append">
+              <button class="btn btn-outline-danger" type="button" aria-label="Delete profile" title="Delete profile"
+                disabled=active.is_none()
+                onclick = self.link.callback( | _ | Msg::ApiKeyProfileDelete)>
+                <i class="fas fa-trash"></i>
+              </button>
+            </div>
+            </>
+            }
+        };
+        html! {
+        <div class="input-group input-group-sm mb-3">
+          { profile_picker }
+          <input
+            type="text"
+            class="form-control"
+            placeholder="Save key as…"
+            aria-label="Profile name"
+            value=&self.profile_name_to_save
+            oninput = self.link.callback( | e: InputData | Msg::ApiKeyProfileNameUpdate(e.value))
+            onkeypress = self.link.callback( |e: KeyboardEvent | {
+                if e.key() == "Enter" { Msg::ApiKeyProfileSave } else { Msg::Nope }
+            })
+            />
+          <div class="input-group-append">
+            <button class="btn btn-outline-secondary" type="button" aria-label="Save profile" title="Save profile"
+              onclick = self.link.callback( | _ | Msg::ApiKeyProfileSave)>
+              <i class="fas fa-save"></i>
+            </button>
+          </div>
+        </div>
+        }
+    }
+
+    fn view_ticker_input(&self) -> Html {
+        let (input_class, error) = match &self.symbol_input_error {
+            Some(error) => (
+                "form-control is-invalid",
+                html! { <div class="invalid-feedback" role="alert">{ error }</div> },
+            ),
+            None => ("form-control", html! {}),
+        };
+        html! {
+        <div class="input-group mb-3">
+          <div class="input-group-prepend">
+            { self.view_exchange_picker() }
+          </div>
+          <input
+            type="text"
+            class=input_class
+            id=TICKER_INPUT_ID
+            placeholder=i18n::t(self.language(), Phrase::TickerPlaceholder)
+            aria-label="Ticker symbol"
+            ref=self.symbol_input_ref.clone()
+            aria-describedby="track-symbol"
+            disabled=!self.online || self.is_follower()
+            value =& self.symbol_to_add.0
+            oninput = self.link.callback( | e: InputData | Msg::UpdateSymbolToTrack(Symbol(e.value)))
+            onkeypress = self.link.callback( |e: KeyboardEvent | {
+                if e.key() == "Enter" { Msg::TrackSymbol } else { Msg::Nope }
+            })
+            />
+          <div class="input-group-append">
+            <button class="btn btn-success"
+             type="button"
+             id="track-symbol"
+             disabled=!self.online || self.is_follower()
+             onclick=self.link.callback( | _ | Msg::TrackSymbol )>
+                 <i class="fas fa-plus-circle"></i>
+            </button>
+          </div>
+          { error }
+        </div>
+        }
+    }
+
+    fn view_settings(&self) -> Html {
+        html! {
+        <>
+            { self.view_heartbeat_timeout_setting() }
+            { self.view_stale_after_setting() }
+            { self.view_moving_average_setting("sma-period", "SMA period (trades)", self.state.settings.sma_period, Msg::SmaPeriodUpdate) }
+            { self.view_moving_average_setting("ema-period", "EMA period (trades)", self.state.settings.ema_period, Msg::EmaPeriodUpdate) }
+            { self.view_history_retention_setting() }
+            { self.view_trade_columns_setting() }
+            {
+                self.view_decimal_input(
+                    "fas fa-cubes",
+                    "Highlight block trades with a notional (price × volume) of at least",
+                    self.state.settings.block_trade_notional,
+                    Msg::BlockTradeNotionalUpdate,
+                )
+            }
+            { self.view_base_currency_setting() }
+            { self.view_show_bid_ask_setting() }
+            { self.view_api_key_storage_setting() }
+            { self.view_api_key_passphrase_setting() }
+            { self.view_auto_connect_setting() }
+            { self.view_pause_when_hidden_setting() }
+            { self.view_subscription_limit_setting() }
+            { self.view_dashboard_columns_setting() }
+            { self.view_spike_threshold_setting() }
+            { self.view_spike_alerts_setting() }
+            { self.view_persistence_setting() }
+            { self.view_sound_muted_setting() }
+            { self.view_do_not_disturb_setting() }
+            {
+                self.view_volume_input(
+                    "fas fa-filter",
+                    "Skip trades with volume below (all symbols)",
+                    self.state.settings.min_volume,
+                    Msg::GlobalMinVolumeUpdate,
+                )
+            }
+            { self.view_theme_setting() }
+            { self.view_time_zone_setting() }
+            { self.view_language_setting() }
+            { self.view_number_format_setting() }
+            { self.view_ws_base_url_setting() }
+            { self.view_ws_auth_setting() }
+            { self.view_debug_logging_setting() }
+            { self.view_show_inspector_setting() }
+        </>
+        }
+    }
+
+    fn view_heartbeat_timeout_setting(&self) -> Html {
+        html! {
+        <div class="input-group input-group-sm mb-3">
+          <div class="input-group-prepend">
+            <label class="input-group-text" for="heartbeat-timeout">{ "Reconnect after silence (s)" }</label>
+          </div>
+          <input
+            type="number"
+            min=MIN_HEARTBEAT_TIMEOUT_SECS.to_string()
+            class="form-control"
+            id="heartbeat-timeout"
+            value = self.state.settings.heartbeat_timeout_secs.to_string()
+            onchange = self.link.callback( | e: ChangeData | match e {
+                ChangeData::Value(v) => match v.parse() {
+                    Ok(secs) => Msg::HeartbeatTimeoutUpdate(secs),
+                    Err(_) => Msg::Nope,
+                },
+                _ => Msg::Nope,
+            })
+            />
+        </div>
+        }
+    }
+
+    fn view_stale_after_setting(&self) -> Html {
+        html! {
+        <div class="input-group input-group-sm mb-3">
+          <div class="input-group-prepend">
+            <label class="input-group-text" for="stale-after">{ "Flag symbols without trades for (min)" }</label>
+          </div>
+          <input
+            type="number"
+            min="1"
+            class="form-control"
+            id="stale-after"
+            value = self.state.settings.stale_after_mins.to_string()
+            onchange = self.link.callback( | e: ChangeData | match e {
+                ChangeData::Value(v) => match v.parse() {
+                    Ok(mins) if mins > 0 => Msg::StaleAfterUpdate(mins),
+                    _ => Msg::Nope,
+                },
+                _ => Msg::Nope,
+            })
+            />
+        </div>
+        }
+    }
+
+    /// Clearing the period hides the column
+    fn view_moving_average_setting(
+        &self,
+        id: &str,
+        label: &str,
+        current: Option<u32>,
+        to_msg: fn(Option<u32>) -> Msg,
+    ) -> Html {
+        html! {
+        <div class="input-group input-group-sm mb-3">
+          <div class="input-group-prepend">
+            <label class="input-group-text" for=id>{ label }</label>
+          </div>
+          <input
+            type="number"
+            min="1"
+            max=TickerHistory::max_len(self.state.settings.history_retention_mins).to_string()
+            class="form-control"
+            id=id
+            placeholder="Off"
+            value = current.map(|p| p.to_string()).unwrap_or_default()
+            onchange = self.link.callback( move | e: ChangeData | match e {
+                ChangeData::Value(v) if v.trim().is_empty() => to_msg(None),
+                ChangeData::Value(v) => match v.trim().parse() {
+                    Ok(period) if period > 0 => to_msg(Some(period)),
+                    _ => Msg::Nope,
+                },
+                _ => Msg::Nope,
+            })
+            />
+        </div>
+        }
+    }
+
+    fn view_pause_when_hidden_setting(&self) -> Html {
+        html! {
+        <div class="input-group input-group-sm mb-3">
+          <div class="input-group-prepend">
+            <label class="input-group-text" for="pause-when-hidden">{ "Unsubscribe while hidden for (min)" }</label>
+          </div>
+          <input
+            type="number"
+            min="1"
+            class="form-control"
+            id="pause-when-hidden"
+            placeholder="Never"
+            value = self.state.settings.pause_when_hidden_mins.map(|m| m.to_string()).unwrap_or_default()
+            onchange = self.link.callback( | e: ChangeData | match e {
+                ChangeData::Value(v) if v.trim().is_empty() => Msg::PauseWhenHiddenUpdate(None),
+                ChangeData::Value(v) => match v.trim().parse() {
+                    Ok(mins) if mins > 0 => Msg::PauseWhenHiddenUpdate(Some(mins)),
+                    _ => Msg::Nope,
+                },
+                _ => Msg::Nope,
+            })
+            />
+        </div>
+        }
+    }
+
+    fn view_dashboard_columns_setting(&self) -> Html {
+        html! {
+        <div class="input-group input-group-sm mb-3">
+          <div class="input-group-prepend">
+            <label class="input-group-text" for="dashboard-columns">{ "Dashboard columns" }</label>
+          </div>
+          <input
+            type="number"
+            min="1"
+            max="6"
+            class="form-control"
+            id="dashboard-columns"
+            value = self.state.settings.dashboard_columns.to_string()
+            onchange = self.link.callback( | e: ChangeData | match e {
+                ChangeData::Value(v) => match v.trim().parse() {
+                    Ok(columns) if (1..=6).contains(&columns) => Msg::DashboardColumnsUpdate(columns),
+                    _ => Msg::Nope,
+                },
+                _ => Msg::Nope,
+            })
+            />
+        </div>
+        }
+    }
+
+    fn view_subscription_limit_setting(&self) -> Html {
+        html! {
+        <div class="input-group input-group-sm mb-3">
+          <div class="input-group-prepend">
+            <label class="input-group-text" for="subscription-limit">{ "Subscribe to at most (symbols)" }</label>
+          </div>
+          <input
+            type="number"
+            min="1"
+            class="form-control"
+            id="subscription-limit"
+            title="Finnhub caps how many symbols each plan can subscribe to at once"
+            value = self.state.settings.subscription_limit.to_string()
+            onchange = self.link.callback( | e: ChangeData | match e {
+                ChangeData::Value(v) => match v.trim().parse() {
+                    Ok(limit) if limit > 0 => Msg::SubscriptionLimitUpdate(limit),
+                    _ => Msg::Nope,
+                },
+                _ => Msg::Nope,
+            })
+            />
+        </div>
+        }
+    }
+
+    fn view_spike_threshold_setting(&self) -> Html {
+        let threshold = self.state.settings.spike_threshold;
+        let unit = threshold.unwrap_or_default();
+        html! {
+        <div class="input-group input-group-sm mb-3">
+          <div class="input-group-prepend">
+            <label class="input-group-text" for="spike-threshold">{ "Flag price spikes beyond" }</label>
+          </div>
+          <input
+            type="number"
+            min="0"
+            step="any"
+            class="form-control"
+            id="spike-threshold"
+            placeholder="Off"
+            value = threshold.map(|t| t.value().to_string()).unwrap_or_default()
+            onchange = self.link.callback( move | e: ChangeData | match e {
+                ChangeData::Value(v) if v.trim().is_empty() => Msg::SpikeThresholdUpdate(None),
+                ChangeData::Value(v) => match v.trim().parse::<f64>() {
+                    Ok(value) if value > 0.0 => Msg::SpikeThresholdUpdate(Some(unit.with_value(value))),
+                    _ => Msg::Nope,
+                },
+                _ => Msg::Nope,
+            })
+            />
+          <div class="input-group-append">
+            <select
+              class="custom-select"
+              aria-label="Spike threshold unit"
+              onchange = self.link.callback( move | e: ChangeData | match e {
+                  ChangeData::Select(select) => match unit.with_unit(&select.value()) {
+                      // only takes effect once there's a number
+                      Some(unit) => Msg::SpikeThresholdUpdate(threshold.map(|_| unit)),
+                      None => Msg::Nope,
+                  },
+                  _ => Msg::Nope,
+              })>
+              <option value="σ" selected=unit.unit() == "σ">{ "std devs" }</option>
+              <option value="%" selected=unit.unit() == "%">{ "% from the mean" }</option>
+            </select>
+          </div>
+        </div>
+        }
+    }
+
+    fn view_spike_alerts_setting(&self) -> Html {
+        let spike_alerts = self.state.settings.spike_alerts;
+        html! {
+        <div class="custom-control custom-switch mb-3 text-left">
+          <input
+            type="checkbox"
+            class="custom-control-input"
+            id="spike-alerts"
+            checked=spike_alerts
+            onclick = self.link.callback( move | _ | Msg::SpikeAlertsUpdate(!spike_alerts))
+            />
+          <label class="custom-control-label" for="spike-alerts">{ "Alert on price spikes too" }</label>
+        </div>
+        }
+    }
+
+    fn view_history_retention_setting(&self) -> Html {
+        html! {
+        <div class="input-group input-group-sm mb-3">
+          <div class="input-group-prepend">
+            <label class="input-group-text" for="history-retention">{ "Keep trades from the last (min)" }</label>
+          </div>
+          <input
+            type="number"
+            min="1"
+            class="form-control"
+            id="history-retention"
+            placeholder=format!("Off (newest {} trades)", TickerHistory::MAX_HISTORY)
+            value = self.state.settings.history_retention_mins.map(|m| m.to_string()).unwrap_or_default()
+            onchange = self.link.callback( | e: ChangeData | match e {
+                ChangeData::Value(v) if v.trim().is_empty() => Msg::HistoryRetentionUpdate(None),
+                ChangeData::Value(v) => match v.trim().parse() {
+                    Ok(mins) if mins > 0 => Msg::HistoryRetentionUpdate(Some(mins)),
+                    _ => Msg::Nope,
+                },
+                _ => Msg::Nope,
+            })
+            />
+        </div>
+        }
+    }
+
+    /// Shown columns first, in order, then the hidden ones
+    fn view_trade_columns_setting(&self) -> Html {
+        let shown = &self.state.settings.trade_columns;
+        let hidden = TradeColumn::ALL.iter().filter(|c| !shown.contains(c));
+        let row = |column: TradeColumn| {
+            let position = shown.iter().position(|c| *c == column);
+            let toggled = {
+                let mut columns = shown.clone();
+                match position {
+                    Some(idx) => {
+                        columns.remove(idx);
+                    }
+                    None => columns.push(column),
+                }
+                columns
+            };
+            let moved = |offset: isize| {
+                let idx = position?;
+                let target = idx
+                    .checked_add_signed(offset)
+                    .filter(|t| *t < shown.len())?;
+                let mut columns = shown.clone();
+                columns.swap(idx, target);
+                Some(columns)
+            };
+            let move_button = |columns: Option<Vec<TradeColumn>>, icon_class: &str, label: &str| {
+                html! {
+                <button type="button" class="btn btn-sm btn-outline-secondary ml-1" aria-label=label title=label
+                  disabled=columns.is_none()
+                  onclick = self.link.callback( move | _ | match &columns {
+                      Some(columns) => Msg::TradeColumnsUpdate(columns.clone()),
+                      None => Msg::Nope,
+                  })>
+                  <i class=icon_class></i>
+                </button>
+                }
+            };
+            let id = format!("trade-column-{:?}", column).to_lowercase();
+            html! {
+            <li class="list-group-item d-flex justify-content-between align-items-center py-1">
+              <div class="custom-control custom-checkbox">
+                <input
+                  type="checkbox"
+                  class="custom-control-input"
+                  id=&id
+                  checked=position.is_some()
+                  disabled=position.is_some() && shown.len() == 1
+                  onclick = self.link.callback( move | _ | Msg::TradeColumnsUpdate(toggled.clone()))
+                  />
+                <label class="custom-control-label" for=&id>{ column.label() }</label>
+              </div>
+              <div>
+                { move_button(moved(-1), "fas fa-arrow-up", "Move left") }
+                { move_button(moved(1), "fas fa-arrow-down", "Move right") }
+              </div>
+            </li>
+            }
+        };
+        html! {
+        <div class="mb-3 text-left">
+          <label class="small mb-1">{ "Trade table columns" }</label>
+          <ul class="list-group list-group-flush">
+            { for shown.iter().chain(hidden).map(|c| row(*c)) }
+          </ul>
+        </div>
+        }
+    }
+
+    fn view_debug_logging_setting(&self) -> Html {
+        let debug_logging = self.state.settings.debug_logging;
+        html! {
+        <div class="custom-control custom-switch mb-3 text-left">
+          <input
+            type="checkbox"
+            class="custom-control-input"
+            id="debug-logging"
+            checked=debug_logging
+            onclick = self.link.callback( move | _ | Msg::DebugLoggingUpdate(!debug_logging))
+            />
+          <label class="custom-control-label" for="debug-logging">{ "Log every message to the browser console" }</label>
+        </div>
+        }
+    }
+
+    fn view_show_inspector_setting(&self) -> Html {
+        let show_inspector = self.state.settings.show_inspector;
+        html! {
+        <div class="custom-control custom-switch mb-3 text-left">
+          <input
+            type="checkbox"
+            class="custom-control-input"
+            id="show-inspector"
+            checked=show_inspector
+            onclick = self.link.callback( move | _ | Msg::ShowInspectorUpdate(!show_inspector))
+            />
+          <label class="custom-control-label" for="show-inspector">{ "Show raw messages and connection events below" }</label>
+        </div>
+        }
+    }
+
+    fn view_ws_base_url_setting(&self) -> Html {
+        html! {
+        <div class="input-group input-group-sm mb-3">
+          <div class="input-group-prepend">
+            <label class="input-group-text" for="ws-base-url">{ "Websocket URL" }</label>
+          </div>
+          <input
+            type="url"
+            class="form-control"
+            id="ws-base-url"
+            placeholder=ws::DEFAULT_BASE_URL
+            title="For proxies or a local mock server; the API key goes where the authentication setting says"
+            value = self.state.settings.ws_base_url.clone().unwrap_or_default()
+            onchange = self.link.callback( | e: ChangeData | match e {
+                ChangeData::Value(v) if v.trim().is_empty() => Msg::WsBaseUrlUpdate(None),
+                ChangeData::Value(v) => Msg::WsBaseUrlUpdate(Some(v.trim().to_string())),
+                _ => Msg::Nope,
+            })
+            />
+        </div>
+        }
+    }
+
+    fn view_ws_auth_setting(&self) -> Html {
+        let ws_auth = self.state.settings.ws_auth;
+        let option = |auth: WsAuth| {
+            html! {
+            <option value=auth.label() selected=auth == ws_auth>{ auth.label() }</option>
+            }
+        };
+        html! {
+        <div class="input-group input-group-sm mb-3">
+          <div class="input-group-prepend">
+            <label class="input-group-text" for="ws-auth">{ "Send API key as" }</label>
+          </div>
+          <select
+            class="custom-select"
+            id="ws-auth"
+            title="finnhub wants a query parameter; some proxies want an auth message or the subprotocol header instead"
+            onchange = self.link.callback( | e: ChangeData | match e {
+                ChangeData::Select(select) => WsAuth::ALL
+                    .iter()
+                    .find(|auth| auth.label() == select.value())
+                    .map_or(Msg::Nope, |auth| Msg::WsAuthUpdate(*auth)),
+                _ => Msg::Nope,
+            })>
+            { for WsAuth::ALL.iter().copied().map(option) }
+          </select>
+        </div>
+        }
+    }
+
+    fn view_show_bid_ask_setting(&self) -> Html {
+        let show_bid_ask = self.state.settings.show_bid_ask;
+        html! {
+        <div class="custom-control custom-switch mb-3 text-left">
+          <input
+            type="checkbox"
+            class="custom-control-input"
+            id="show-bid-ask"
+            checked=show_bid_ask
+            onclick = self.link.callback( move | _ | Msg::ShowBidAskUpdate(!show_bid_ask))
+            />
+          <label class="custom-control-label" for="show-bid-ask">
+            { format!("Show bid/ask for stocks, refreshed every {}s", BID_ASK_REFRESH_INTERVAL_SECS) }
+          </label>
+          { self.view_not_entitled_note(PlanFeature::BidAsk) }
+        </div>
+        }
+    }
+
+    /// Next to a setting whose data the key's plan turned out not to cover
+    fn view_not_entitled_note(&self, feature: PlanFeature) -> Html {
+        if self.is_entitled(feature) {
+            return html! {};
+        }
+        html! {
+        <small class="form-text text-muted">{ "Requires a paid finnhub plan" }</small>
+        }
+    }
+
+    fn view_auto_connect_setting(&self) -> Html {
+        let auto_connect = self.state.settings.auto_connect;
+        html! {
+        <div class="custom-control custom-switch mb-3 text-left">
+          <input
+            type="checkbox"
+            class="custom-control-input"
+            id="auto-connect"
+            checked=auto_connect
+            onclick = self.link.callback( move | _ | Msg::AutoConnectUpdate(!auto_connect))
+            />
+          <label class="custom-control-label" for="auto-connect">
+            { "Connect automatically when the page loads, if there's an API key and something to track" }
+          </label>
+        </div>
+        }
+    }
+
+    fn view_base_currency_setting(&self) -> Html {
+        html! {
+        <>
+        <div class="input-group input-group-sm mb-3">
+          <div class="input-group-prepend">
+            <label class="input-group-text" for="base-currency">{ "Also show prices in" }</label>
+          </div>
+          <input
+            type="text"
+            maxlength="3"
+            class="form-control"
+            id="base-currency"
+            placeholder="Currency code, e.g. USD"
+            value = self.state.settings.base_currency.clone().unwrap_or_default()
+            onchange = self.link.callback( | e: ChangeData | match e {
+                ChangeData::Value(v) if v.trim().is_empty() => Msg::BaseCurrencyUpdate(None),
+                ChangeData::Value(v) if v.trim().len() == 3 && v.trim().chars().all(|c| c.is_ascii_alphabetic()) => {
+                    Msg::BaseCurrencyUpdate(Some(v.trim().to_ascii_uppercase()))
+                }
+                _ => Msg::Nope,
+            })
+            />
+        </div>
+        { self.view_not_entitled_note(PlanFeature::FxRates) }
+        </>
+        }
+    }
+
+    fn view_api_key_storage_setting(&self) -> Html {
+        html! {
+        <div class="input-group input-group-sm mb-3">
+          <div class="input-group-prepend">
+            <label class="input-group-text" for="api-key-storage">{ "Remember API key in" }</label>
+          </div>
+          <select
+            class="custom-select"
+            id="api-key-storage"
+            onchange = self.link.callback( | e: ChangeData | match e {
+                ChangeData::Select(select) => match select.value().as_str() {
+                    "local" => Msg::ApiKeyStorageUpdate(ApiKeyStorage::Local),
+                    "session" => Msg::ApiKeyStorageUpdate(ApiKeyStorage::Session),
+                    "memory" => Msg::ApiKeyStorageUpdate(ApiKeyStorage::Memory),
+                    _ => Msg::Nope,
+                },
+                _ => Msg::Nope,
+            })>
+            <option value="local" selected=self.state.settings.api_key_storage == ApiKeyStorage::Local>{ "LocalStorage" }</option>
+            <option value="session" selected=self.state.settings.api_key_storage == ApiKeyStorage::Session>{ "SessionStorage (until the tab closes)" }</option>
+            <option value="memory" selected=self.state.settings.api_key_storage == ApiKeyStorage::Memory>{ "Nowhere (re-enter every visit)" }</option>
+          </select>
+        </div>
+        }
+    }
+
+    fn view_api_key_passphrase_setting(&self) -> Html {
+        if self.locked_api_keys.is_some() {
+            return html! {};
+        }
+        if self.passphrase.is_some() {
+            return html! {
+            <div class="mb-3 text-left">
+              <span class="text-muted small mr-2">
+                <i class="fas fa-lock mr-1"></i>{ "The API key is stored encrypted" }
+              </span>
+              <button class="btn btn-sm btn-outline-secondary"
+               type="button"
+               onclick=self.link.callback(|_| Msg::RemovePassphrase)>
+                { "Remove passphrase" }
+              </button>
+            </div>
+            };
+        }
+        let storing = self.state.settings.api_key_storage != ApiKeyStorage::Memory;
+        html! {
+        <div class="input-group input-group-sm mb-3">
+          <div class="input-group-prepend">
+            <label class="input-group-text" for="api-key-passphrase">{ "Encrypt API key with" }</label>
+          </div>
+          <input
+            type="password"
+            class="form-control"
+            id="api-key-passphrase"
+            placeholder="a passphrase, asked for on every visit"
+            autocomplete="new-password"
+            value=&self.passphrase_input
+            disabled=!storing
+            oninput=self.link.callback(|e: InputData| Msg::PassphraseInput(e.value))
+            onkeypress=self.link.callback(|e: KeyboardEvent| {
+                if e.key() == "Enter" { Msg::SetPassphrase } else { Msg::Nope }
+            })
+            />
+          <div class="input-group-append">
+            <button class="btn btn-outline-secondary"
+             type="button"
+             disabled=!storing || self.passphrase_input.is_empty()
+             onclick=self.link.callback(|_| Msg::SetPassphrase)>
+              { "Encrypt" }
+            </button>
+          </div>
+        </div>
+        }
+    }
+
+    fn view_persistence_setting(&self) -> Html {
+        let persistence = self.state.settings.persistence;
+        html! {
+        <div class="input-group input-group-sm mb-3">
+          <div class="input-group-prepend">
+            <label class="input-group-text" for="persistence">{ "Remember between visits" }</label>
+          </div>
+          <select
+            class="custom-select"
+            id="persistence"
+            onchange = self.link.callback( | e: ChangeData | match e {
+                ChangeData::Select(select) => match select.value().as_str() {
+                    "watchlist" => Msg::PersistenceUpdate(PersistencePolicy::Watchlist),
+                    "settings" => Msg::PersistenceUpdate(PersistencePolicy::WatchlistAndSettings),
+                    "everything" => Msg::PersistenceUpdate(PersistencePolicy::Everything),
+                    _ => Msg::Nope,
+                },
+                _ => Msg::Nope,
+            })>
+            <option value="watchlist" selected=persistence == PersistencePolicy::Watchlist>{ "Watchlist only" }</option>
+            <option value="settings" selected=persistence == PersistencePolicy::WatchlistAndSettings>{ "Watchlist and settings" }</option>
+            <option value="everything" selected=persistence == PersistencePolicy::Everything>{ "Everything, including trade history" }</option>
+          </select>
+        </div>
+        }
+    }
+
+    fn view_language_setting(&self) -> Html {
+        let chosen = self.state.settings.language.filter(|l| l.is_offered());
+        let language = self.language();
+        html! {
+        <div class="input-group input-group-sm mb-3">
+          <div class="input-group-prepend">
+            <label class="input-group-text" for="language">{ i18n::t(language, Phrase::Language) }</label>
+          </div>
+          <select
+            class="custom-select"
+            id="language"
+            onchange = self.link.callback( | e: ChangeData | match e {
+                ChangeData::Select(select) => Msg::LanguageUpdate(
+                    Language::OFFERED.iter().copied().find(|l| l.code() == select.value())
+                ),
+                _ => Msg::Nope,
+            })>
+            <option value="" selected=chosen.is_none()>{ i18n::t(language, Phrase::BrowserLanguage) }</option>
+            { for Language::OFFERED.iter().map(|l| html! {
+                <option value=l.code() selected=chosen == Some(*l)>{ l.name() }</option>
+            }) }
+          </select>
+        </div>
+        }
+    }
+
+    fn view_number_format_setting(&self) -> Html {
+        let number_style = self.state.settings.number_style;
+        let abbreviate_volumes = self.state.settings.abbreviate_volumes;
+        let tick_size = self.state.settings.price_decimals.tick_size;
+        let decimals_input = |kind: SymbolKind| {
+            let id = format!("price-decimals-{}", kind.label().to_lowercase());
+            html! {
+            <div class="input-group input-group-sm mb-3">
+              <div class="input-group-prepend">
+                <label class="input-group-text" for=id.clone()>{ format!("{} price decimals", kind.label()) }</label>
+              </div>
+              <input
+                type="number"
+                min="0"
+                max="10"
+                class="form-control"
+                id=id
+                list="price-decimals-options"
+                placeholder="Automatic"
+                value = self.state.settings.price_decimals.get(kind).map(|d| d.to_string()).unwrap_or_default()
+                onchange = self.link.callback( move | e: ChangeData | match e {
+                    ChangeData::Value(v) if v.trim().is_empty() => Msg::PriceDecimalsUpdate(kind, None),
+                    ChangeData::Value(v) => match v.trim().parse() {
+                        Ok(decimals) if decimals <= 10 => Msg::PriceDecimalsUpdate(kind, Some(decimals)),
+                        _ => Msg::Nope,
+                    },
+                    _ => Msg::Nope,
+                })
+                />
+            </div>
+            }
+        };
+        html! {
+        <>
+        <div class="input-group input-group-sm mb-3">
+          <div class="input-group-prepend">
+            <label class="input-group-text" for="number-style">{ "Numbers" }</label>
+          </div>
+          <select
+            class="custom-select"
+            id="number-style"
+            onchange = self.link.callback( | e: ChangeData | match e {
+                ChangeData::Select(select) => select
+                    .value()
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|idx| NumberStyle::ALL.get(idx))
+                    .map_or(Msg::Nope, |style| Msg::NumberStyleUpdate(*style)),
+                _ => Msg::Nope,
+            })>
+            { for NumberStyle::ALL.iter().enumerate().map(|(idx, style)| html! {
+                <option value=idx.to_string() selected=*style == number_style>{ style.label() }</option>
+            }) }
+          </select>
+        </div>
+        <datalist id="price-decimals-options">
+          <option value="2" />
+          <option value="4" />
+          <option value="6" />
+        </datalist>
+        { for SymbolKind::ALL.iter().map(|kind| decimals_input(*kind)) }
+        <div class="custom-control custom-switch mb-3 text-left">
+          <input
+            type="checkbox"
+            class="custom-control-input"
+            id="tick-size-decimals"
+            checked=tick_size
+            onclick = self.link.callback( move | _ | Msg::TickSizeDecimalsUpdate(!tick_size))
+            />
+          <label class="custom-control-label" for="tick-size-decimals">{ "Show automatic decimals to each symbol's tick size" }</label>
+        </div>
+        <div class="custom-control custom-switch mb-3 text-left">
+          <input
+            type="checkbox"
+            class="custom-control-input"
+            id="abbreviate-volumes"
+            checked=abbreviate_volumes
+            onclick = self.link.callback( move | _ | Msg::AbbreviateVolumesUpdate(!abbreviate_volumes))
+            />
+          <label class="custom-control-label" for="abbreviate-volumes">{ "Abbreviate large volumes, e.g. 1.2M" }</label>
+        </div>
+        </>
+        }
+    }
+
+    fn view_theme_setting(&self) -> Html {
+        let theme = self.state.settings.theme;
+        html! {
+        <div class="input-group input-group-sm mb-3">
+          <div class="input-group-prepend">
+            <label class="input-group-text" for="theme">{ "Theme" }</label>
+          </div>
+          <select
+            class="custom-select"
+            id="theme"
+            onchange = self.link.callback( | e: ChangeData | match e {
+                ChangeData::Select(select) => match select.value().as_str() {
+                    "light" => Msg::ThemeUpdate(Theme::Light),
+                    "dark" => Msg::ThemeUpdate(Theme::Dark),
+                    "auto" => Msg::ThemeUpdate(Theme::Auto),
+                    _ => Msg::Nope,
+                },
+                _ => Msg::Nope,
+            })>
+            <option value="auto" selected=theme == Theme::Auto>{ "Same as system" }</option>
+            <option value="light" selected=theme == Theme::Light>{ "Light" }</option>
+            <option value="dark" selected=theme == Theme::Dark>{ "Dark" }</option>
+          </select>
+        </div>
+        }
+    }
+
+    fn view_time_zone_setting(&self) -> Html {
+        let time_zone = &self.state.settings.time_zone;
+        let named_zone = match time_zone {
+            TimeZoneSetting::Named(zone) => Some(zone.clone()),
+            _ => None,
+        };
+        let zone_input = if let Some(zone) = &named_zone {
+            html! {
+            <input
+              type="text"
+              class="form-control"
+              placeholder="e.g. America/New_York"
+              value=zone
+              onchange = self.link.callback( | e: ChangeData | match e {
+                  ChangeData::Value(v) if !v.trim().is_empty() => {
+                      Msg::TimeZoneUpdate(TimeZoneSetting::Named(v.trim().to_string()))
+                  }
+                  _ => Msg::Nope,
+              })
+              />
+            }
+        } else {
+            html! {}
+        };
+        html! {
+        <div class="input-group input-group-sm mb-3">
+          <div class="input-group-prepend">
+            <label class="input-group-text" for="time-zone">{ "Trade times" }</label>
+          </div>
+          <select
+            class="custom-select"
+            id="time-zone"
+            onchange = self.link.callback( | e: ChangeData | match e {
+                ChangeData::Select(select) => match select.value().as_str() {
+                    "local" => Msg::TimeZoneUpdate(TimeZoneSetting::Local),
+                    "utc" => Msg::TimeZoneUpdate(TimeZoneSetting::Utc),
+                    "relative" => Msg::TimeZoneUpdate(TimeZoneSetting::Relative),
+                    // start from wherever the browser is, to be edited from there
+                    "named" => Msg::TimeZoneUpdate(TimeZoneSetting::Named(
+                        time_format::local_zone_name().unwrap_or_else(|| "UTC".to_string()),
+                    )),
+                    _ => Msg::Nope,
+                },
+                _ => Msg::Nope,
+            })>
+            <option value="local" selected=time_zone == &TimeZoneSetting::Local>{ "Local time" }</option>
+            <option value="utc" selected=time_zone == &TimeZoneSetting::Utc>{ "UTC" }</option>
+            <option value="named" selected=named_zone.is_some()>{ "Another time zone" }</option>
+            <option value="relative" selected=time_zone == &TimeZoneSetting::Relative>{ "Relative (x seconds ago)" }</option>
+          </select>
+          { zone_input }
+        </div>
+        }
+    }
+
+    fn view_sound_muted_setting(&self) -> Html {
+        html! {
+        <div class="custom-control custom-switch mb-3 text-left">
+          <input
+            type="checkbox"
+            class="custom-control-input"
+            id="sound-muted"
+            checked=self.state.settings.sound_muted
+            onclick = self.link.callback( | _ | Msg::SoundMutedToggle)
+            />
+          <label class="custom-control-label" for="sound-muted">{ "Mute volume alerts" }</label>
+        </div>
+        }
+    }
+
+    fn view_do_not_disturb_setting(&self) -> Html {
+        let do_not_disturb = self.state.settings.do_not_disturb;
+        let hours = match do_not_disturb {
+            DoNotDisturb::QuietHours { from, to } => html! {
+            <div class="input-group input-group-sm mb-3">
+              <div class="input-group-prepend">
+                <label class="input-group-text" for="quiet-from">{ "Quiet from (hour)" }</label>
+              </div>
+              { self.view_quiet_hour_input("quiet-from", from, move |from| DoNotDisturb::QuietHours { from, to }) }
+              <div class="input-group-prepend input-group-append">
+                <label class="input-group-text" for="quiet-to">{ "to" }</label>
+              </div>
+              { self.view_quiet_hour_input("quiet-to", to, move |to| DoNotDisturb::QuietHours { from, to }) }
+            </div>
+            },
+            _ => html! {},
+        };
+        html! {
+        <>
+        <div class="input-group input-group-sm mb-3">
+          <div class="input-group-prepend">
+            <label class="input-group-text" for="do-not-disturb">{ "Do not disturb" }</label>
+          </div>
+          <select
+            class="custom-select"
+            id="do-not-disturb"
+            title="Alerts still go in the alert log, without a notification or sound"
+            onchange = self.link.callback( | e: ChangeData | match e {
+                ChangeData::Select(select) => match select.value().as_str() {
+                    "off" => Msg::DoNotDisturbUpdate(DoNotDisturb::Off),
+                    "market" => Msg::DoNotDisturbUpdate(DoNotDisturb::OutsideMarketHours),
+                    "hours" => Msg::DoNotDisturbUpdate(DoNotDisturb::QuietHours { from: 22, to: 7 }),
+                    _ => Msg::Nope,
+                },
+                _ => Msg::Nope,
+            })>
+            <option value="off" selected=do_not_disturb == DoNotDisturb::Off>{ "Off" }</option>
+            <option value="market" selected=do_not_disturb == DoNotDisturb::OutsideMarketHours>{ "Outside market hours" }</option>
+            <option value="hours" selected=matches!(do_not_disturb, DoNotDisturb::QuietHours { .. })>{ "During quiet hours" }</option>
+          </select>
+        </div>
+        { hours }
+        </>
+        }
+    }
 
-impl Component for Model {
-    type Message = Msg;
-    type Properties = ();
+    fn view_quiet_hour_input(
+        &self,
+        id: &'static str,
+        hour: u32,
+        to_schedule: impl Fn(u32) -> DoNotDisturb + 'static,
+    ) -> Html {
+        html! {
+        <input
+          type="number"
+          min="0"
+          max="23"
+          class="form-control"
+          id=id
+          value=hour.to_string()
+          onchange = self.link.callback(move | e: ChangeData | match e {
+              ChangeData::Value(v) => match v.parse::<u32>() {
+                  Ok(hour) if hour < 24 => Msg::DoNotDisturbUpdate(to_schedule(hour)),
+                  _ => Msg::Nope,
+              },
+              _ => Msg::Nope,
+          })
+          />
+        }
+    }
 
-    fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
-        let mut console_service = ConsoleService::new();
-        let maybe_storage_service = StorageService::new(Area::Local).ok();
-        if maybe_storage_service.is_none() {
-            console_service.warn("Local storage is disabled, nothing will be saved.");
+    fn view_profile(&self, symbol: &Symbol) -> Html {
+        if !self.expanded_profiles.contains(symbol) {
+            return html! {};
         }
-        let state = maybe_storage_service
-            .as_ref()
-            .and_then(|s| {
-                if let Json(Ok(restored)) = s.restore(STATE_STORAGE_KEY) {
-                    Some(restored)
-                } else {
-                    None
+        match self.state.metadata.profile(symbol) {
+            None => html! {
+                <div class="text-left mb-2">
+                    <small class="text-muted">{ "Loading company profile..." }</small>
+                </div>
+            },
+            Some(profile) if profile.name.is_none() => html! {
+                <div class="text-left mb-2">
+                    <small class="text-muted">{ "No company profile available" }</small>
+                </div>
+            },
+            Some(profile) => {
+                let logo = match &profile.logo {
+                    Some(logo) if !logo.is_empty() => html! {
+                        <img src=logo alt="logo" class="mr-3" style="max-height: 48px; max-width: 48px;"/>
+                    },
+                    _ => html! {},
+                };
+                let name = match &profile.weburl {
+                    Some(url) if !url.is_empty() => html! {
+                        <a href=url.as_str() target="_blank" rel="noopener noreferrer">{ profile.name.clone().unwrap_or_default() }</a>
+                    },
+                    _ => html! { { profile.name.clone().unwrap_or_default() } },
+                };
+                html! {
+                <div class="media text-left mb-2">
+                    { logo }
+                    <div class="media-body">
+                        <h6 class="mt-0 mb-1">{ name }</h6>
+                        <small class="d-block">{ profile.industry.clone().unwrap_or_default() }</small>
+                        <small class="d-block text-muted">
+                            { profile.formatted_market_capitalization().map(|m| format!("Market cap {}", m)).unwrap_or_default() }
+                        </small>
+                    </div>
+                </div>
                 }
-            })
-            .unwrap_or_else(|| State {
-                api_key: ApiKey("".into()),
-                tracked: vec![],
-                history: TickerHistory::new(),
-            });
-
-        Model {
-            symbol_to_add: Symbol("".into()),
-            state,
-            storage_service: maybe_storage_service,
-            websocket_service: WebSocketService::new(),
-            dialog_service: DialogService::new(),
-            console_service,
-            link,
-            websocket_task: None,
+            }
         }
     }
 
-    fn update(&mut self, msg: Self::Message) -> ShouldRender {
-        match msg {
-            Msg::ApiKeyUpdate(key) => {
-                self.state.api_key = key;
-                self.persist_state();
-            }
-            Msg::ApiKeyConnect => {
-                return self.connect_to_api();
-            }
-            Msg::ApiKeyDisconnect => {
-                self.websocket_task = None;
+    fn view_card_tabs(&self, symbol: &Symbol) -> Html {
+        let current = self.card_tab(symbol);
+        let tab = |tab: CardTab, label: &str| {
+            let symbol = symbol.clone();
+            let class = if tab == current {
+                "nav-link active"
+            } else {
+                "nav-link"
+            };
+            html! {
+            <li class="nav-item">
+                <button type="button" class=class style="border: none;" onclick = self.link.callback( move | _ | Msg::SelectCardTab(symbol.clone(), tab)) >
+                    { label }
+                </button>
+            </li>
             }
-            Msg::UpdateSymbolToTrack(symbol) => self.symbol_to_add = symbol,
-            Msg::TrackSymbol => {
-                if self.symbol_to_add.0.is_empty() {
-                    return false;
-                } else {
-                    let symbol_to_add = self.symbol_to_add.clone();
-                    self.state.add_symbol(symbol_to_add.clone());
-                    self.symbol_to_add = Symbol("".into());
-                    if let Some(websocket_task) = &mut self.websocket_task {
-                        let subscribe = Request::Subscribe {
-                            symbol: symbol_to_add,
-                        };
-                        websocket_task.send(Json(&subscribe));
-                    }
+        };
+        html! {
+        <ul class="nav nav-tabs mb-2">
+            { tab(CardTab::Trades, "Trades") }
+            { tab(CardTab::News, "News") }
+        </ul>
+        }
+    }
+
+    fn view_news(&self, symbol: &Symbol) -> Html {
+        match self.news.get(symbol) {
+            None if !self.is_entitled_for(PlanFeature::News, symbol) => html! {
+                <div class="text-left">
+                    <p class="card-text text-muted">{ "Company news requires a paid finnhub plan" }</p>
+                </div>
+            },
+            None => html! {
+                <div class="text-left">
+                    <p class="card-text">{ "Loading news..." }</p>
+                </div>
+            },
+            Some(news) if news.is_empty() => html! {
+                <div class="text-left">
+                    <p class="card-text">{ "No recent news" }</p>
+                </div>
+            },
+            Some(news) => {
+                let highlight_after = self.news_highlight_after.get(symbol).copied();
+                html! {
+                <div class="list-group text-left">
+                    { for news.iter().map(|item| self.view_news_item(item, highlight_after)) }
+                </div>
                 }
-                self.persist_state();
             }
-            Msg::UnTrackSymbolAtIdx(idx) => {
-                let result = self.state.untrack_symbol(idx);
-                if result.is_last {
-                    if let Some(websocket_task) = &mut self.websocket_task {
-                        let unsubscribe = Request::Unsubscribe {
-                            symbol: result.symbol,
-                        };
-                        websocket_task.send(Json(&unsubscribe));
-                    }
-                }
-                self.persist_state();
+        }
+    }
+
+    fn view_news_item(&self, item: &NewsItem, highlight_after: Option<DateTime<Utc>>) -> Html {
+        let is_new = highlight_after
+            .map(|after| item.datetime > after)
+            .unwrap_or(false);
+        let class = if is_new {
+            "list-group-item list-group-item-action list-group-item-info"
+        } else {
+            "list-group-item list-group-item-action"
+        };
+        let new_badge = if is_new {
+            html! { <span class="badge badge-primary mr-2">{ "New" }</span> }
+        } else {
+            html! {}
+        };
+        html! {
+        <a href=item.url.as_str() target="_blank" rel="noopener noreferrer" class=class key=item.id.to_string()>
+            <div>{ new_badge }{ &item.headline }</div>
+            <small class="text-muted">{ format!("{} · {}", item.source, item.datetime.format("%Y-%m-%d %H:%M")) }</small>
+        </a>
+        }
+    }
+
+    fn view_volume_alert_input(&self, symbol: &Symbol) -> Html {
+        let callback_symbol = symbol.clone();
+        self.view_volume_input(
+            "fas fa-bell",
+            "Alert on volume at or above",
+            self.state.volume_alerts.get(symbol).copied(),
+            move |volume| Msg::VolumeAlertUpdate(callback_symbol.clone(), volume),
+        )
+    }
+
+    fn view_min_volume_input(&self, symbol: &Symbol) -> Html {
+        let callback_symbol = symbol.clone();
+        self.view_volume_input(
+            "fas fa-filter",
+            "Skip trades with volume below",
+            self.state.min_volumes.get(symbol).copied(),
+            move |volume| Msg::MinVolumeUpdate(callback_symbol.clone(), volume),
+        )
+    }
+
+    fn view_earnings_badge(&self, symbol: &Symbol) -> Html {
+        let cache = self.state.earnings.get(symbol);
+        match cache.and_then(|c| c.badge_label(Utc::now()).zip(c.next.as_ref())) {
+            Some((label, release)) => {
+                let title = match release.hour.as_deref() {
+                    Some("bmo") => format!("Reports {} before the open", release.date),
+                    Some("amc") => format!("Reports {} after the close", release.date),
+                    Some("dmh") => format!("Reports {} during market hours", release.date),
+                    _ => format!("Reports {}", release.date),
+                };
+                html! { <span class="badge badge-info ml-1" title=title>{ label }</span> }
             }
-            Msg::WsIncoming(data) => {
-                match data {
-                    Ok(ws_message) => {
-                        self.console_service
-                            .info(format!("Received message [{:?}]", ws_message).as_str());
-                        match ws_message {
-                            WsMessage::Error { message } => {
-                                // assume the last tracked ticker was bad
-                                if message == "Invalid symbol" {
-                                    if let Some(last_added_ticker) = self.state.last_added() {
-                                        let delete_last = self.dialog_service.confirm(
-                                            format!("Invalid symbol detected. Do you want to untrack the last added one: [{}]", last_added_ticker.0).as_str()
-                                        );
-                                        if delete_last {
-                                            self.state.remove_last_added();
-                                            self.persist_state();
-                                        }
-                                    }
-                                }
-                            }
-                            WsMessage::Trade { data: tickers_data } => {
-                                // go through each one, find the state to update and update it
-                                for i in tickers_data {
-                                    self.state.add_history(i);
-                                }
-                                self.persist_state();
-                            }
-                            WsMessage::Ping => return false,
-                        }
-                    }
-                    Err(sucks) => {
-                        self.console_service
-                            .error(format!("Got some undeserialisable data [{}]", sucks).as_str());
-                        return false;
-                    }
+            None => html! {},
+        }
+    }
+
+    /// The spread next to the last trade
+    fn view_bid_ask(&self, symbol: &Symbol, price_format: &PriceFormat) -> Html {
+        let bid_ask = match self.bid_asks.get(symbol) {
+            Some(bid_ask) => bid_ask,
+            None => return html! {},
+        };
+        let last = self
+            .state
+            .latest_price(symbol)
+            .map(|price| format!("Last {} · ", price_format.format(price)))
+            .unwrap_or_default();
+        html! {
+        <p class="small text-left mb-2">
+            { last }
+            { format!("Bid {} · Ask {} · ", price_format.format(bid_ask.bid), price_format.format(bid_ask.ask)) }
+            <span class="text-muted" title="Ask minus bid, and as a percentage of the midpoint">
+                { format!("Spread {} ({:.2}%)", price_format.format_f64(bid_ask.spread()), bid_ask.spread_percent()) }
+            </span>
+        </p>
+        }
+    }
+
+    fn view_position_summary(&self, symbol: &Symbol) -> Html {
+        let position = self.state.positions.get(symbol).copied();
+        match (position, self.state.latest_price(symbol)) {
+            (Some(position), Some(price)) => {
+                let pnl = position.unrealized_pnl(price);
+                let pnl_class = if pnl >= 0.0 {
+                    "text-success"
+                } else {
+                    "text-danger"
+                };
+                let number_format = self.state.number_format();
+                let percent = position
+                    .unrealized_pnl_percent(price)
+                    .map(|p| format!(" ({:+.2}%)", p))
+                    .unwrap_or_default();
+                let value = position.market_value(price);
+                let converted = self
+                    .to_base_currency(symbol, value)
+                    .zip(self.to_base_currency(symbol, pnl))
+                    .map(|(value, pnl)| {
+                        format!(
+                            " ({} · {})",
+                            self.format_base_currency(value),
+                            number_format.signed(pnl, 2)
+                        )
+                    })
+                    .unwrap_or_default();
+                html! {
+                <p class="small text-left mb-2">
+                    { format!("Value {} · P&L ", number_format.fixed(value, 2)) }
+                    <span class=pnl_class>{ format!("{}{}", number_format.signed(pnl, 2), percent) }</span>
+                    <span class="text-muted">{ converted }</span>
+                </p>
                 }
             }
-            Msg::WsOpened => {
-                // subscribe
-                if let Some(websocket_task) = &mut self.websocket_task {
-                    for tracked in &self.state.tracked {
-                        let subscribe = Request::Subscribe {
-                            symbol: tracked.clone(),
-                        };
-                        websocket_task.send(Json(&subscribe));
-                    }
+            _ => html! {},
+        }
+    }
+
+    fn view_price_target_progress(&self, symbol: &Symbol, price_format: &PriceFormat) -> Html {
+        let maybe_target = self.state.price_targets.get(symbol);
+        match (maybe_target, self.state.latest_price(symbol)) {
+            (Some(target), Some(price)) => {
+                let reference = self
+                    .state
+                    .price_target_reference(symbol, target)
+                    .unwrap_or(price);
+                let percent = target.progress_percent(reference, price);
+                let bar_class = if percent >= 100.0 {
+                    "progress-bar bg-success"
                 } else {
-                    // impossible,
-                    self.dialog_service
-                        .alert("The no websocket connection despite it being open, wtf?");
+                    "progress-bar"
+                };
+                html! {
+                <>
+                <div class="progress mb-1">
+                    <div
+                      class=bar_class
+                      role="progressbar"
+                      style=format!("width: {:.0}%;", percent)
+                      aria-valuenow=format!("{:.0}", percent)
+                      aria-valuemin="0"
+                      aria-valuemax="100">
+                    </div>
+                </div>
+                <p class="small text-muted text-left mb-2">
+                    { format!(
+                        "{:.0}% of the way from {} to {}",
+                        percent,
+                        price_format.format(reference),
+                        price_format.format(target.target)
+                    ) }
+                </p>
+                </>
                 }
-                return true;
             }
-            Msg::WsDead => {
-                if self
-                    .dialog_service
-                    .confirm("The Websocket connection failed 😞\n\nThis might be because our API key is wrong, but if you were previously connected, you might want to try reconnecting?")
-                {
-                    return self.connect_to_api();
+            _ => html! {},
+        }
+    }
+
+    fn view_portfolio_summary(&self) -> Html {
+        match self.state.portfolio_totals() {
+            Some((value, pnl)) => {
+                let pnl_class = if pnl >= 0.0 {
+                    "text-success"
                 } else {
-                    self.websocket_task = None;
+                    "text-danger"
+                };
+                let number_format = self.state.number_format();
+                // positions in different currencies only add up once they're converted
+                let converted = self
+                    .state
+                    .portfolio_totals_converted(|symbol, amount| {
+                        self.to_base_currency(symbol, amount).or_else(|| {
+                            let base = self.state.settings.base_currency.as_deref()?;
+                            self.state
+                                .currency(symbol)
+                                .filter(|c| c.code() == base)
+                                .map(|_| amount)
+                        })
+                    })
+                    .map(|(value, pnl)| {
+                        format!(
+                            " ({} · {})",
+                            self.format_base_currency(value),
+                            number_format.signed(pnl, 2)
+                        )
+                    })
+                    .unwrap_or_default();
+                html! {
+                <p>
+                    { format!("Portfolio value {} · unrealized P&L ", number_format.fixed(value, 2)) }
+                    <span class=pnl_class>{ number_format.signed(pnl, 2) }</span>
+                    <span class="text-muted">{ converted }</span>
+                </p>
                 }
             }
-            Msg::Nope => (),
+            None => html! {},
         }
-        true
     }
 
-    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
-        // Should only return "true" if new properties are different to
-        // previously received properties.
-        // This component has no properties so we will always return "false".
-        false
+    /// An optional volume input; clearing it emits `None`
+    fn view_volume_input<F>(
+        &self,
+        icon_class: &str,
+        label: &str,
+        current: Option<Volume>,
+        to_msg: F,
+    ) -> Html
+    where
+        F: Fn(Option<Volume>) -> Msg + 'static,
+    {
+        self.view_decimal_input(icon_class, label, current.map(|v| v.0), move |decimal| {
+            to_msg(decimal.map(Volume))
+        })
     }
 
-    fn view(&self) -> Html {
+    /// An optional non-negative number input; clearing it emits `None`
+    fn view_decimal_input<F>(
+        &self,
+        icon_class: &str,
+        label: &str,
+        current: Option<Decimal>,
+        to_msg: F,
+    ) -> Html
+    where
+        F: Fn(Option<Decimal>) -> Msg + 'static,
+    {
+        let current = current.map(|d| d.to_string()).unwrap_or_default();
         html! {
-        < div class = "container-fluid text-center" >
-            < div class ="row" >
-                < div class ="col text-center" >
-                    < h1 class = "display-3">{ "finnhub trades" }< / h1 >
-                < /div >
-            < /div>
-            <div class = "row" >
-                < div class ="col text-center" >
-                    <p>{ "WASM app written in " }<a href={"https://www.rust-lang.org"}>{ "Rust" }</a>{ " using "}<a href={"https://yew.rs"}>{ "Yew" }</a>< / p >
-                    <p>{ "Connects to the " }<a href={"https://finnhub.io"}>{ "finnhub.io" }</a>{ " Websocket Trades API and persists to LocalStorage"}< / p >
-                    <p class="text-muted">
-                        { "Github" }
-                        <a class={"p-2"} href={ "https://github.com/lloydmeta/finnhub-ws-rs"}>
-                            <img src={ "https://img.shields.io/github/stars/lloydmeta/finnhub-ws-rs?style=social" } alt={"github"}/>
-                        </a>
-                    < / p >
-                < /div >
-            </div>
-            < div class ="row" >
-                < div class ="offset-md-4 col-md-4" >
-                    { self.view_api_key_input() }
-                    { self.view_ticker_input() }
-                < /div >
-            < /div>
-            <div class = "row" >
-                < div class ="offset-md-2 col-md-8" >
-                        { for self.state.tracked.iter().enumerate().map( | e | self.view_symbol(e)) }
-                < /div>
-            < /div>
-        < / div >
+        <div class="input-group input-group-sm mb-2">
+          <div class="input-group-prepend">
+            <span class="input-group-text"><i class=icon_class></i></span>
+          </div>
+          <input
+            type="number"
+            min="0"
+            class="form-control"
+            placeholder=label
+            aria-label=label
+            title=label
+            value = current
+            onchange = self.link.callback( move | e: ChangeData | match e {
+                ChangeData::Value(v) if v.trim().is_empty() => to_msg(None),
+                ChangeData::Value(v) => match v.trim().parse() {
+                    Ok(decimal) => to_msg(Some(decimal)),
+                    Err(_) => Msg::Nope,
+                },
+                _ => Msg::Nope,
+            })
+            />
+        </div>
         }
     }
-}
 
-impl Model {
-    fn persist_state(&mut self) {
-        if let Some(storage_service) = &mut self.storage_service {
-            storage_service.store(STATE_STORAGE_KEY, Json(&self.state));
+    fn view_exchange_picker(&self) -> Html {
+        html! {
+        <select
+          class="custom-select"
+          aria-label="Exchange"
+          onchange = self.link.callback( | e: ChangeData | match e {
+              ChangeData::Select(select) => Msg::UpdateExchangeToTrack(select.value()),
+              _ => Msg::Nope,
+          })>
+          <option value="" selected=self.exchange_to_add.is_empty()>{ "Stock" }</option>
+          { for EXCHANGES.iter().map(|(exchange, _)| html! {
+              <option value=exchange selected=self.exchange_to_add == *exchange>{ exchange }</option>
+          }) }
+        </select>
         }
     }
 
-    fn connect_to_api(&mut self) -> bool {
-        let callback = self.link.callback(|Json(data)| Msg::WsIncoming(data));
+    fn view_sortable_header(&self, symbol: &Symbol, column: SortColumn, label: &str) -> Html {
+        let sort = self.table_sort(symbol);
+        let (icon_class, aria_sort) = if sort.column != column {
+            ("fas fa-sort text-muted", "none")
+        } else if sort.ascending {
+            ("fas fa-sort-up", "ascending")
+        } else {
+            ("fas fa-sort-down", "descending")
+        };
+        let symbol = symbol.clone();
+        html! {
+        <th scope="col" aria-sort=aria_sort style="cursor: pointer;"
+            onclick = self.link.callback( move | _ | Msg::SortTable(symbol.clone(), column))>
+            { label }{ " " }<i class=icon_class></i>
+        </th>
+        }
+    }
 
-        let notification = self.link.callback(|status| match status {
-            WebSocketStatus::Opened => Msg::WsOpened,
-            WebSocketStatus::Closed | WebSocketStatus::Error => Msg::WsDead,
-        });
+    /// SMA and EMA for each trade in a history, per the periods in the settings, and in
+    /// the same (newest first) order
+    fn moving_averages(&self, history: &VecDeque<Trade>) -> Vec<MovingAverages> {
+        let prices: Vec<f64> = history.iter().rev().map(|t| t.price.0.to_f64()).collect();
+        let averages = |period: Option<u32>, f: fn(&[f64], usize) -> Vec<Option<f64>>| {
+            period.map(|period| f(&prices, period as usize))
+        };
+        let sma = averages(self.state.settings.sma_period, indicators::sma);
+        let ema = averages(self.state.settings.ema_period, indicators::ema);
+        (0..prices.len())
+            .rev()
+            .map(|idx| MovingAverages {
+                sma: sma.as_ref().and_then(|sma| sma[idx]),
+                ema: ema.as_ref().and_then(|ema| ema[idx]),
+            })
+            .collect()
+    }
 
-        let websocket_task_result = self.websocket_service.connect(
-            format!("wss://ws.finnhub.io?token={}", self.state.api_key.0).as_str(),
-            callback,
-            notification,
-        );
-        match websocket_task_result {
-            Ok(websocket_task) => {
-                self.websocket_task = Some(websocket_task);
-                true
-            }
-            Err(yikes) => {
-                self.dialog_service.alert(yikes);
-                false
-            }
+    /// Spans the whole table, whichever columns are shown
+    fn view_session_totals(&self, symbol: &Symbol, price_format: &PriceFormat) -> Html {
+        let totals = match self.session_totals.get(symbol) {
+            Some(totals) => totals,
+            None => return html! {},
+        };
+        let settings = &self.state.settings;
+        let columns = settings.trade_columns.len()
+            + settings.sma_period.iter().count()
+            + settings.ema_period.iter().count();
+        let number_format = self.state.number_format();
+        // crypto trades in fractions
+        let volume_decimals = if totals.volume.fract() == 0.0 { 0 } else { 4 };
+        let vwap = totals
+            .vwap()
+            .map(|vwap| format!(" · VWAP {}", price_format.format_f64(vwap)))
+            .unwrap_or_default();
+        let upticks = self
+            .tick_counts
+            .get(symbol)
+            .and_then(|counts| counts.uptick_ratio())
+            .map(|ratio| format!(" · upticks {}%", number_format.fixed(ratio * 100.0, 0)))
+            .unwrap_or_default();
+        html! {
+        <tfoot>
+          <tr class="small text-muted">
+            <td colspan=columns.to_string() title="Since the page was loaded">
+              { format!(
+                  "Session: {} trades · volume {}{}{}",
+                  number_format.fixed(totals.trades as f64, 0),
+                  number_format.volume(totals.volume, volume_decimals),
+                  vwap,
+                  upticks
+              ) }
+            </td>
+          </tr>
+          { self.view_history_stats(symbol, price_format, columns) }
+        </tfoot>
         }
     }
 
-    fn view_api_key_input(&self) -> Html {
-        let ws_connected = self.websocket_task.is_some();
-        let button_class = if ws_connected {
-            "btn btn-secondary"
-        } else {
-            "btn btn-primary"
-        };
-        let button_text = if ws_connected {
-            "Disconnect"
-        } else {
-            "Connect"
+    /// Over the trades still in history, unlike the session totals above it
+    fn view_history_stats(
+        &self,
+        symbol: &Symbol,
+        price_format: &PriceFormat,
+        columns: usize,
+    ) -> Html {
+        let stats = match self.state.history.stats(symbol) {
+            Some(stats) if stats.count() > 0 => stats,
+            _ => return html! {},
         };
-        let button_onclick = if ws_connected {
-            self.link.callback(|_| Msg::ApiKeyDisconnect)
-        } else {
-            self.link.callback(|_| Msg::ApiKeyConnect)
+        let mut parts = vec![format!(
+            "History: {} trades",
+            self.state.number_format().fixed(stats.count() as f64, 0)
+        )];
+        parts.extend(
+            stats
+                .mean()
+                .map(|mean| format!("mean {}", price_format.format_f64(mean))),
+        );
+        parts.extend(
+            stats
+                .std_dev()
+                .map(|sd| format!("σ {}", price_format.format_f64(sd))),
+        );
+        parts.extend(
+            stats
+                .vwap()
+                .map(|vwap| format!("VWAP {}", price_format.format_f64(vwap))),
+        );
+        html! {
+          <tr class="small text-muted">
+            <td colspan=columns.to_string() title="Over the trades kept in history">
+              { parts.join(" · ") }
+            </td>
+          </tr>
+        }
+    }
+
+    fn view_moving_average_headers(&self) -> Html {
+        let header = |label: &str, period: Option<u32>| match period {
+            Some(period) => html! { <th scope="col">{ format!("{}({})", label, period) }</th> },
+            None => html! {},
         };
+        html! {
+        <>
+        { header("SMA", self.state.settings.sma_period) }
+        { header("EMA", self.state.settings.ema_period) }
+        </>
+        }
+    }
 
-        let button_icon = if ws_connected {
-            html! {
-            <i class="fas fa-unlink" style="color:red;"></i>
-            }
-        } else {
-            html! {
-            <i class="fas fa-link"></i>
+    fn view_trade_column_headers(&self, symbol: &Symbol, price_format: &PriceFormat) -> Html {
+        let header = |column: TradeColumn| {
+            let label = match column {
+                TradeColumn::Price => price_format.header(),
+                _ => column.label().to_string(),
+            };
+            match column.sort_column() {
+                Some(sort_column) => self.view_sortable_header(symbol, sort_column, &label),
+                None => html! { <th scope="col">{ label }</th> },
             }
         };
-
         html! {
-        <div class="input-group mb-3">
-          <input
-            type="text"
-            class="form-control"
-            placeholder="finnhub.io API Key"
-            aria-label="API Key from finnhub.io"
-            aria-describedby="api-key-connect"
-            value =& self.state.api_key.0
-            oninput = self.link.callback( | e: InputData | Msg::ApiKeyUpdate(ApiKey(e.value)))
-            onkeypress = self.link.callback( |e: KeyboardEvent | {
-                if e.key() == "Enter" { Msg::ApiKeyConnect } else { Msg::Nope }
-            })
-            disabled=ws_connected
-            />
-          <div class="input-group-append">
-            <button class=button_class
-             type="button"
-             id="api-key-connect"
-             aria-label={ button_text }
-             onclick=button_onclick>
-                 { button_icon }
-            </button>
-          </div>
-        </div>
+            { for self.state.settings.trade_columns.iter().map(|c| header(*c)) }
         }
     }
 
-    fn view_ticker_input(&self) -> Html {
-        html! {
-        <div class="input-group mb-3">
-          <input
-            type="text"
-            class="form-control"
-            placeholder="Ticker symbol"
-            aria-label="Ticker symbol"
-            aria-describedby="track-symbol"
-            value =& self.symbol_to_add.0
-            oninput = self.link.callback( | e: InputData | Msg::UpdateSymbolToTrack(Symbol(e.value)))
-            onkeypress = self.link.callback( |e: KeyboardEvent | {
-                if e.key() == "Enter" { Msg::TrackSymbol } else { Msg::Nope }
-            })
-            />
-          <div class="input-group-append">
-            <button class="btn btn-success"
-             type="button"
-             id="track-symbol"
-             onclick=self.link.callback( | _ | Msg::TrackSymbol )>
-                 <i class="fas fa-plus-circle"></i>
-            </button>
-          </div>
-        </div>
+    fn view_ticker_info_row(
+        &self,
+        price_format: &PriceFormat,
+        history: &VecDeque<Trade>,
+        averages: &[MovingAverages],
+        cumulative_volumes: &[(f64, usize)],
+        (idx, ticker_info): (usize, &Trade),
+    ) -> Html {
+        let average_cell = |period: Option<u32>, value: Option<f64>| match period {
+            Some(_) => html! {
+                <td>{ value.map(|v| price_format.format_f64(v)).unwrap_or_default() }</td>
+            },
+            None => html! {},
+        };
+        let row_averages = averages.get(idx).copied().unwrap_or_default();
+        let number_format = self.state.number_format();
+        // only the newest trade flashes
+        let price_class = match (idx, ticker_info.direction) {
+            (0, Some(PriceDirection::Up)) | (0, Some(PriceDirection::Down)) => {
+                let direction = if ticker_info.direction == Some(PriceDirection::Up) {
+                    "up"
+                } else {
+                    "down"
+                };
+                let parity = self
+                    .flash_parity
+                    .get(&ticker_info.symbol)
+                    .copied()
+                    .unwrap_or(false) as u8;
+                format!("flash-{}-{}", direction, parity)
+            }
+            _ => "".to_string(),
+        };
+        let session_badge = match market_hours::session(&ticker_info.symbol, ticker_info.time)
+            .and_then(|session| session.label())
+        {
+            Some(label) => html! { <span class="badge badge-light ml-1">{ label }</span> },
+            None => html! {},
+        };
+        let (volume, delayed_badge) = if ticker_info.delayed {
+            (
+                "—".to_string(),
+                html! { <span class="badge badge-secondary ml-1">{ "delayed" }</span> },
+            )
+        } else {
+            (self.state.format_volume(ticker_info.volume), html! {})
+        };
+        let cell = |column: TradeColumn| match column {
+            TradeColumn::Time => html! {
+                <td>{ self.time_formatter.format(ticker_info.time, Utc::now()) }{ delayed_badge.clone() }{ session_badge.clone() }</td>
+            },
+            TradeColumn::Price => html! {
+                <td class=&price_class>{ price_format.format(ticker_info.price) }{ view_session_extreme(ticker_info.session_extreme) }</td>
+            },
+            TradeColumn::Volume => html! { <td>{ &volume }</td> },
+            TradeColumn::Notional => html! {
+                <td>{ ticker_info.notional().map_or("—".to_string(), |n| number_format.fixed(n, 2)) }</td>
+            },
+            TradeColumn::CumulativeVolume => html! {
+                <td>{ cumulative_volumes.get(idx).map(|(v, scale)| number_format.volume(*v, *scale)).unwrap_or_default() }</td>
+            },
+            TradeColumn::Delta => {
+                // the history is newest first, so the trade before is the next one
+                match history.get(idx + 1) {
+                    Some(previous) => {
+                        let delta = ticker_info.price.0.to_f64() - previous.price.0.to_f64();
+                        let class = if delta > 0.0 {
+                            "text-success"
+                        } else if delta < 0.0 {
+                            "text-danger"
+                        } else {
+                            ""
+                        };
+                        html! { <td class=class>{ format!("{:+.*}", price_format.precision, delta) }</td> }
+                    }
+                    None => html! { <td></td> },
+                }
+            }
+            TradeColumn::Tick => {
+                let class = match ticker_info.direction {
+                    Some(PriceDirection::Up) => "text-success",
+                    Some(PriceDirection::Down) => "text-danger",
+                    _ => "text-muted",
+                };
+                html! { <td class=class>{ ticker_info.direction.map(PriceDirection::arrow).unwrap_or_default() }</td> }
+            }
+        };
+        let is_block_trade = self
+            .state
+            .settings
+            .block_trade_notional
+            .zip(ticker_info.notional())
+            .is_some_and(|(threshold, notional)| notional >= threshold.to_f64());
+        let (row_class, row_title) = if ticker_info.spike {
+            ("table-danger", "Price spike: far from the recent mean")
+        } else if is_block_trade {
+            ("table-warning", "Block trade")
+        } else {
+            ("", "")
+        };
+        html! {
+            <tr class=row_class title=row_title>
+              { for self.state.settings.trade_columns.iter().map(|c| cell(*c)) }
+              { average_cell(self.state.settings.sma_period, row_averages.sma) }
+              { average_cell(self.state.settings.ema_period, row_averages.ema) }
+            </tr>
         }
     }
 
-    fn view_ticker_info_row(&self, ticker_info: &TickerInfo) -> Html {
+    /// Only renders the card if something on it has changed, leaving `SymbolCard` to
+    /// keep what it's got otherwise
+    fn view_symbol(&self, (idx, symbol): (usize, &Symbol)) -> Html {
+        let redraw = self.redraw_all_cards
+            || self.redraw_cards.contains(symbol)
+            || !self.mounted_cards.contains(symbol);
+        self.cards_in_view
+            .borrow_mut()
+            .push((symbol.clone(), redraw));
+        let card = if redraw {
+            Some(self.view_symbol_card(idx, symbol))
+        } else {
+            None
+        };
+        html! { <SymbolCard key=symbol.0.clone() card=card /> }
+    }
+
+    /// Lets a table go further back than its first page of trades, a page at a time
+    fn view_table_depth_controls(&self, symbol: &Symbol, total: usize, depth: usize) -> Html {
+        let older = total.saturating_sub(depth);
+        if older == 0 && depth <= TABLE_PAGE_ROWS {
+            return html! {};
+        }
+        let more = if older > 0 {
+            let more_symbol = symbol.clone();
+            html! {
+            <button type="button" class="btn btn-sm btn-link"
+              onclick = self.link.callback( move | _ | Msg::ShowMoreTrades(more_symbol.clone()))>
+              { format!("Show {} more ({} older)", older.min(TABLE_PAGE_ROWS), older) }
+            </button>
+            }
+        } else {
+            html! {}
+        };
+        let fewer = if depth > TABLE_PAGE_ROWS {
+            let fewer_symbol = symbol.clone();
+            html! {
+            <button type="button" class="btn btn-sm btn-link"
+              onclick = self.link.callback( move | _ | Msg::ShowFewerTrades(fewer_symbol.clone()))>
+              { "Show fewer" }
+            </button>
+            }
+        } else {
+            html! {}
+        };
         html! {
-            <tr>
-              <td>{ ticker_info.time }</td>
-              <td>{ ticker_info.volume.0 }</td>
-              <td>{ ticker_info.price.0 }</td>
-            </tr>
+        <div class="d-flex justify-content-center small">
+          { more }
+          { fewer }
+        </div>
         }
     }
 
-    fn view_symbol(&self, (idx, symbol): (usize, &Symbol)) -> Html {
+    fn view_symbol_card(&self, idx: usize, symbol: &Symbol) -> Html {
         let maybe_symbol_history = self.state.history.get(symbol);
+        let price_format = self.state.price_format(symbol);
+        let dark = self.is_dark();
+        let table_class = if dark {
+            "table table-hover table-dark"
+        } else {
+            "table table-hover"
+        };
 
-        let mut ticker_health = TickerHealth::Normal;
+        let ticker_health = self.state.ticker_health(symbol);
+        let maximized = self.is_maximized(symbol);
+        let newest_extreme = maybe_symbol_history
+            .and_then(|h| h.front())
+            .and_then(|newest| newest.session_extreme);
 
         let last_trade_details = if let Some(symbol_history) = maybe_symbol_history {
-            if let (Some(last_trade), Some(second_last)) =
-                (symbol_history.get(0), symbol_history.get(1))
+            let depth = self.table_depth(symbol);
+            let rows = self.table_sort(symbol).sorted(symbol_history, depth);
+            let averages = self.moving_averages(symbol_history);
+            let cumulative_volumes = if self
+                .state
+                .settings
+                .trade_columns
+                .contains(&TradeColumn::CumulativeVolume)
             {
-                if last_trade.price > second_last.price {
-                    ticker_health = TickerHealth::Good;
-                } else if last_trade.price < second_last.price {
-                    ticker_health = TickerHealth::Bad;
-                }
-            }
-
+                cumulative_volumes(symbol_history)
+            } else {
+                vec![]
+            };
+            let scroll_top = self.table_scroll_tops.get(symbol).copied().unwrap_or(0.0);
+            let visible = visible_rows(scroll_top, rows.len(), self.table_shown_rows(symbol));
+            let spacer = |rows: usize| {
+                let style = format!("height: {}px;", rows as f64 * TABLE_ROW_HEIGHT_PX);
+                html! { <tr style=style aria-hidden="true"></tr> }
+            };
+            let top_spacer = spacer(visible.start);
+            let bottom_spacer = spacer(rows.len() - visible.end);
+            let scroll_symbol = symbol.clone();
+            let (table_container_class, chart) = if maximized {
+                let change = symbol_history
+                    .back()
+                    .zip(symbol_history.front())
+                    .map_or(0.0, |(oldest, newest)| {
+                        newest.price.0.to_f64() - oldest.price.0.to_f64()
+                    });
+                let chart = Chart::Symbol(symbol.clone());
+                let spacing = self.chart_spacing(&chart);
+                (
+                    "table-responsive virtual-table maximized",
+                    html! {
+                    <>
+                    { self.view_chart_spacing_toggle(chart) }
+                    { view_sparkline(symbol_history, change, 800.0, 160.0, spacing, true) }
+                    </>
+                    },
+                )
+            } else {
+                ("table-responsive virtual-table", html! {})
+            };
             html! {
-                <div class="table-responsive">
-                  <table class="table table-hover">
+                <>
+                { chart }
+                <div
+                  class=table_container_class
+                  onscroll = self.link.callback( move | e: UiEvent | {
+                      let scroll_top = e
+                          .target()
+                          .and_then(|t| t.dyn_into::<Element>().ok())
+                          .map_or(0.0, |el| f64::from(el.scroll_top()));
+                      Msg::TableScroll(scroll_symbol.clone(), scroll_top)
+                  })>
+                  <table class={ table_class }>
+                      <caption class="sr-only">{ format!("Recent trades for {}", symbol.0) }</caption>
                       <thead>
                         <tr>
-                          <th scope="col">{ "Time" }</th>
-                          <th scope="col">{ "Volume" }</th>
-                          <th scope="col">{ "Price ($)" }</th>
+                          { self.view_trade_column_headers(symbol, &price_format) }
+                          { self.view_moving_average_headers() }
                         </tr>
                       </thead>
                       <tbody class="text-right">
-                        { for symbol_history.iter().map( | t | self.view_ticker_info_row(t))}
+                        { top_spacer }
+                        { for rows[visible].iter().map( | t | self.view_ticker_info_row(&price_format, symbol_history, &averages, &cumulative_volumes, *t))}
+                        { bottom_spacer }
                       </tbody>
+                      { self.view_session_totals(symbol, &price_format) }
                   </table>
                 </div>
+                { self.view_table_depth_controls(symbol, symbol_history.len(), depth) }
+                </>
             }
         } else {
             html! {
@@ -535,52 +9090,1015 @@ impl Model {
             }
         };
 
-        let not_connected_to_api = self.websocket_task.is_none();
+        let not_connected_to_api = self.connection_state != ConnectionState::Connected;
+        let paused = self.state.is_paused(symbol);
+        let symbol_error = self.symbol_errors.get(symbol).copied();
+        let invalid = symbol_error.is_some();
+        let waiting = self.is_waiting_for_slot(symbol);
 
         let card_class = {
-            let card_health_class = if not_connected_to_api {
+            let card_health_class = if invalid {
+                "border-danger"
+            } else if not_connected_to_api {
                 "border-warning"
+            } else if paused {
+                "border-secondary"
             } else {
-                match ticker_health {
-                    TickerHealth::Good => "border-success",
-                    TickerHealth::Bad => "border-danger",
-                    TickerHealth::Normal => "border-primary",
-                }
+                ticker_health.border_class()
             };
-            format!("card m-2 {}", card_health_class)
+            let card_theme_class = if dark { "bg-dark text-light" } else { "" };
+            format!("card m-2 {} {}", card_health_class, card_theme_class)
+        };
+
+        let not_connected_message = if self.quote_poll_task.is_some() && !self.api_key.0.is_empty()
+        {
+            "Not connected to API, showing delayed quotes"
+        } else {
+            "Not connected to API"
+        };
+        let not_connected_warning = if let Some(error) = symbol_error {
+            let retry_symbol = symbol.clone();
+            html! {
+            <div class="alert alert-danger d-flex align-items-center m-2 py-1 px-2 mb-0" role="alert">
+              <small class="mr-auto">{ error.label() }</small>
+              <button type="button" class="btn btn-sm btn-outline-danger ml-2"
+                onclick = self.link.callback( move | _ | Msg::RetrySymbol(retry_symbol.clone())) >
+                { "Retry" }
+              </button>
+              <button type="button" class="btn btn-sm btn-danger ml-2"
+                onclick = self.link.callback( move | _ | Msg::UnTrackSymbolAtIdx(idx)) >
+                { "Untrack" }
+              </button>
+            </div>
+            }
+        } else if not_connected_to_api {
+            html! {
+            <small class="text-muted p-2">{ not_connected_message }</small>
+            }
+        } else if paused {
+            html! {
+            <small class="text-muted p-2">{ "Paused"}</small>
+            }
+        } else if waiting {
+            html! {
+            <small class="text-muted p-2" title="Over the subscription limit">{ "Waiting for a subscription slot" }</small>
+            }
+        } else {
+            html! {}
+        };
+
+        let trade_rate = if not_connected_to_api || paused || invalid || waiting {
+            html! {}
+        } else {
+            html! {
+            <small class="text-muted p-2">
+                { format!("{:.1} trades/s", self.metrics.trades_per_sec_for(symbol, Utc::now())) }
+            </small>
+            }
+        };
+
+        let stale_badge = match self.state.history.get(symbol).and_then(|h| h.front()) {
+            Some(newest) if self.stale_symbols.contains(symbol) && !paused => {
+                let age = Utc::now() - newest.time;
+                let age = if age.num_hours() > 0 {
+                    format!("{}h {}m", age.num_hours(), age.num_minutes() % 60)
+                } else {
+                    format!("{}m", age.num_minutes())
+                };
+                // no trades is expected while the market's shut
+                if market_hours::is_closed(symbol, Utc::now()) {
+                    html! {
+                    <span class="badge badge-secondary ml-1" title="The market is closed">
+                        { format!("Market closed · last trade {} ago", age) }
+                    </span>
+                    }
+                } else {
+                    html! {
+                    <span class="badge badge-warning ml-1" title="No recent trades">
+                        { format!("Stale · last trade {} ago", age) }
+                    </span>
+                    }
+                }
+            }
+            _ => html! {},
+        };
+
+        let profile_symbol = symbol.clone();
+
+        let maximize_button = if maximized {
+            html! {
+            <button type="button" class="close mr-2" aria-label="Show all cards" title="Show all cards (Esc)" onclick = self.link.callback( | _ | Msg::Maximize(None)) >
+              <i class="fas fa-compress"></i>
+            </button>
+            }
+        } else {
+            let symbol = symbol.clone();
+            html! {
+            <button type="button" class="close mr-2" aria-label=format!("Maximize {}", symbol.0) title="Maximize" onclick = self.link.callback( move | _ | Msg::Maximize(Some(symbol.clone()))) >
+              <i class="fas fa-expand"></i>
+            </button>
+            }
         };
 
-        let not_connected_warning = if not_connected_to_api {
+        let replay_button = if self.is_replaying(symbol) {
+            html! {}
+        } else {
+            let symbol = symbol.clone();
+            html! {
+            <button type="button" class="close mr-2" aria-label=format!("Replay {}", symbol.0) title="Replay history"
+              disabled=self.state.history.get(&symbol).is_none()
+              onclick = self.link.callback( move | _ | Msg::StartReplay(symbol.clone())) >
+              <i class="fas fa-history"></i>
+            </button>
+            }
+        };
+        let clear_history_button = {
+            let symbol = symbol.clone();
+            html! {
+            <button type="button" class="close mr-2" aria-label=format!("Clear history of {}", symbol.0) title="Clear history only"
+              disabled=self.state.history.get(&symbol).is_none()
+              onclick = self.link.callback( move | _ | Msg::ClearSymbolHistory(symbol.clone())) >
+              <i class="fas fa-eraser"></i>
+            </button>
+            }
+        };
+        let dashboard = self.state.settings.view_mode == ViewMode::Dashboard && !maximized;
+        let card_size = self
+            .state
+            .card_sizes
+            .get(symbol)
+            .copied()
+            .unwrap_or_default();
+        let columns = self.state.settings.dashboard_columns;
+        let card_style = if dashboard {
+            card_size.grid_style(columns)
+        } else {
+            String::new()
+        };
+        let resize_buttons = if dashboard {
+            let wider_symbol = symbol.clone();
+            let taller_symbol = symbol.clone();
             html! {
-            <small class="text-muted p-2">{ "Not connected to API"}</small>
+            <>
+            <button type="button" class="close mr-2" aria-label=format!("Change the width of {}", symbol.0)
+              title=format!("Width: {} of {} columns", card_size.cols.min(columns), columns)
+              onclick = self.link.callback( move | _ | Msg::CardSizeUpdate(wider_symbol.clone(), card_size.wider(columns))) >
+              <i class="fas fa-arrows-alt-h"></i>
+            </button>
+            <button type="button" class="close mr-2" aria-label=format!("Change the height of {}", symbol.0)
+              title=format!("Height: {} of {} rows", card_size.rows, CardSize::MAX_ROWS)
+              onclick = self.link.callback( move | _ | Msg::CardSizeUpdate(taller_symbol.clone(), card_size.taller())) >
+              <i class="fas fa-arrows-alt-v"></i>
+            </button>
+            </>
             }
         } else {
             html! {}
         };
+        let pause_button = {
+            let symbol = symbol.clone();
+            if paused {
+                html! {
+                <button type="button" class="close mr-2" aria-label=format!("Resume {}", symbol.0) title="Resume" onclick = self.link.callback( move | _ | Msg::ResumeSymbol(symbol.clone())) >
+                  <i class="fas fa-play"></i>
+                </button>
+                }
+            } else {
+                html! {
+                <button type="button" class="close mr-2" aria-label=format!("Pause {}", symbol.0) title="Pause" onclick = self.link.callback( move | _ | Msg::PauseSymbol(symbol.clone())) >
+                  <i class="fas fa-pause"></i>
+                </button>
+                }
+            }
+        };
 
         html! {
-        <div class={ card_class }>
+        <div class={ card_class }
+          style=card_style
+          id=card_element_id(idx)
+          data-card-idx=idx.to_string()
+          tabindex="0"
+          draggable="true"
+          ondragstart = self.link.callback( move | e: DragEvent | {
+              // firefox won't start dragging without some data set
+              if let Some(data_transfer) = e.data_transfer() {
+                  let _ = data_transfer.set_data("text/plain", &idx.to_string());
+              }
+              Msg::DragStart(idx)
+          })
+          ondragover = self.link.callback( | e: DragEvent | {
+              // allows dropping
+              e.prevent_default();
+              Msg::DragOver
+          })
+          ondrop = self.link.callback( move | e: DragEvent | {
+              e.prevent_default();
+              Msg::DropAtIdx(idx)
+          })
+          ondragend = self.link.callback( | _ | Msg::DragEnd)>
           <div class="card-header">
             < div class ="d-flex w-100 justify-content-between" >
                 <div class="flex-fill text-left">
-                    <h5 class="mb-1">{ & symbol.0 }{ not_connected_warning }</h5>
+                    <h5 class="mb-1">
+                        <button type="button" class="btn btn-link p-0 align-baseline" style="font-size: inherit;" aria-label="Company profile" onclick = self.link.callback( move | _ | Msg::ToggleProfile(profile_symbol.clone())) >
+                            { & symbol.0 }
+                        </button>
+                        { not_connected_warning }
+                        { trade_rate }
+                        { stale_badge }
+                        { self.view_earnings_badge(symbol) }
+                        { view_session_extreme(newest_extreme) }
+                        { self.view_tags(symbol) }
+                    </h5>
+                    { self.view_note(symbol) }
                 </div>
                 < div class="flex-fill text-right">
-                    <button type="button" class="close" aria-label="Untrack" onclick = self.link.callback( move | _ | Msg::UnTrackSymbolAtIdx(idx)) >
+                    <button type="button" class="close" aria-label=format!("Untrack {}", symbol.0) title="Untrack" onclick = self.link.callback( move | _ | Msg::UnTrackSymbolAtIdx(idx)) >
                       <i class="fas fa-times"></i>
                     </button>
+                    { pause_button }
+                    { replay_button }
+                    { clear_history_button }
+                    { resize_buttons }
+                    { maximize_button }
                 </div>
             < / div >
           </div>
           <div class="card-body">
-             { last_trade_details }
+             { self.view_replay_controls(symbol) }
+             { self.view_profile(symbol) }
+             { self.view_group_input(symbol) }
+             { self.view_notes_inputs(symbol) }
+             { self.view_bid_ask(symbol, &price_format) }
+             { self.view_position_summary(symbol) }
+             { self.view_price_target_progress(symbol, &price_format) }
+             { self.view_card_tabs(symbol) }
+             {
+                 match self.card_tab(symbol) {
+                     CardTab::Trades => last_trade_details,
+                     CardTab::News => self.view_news(symbol),
+                 }
+             }
           </div>
         </div>
         }
     }
 }
 
+fn view_session_extreme(extreme: Option<SessionExtreme>) -> Html {
+    match extreme {
+        Some(extreme) => html! {
+            <span class=extreme.badge_class() title=extreme.title()>{ extreme.label() }</span>
+        },
+        None => html! {},
+    }
+}
+
+/// Comma separated, trimmed and without duplicates
+fn parse_tags(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = vec![];
+    for tag in input.split(',').map(str::trim) {
+        if !tag.is_empty() && !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+/// The same tag always gets the same colour, wherever it's shown
+fn tag_badge_class(tag: &str) -> &'static str {
+    const CLASSES: [&str; 6] = [
+        "badge-primary",
+        "badge-success",
+        "badge-danger",
+        "badge-warning",
+        "badge-info",
+        "badge-dark",
+    ];
+    let hash = tag.bytes().fold(0usize, |hash, b| {
+        hash.wrapping_mul(31).wrapping_add(b as usize)
+    });
+    CLASSES[hash % CLASSES.len()]
+}
+
+/// A tiny line chart of the prices in a history window, oldest on the left. Gaps
+/// squeezed out by sequence spacing are marked if `mark_gaps`, which is too busy for
+/// the smallest charts
+fn view_sparkline(
+    history: &VecDeque<Trade>,
+    change: f64,
+    width: f64,
+    height: f64,
+    spacing: AxisSpacing,
+    mark_gaps: bool,
+) -> Html {
+    if history.len() < 2 {
+        return html! {};
+    }
+    let axis = TimeAxis::new(history.iter().map(|t| t.time), spacing, width);
+    let prices: Vec<f64> = history.iter().map(|t| t.price.0.to_f64()).collect();
+    let min = prices.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = prices.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let points = history
+        .iter()
+        .rev()
+        .map(|t| {
+            let price = t.price.0.to_f64();
+            let y = if max > min {
+                height - (price - min) / (max - min) * height
+            } else {
+                height / 2.0
+            };
+            format!("{:.1},{:.1}", axis.x(t.time), y)
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    let class = if change > 0.0 {
+        "text-success"
+    } else if change < 0.0 {
+        "text-danger"
+    } else {
+        "text-muted"
+    };
+    html! {
+    <svg class=class width=width.to_string() style="max-width: 100%;" height=height.to_string() viewBox=format!("0 0 {} {}", width, height) preserveAspectRatio="none" aria-hidden="true">
+        <polyline points=points fill="none" stroke="currentColor" stroke-width="1.5" />
+        { if mark_gaps { view_gap_markers(&axis, height) } else { html! {} } }
+    </svg>
+    }
+}
+
+/// Dashed lines where time was skipped
+fn view_gap_markers(axis: &TimeAxis, height: f64) -> Html {
+    let marker = |x: f64| {
+        html! {
+        <line x1=x.to_string() x2=x.to_string() y1="0" y2=height.to_string()
+          stroke="currentColor" stroke-opacity="0.3" stroke-dasharray="2 3" />
+        }
+    };
+    html! {
+        { for axis.compressed_gaps(Duration::minutes(CHART_GAP_MINS)).into_iter().map(marker) }
+    }
+}
+
 #[wasm_bindgen(start)]
 pub fn run_app() {
-    App::<Model>::new().mount_to_body();
+    // the trade worker loads this same module, but has no window to mount to
+    if web_sys::window().is_some() {
+        App::<Model>::new().mount_to_body();
+    } else {
+        TradeWorker::register();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, TimeZone};
+    use ws::mock::MockConnector;
+
+    fn symbol(s: &str) -> Symbol {
+        Symbol(s.to_string())
+    }
+
+    fn trade(s: &str, price: i64) -> TickerInfo {
+        TickerInfo {
+            symbol: symbol(s),
+            price: Price(Decimal::new(price, 0)),
+            volume: Volume(Decimal::new(1, 0)),
+            time: Utc::now(),
+            delayed: false,
+        }
+    }
+
+    fn watchlist(symbols: &[&str]) -> State {
+        State {
+            tracked: symbols.iter().map(|s| symbol(s)).collect(),
+            ..State::default()
+        }
+    }
+
+    #[test]
+    fn totals_volumes_from_the_oldest_trade() {
+        let mut history = VecDeque::new();
+        for (price, volume, scale) in [(10, 15, 1), (11, 2, 0), (12, 25, 2)] {
+            history.push_front(Trade::from(TickerInfo {
+                volume: Volume(Decimal::new(volume, scale)),
+                ..trade("AAPL", price)
+            }));
+        }
+        let totals: Vec<String> = cumulative_volumes(&history)
+            .into_iter()
+            .map(|(total, scale)| format!("{:.*}", scale, total))
+            .collect();
+        assert_eq!(totals, vec!["3.75", "3.5", "1.5"]);
+        assert_eq!(history[0].notional(), Some(3.0));
+    }
+
+    #[test]
+    fn formats_prices_in_the_symbols_currency() {
+        let mut state = watchlist(&["7203.T", "OANDA:EUR_GBP", "BINANCE:BTCUSDT"]);
+        state.metadata.set_profile(
+            symbol("7203.T"),
+            CompanyProfile {
+                currency: Some("JPY".to_string()),
+                ..CompanyProfile::default()
+            },
+            Utc::now(),
+        );
+        let yen = state.price_format(&symbol("7203.T"));
+        assert_eq!(yen.header(), "Price (¥)");
+        assert_eq!(yen.format(Price(Decimal::new(2150, 0))), "2,150");
+        assert_eq!(
+            state.price_format(&symbol("OANDA:EUR_GBP")).header(),
+            "Price (£)"
+        );
+        assert_eq!(
+            state.price_format(&symbol("BINANCE:BTCUSDT")).header(),
+            "Price"
+        );
+        // decimals chosen in the settings round rather than only pad
+        state.settings.price_decimals.crypto = Some(2);
+        assert_eq!(
+            state
+                .price_format(&symbol("BINANCE:BTCUSDT"))
+                .format(Price(Decimal::new(91234567, 3))),
+            "91,234.57"
+        );
+        // no profile yet
+        assert_eq!(state.price_format(&symbol("AAPL")).header(), "Price");
+        // padded to the most decimals recent trades needed, without dropping any
+        state.settings.price_decimals.tick_size = true;
+        for (price, scale) in [(12345, 3), (123500, 4)] {
+            state.add_history(Trade::from(TickerInfo {
+                price: Price(Decimal::new(price, scale)),
+                ..trade("OANDA:EUR_GBP", 0)
+            }));
+        }
+        let pound = state.price_format(&symbol("OANDA:EUR_GBP"));
+        assert_eq!(pound.format(Price(Decimal::new(124, 2))), "1.240");
+        assert_eq!(pound.format(Price(Decimal::new(12346, 4))), "1.2346");
+    }
+
+    #[test]
+    fn untracking_the_last_entry_clears_its_data() {
+        let mut state = watchlist(&["AAPL", "MSFT"]);
+        state.add_history(trade("AAPL", 100).into());
+        state.paused.insert(symbol("AAPL"));
+        state
+            .symbol_groups
+            .insert(symbol("AAPL"), "Tech".to_string());
+        state.collapsed_groups.insert("Tech".to_string());
+        state
+            .notes
+            .insert(symbol("AAPL"), "earnings play".to_string());
+        state
+            .tags
+            .insert(symbol("AAPL"), parse_tags("swing, tech, swing"));
+
+        let result = state.untrack_symbol(0);
+        assert!(result.is_last);
+        assert_eq!(result.symbol, symbol("AAPL"));
+        assert!(result.tombstone.is_some());
+        assert_eq!(state.tracked, vec![symbol("MSFT")]);
+        assert!(state.history.get(&symbol("AAPL")).is_none());
+        assert!(!state.is_paused(&symbol("AAPL")));
+        assert!(state.symbol_groups.is_empty());
+        assert!(state.collapsed_groups.is_empty());
+        assert!(state.notes.is_empty());
+        assert!(state.all_tags().is_empty());
+        assert_eq!(
+            result.tombstone.and_then(|t| t.tags),
+            Some(vec!["swing".to_string(), "tech".to_string()])
+        );
+    }
+
+    #[test]
+    fn untracking_a_duplicate_keeps_its_data() {
+        let mut state = watchlist(&["AAPL", "AAPL"]);
+        state.add_history(trade("AAPL", 100).into());
+
+        let result = state.untrack_symbol(1);
+        assert!(!result.is_last);
+        assert!(result.tombstone.is_none());
+        assert!(state.history.get(&symbol("AAPL")).is_some());
+    }
+
+    #[test]
+    fn undoing_an_untrack_restores_the_entry() {
+        let mut state = watchlist(&["AAPL", "MSFT", "TSLA"]);
+        state.add_history(trade("MSFT", 180).into());
+        state.add_history(trade("MSFT", 181).into());
+        state.paused.insert(symbol("MSFT"));
+
+        let tombstone = state.untrack_symbol(1).tombstone.unwrap();
+        assert!(state.restore(tombstone));
+        assert_eq!(
+            state.tracked,
+            vec![symbol("AAPL"), symbol("MSFT"), symbol("TSLA")]
+        );
+        assert_eq!(state.history.get(&symbol("MSFT")).map(|h| h.len()), Some(2));
+        assert!(state.is_paused(&symbol("MSFT")));
+    }
+
+    #[test]
+    fn undo_is_skipped_once_tracked_again() {
+        let mut state = watchlist(&["AAPL"]);
+        let tombstone = state.untrack_symbol(0).tombstone.unwrap();
+        state.tracked.push(symbol("AAPL"));
+        assert!(!state.restore(tombstone));
+        assert_eq!(state.tracked, vec![symbol("AAPL")]);
+    }
+
+    #[test]
+    fn price_targets_are_reached_from_either_side() {
+        let price = |p| Price(Decimal::new(p, 0));
+        let target = PriceTarget {
+            target: price(100),
+            reference: None,
+        };
+        assert!(target.is_reached(Some(price(99)), price(100)));
+        assert!(target.is_reached(Some(price(101)), price(95)));
+        assert!(!target.is_reached(Some(price(100)), price(101)));
+        assert!(!target.is_reached(Some(price(90)), price(95)));
+        assert!(!target.is_reached(None, price(100)));
+    }
+
+    #[test]
+    fn badges_upcoming_earnings() {
+        let calendar: EarningsCalendar = serde_json::from_str(
+            r#"{"earningsCalendar":[
+                {"date":"2020-07-30","hour":"amc","symbol":"AAPL"},
+                {"date":"2020-06-01","hour":"","symbol":"AAPL"},
+                {"date":"2020-07-20","hour":"bmo","symbol":"AAPL"}
+            ]}"#,
+        )
+        .unwrap();
+        let now = Utc.ymd(2020, 7, 17).and_hms(12, 0, 0);
+        let cache = EarningsCache::new(calendar, now);
+        assert_eq!(
+            cache.next.as_ref().map(|r| r.date),
+            NaiveDate::from_ymd_opt(2020, 7, 20)
+        );
+        assert_eq!(
+            cache.badge_label(now).as_deref(),
+            Some("Earnings in 3 days")
+        );
+        assert_eq!(
+            cache.badge_label(now + Duration::days(2)).as_deref(),
+            Some("Earnings tomorrow")
+        );
+        assert_eq!(cache.badge_label(now - Duration::days(20)), None);
+        assert!(!cache.is_stale(now + Duration::hours(23)));
+        assert!(cache.is_stale(now + Duration::days(1)));
+    }
+
+    #[test]
+    fn compares_from_the_latest_common_start() {
+        let at = |s, price, secs| TickerInfo {
+            time: Utc.timestamp(secs, 0),
+            ..trade(s, price)
+        };
+        // newest first, as histories are kept
+        let aapl: VecDeque<Trade> =
+            vec![at("AAPL", 125, 30), at("AAPL", 100, 20), at("AAPL", 90, 10)]
+                .into_iter()
+                .map(Trade::from)
+                .collect();
+        let msft: VecDeque<Trade> = vec![at("MSFT", 150, 40), at("MSFT", 200, 20)]
+            .into_iter()
+            .map(Trade::from)
+            .collect();
+        let (baseline, series) = percent_change_series(&[&aapl, &msft]).unwrap();
+        assert_eq!(baseline, Utc.timestamp(20, 0));
+        assert_eq!(
+            series,
+            vec![
+                vec![(baseline, 0.0), (Utc.timestamp(30, 0), 25.0)],
+                vec![(baseline, 0.0), (Utc.timestamp(40, 0), -25.0)],
+            ]
+        );
+        assert_eq!(percent_change_series(&[&aapl, &VecDeque::new()]), None);
+    }
+
+    #[test]
+    fn counts_upticks() {
+        let mut counts = TickCounts::default();
+        assert_eq!(counts.uptick_ratio(), None);
+        for direction in [
+            PriceDirection::Up,
+            PriceDirection::Unchanged,
+            PriceDirection::Up,
+            PriceDirection::Down,
+            PriceDirection::Up,
+        ] {
+            counts.record(direction);
+        }
+        assert_eq!(counts.uptick_ratio(), Some(0.75));
+    }
+
+    #[test]
+    fn filters_cards_by_symbol_or_tag() {
+        let mut state = watchlist(&["AAPL", "MSFT"]);
+        state.tags.insert(symbol("MSFT"), vec!["Swing".to_string()]);
+        assert!(state.matches_query(&symbol("AAPL"), ""));
+        assert!(state.matches_query(&symbol("AAPL"), " ap "));
+        assert!(!state.matches_query(&symbol("AAPL"), "swing"));
+        assert!(state.matches_query(&symbol("MSFT"), "swing"));
+    }
+
+    #[test]
+    fn flags_new_session_extremes() {
+        let price = |p| Price(Decimal::new(p, 0));
+        let mut range = SessionRange::new(price(100));
+        assert_eq!(range.update(price(100)), None);
+        assert_eq!(range.update(price(101)), Some(SessionExtreme::High));
+        assert_eq!(range.update(price(99)), Some(SessionExtreme::Low));
+        assert_eq!(range.update(price(100)), None);
+        assert_eq!(range.update(price(101)), None);
+    }
+
+    #[test]
+    fn undoing_untrack_all_restores_the_order() {
+        let mut state = watchlist(&["AAPL", "MSFT", "TSLA"]);
+        state.add_history(trade("TSLA", 700).into());
+        let tombstones: Vec<Tombstone> = (0..state.tracked.len())
+            .rev()
+            .filter_map(|idx| state.untrack_symbol(idx).tombstone)
+            .collect();
+        assert!(state.tracked.is_empty());
+
+        state.tracked.push(symbol("MSFT"));
+        let restored = state.restore_all(tombstones);
+        assert_eq!(restored, vec![symbol("AAPL"), symbol("TSLA")]);
+        assert_eq!(
+            state.tracked,
+            vec![symbol("AAPL"), symbol("MSFT"), symbol("TSLA")]
+        );
+        assert_eq!(state.history.get(&symbol("TSLA")).map(|h| h.len()), Some(1));
+    }
+
+    #[test]
+    fn card_sizes_cycle_within_the_dashboard() {
+        let size = CardSize::default();
+        assert_eq!(size.wider(3).wider(3), CardSize { cols: 3, rows: 1 });
+        assert_eq!(size.wider(3).wider(3).wider(3), size);
+        assert_eq!(size.taller().taller().taller(), size);
+        // a card wider than the dashboard has become is only as wide as it
+        let wide = CardSize { cols: 4, rows: 2 };
+        assert_eq!(wide.grid_style(2), "grid-column: span 2; grid-row: span 2;");
+        assert_eq!(wide.wider(2).cols, 1);
+    }
+
+    #[test]
+    fn salvages_the_watchlist_from_an_unreadable_state() {
+        let saved = r#"{"version":1,"state":{"tracked":["AAPL",7,"MSFT"],"settings":{"theme":"Sepia"},"paused":"AAPL"}}"#;
+        match migrations::restore(saved, State::salvage).unwrap() {
+            Restored::Salvaged { state, .. } => {
+                assert_eq!(state.tracked, watchlist(&["AAPL", "MSFT"]).tracked);
+                assert!(state.settings.theme == Theme::default());
+            }
+            Restored::Upgraded(_) => panic!("Expected the state to be salvaged"),
+        }
+    }
+
+    #[test]
+    fn keeps_history_in_order_without_duplicates() {
+        let mut history = TickerHistory::new();
+        let at = |secs: i64, price: i64| TickerInfo {
+            time: Utc.timestamp(secs, 0),
+            ..trade("AAPL", price)
+        };
+        assert!(history.insert(at(10, 100).into()));
+        assert!(history.insert(at(30, 102).into()));
+        // resent after a reconnect
+        assert!(!history.insert(at(30, 102).into()));
+        assert!(history.contains(&at(10, 100)));
+        // late, and a different print at the same time
+        assert!(history.insert(at(20, 101).into()));
+        assert!(history.insert(at(30, 103).into()));
+        let prices: Vec<Price> = history
+            .get(&symbol("AAPL"))
+            .unwrap()
+            .iter()
+            .map(|t| t.price)
+            .collect();
+        assert_eq!(
+            prices,
+            [103, 102, 101, 100].map(|p| Price(Decimal::new(p, 0)))
+        );
+        assert_eq!(history.stats(&symbol("AAPL")).map(|s| s.count()), Some(4));
+    }
+
+    #[test]
+    fn measures_change_since_the_oldest_trade() {
+        let mut state = watchlist(&["AAPL"]);
+        assert_eq!(state.change_percent(&symbol("AAPL")), None);
+        for (secs, price) in [(0, 200), (60, 190), (120, 210)] {
+            state.add_history(Trade::from(TickerInfo {
+                time: Utc.timestamp(secs, 0),
+                ..trade("AAPL", price)
+            }));
+        }
+        assert_eq!(state.change_percent(&symbol("AAPL")), Some(5.0));
+    }
+
+    #[test]
+    fn backfills_only_from_before_live_trades() {
+        let mut state = watchlist(&["AAPL"]);
+        let live = trade("AAPL", 110);
+        let started = live.time;
+        state.add_history(live.into());
+        let candle = |mins: i64, price| TickerInfo {
+            time: started - Duration::minutes(mins),
+            ..trade("AAPL", price)
+        };
+        let backfilled = state.history.backfill(
+            symbol("AAPL"),
+            vec![
+                candle(2, 100),
+                candle(1, 105),
+                TickerInfo {
+                    time: started - Duration::seconds(30),
+                    ..trade("AAPL", 108)
+                },
+                candle(0, 109),
+            ],
+        );
+        assert_eq!(backfilled, 2);
+        let prices: Vec<Price> = state
+            .history
+            .get(&symbol("AAPL"))
+            .unwrap()
+            .iter()
+            .map(|t| t.price)
+            .collect();
+        assert_eq!(
+            prices,
+            vec![110, 105, 100]
+                .into_iter()
+                .map(|p| Price(Decimal::new(p, 0)))
+                .collect::<Vec<Price>>()
+        );
+        assert_eq!(
+            state.history.stats(&symbol("AAPL")).map(|s| s.count()),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn sorts_only_the_newest_page_of_trades() {
+        let history: VecDeque<Trade> = [3, 1, 4, 1, 5]
+            .iter()
+            .map(|price| Trade::from(trade("AAPL", *price)))
+            .collect();
+        let by_price = TableSort::default().toggled(SortColumn::Price);
+        let indices = |rows: Vec<(usize, &Trade)>| -> Vec<usize> {
+            rows.into_iter().map(|(idx, _)| idx).collect()
+        };
+        assert_eq!(indices(by_price.sorted(&history, 3)), vec![2, 0, 1]);
+        assert_eq!(
+            indices(TableSort::default().sorted(&history, 10)),
+            vec![0, 1, 2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn sorts_delayed_quotes_last_by_notional() {
+        let mut delayed = trade("AAPL", 9);
+        delayed.delayed = true;
+        let history: VecDeque<Trade> = vec![trade("AAPL", 1), delayed, trade("AAPL", 2)]
+            .into_iter()
+            .map(Trade::from)
+            .collect();
+        let indices = |sort: TableSort| -> Vec<usize> {
+            sort.sorted(&history, 10)
+                .into_iter()
+                .map(|(idx, _)| idx)
+                .collect()
+        };
+        let descending = TableSort::default().toggled(SortColumn::Notional);
+        assert_eq!(indices(descending), vec![2, 0, 1]);
+        assert_eq!(
+            indices(descending.toggled(SortColumn::Notional)),
+            vec![0, 2, 1]
+        );
+    }
+
+    #[test]
+    fn saves_trades_as_finnhub_sent_them() {
+        let mut trade = Trade::from(trade("AAPL", 100));
+        trade.direction = Some(PriceDirection::Up);
+        trade.spike = true;
+        let json = serde_json::to_string(&trade).unwrap();
+        assert_eq!(json, serde_json::to_string(&trade.info).unwrap());
+        let restored: Trade = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.price, trade.price);
+        assert_eq!(restored.direction, None);
+    }
+
+    #[test]
+    fn moves_saved_profiles_into_the_metadata_cache() {
+        let saved = r#"{"version":1,"state":{"tracked":["AAPL"],"profiles":{"AAPL":{"name":"Apple Inc"}}}}"#;
+        let state = match migrations::restore(saved, State::salvage).unwrap() {
+            Restored::Upgraded(state) => state,
+            Restored::Salvaged { error, .. } => panic!("Expected an upgrade, got [{}]", error),
+        };
+        let aapl = symbol("AAPL");
+        assert_eq!(
+            state.metadata.profile(&aapl).and_then(|p| p.name.clone()),
+            Some("Apple Inc".to_string())
+        );
+        // to be fetched again
+        assert!(state.metadata.profile_is_stale(&aapl, Utc::now()));
+        // and kept out of the state from then on
+        assert!(!migrations::save(&state).unwrap().contains("Apple Inc"));
+    }
+
+    #[test]
+    fn keeps_the_heartbeat_timeout_above_normal_gaps() {
+        let saved =
+            r#"{"version":2,"state":{"tracked":["AAPL"],"settings":{"heartbeat_timeout_secs":2}}}"#;
+        let mut state = match migrations::restore(saved, State::salvage).unwrap() {
+            Restored::Upgraded(state) => state,
+            Restored::Salvaged { error, .. } => panic!("Expected an upgrade, got [{}]", error),
+        };
+        state.normalise();
+        assert_eq!(
+            state.settings.heartbeat_timeout_secs,
+            MIN_HEARTBEAT_TIMEOUT_SECS
+        );
+    }
+
+    #[test]
+    fn keeps_legacy_history_in_the_saved_state_until_moved() {
+        let mut state = watchlist(&["AAPL"]);
+        state.add_history(trade("AAPL", 100).into());
+        let with_history = migrations::save(&LegacyHistoryState {
+            state: &state,
+            history: &state.history,
+        })
+        .unwrap();
+        let restored = match migrations::restore(&with_history, State::salvage).unwrap() {
+            Restored::Upgraded(state) => state,
+            Restored::Salvaged { error, .. } => panic!("Expected an upgrade, got [{}]", error),
+        };
+        assert_eq!(
+            restored.history.get(&symbol("AAPL")).map(|h| h.len()),
+            Some(1)
+        );
+        assert_eq!(restored.tracked, state.tracked);
+
+        let without = migrations::save(&state).unwrap();
+        let restored = match migrations::restore(&without, State::salvage).unwrap() {
+            Restored::Upgraded(state) => state,
+            Restored::Salvaged { error, .. } => panic!("Expected an upgrade, got [{}]", error),
+        };
+        assert!(restored.history.get(&symbol("AAPL")).is_none());
+    }
+
+    #[test]
+    fn only_messages_that_change_cards_redraw_them() {
+        let aapl = symbol("AAPL");
+        assert_eq!(Msg::PersistDirty.cards_changed(), CardsChanged::None);
+        assert_eq!(Msg::TabSyncTick.cards_changed(), CardsChanged::None);
+        assert_eq!(Msg::HeartbeatCheck.cards_changed(), CardsChanged::None);
+        assert_eq!(
+            Msg::SelectCardTab(aapl.clone(), CardTab::News).cards_changed(),
+            CardsChanged::One(&aapl)
+        );
+        assert_eq!(
+            Msg::ShowMoreTrades(aapl.clone()).cards_changed(),
+            CardsChanged::One(&aapl)
+        );
+        assert_eq!(
+            Msg::ThemeUpdate(Theme::Dark).cards_changed(),
+            CardsChanged::All
+        );
+        assert_eq!(Msg::StaleCheck.cards_changed(), CardsChanged::All);
+    }
+
+    fn connected_session(connector: &MockConnector) -> Session {
+        let mut session = Session::new(connector.connection(), 50);
+        session.opened(Utc::now());
+        session
+    }
+
+    // what `reconcile_subscriptions` does after each message changes things
+    fn reconcile(
+        session: &mut Session,
+        state: &State,
+        hidden_pause: bool,
+        symbol_errors: &HashMap<Symbol, SymbolError>,
+    ) {
+        session.reconcile(
+            state.wanted_subscriptions(hidden_pause, symbol_errors),
+            Utc::now(),
+        );
+    }
+
+    fn subscribe(s: &str) -> Request {
+        Request::Subscribe { symbol: symbol(s) }
+    }
+
+    fn unsubscribe(s: &str) -> Request {
+        Request::Unsubscribe { symbol: symbol(s) }
+    }
+
+    #[test]
+    fn subscribes_to_the_watchlist_once_open_but_not_to_paused_symbols() {
+        let connector = MockConnector::default();
+        let mut session = Session::new(connector.connection(), 50);
+        let mut state = watchlist(&["AAPL", "MSFT", "TSLA"]);
+        state.paused.insert(symbol("MSFT"));
+        let no_errors = HashMap::new();
+        // WsOpened, with the watchlist reconciled while still connecting
+        reconcile(&mut session, &state, false, &no_errors);
+        assert!(connector.sent_requests().is_empty());
+        session.opened(Utc::now());
+        assert_eq!(
+            connector.sent_requests(),
+            vec![subscribe("AAPL"), subscribe("TSLA")]
+        );
+    }
+
+    #[test]
+    fn untracking_unsubscribes_only_from_the_last_of_a_symbol_streaming() {
+        let connector = MockConnector::default();
+        let mut session = connected_session(&connector);
+        let mut state = watchlist(&["AAPL", "MSFT", "AAPL", "TSLA"]);
+        state.paused.insert(symbol("TSLA"));
+        let no_errors = HashMap::new();
+        reconcile(&mut session, &state, false, &no_errors);
+        connector.sent.borrow_mut().clear();
+
+        // UnTrackSymbolAtIdx, on one AAPL of two
+        state.untrack_symbol(0);
+        reconcile(&mut session, &state, false, &no_errors);
+        assert!(connector.sent_requests().is_empty());
+        // then on the other
+        state.untrack_symbol(1);
+        reconcile(&mut session, &state, false, &no_errors);
+        assert_eq!(connector.sent_requests(), vec![unsubscribe("AAPL")]);
+        // paused, so there's nothing to unsubscribe from
+        state.untrack_symbol(1);
+        reconcile(&mut session, &state, false, &no_errors);
+        assert_eq!(connector.sent_requests(), vec![unsubscribe("AAPL")]);
+        assert_eq!(session.subscribed_count(), 1);
+    }
+
+    #[test]
+    fn pausing_and_resuming_go_through_reconciling() {
+        let connector = MockConnector::default();
+        let mut session = connected_session(&connector);
+        let mut state = watchlist(&["AAPL", "MSFT"]);
+        let mut symbol_errors = HashMap::new();
+        reconcile(&mut session, &state, false, &symbol_errors);
+        connector.sent.borrow_mut().clear();
+
+        // PauseSymbol then ResumeSymbol
+        state.paused.insert(symbol("AAPL"));
+        reconcile(&mut session, &state, false, &symbol_errors);
+        state.paused.remove(&symbol("AAPL"));
+        reconcile(&mut session, &state, false, &symbol_errors);
+        assert_eq!(
+            connector.sent_requests(),
+            vec![unsubscribe("AAPL"), subscribe("AAPL")]
+        );
+        connector.sent.borrow_mut().clear();
+
+        // finnhub rejects MSFT, which then stays unsubscribed through a pause and resume
+        symbol_errors.insert(symbol("MSFT"), SymbolError::Invalid);
+        reconcile(&mut session, &state, false, &symbol_errors);
+        state.paused.insert(symbol("MSFT"));
+        reconcile(&mut session, &state, false, &symbol_errors);
+        state.paused.remove(&symbol("MSFT"));
+        reconcile(&mut session, &state, false, &symbol_errors);
+        assert_eq!(connector.sent_requests(), vec![unsubscribe("MSFT")]);
+        connector.sent.borrow_mut().clear();
+
+        // HiddenTooLong, with a resume while hidden that mustn't subscribe
+        reconcile(&mut session, &state, true, &symbol_errors);
+        state.paused.insert(symbol("AAPL"));
+        reconcile(&mut session, &state, true, &symbol_errors);
+        state.paused.remove(&symbol("AAPL"));
+        reconcile(&mut session, &state, true, &symbol_errors);
+        assert_eq!(connector.sent_requests(), vec![unsubscribe("AAPL")]);
+        // and visible again
+        reconcile(&mut session, &state, false, &symbol_errors);
+        assert_eq!(
+            connector.sent_requests(),
+            vec![unsubscribe("AAPL"), subscribe("AAPL")]
+        );
+    }
+
+    #[test]
+    fn routes_round_trip_through_the_hash() {
+        for route in Route::ALL.iter().copied() {
+            assert_eq!(Route::from_hash(route.hash()), route);
+        }
+        assert_eq!(Route::from_hash(""), Route::Watchlist);
+        assert_eq!(Route::from_hash("#/nowhere"), Route::Watchlist);
+        assert_eq!(Route::from_hash("#/Settings"), Route::Watchlist);
+    }
 }
@@ -0,0 +1,123 @@
+use yew::services::ConsoleService;
+
+const REDACTED: &str = "[redacted]";
+
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug, Default)]
+pub enum LogLevel {
+    /// Everything, including every message received
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+/// Logs to the browser console, leaving out anything below `level` and scrubbing
+/// the API key from whatever does get logged
+pub struct Logger {
+    console: ConsoleService,
+    level: LogLevel,
+    secret: String,
+}
+
+impl Logger {
+    pub fn new(level: LogLevel) -> Logger {
+        Logger {
+            console: ConsoleService::new(),
+            level,
+            secret: String::new(),
+        }
+    }
+
+    pub fn set_level(&mut self, level: LogLevel) {
+        self.level = level;
+    }
+
+    /// The API key, which then never makes it to the console
+    pub fn set_secret(&mut self, secret: &str) {
+        self.secret = secret.to_string();
+    }
+
+    pub fn debug(&mut self, message: impl AsRef<str>) {
+        if self.level <= LogLevel::Debug {
+            let message = redact(message.as_ref(), &self.secret);
+            self.console.debug(&message);
+        }
+    }
+
+    pub fn info(&mut self, message: impl AsRef<str>) {
+        if self.level <= LogLevel::Info {
+            let message = redact(message.as_ref(), &self.secret);
+            self.console.info(&message);
+        }
+    }
+
+    pub fn warn(&mut self, message: impl AsRef<str>) {
+        if self.level <= LogLevel::Warn {
+            let message = redact(message.as_ref(), &self.secret);
+            self.console.warn(&message);
+        }
+    }
+
+    pub fn error(&mut self, message: impl AsRef<str>) {
+        if self.level <= LogLevel::Error {
+            let message = redact(message.as_ref(), &self.secret);
+            self.console.error(&message);
+        }
+    }
+}
+
+/// Blanks out `secret` wherever it appears, along with the value of any `token`
+/// query parameter in case it's a key we weren't told about
+pub fn redact(message: &str, secret: &str) -> String {
+    let message = if secret.is_empty() {
+        message.to_string()
+    } else {
+        message.replace(secret, REDACTED)
+    };
+    let mut redacted = String::with_capacity(message.len());
+    let mut rest = &message[..];
+    while let Some(idx) = rest.find("token=") {
+        let (before, after) = rest.split_at(idx + "token=".len());
+        redacted.push_str(before);
+        let end = after
+            .find(|c: char| c == '&' || c == '"' || c == '\'' || c.is_whitespace())
+            .unwrap_or(after.len());
+        if end > 0 && &after[..end] != REDACTED {
+            redacted.push_str(REDACTED);
+        } else {
+            redacted.push_str(&after[..end]);
+        }
+        rest = &after[end..];
+    }
+    redacted.push_str(rest);
+    redacted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_the_secret_and_token_params() {
+        assert_eq!(
+            redact("Could not connect to wss://ws.finnhub.io?token=abc123", ""),
+            "Could not connect to wss://ws.finnhub.io?token=[redacted]"
+        );
+        assert_eq!(
+            redact("GET /quote?symbol=AAPL&token=abc123&x=1 failed", "abc123"),
+            "GET /quote?symbol=AAPL&token=[redacted]&x=1 failed"
+        );
+        assert_eq!(
+            redact("key abc123 was rejected", "abc123"),
+            "key [redacted] was rejected"
+        );
+        assert_eq!(redact("nothing to see", "abc123"), "nothing to see");
+    }
+
+    #[test]
+    fn orders_levels_by_severity() {
+        assert!(LogLevel::Debug < LogLevel::Info);
+        assert!(LogLevel::Warn < LogLevel::Error);
+    }
+}
@@ -0,0 +1,280 @@
+use crate::{Symbol, SymbolKind};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Timelike, Utc, Weekday};
+
+/// Where in its trading day a market is
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MarketSession {
+    PreMarket,
+    Regular,
+    AfterHours,
+    Closed,
+}
+
+impl MarketSession {
+    /// Short label for trades outside regular hours
+    pub fn label(self) -> Option<&'static str> {
+        match self {
+            MarketSession::PreMarket => Some("pre-market"),
+            MarketSession::AfterHours => Some("after hours"),
+            MarketSession::Regular | MarketSession::Closed => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum DaylightSaving {
+    None,
+    /// Second Sunday of March to the first Sunday of November
+    Us,
+    /// Last Sunday of March to the last Sunday of October
+    Eu,
+}
+
+/// A stock exchange's trading day in its own local time, as minutes since midnight.
+/// Lunch breaks and holidays aren't accounted for
+struct Market {
+    /// Suffix finnhub puts on the exchange's symbols, e.g. `.L` for `VOD.L`
+    suffix: &'static str,
+    utc_offset_mins: i64,
+    daylight_saving: DaylightSaving,
+    pre_market: u32,
+    open: u32,
+    close: u32,
+    after_hours: u32,
+}
+
+const fn hm(hours: u32, mins: u32) -> u32 {
+    hours * 60 + mins
+}
+
+const fn market(
+    suffix: &'static str,
+    utc_offset_mins: i64,
+    daylight_saving: DaylightSaving,
+    open: u32,
+    close: u32,
+) -> Market {
+    Market {
+        suffix,
+        utc_offset_mins,
+        daylight_saving,
+        pre_market: open,
+        open,
+        close,
+        after_hours: close,
+    }
+}
+
+/// US listings have no suffix, and are the only ones with extended hours
+const US: Market = Market {
+    suffix: "",
+    utc_offset_mins: -5 * 60,
+    daylight_saving: DaylightSaving::Us,
+    pre_market: hm(4, 0),
+    open: hm(9, 30),
+    close: hm(16, 0),
+    after_hours: hm(20, 0),
+};
+
+const MARKETS: &[Market] = &[
+    market(".TO", -5 * 60, DaylightSaving::Us, hm(9, 30), hm(16, 0)),
+    market(".L", 0, DaylightSaving::Eu, hm(8, 0), hm(16, 30)),
+    market(".DE", 60, DaylightSaving::Eu, hm(9, 0), hm(17, 30)),
+    market(".F", 60, DaylightSaving::Eu, hm(8, 0), hm(20, 0)),
+    market(".PA", 60, DaylightSaving::Eu, hm(9, 0), hm(17, 30)),
+    market(".AS", 60, DaylightSaving::Eu, hm(9, 0), hm(17, 30)),
+    market(".MI", 60, DaylightSaving::Eu, hm(9, 0), hm(17, 30)),
+    market(".MC", 60, DaylightSaving::Eu, hm(9, 0), hm(17, 30)),
+    market(".SW", 60, DaylightSaving::Eu, hm(9, 0), hm(17, 30)),
+    market(".T", 9 * 60, DaylightSaving::None, hm(9, 0), hm(15, 0)),
+    market(".HK", 8 * 60, DaylightSaving::None, hm(9, 30), hm(16, 0)),
+    market(".SS", 8 * 60, DaylightSaving::None, hm(9, 30), hm(15, 0)),
+    market(".SZ", 8 * 60, DaylightSaving::None, hm(9, 30), hm(15, 0)),
+    market(
+        ".NS",
+        5 * 60 + 30,
+        DaylightSaving::None,
+        hm(9, 15),
+        hm(15, 30),
+    ),
+    market(
+        ".BO",
+        5 * 60 + 30,
+        DaylightSaving::None,
+        hm(9, 15),
+        hm(15, 30),
+    ),
+];
+
+/// Forex trades around the clock from Sunday to Friday 17:00 in New York
+const FOREX_CLOSE: u32 = hm(17, 0);
+
+/// The nth (1-based) `weekday` of a month, or the last one for `n == 0`
+fn nth_weekday(year: i32, month: u32, weekday: Weekday, n: u32) -> Option<NaiveDate> {
+    let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+    let offset = (7 + weekday.num_days_from_monday() - first.weekday().num_days_from_monday()) % 7;
+    let first_match = first + Duration::days(i64::from(offset));
+    if n > 0 {
+        let date = first_match + Duration::weeks(i64::from(n - 1));
+        Some(date).filter(|d| d.month() == month)
+    } else {
+        let mut date = first_match;
+        while (date + Duration::weeks(1)).month() == month {
+            date += Duration::weeks(1);
+        }
+        Some(date)
+    }
+}
+
+impl DaylightSaving {
+    /// Good to the day, which is plenty given nothing trades at 2am on a Sunday
+    fn applies(self, date: NaiveDate) -> bool {
+        let (start, end) = match self {
+            DaylightSaving::None => return false,
+            DaylightSaving::Us => (
+                nth_weekday(date.year(), 3, Weekday::Sun, 2),
+                nth_weekday(date.year(), 11, Weekday::Sun, 1),
+            ),
+            DaylightSaving::Eu => (
+                nth_weekday(date.year(), 3, Weekday::Sun, 0),
+                nth_weekday(date.year(), 10, Weekday::Sun, 0),
+            ),
+        };
+        match (start, end) {
+            (Some(start), Some(end)) => date >= start && date < end,
+            _ => false,
+        }
+    }
+}
+
+impl Market {
+    fn for_symbol(symbol: &Symbol) -> Option<&'static Market> {
+        match symbol.0.rfind('.') {
+            // class shares like BRK.B are US listings too
+            Some(idx) => MARKETS
+                .iter()
+                .find(|m| m.suffix.eq_ignore_ascii_case(&symbol.0[idx..]))
+                .or_else(|| Some(&US).filter(|_| symbol.0.len() - idx <= 2)),
+            None => Some(&US),
+        }
+    }
+
+    fn session(&self, at: DateTime<Utc>) -> MarketSession {
+        let (date, mins) = local_time(at, self.utc_offset_mins, self.daylight_saving);
+        let weekend = date.weekday() == Weekday::Sat || date.weekday() == Weekday::Sun;
+        if weekend || mins < self.pre_market || mins >= self.after_hours {
+            MarketSession::Closed
+        } else if mins < self.open {
+            MarketSession::PreMarket
+        } else if mins < self.close {
+            MarketSession::Regular
+        } else {
+            MarketSession::AfterHours
+        }
+    }
+}
+
+/// The local date and minutes since midnight
+fn local_time(
+    at: DateTime<Utc>,
+    utc_offset_mins: i64,
+    daylight_saving: DaylightSaving,
+) -> (NaiveDate, u32) {
+    let standard = at + Duration::minutes(utc_offset_mins);
+    let local = if daylight_saving.applies(standard.naive_utc().date()) {
+        standard + Duration::hours(1)
+    } else {
+        standard
+    };
+    (local.naive_utc().date(), local.hour() * 60 + local.minute())
+}
+
+fn forex_session(at: DateTime<Utc>) -> MarketSession {
+    let (date, mins) = local_time(at, US.utc_offset_mins, DaylightSaving::Us);
+    let closed = match date.weekday() {
+        Weekday::Fri => mins >= FOREX_CLOSE,
+        Weekday::Sat => true,
+        Weekday::Sun => mins < FOREX_CLOSE,
+        _ => false,
+    };
+    if closed {
+        MarketSession::Closed
+    } else {
+        MarketSession::Regular
+    }
+}
+
+/// `None` for symbols that trade around the clock, or whose market we don't know
+pub fn session(symbol: &Symbol, at: DateTime<Utc>) -> Option<MarketSession> {
+    if symbol.is_demo() {
+        return None;
+    }
+    match symbol.kind() {
+        SymbolKind::Crypto => None,
+        SymbolKind::Forex => Some(forex_session(at)),
+        SymbolKind::Stock => Market::for_symbol(symbol).map(|m| m.session(at)),
+    }
+}
+
+pub fn is_closed(symbol: &Symbol, at: DateTime<Utc>) -> bool {
+    session(symbol, at) == Some(MarketSession::Closed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn symbol(s: &str) -> Symbol {
+        Symbol(s.to_string())
+    }
+
+    #[test]
+    fn finds_daylight_saving_boundaries() {
+        assert_eq!(
+            nth_weekday(2020, 3, Weekday::Sun, 2),
+            NaiveDate::from_ymd_opt(2020, 3, 8)
+        );
+        assert_eq!(
+            nth_weekday(2020, 10, Weekday::Sun, 0),
+            NaiveDate::from_ymd_opt(2020, 10, 25)
+        );
+    }
+
+    #[test]
+    fn labels_us_sessions() {
+        // a Wednesday in summer, so New York is UTC-4
+        let at = |h, m| Utc.ymd(2020, 6, 17).and_hms(h, m, 0);
+        let aapl = symbol("AAPL");
+        assert_eq!(session(&aapl, at(7, 59)), Some(MarketSession::Closed));
+        assert_eq!(session(&aapl, at(8, 0)), Some(MarketSession::PreMarket));
+        assert_eq!(session(&aapl, at(13, 30)), Some(MarketSession::Regular));
+        assert_eq!(session(&aapl, at(20, 0)), Some(MarketSession::AfterHours));
+        assert_eq!(
+            session(&symbol("BRK.B"), at(13, 30)),
+            Some(MarketSession::Regular)
+        );
+        // and in winter, UTC-5
+        let winter = Utc.ymd(2020, 1, 15).and_hms(14, 0, 0);
+        assert_eq!(session(&aapl, winter), Some(MarketSession::PreMarket));
+    }
+
+    #[test]
+    fn knows_other_markets_and_weekends() {
+        let wednesday = Utc.ymd(2020, 6, 17).and_hms(10, 0, 0);
+        let saturday = Utc.ymd(2020, 6, 20).and_hms(10, 0, 0);
+        assert_eq!(
+            session(&symbol("VOD.L"), wednesday),
+            Some(MarketSession::Regular)
+        );
+        assert_eq!(
+            session(&symbol("7203.T"), wednesday),
+            Some(MarketSession::Closed)
+        );
+        assert!(is_closed(&symbol("VOD.L"), saturday));
+        assert!(is_closed(&symbol("OANDA:EUR_USD"), saturday));
+        assert!(!is_closed(&symbol("OANDA:EUR_USD"), wednesday));
+        assert_eq!(session(&symbol("BINANCE:BTCUSDT"), saturday), None);
+        assert_eq!(session(&symbol("FOO.XYZ"), wednesday), None);
+    }
+}
@@ -0,0 +1,181 @@
+use crate::currency::FxRates;
+use crate::finnhub::{CompanyProfile, Symbol};
+use crate::symbol_list::SymbolList;
+use chrono::serde::ts_seconds;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Company details hardly ever change
+const PROFILE_TTL_HOURS: i64 = 7 * 24;
+/// Listings change a few times a day at most
+const SYMBOL_LIST_TTL_HOURS: i64 = 24;
+/// Rates are refreshed on an interval while the page is open anyway; this only saves
+/// asking again straight after a reload
+const FX_RATES_TTL_MINS: i64 = 5;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+struct Cached<T> {
+    value: T,
+    #[serde(with = "ts_seconds")]
+    fetched_at: DateTime<Utc>,
+}
+
+impl<T> Cached<T> {
+    fn new(value: T, now: DateTime<Utc>) -> Cached<T> {
+        Cached {
+            value,
+            fetched_at: now,
+        }
+    }
+}
+
+/// Whether there's nothing under `key`, or it's older than `ttl`
+fn is_stale<K: Eq + Hash, T>(
+    entries: &HashMap<K, Cached<T>>,
+    key: &K,
+    ttl: Duration,
+    now: DateTime<Utc>,
+) -> bool {
+    entries
+        .get(key)
+        .is_none_or(|cached| now - cached.fetched_at >= ttl)
+}
+
+/// What's fetched from REST about symbols and currencies rather than about trades,
+/// kept between visits for as long as it's good for. Stale entries are still used
+/// until they've been fetched again
+#[derive(Deserialize, Serialize, Default, Debug)]
+#[serde(default)]
+pub struct MetadataCache {
+    profiles: HashMap<Symbol, Cached<CompanyProfile>>,
+    /// By exchange
+    symbol_lists: HashMap<String, Cached<SymbolList>>,
+    /// By base currency
+    fx_rates: HashMap<String, Cached<FxRates>>,
+}
+
+impl MetadataCache {
+    pub fn profile(&self, symbol: &Symbol) -> Option<&CompanyProfile> {
+        self.profiles.get(symbol).map(|cached| &cached.value)
+    }
+
+    pub fn profile_is_stale(&self, symbol: &Symbol, now: DateTime<Utc>) -> bool {
+        is_stale(
+            &self.profiles,
+            symbol,
+            Duration::hours(PROFILE_TTL_HOURS),
+            now,
+        )
+    }
+
+    pub fn set_profile(&mut self, symbol: Symbol, profile: CompanyProfile, now: DateTime<Utc>) {
+        self.profiles.insert(symbol, Cached::new(profile, now));
+    }
+
+    /// Drops the stale profiles of symbols that aren't wanted any more, e.g. untracked
+    pub fn prune_profiles(&mut self, keep: impl Fn(&Symbol) -> bool, now: DateTime<Utc>) {
+        let ttl = Duration::hours(PROFILE_TTL_HOURS);
+        self.profiles
+            .retain(|symbol, cached| keep(symbol) || now - cached.fetched_at < ttl);
+    }
+
+    pub fn symbol_list(&self, exchange: &str) -> Option<&SymbolList> {
+        self.symbol_lists.get(exchange).map(|cached| &cached.value)
+    }
+
+    pub fn symbol_list_is_stale(&self, exchange: &str, now: DateTime<Utc>) -> bool {
+        is_stale(
+            &self.symbol_lists,
+            &exchange.to_string(),
+            Duration::hours(SYMBOL_LIST_TTL_HOURS),
+            now,
+        )
+    }
+
+    pub fn set_symbol_list(&mut self, exchange: &str, list: SymbolList, now: DateTime<Utc>) {
+        self.symbol_lists
+            .insert(exchange.to_string(), Cached::new(list, now));
+    }
+
+    pub fn fx_rates(&self, base: &str) -> Option<&FxRates> {
+        self.fx_rates.get(base).map(|cached| &cached.value)
+    }
+
+    pub fn fx_rates_are_stale(&self, base: &str, now: DateTime<Utc>) -> bool {
+        is_stale(
+            &self.fx_rates,
+            &base.to_string(),
+            Duration::minutes(FX_RATES_TTL_MINS),
+            now,
+        )
+    }
+
+    pub fn set_fx_rates(&mut self, rates: FxRates, now: DateTime<Utc>) {
+        self.fx_rates
+            .insert(rates.base.clone(), Cached::new(rates, now));
+    }
+
+    /// Makes everything due a refresh, while keeping it to use until then
+    pub fn expire(&mut self) {
+        let long_ago = Utc.timestamp(0, 0);
+        let fetched_ats = self
+            .profiles
+            .values_mut()
+            .map(|c| &mut c.fetched_at)
+            .chain(self.symbol_lists.values_mut().map(|c| &mut c.fetched_at))
+            .chain(self.fx_rates.values_mut().map(|c| &mut c.fetched_at));
+        for fetched_at in fetched_ats {
+            *fetched_at = long_ago;
+        }
+    }
+
+    /// When the longest-cached entry was fetched, if there's anything cached
+    pub fn oldest(&self) -> Option<DateTime<Utc>> {
+        self.profiles
+            .values()
+            .map(|c| c.fetched_at)
+            .chain(self.symbol_lists.values().map(|c| c.fetched_at))
+            .chain(self.fx_rates.values().map(|c| c.fetched_at))
+            .min()
+    }
+
+    pub fn len(&self) -> usize {
+        self.profiles.len() + self.symbol_lists.len() + self.fx_rates.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn goes_stale_after_its_ttl() {
+        let aapl = Symbol("AAPL".to_string());
+        let now = Utc::now();
+        let mut cache = MetadataCache::default();
+        assert!(cache.profile_is_stale(&aapl, now));
+        cache.set_profile(aapl.clone(), CompanyProfile::default(), now);
+        assert!(!cache.profile_is_stale(&aapl, now + Duration::hours(1)));
+        assert!(cache.profile_is_stale(&aapl, now + Duration::days(7)));
+
+        cache.set_symbol_list("US", SymbolList::default(), now);
+        assert!(!cache.symbol_list_is_stale("US", now));
+        assert!(cache.symbol_list_is_stale("L", now));
+
+        // survives being saved and read back
+        let json = serde_json::to_string(&cache).unwrap();
+        let mut cache: MetadataCache = serde_json::from_str(&json).unwrap();
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.profile_is_stale(&aapl, now + Duration::hours(1)));
+
+        cache.prune_profiles(|_| false, now);
+        assert!(cache.profile(&aapl).is_some());
+        cache.expire();
+        assert!(cache.profile_is_stale(&aapl, now));
+        assert!(cache.profile(&aapl).is_some());
+        cache.prune_profiles(|_| false, now);
+        assert!(cache.profile(&aapl).is_none());
+    }
+}
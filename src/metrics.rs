@@ -0,0 +1,114 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+/// Counts events over a sliding window to give a per-second rate
+pub struct RateMeter {
+    // (when, how many), oldest first
+    events: VecDeque<(DateTime<Utc>, u32)>,
+}
+
+impl RateMeter {
+    const WINDOW_SECS: i64 = 10;
+
+    pub fn new() -> RateMeter {
+        RateMeter {
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn record(&mut self, count: u32, now: DateTime<Utc>) {
+        self.events.push_back((now, count));
+        self.expire(now);
+    }
+
+    pub fn per_sec(&self, now: DateTime<Utc>) -> f64 {
+        let cutoff = Self::cutoff(now);
+        let total: u32 = self
+            .events
+            .iter()
+            .filter(|(at, _)| *at > cutoff)
+            .map(|(_, count)| count)
+            .sum();
+        f64::from(total) / Self::WINDOW_SECS as f64
+    }
+
+    fn expire(&mut self, now: DateTime<Utc>) {
+        let cutoff = Self::cutoff(now);
+        while let Some((at, _)) = self.events.front() {
+            if *at > cutoff {
+                break;
+            }
+            self.events.pop_front();
+        }
+    }
+
+    fn cutoff(now: DateTime<Utc>) -> DateTime<Utc> {
+        now - Duration::seconds(Self::WINDOW_SECS)
+    }
+}
+
+/// Live websocket throughput: messages overall, plus trades overall and per key. Also
+/// how many cards each render rebuilds, against how many it leaves as they were
+pub struct Metrics<K> {
+    messages: RateMeter,
+    trades: RateMeter,
+    trades_by_key: HashMap<K, RateMeter>,
+    cards_rendered: RateMeter,
+    cards_kept: RateMeter,
+}
+
+impl<K: Hash + Eq> Metrics<K> {
+    pub fn new() -> Metrics<K> {
+        Metrics {
+            messages: RateMeter::new(),
+            trades: RateMeter::new(),
+            trades_by_key: HashMap::new(),
+            cards_rendered: RateMeter::new(),
+            cards_kept: RateMeter::new(),
+        }
+    }
+
+    pub fn record_message(&mut self, now: DateTime<Utc>) {
+        self.messages.record(1, now);
+    }
+
+    pub fn record_trades(&mut self, key: K, count: u32, now: DateTime<Utc>) {
+        self.trades.record(count, now);
+        self.trades_by_key
+            .entry(key)
+            .or_insert_with(RateMeter::new)
+            .record(count, now);
+    }
+
+    pub fn record_cards(&mut self, rendered: u32, kept: u32, now: DateTime<Utc>) {
+        self.cards_rendered.record(rendered, now);
+        self.cards_kept.record(kept, now);
+    }
+
+    pub fn remove(&mut self, key: &K) {
+        self.trades_by_key.remove(key);
+    }
+
+    pub fn messages_per_sec(&self, now: DateTime<Utc>) -> f64 {
+        self.messages.per_sec(now)
+    }
+
+    pub fn trades_per_sec(&self, now: DateTime<Utc>) -> f64 {
+        self.trades.per_sec(now)
+    }
+
+    pub fn trades_per_sec_for(&self, key: &K, now: DateTime<Utc>) -> f64 {
+        self.trades_by_key
+            .get(key)
+            .map_or(0.0, |meter| meter.per_sec(now))
+    }
+
+    pub fn cards_rendered_per_sec(&self, now: DateTime<Utc>) -> f64 {
+        self.cards_rendered.per_sec(now)
+    }
+
+    pub fn cards_kept_per_sec(&self, now: DateTime<Utc>) -> f64 {
+        self.cards_kept.per_sec(now)
+    }
+}
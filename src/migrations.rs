@@ -0,0 +1,143 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+/// Bumped whenever what's saved changes in a way `#[serde(default)]` can't cover, with
+/// a step added to `MIGRATIONS` to get from the version before
+pub const VERSION: u32 = 2;
+
+/// Each step takes what was saved at the version it's at in the list up to the next
+const MIGRATIONS: [fn(Value) -> Value; VERSION as usize] =
+    [from_unversioned, from_profiles_in_state];
+
+/// Saves from before there were versions are the state itself, without an envelope
+fn from_unversioned(state: Value) -> Value {
+    state
+}
+
+/// Company profiles went from the state into the metadata cache, which is saved under
+/// its own key. They're handed over as fetched long ago, so that they're shown until
+/// they've been fetched again
+fn from_profiles_in_state(mut state: Value) -> Value {
+    let profiles = match state.as_object_mut().and_then(|s| s.remove("profiles")) {
+        Some(Value::Object(profiles)) => profiles,
+        _ => return state,
+    };
+    let cached: Map<String, Value> = profiles
+        .into_iter()
+        .map(|(symbol, profile)| (symbol, json!({ "value": profile, "fetched_at": 0 })))
+        .collect();
+    state["metadata"] = json!({ "profiles": cached });
+    state
+}
+
+/// What's saved: the state, along with the version of it
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    version: u32,
+    state: &'a T,
+}
+
+pub fn save<T: Serialize>(state: &T) -> serde_json::Result<String> {
+    serde_json::to_string(&Envelope {
+        version: VERSION,
+        state,
+    })
+}
+
+#[derive(Debug)]
+pub enum Restored<T> {
+    Upgraded(T),
+    /// Couldn't be read as a whole even once upgraded, so only what `salvage` could
+    /// make out was kept
+    Salvaged {
+        state: T,
+        error: String,
+    },
+}
+
+/// Reads back what `save` saved at this or any earlier version. Saves from a later
+/// version are read as they are, and anything unreadable is left to `salvage`
+pub fn restore<T: DeserializeOwned>(
+    json: &str,
+    salvage: impl FnOnce(&Value) -> T,
+) -> serde_json::Result<Restored<T>> {
+    let saved: Value = serde_json::from_str(json)?;
+    let (version, mut state) = match saved {
+        Value::Object(mut envelope) if envelope.contains_key("version") => {
+            let version = envelope.get("version").and_then(Value::as_u64).unwrap_or(0) as usize;
+            (
+                version,
+                envelope.remove("state").unwrap_or_else(|| json!({})),
+            )
+        }
+        unversioned => (0, unversioned),
+    };
+    for migrate in MIGRATIONS.iter().skip(version) {
+        state = migrate(state);
+    }
+    Ok(match serde_json::from_value(state.clone()) {
+        Ok(state) => Restored::Upgraded(state),
+        Err(e) => Restored::Salvaged {
+            state: salvage(&state),
+            error: e.to_string(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Serialize, PartialEq, Debug)]
+    struct Saved {
+        tracked: Vec<String>,
+        #[serde(default)]
+        muted: bool,
+    }
+
+    fn salvage(state: &Value) -> Saved {
+        Saved {
+            tracked: state
+                .get("tracked")
+                .and_then(Value::as_array)
+                .map(|tracked| {
+                    tracked
+                        .iter()
+                        .filter_map(|s| s.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            muted: false,
+        }
+    }
+
+    #[test]
+    fn upgrades_every_version() {
+        let current = Saved {
+            tracked: vec!["AAPL".to_string()],
+            muted: true,
+        };
+        let saved = save(&current).unwrap();
+        assert!(matches!(
+            restore(&saved, salvage).unwrap(),
+            Restored::Upgraded(state) if state == current
+        ));
+        let unversioned = r#"{"tracked":["AAPL"],"muted":true}"#;
+        assert!(matches!(
+            restore(unversioned, salvage).unwrap(),
+            Restored::Upgraded(state) if state == current
+        ));
+        assert!(restore::<Saved>("not json", salvage).is_err());
+    }
+
+    #[test]
+    fn salvages_what_it_can() {
+        let broken = r#"{"version":1,"state":{"tracked":["AAPL"],"muted":"very"}}"#;
+        match restore(broken, salvage).unwrap() {
+            Restored::Salvaged { state, .. } => assert_eq!(state.tracked, vec!["AAPL"]),
+            restored => panic!("Expected a salvaged state, got {:?}", restored),
+        }
+    }
+}
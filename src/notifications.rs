@@ -0,0 +1,157 @@
+use yew::prelude::*;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Level {
+    fn alert_class(self) -> &'static str {
+        match self {
+            Level::Info => "alert alert-info",
+            Level::Warning => "alert alert-warning",
+            Level::Error => "alert alert-danger",
+        }
+    }
+}
+
+/// What gets rendered for a notification; the action itself stays in `Notifications`
+#[derive(Clone, PartialEq, Debug)]
+pub struct Notification {
+    pub id: usize,
+    pub level: Level,
+    pub message: String,
+    pub action_label: Option<String>,
+}
+
+/// In-app notifications, each with an optional action of type `A` the user can trigger
+pub struct Notifications<A> {
+    next_id: usize,
+    items: Vec<(Notification, Option<A>)>,
+}
+
+impl<A> Notifications<A> {
+    pub fn new() -> Notifications<A> {
+        Notifications {
+            next_id: 0,
+            items: vec![],
+        }
+    }
+
+    pub fn push(&mut self, level: Level, message: impl Into<String>) -> usize {
+        self.push_item(level, message.into(), None)
+    }
+
+    pub fn push_with_action(
+        &mut self,
+        level: Level,
+        message: impl Into<String>,
+        action_label: impl Into<String>,
+        action: A,
+    ) -> usize {
+        self.push_item(level, message.into(), Some((action_label.into(), action)))
+    }
+
+    fn push_item(&mut self, level: Level, message: String, action: Option<(String, A)>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        let (action_label, action) = match action {
+            Some((label, action)) => (Some(label), Some(action)),
+            None => (None, None),
+        };
+        self.items.push((
+            Notification {
+                id,
+                level,
+                message,
+                action_label,
+            },
+            action,
+        ));
+        id
+    }
+
+    /// Removes a notification, returning its action if it had one
+    pub fn dismiss(&mut self, id: usize) -> Option<A> {
+        let idx = self.items.iter().position(|(n, _)| n.id == id)?;
+        self.items.remove(idx).1
+    }
+
+    pub fn to_vec(&self) -> Vec<Notification> {
+        self.items.iter().map(|(n, _)| n.clone()).collect()
+    }
+}
+
+impl<A> Default for Notifications<A> {
+    fn default() -> Notifications<A> {
+        Notifications::new()
+    }
+}
+
+#[derive(Properties, Clone)]
+pub struct Props {
+    pub notifications: Vec<Notification>,
+    pub on_dismiss: Callback<usize>,
+    pub on_action: Callback<usize>,
+}
+
+/// Renders notifications stacked in the top right corner
+pub struct NotificationList {
+    props: Props,
+}
+
+impl Component for NotificationList {
+    type Message = ();
+    type Properties = Props;
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        NotificationList { props }
+    }
+
+    fn update(&mut self, _: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if self.props.notifications != props.notifications {
+            self.props = props;
+            true
+        } else {
+            self.props = props;
+            false
+        }
+    }
+
+    fn view(&self) -> Html {
+        html! {
+        <div style="position: fixed; top: 1rem; right: 1rem; z-index: 1050; max-width: 400px;" aria-live="polite">
+            { for self.props.notifications.iter().map(|n| self.view_notification(n)) }
+        </div>
+        }
+    }
+}
+
+impl NotificationList {
+    fn view_notification(&self, notification: &Notification) -> Html {
+        let id = notification.id;
+        let action = match &notification.action_label {
+            Some(label) => html! {
+                <button type="button" class="btn btn-sm btn-outline-dark mt-2" onclick = self.props.on_action.reform( move | _ | id)>
+                    { label }
+                </button>
+            },
+            None => html! {},
+        };
+        html! {
+        <div class={ format!("{} alert-dismissible text-left shadow", notification.level.alert_class()) } role="alert" key=id.to_string()>
+            <div style="white-space: pre-line;">{ &notification.message }</div>
+            { action }
+            <button type="button" class="close" aria-label="Dismiss" onclick = self.props.on_dismiss.reform( move | _ | id)>
+                <span aria-hidden="true">{ "×" }</span>
+            </button>
+        </div>
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use crate::decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// From the largest down, so that the biggest one that fits wins
+const ABBREVIATIONS: &[(f64, &str)] = &[(1e12, "T"), (1e9, "B"), (1e6, "M"), (1e3, "K")];
+
+/// How digits are grouped and which character is the decimal point
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum NumberStyle {
+    /// Whatever's usual for the browser's language
+    #[default]
+    Locale,
+    Comma,
+    Period,
+    Space,
+    Plain,
+}
+
+impl NumberStyle {
+    pub const ALL: [NumberStyle; 5] = [
+        NumberStyle::Locale,
+        NumberStyle::Comma,
+        NumberStyle::Period,
+        NumberStyle::Space,
+        NumberStyle::Plain,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            NumberStyle::Locale => "Browser language",
+            NumberStyle::Comma => "1,234.56",
+            NumberStyle::Period => "1.234,56",
+            NumberStyle::Space => "1 234,56",
+            NumberStyle::Plain => "1234.56",
+        }
+    }
+
+    /// (thousands separator, decimal point)
+    fn separators(self, locale: &str) -> (Option<char>, char) {
+        match self {
+            NumberStyle::Locale => locale_style(locale).separators(locale),
+            NumberStyle::Comma => (Some(','), '.'),
+            NumberStyle::Period => (Some('.'), ','),
+            // no-break, so numbers never wrap across lines
+            NumberStyle::Space => (Some('\u{a0}'), ','),
+            NumberStyle::Plain => (None, '.'),
+        }
+    }
+}
+
+/// Good enough for the common languages; anything else gets `1,234.56`
+fn locale_style(locale: &str) -> NumberStyle {
+    let language = locale.split(['-', '_']).next().unwrap_or("");
+    match language.to_ascii_lowercase().as_str() {
+        "de" | "es" | "it" | "nl" | "pt" | "da" | "id" | "tr" | "el" | "ro" | "hr" | "sl"
+        | "sr" | "vi" => NumberStyle::Period,
+        "fr" | "ru" | "pl" | "cs" | "sk" | "sv" | "fi" | "nb" | "nn" | "no" | "uk" | "hu"
+        | "bg" | "lt" | "lv" | "et" => NumberStyle::Space,
+        _ => NumberStyle::Comma,
+    }
+}
+
+/// The browser's preferred language, e.g. `en-GB`
+pub fn browser_locale() -> String {
+    web_sys::window()
+        .and_then(|w| w.navigator().language())
+        .unwrap_or_default()
+}
+
+/// Turns plain numbers into what the user wants to read
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct NumberFormat {
+    thousands: Option<char>,
+    decimal_point: char,
+    /// Large volumes as e.g. `1.2M`
+    abbreviate: bool,
+}
+
+impl NumberFormat {
+    pub fn new(style: NumberStyle, locale: &str, abbreviate: bool) -> NumberFormat {
+        let (thousands, decimal_point) = style.separators(locale);
+        NumberFormat {
+            thousands,
+            decimal_point,
+            abbreviate,
+        }
+    }
+
+    /// Takes a number as Rust formats it, e.g. `-1234.5` or `+12`
+    pub fn localize(&self, plain: &str) -> String {
+        let (sign, unsigned) = match plain.chars().next() {
+            Some(c @ '-') | Some(c @ '+') => (Some(c), &plain[1..]),
+            _ => (None, plain),
+        };
+        let (int_part, frac_part) = match unsigned.find('.') {
+            Some(idx) => (&unsigned[..idx], Some(&unsigned[idx + 1..])),
+            None => (unsigned, None),
+        };
+        let mut localized = String::with_capacity(plain.len() + int_part.len() / 3);
+        localized.extend(sign);
+        for (idx, digit) in int_part.chars().enumerate() {
+            let remaining = int_part.len() - idx;
+            if idx > 0 && remaining % 3 == 0 {
+                localized.extend(self.thousands);
+            }
+            localized.push(digit);
+        }
+        if let Some(frac_part) = frac_part {
+            localized.push(self.decimal_point);
+            localized.push_str(frac_part);
+        }
+        localized
+    }
+
+    pub fn fixed(&self, value: f64, decimals: usize) -> String {
+        self.localize(&format!("{:.*}", decimals, value))
+    }
+
+    /// Always with a sign, like P&L
+    pub fn signed(&self, value: f64, decimals: usize) -> String {
+        self.localize(&format!("{:+.*}", decimals, value))
+    }
+
+    pub fn decimal(&self, value: Decimal, decimals: usize) -> String {
+        self.localize(&format!("{:.*}", decimals, value))
+    }
+
+    /// Abbreviated once it's in the thousands, if that's turned on
+    pub fn volume(&self, value: f64, decimals: usize) -> String {
+        match self.abbreviated(value) {
+            Some(abbreviated) => abbreviated,
+            None => self.fixed(value, decimals),
+        }
+    }
+
+    fn abbreviated(&self, value: f64) -> Option<String> {
+        if !self.abbreviate {
+            return None;
+        }
+        let idx = ABBREVIATIONS
+            .iter()
+            .position(|(unit, _)| value.abs() >= *unit)?;
+        // 999,990 would round to 1000K, which reads better as 1M
+        let rounds_up = (value.abs() / ABBREVIATIONS[idx].0 * 10.0).round() >= 10_000.0;
+        let (unit, suffix) = ABBREVIATIONS[if rounds_up && idx > 0 { idx - 1 } else { idx }];
+        let plain = format!("{:.1}", value / unit);
+        let plain = plain.strip_suffix(".0").unwrap_or(&plain);
+        Some(format!("{}{}", self.localize(plain), suffix))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_thousands_per_style() {
+        let number = |style| NumberFormat::new(style, "en-US", false);
+        assert_eq!(
+            number(NumberStyle::Comma).fixed(1234567.891, 2),
+            "1,234,567.89"
+        );
+        assert_eq!(number(NumberStyle::Period).fixed(-1234.5, 2), "-1.234,50");
+        assert_eq!(
+            number(NumberStyle::Space).signed(1234.5, 1),
+            "+1\u{a0}234,5"
+        );
+        assert_eq!(number(NumberStyle::Plain).fixed(1234.5, 1), "1234.5");
+        assert_eq!(number(NumberStyle::Comma).fixed(123.0, 0), "123");
+        assert_eq!(
+            number(NumberStyle::Comma).decimal(Decimal::new(1234567, 3), 3),
+            "1,234.567"
+        );
+    }
+
+    #[test]
+    fn follows_the_browser_language() {
+        let locale =
+            |locale| NumberFormat::new(NumberStyle::Locale, locale, false).fixed(1234.5, 1);
+        assert_eq!(locale("en-GB"), "1,234.5");
+        assert_eq!(locale("de-DE"), "1.234,5");
+        assert_eq!(locale("fr"), "1\u{a0}234,5");
+        assert_eq!(locale(""), "1,234.5");
+    }
+
+    #[test]
+    fn abbreviates_volumes() {
+        let number = NumberFormat::new(NumberStyle::Comma, "", true);
+        assert_eq!(number.volume(1_234_567.0, 0), "1.2M");
+        assert_eq!(number.volume(999_990.0, 0), "1M");
+        assert_eq!(number.volume(2_500.0, 0), "2.5K");
+        assert_eq!(number.volume(999.0, 0), "999");
+        assert_eq!(number.volume(0.25, 2), "0.25");
+        assert_eq!(
+            NumberFormat::new(NumberStyle::Comma, "", false).volume(1_234_567.0, 0),
+            "1,234,567"
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use crate::finnhub::{Symbol, TickerInfo};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::VecDeque;
+
+/// Lulls longer than this are skipped over rather than sat through
+const MAX_GAP_SECS: i64 = 60;
+/// How many trades go in at a time at `ReplaySpeed::Max`
+const MAX_BATCH: usize = 50;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ReplaySpeed {
+    RealTime,
+    Fast,
+    /// As fast as the trades can be rendered
+    Max,
+}
+
+impl ReplaySpeed {
+    pub const ALL: [ReplaySpeed; 3] = [ReplaySpeed::RealTime, ReplaySpeed::Fast, ReplaySpeed::Max];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ReplaySpeed::RealTime => "1x",
+            ReplaySpeed::Fast => "5x",
+            ReplaySpeed::Max => "Max",
+        }
+    }
+
+    fn multiplier(self) -> Option<i32> {
+        match self {
+            ReplaySpeed::RealTime => Some(1),
+            ReplaySpeed::Fast => Some(5),
+            ReplaySpeed::Max => None,
+        }
+    }
+}
+
+/// Plays a symbol's history back from the start, handing out trades as they come due
+#[derive(Debug)]
+pub struct Replay {
+    pub symbol: Symbol,
+    pub speed: ReplaySpeed,
+    /// Still to be played, oldest first
+    pending: VecDeque<TickerInfo>,
+    /// Live trades that came in meanwhile, to go back in once done
+    live: Vec<TickerInfo>,
+    /// How far into the history the replay has got
+    clock: Option<DateTime<Utc>>,
+    total: usize,
+}
+
+impl Replay {
+    /// `history` is newest first, as it's stored
+    pub fn new(symbol: Symbol, history: VecDeque<TickerInfo>, speed: ReplaySpeed) -> Replay {
+        let pending: VecDeque<TickerInfo> = history.into_iter().rev().collect();
+        Replay {
+            symbol,
+            speed,
+            total: pending.len(),
+            pending,
+            live: vec![],
+            clock: None,
+        }
+    }
+
+    /// The trades that have come due, oldest first, `elapsed` after the last step
+    pub fn step(&mut self, elapsed: Duration) -> Vec<TickerInfo> {
+        let next_time = match self.pending.front() {
+            Some(next) => next.time,
+            None => return vec![],
+        };
+        let multiplier = match self.speed.multiplier() {
+            Some(multiplier) => multiplier,
+            None => {
+                let batch = MAX_BATCH.min(self.pending.len());
+                let due: Vec<TickerInfo> = self.pending.drain(..batch).collect();
+                self.clock = due.last().map(|t| t.time);
+                return due;
+            }
+        };
+        let clock = match self.clock {
+            Some(clock) => clock + elapsed * multiplier,
+            // the first trade goes in straight away
+            None => next_time,
+        };
+        let clock = if next_time - clock > Duration::seconds(MAX_GAP_SECS) {
+            next_time
+        } else {
+            clock
+        };
+        self.clock = Some(clock);
+        let due = self.pending.iter().take_while(|t| t.time <= clock).count();
+        self.pending.drain(..due).collect()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn played(&self) -> usize {
+        self.total - self.pending.len()
+    }
+
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// The time of the trade the replay has got up to
+    pub fn clock(&self) -> Option<DateTime<Utc>> {
+        self.clock
+    }
+
+    pub fn stash_live(&mut self, ticker_info: TickerInfo) {
+        self.live.push(ticker_info);
+    }
+
+    /// What's left to put back into the history: trades that weren't played, and
+    /// those that came in live during the replay
+    pub fn finish(self) -> impl Iterator<Item = TickerInfo> {
+        self.pending.into_iter().chain(self.live)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decimal::Decimal;
+    use crate::finnhub::{Price, Volume};
+    use chrono::TimeZone;
+
+    fn trade(secs: i64) -> TickerInfo {
+        TickerInfo {
+            symbol: Symbol("AAPL".to_string()),
+            price: Price(Decimal::new(100, 0)),
+            volume: Volume(Decimal::new(1, 0)),
+            time: Utc.timestamp(secs, 0),
+            delayed: false,
+        }
+    }
+
+    fn times(trades: Vec<TickerInfo>) -> Vec<i64> {
+        trades.iter().map(|t| t.time.timestamp()).collect()
+    }
+
+    #[test]
+    fn plays_trades_back_as_they_come_due() {
+        // newest first, with an hour's lull in the middle
+        let history: VecDeque<TickerInfo> = vec![trade(3_610), trade(10), trade(5), trade(0)]
+            .into_iter()
+            .collect();
+        let mut replay = Replay::new(Symbol("AAPL".to_string()), history, ReplaySpeed::Fast);
+        let step = Duration::seconds(1);
+        assert_eq!(times(replay.step(step)), vec![0]);
+        replay.speed = ReplaySpeed::RealTime;
+        assert!(replay.step(step).is_empty());
+        replay.speed = ReplaySpeed::Fast;
+        assert_eq!(times(replay.step(step)), vec![5]);
+        assert_eq!(times(replay.step(step)), vec![10]);
+        // rather than an hour of nothing
+        assert_eq!(times(replay.step(step)), vec![3_610]);
+        assert!(replay.is_done());
+        assert_eq!(replay.played(), 4);
+
+        let history: VecDeque<TickerInfo> = (0..MAX_BATCH as i64 + 1).rev().map(trade).collect();
+        let mut replay = Replay::new(Symbol("AAPL".to_string()), history, ReplaySpeed::Max);
+        assert_eq!(replay.step(step).len(), MAX_BATCH);
+        replay.stash_live(trade(9_999));
+        assert_eq!(
+            times(replay.finish().collect()),
+            vec![MAX_BATCH as i64, 9_999]
+        );
+    }
+}
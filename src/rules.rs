@@ -0,0 +1,395 @@
+use crate::finnhub::{Symbol, TickerInfo};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+/// Rules keep the trades they need themselves, so this is what bounds them rather
+/// than how much history is kept
+const MAX_WINDOW_MINS: u32 = 24 * 60;
+const MAX_SMA_PERIOD: usize = 1_000;
+
+/// Something about a symbol's recent trades worth being alerted to. Written and shown
+/// as e.g. `move 2% in 5m`, `volume > 50000 in 1m` or `crosses sma 20`
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub enum Condition {
+    /// The price moved by at least this much, either way, within the window
+    PercentMove { percent: f64, mins: u32 },
+    /// More than this much traded within the window
+    VolumeAbove { volume: f64, mins: u32 },
+    /// The latest trade took the price across its simple moving average over this
+    /// many trades
+    CrossesSma { period: usize },
+}
+
+impl Condition {
+    pub fn parse(input: &str) -> Result<Condition, String> {
+        let input = input.to_lowercase().replace(['(', ')'], " ");
+        let words: Vec<&str> = input.split_whitespace().collect();
+        match words.as_slice() {
+            ["move", percent, "in", mins] => Ok(Condition::PercentMove {
+                percent: parse_number(percent.trim_end_matches('%'))?,
+                mins: parse_mins(mins)?,
+            }),
+            ["move", percent, "%", "in", mins] => Ok(Condition::PercentMove {
+                percent: parse_number(percent)?,
+                mins: parse_mins(mins)?,
+            }),
+            ["volume", ">" | "above", volume, "in", mins] => Ok(Condition::VolumeAbove {
+                volume: parse_number(volume)?,
+                mins: parse_mins(mins)?,
+            }),
+            ["crosses", "sma", period] => match period.parse() {
+                Ok(period) if period > MAX_SMA_PERIOD => Err(format!(
+                    "Moving averages can be over at most {} trades",
+                    MAX_SMA_PERIOD
+                )),
+                Ok(period) if period > 0 => Ok(Condition::CrossesSma { period }),
+                _ => Err(format!("[{}] isn't a number of trades", period)),
+            },
+            _ => Err(
+                "Try \"move 2% in 5m\", \"volume > 50000 in 1m\" or \"crosses sma 20\"".to_string(),
+            ),
+        }
+    }
+
+    /// Crossings fire every time; the rest only on becoming true, rather than for
+    /// every trade while they stay true
+    fn is_event(&self) -> bool {
+        matches!(self, Condition::CrossesSma { .. })
+    }
+}
+
+/// What a rule needs to know of the trades so far, kept up to date trade by trade
+#[derive(Debug)]
+enum Tracker {
+    /// Time, price and volume of the trades within the window, oldest first, and
+    /// their total volume
+    Window {
+        mins: u32,
+        trades: VecDeque<(DateTime<Utc>, f64, f64)>,
+        volume: f64,
+    },
+    /// The latest `period + 1` prices, oldest first, and their sum
+    Sma {
+        period: usize,
+        prices: VecDeque<f64>,
+        sum: f64,
+        latest: Option<DateTime<Utc>>,
+    },
+}
+
+impl Tracker {
+    fn new(condition: &Condition) -> Tracker {
+        match *condition {
+            Condition::PercentMove { mins, .. } | Condition::VolumeAbove { mins, .. } => {
+                Tracker::Window {
+                    mins,
+                    trades: VecDeque::new(),
+                    volume: 0.0,
+                }
+            }
+            Condition::CrossesSma { period } => Tracker::Sma {
+                period,
+                prices: VecDeque::with_capacity(period + 1),
+                sum: 0.0,
+                latest: None,
+            },
+        }
+    }
+
+    fn observe(&mut self, trade: &TickerInfo) {
+        let (price, volume) = trade.sample();
+        match self {
+            Tracker::Window {
+                mins,
+                trades,
+                volume: total,
+            } => {
+                // stragglers go where they belong
+                let idx = trades.partition_point(|(time, _, _)| *time <= trade.time);
+                trades.insert(idx, (trade.time, price, volume));
+                *total += volume;
+                let newest = trades.back().map(|(time, _, _)| *time);
+                let since = newest.map(|newest| newest - Duration::minutes(i64::from(*mins)));
+                while let Some((time, _, volume)) = trades.front() {
+                    if since.is_some_and(|since| *time >= since) {
+                        break;
+                    }
+                    *total -= volume;
+                    trades.pop_front();
+                }
+            }
+            Tracker::Sma {
+                period,
+                prices,
+                sum,
+                latest,
+            } => {
+                // a straggler would be crossing a line that's already moved on
+                if latest.is_some_and(|latest| trade.time < latest) {
+                    return;
+                }
+                *latest = Some(trade.time);
+                prices.push_back(price);
+                *sum += price;
+                if prices.len() > *period + 1 {
+                    *sum -= prices.pop_front().unwrap_or_default();
+                }
+            }
+        }
+    }
+
+    /// As of the newest trade it's seen
+    fn holds(&self, condition: &Condition) -> bool {
+        match (condition, self) {
+            (Condition::PercentMove { percent, .. }, Tracker::Window { trades, .. }) => {
+                match (trades.front(), trades.back()) {
+                    (Some((_, oldest, _)), Some((_, newest, _))) => {
+                        *oldest != 0.0 && ((newest - oldest) / oldest).abs() * 100.0 >= *percent
+                    }
+                    _ => false,
+                }
+            }
+            (Condition::VolumeAbove { volume, .. }, Tracker::Window { volume: total, .. }) => {
+                total > volume
+            }
+            (
+                Condition::CrossesSma { .. },
+                Tracker::Sma {
+                    period,
+                    prices,
+                    sum,
+                    ..
+                },
+            ) => {
+                if prices.len() <= *period {
+                    return false;
+                }
+                let newest = prices[*period];
+                let before = prices[*period - 1];
+                let sma_now = (sum - prices[0]) / *period as f64;
+                let sma_before = (sum - newest) / *period as f64;
+                let side_now = (newest - sma_now).signum();
+                let side_before = (before - sma_before).signum();
+                newest != sma_now && before != sma_before && side_now != side_before
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::PercentMove { percent, mins } => write!(f, "move {}% in {}m", percent, mins),
+            Condition::VolumeAbove { volume, mins } => {
+                write!(f, "volume > {} in {}m", volume, mins)
+            }
+            Condition::CrossesSma { period } => write!(f, "crosses sma {}", period),
+        }
+    }
+}
+
+fn parse_number(input: &str) -> Result<f64, String> {
+    match input.replace([',', '_'], "").parse::<f64>() {
+        Ok(n) if n.is_finite() && n >= 0.0 => Ok(n),
+        _ => Err(format!("[{}] isn't a number", input)),
+    }
+}
+
+/// `5m`, `5min` or just `5`
+fn parse_mins(input: &str) -> Result<u32, String> {
+    let digits = input.trim_end_matches(|c: char| c.is_alphabetic());
+    match (digits.parse(), &input[digits.len()..]) {
+        (Ok(mins), "" | "m" | "min" | "mins") if mins > MAX_WINDOW_MINS => Err(format!(
+            "Rules can look back at most {} minutes",
+            MAX_WINDOW_MINS
+        )),
+        (Ok(mins), "" | "m" | "min" | "mins") if mins > 0 => Ok(mins),
+        _ => Err(format!("[{}] isn't a number of minutes", input)),
+    }
+}
+
+#[derive(Debug)]
+struct RuleState {
+    tracker: Tracker,
+    /// As of the last trade
+    met: bool,
+}
+
+/// Runs each symbol's trades through its rules, keeping track of which held as of the
+/// last one so that they fire when they start to hold rather than for as long as
+/// they do
+#[derive(Default, Debug)]
+pub struct RuleEngine {
+    /// The rules they were started for, alongside
+    states: HashMap<Symbol, (Vec<Condition>, Vec<RuleState>)>,
+}
+
+impl RuleEngine {
+    /// Runs a new trade through its symbol's rules, returning those that fired. Rules
+    /// new to the engine first catch up on `history`, newest first, which shouldn't
+    /// have the trade in it yet
+    pub fn observe<'a, 'h>(
+        &mut self,
+        rules: &'a [Condition],
+        history: impl DoubleEndedIterator<Item = &'h TickerInfo>,
+        trade: &TickerInfo,
+    ) -> Vec<&'a Condition> {
+        let (started_for, states) = self.states.entry(trade.symbol.clone()).or_default();
+        if started_for.as_slice() != rules {
+            *started_for = rules.to_vec();
+            *states = rules
+                .iter()
+                .map(|rule| RuleState {
+                    tracker: Tracker::new(rule),
+                    met: false,
+                })
+                .collect();
+            for past in history.rev() {
+                for state in states.iter_mut() {
+                    state.tracker.observe(past);
+                }
+            }
+        }
+        rules
+            .iter()
+            .zip(states.iter_mut())
+            .filter_map(|(rule, state)| {
+                state.tracker.observe(trade);
+                let holds = state.tracker.holds(rule);
+                let fires = holds && (rule.is_event() || !state.met);
+                state.met = holds;
+                if fires {
+                    Some(rule)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// For when the symbol's rules change
+    pub fn forget(&mut self, symbol: &Symbol) {
+        self.states.remove(symbol);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decimal::Decimal;
+    use crate::finnhub::{Price, Volume};
+    use chrono::{TimeZone, Utc};
+
+    /// Oldest first, a minute apart
+    fn trades(trades: &[(i64, i64)]) -> Vec<TickerInfo> {
+        trades
+            .iter()
+            .enumerate()
+            .map(|(idx, (price, volume))| TickerInfo {
+                symbol: Symbol("AAPL".to_string()),
+                price: Price(Decimal::new(*price, 0)),
+                volume: Volume(Decimal::new(*volume, 0)),
+                time: Utc.timestamp(idx as i64 * 60, 0),
+                delayed: false,
+            })
+            .collect()
+    }
+
+    /// Whether `condition` holds as of the last of `trades`
+    fn holds(condition: Condition, trades: &[TickerInfo]) -> bool {
+        let mut tracker = Tracker::new(&condition);
+        for trade in trades {
+            tracker.observe(trade);
+        }
+        tracker.holds(&condition)
+    }
+
+    #[test]
+    fn parses_what_it_displays() {
+        for input in ["move 2.5% in 5m", "volume > 50000 in 1m", "crosses sma 20"] {
+            let condition = Condition::parse(input).unwrap();
+            assert_eq!(condition.to_string(), input);
+        }
+        assert_eq!(
+            Condition::parse("Move 2 % in 5min"),
+            Ok(Condition::PercentMove {
+                percent: 2.0,
+                mins: 5
+            })
+        );
+        assert_eq!(
+            Condition::parse("crosses SMA(20)"),
+            Ok(Condition::CrossesSma { period: 20 })
+        );
+        assert!(Condition::parse("volume > lots in 1m").is_err());
+        assert!(Condition::parse("move 2% in 0m").is_err());
+        assert!(Condition::parse("move 2% in 1441m").is_err());
+        assert!(Condition::parse("crosses sma 1001").is_err());
+        assert!(Condition::parse("moon").is_err());
+    }
+
+    #[test]
+    fn evaluates_against_recent_trades() {
+        let recent = trades(&[(50, 100), (100, 5), (104, 5), (110, 5)]);
+        let moved = |percent, mins| holds(Condition::PercentMove { percent, mins }, &recent);
+        assert!(moved(10.0, 2));
+        assert!(!moved(10.0, 1));
+        let traded = |volume, mins| holds(Condition::VolumeAbove { volume, mins }, &recent);
+        assert!(traded(14.0, 2));
+        assert!(!traded(15.0, 2));
+
+        let crosses = Condition::CrossesSma { period: 2 };
+        assert!(holds(
+            crosses.clone(),
+            &trades(&[(101, 1), (99, 1), (105, 1)])
+        ));
+        assert!(!holds(
+            crosses.clone(),
+            &trades(&[(101, 1), (103, 1), (105, 1)])
+        ));
+        assert!(!holds(crosses, &trades(&[(99, 1), (105, 1)])));
+    }
+
+    #[test]
+    fn looks_further_back_than_the_history() {
+        // far more trades than a 25 trade history holds
+        let busy = trades(&[(100, 10); 60]);
+        let traded = Condition::VolumeAbove {
+            volume: 500.0,
+            mins: 60,
+        };
+        assert!(holds(traded, &busy));
+        let mut prices = vec![(100, 1); 50];
+        prices.push((90, 1));
+        prices.push((110, 1));
+        assert!(holds(
+            Condition::CrossesSma { period: 50 },
+            &trades(&prices)
+        ));
+    }
+
+    #[test]
+    fn fires_on_becoming_true() {
+        let rules = vec![Condition::VolumeAbove {
+            volume: 10.0,
+            mins: 60,
+        }];
+        let trades = trades(&[(1, 11), (1, 1), (1, 1)]);
+        let mut engine = RuleEngine::default();
+        let none = std::iter::empty();
+        assert_eq!(engine.observe(&rules, none.clone(), &trades[0]).len(), 1);
+        assert!(engine.observe(&rules, none.clone(), &trades[1]).is_empty());
+        engine.forget(&trades[0].symbol);
+        // catches up on the history first, which it doesn't fire for
+        let history = [&trades[1], &trades[0]];
+        assert_eq!(
+            engine
+                .observe(&rules, history.iter().copied(), &trades[2])
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use crate::Symbol;
+
+/// Location hash of a shared watchlist, followed by the encoded symbols
+pub const HASH_PREFIX: &str = "#/import/";
+
+/// Packs symbols into something that can go in a URL. Runs of symbols on the same
+/// exchange share a single prefix, e.g. `BINANCE:BTCUSDT,ETHUSDT;AAPL,MSFT`, so it's
+/// never longer than the plain list. Everything a symbol can hold is fine in a fragment
+pub fn encode(symbols: &[Symbol]) -> String {
+    let mut packed = String::new();
+    let mut current_exchange = None;
+    for symbol in symbols {
+        let exchange = symbol.exchange();
+        let name = exchange.map_or(&symbol.0[..], |e| &symbol.0[e.len() + 1..]);
+        if packed.is_empty() || exchange != current_exchange {
+            if !packed.is_empty() {
+                packed.push(';');
+            }
+            if let Some(exchange) = exchange {
+                packed.push_str(exchange);
+                packed.push(':');
+            }
+            current_exchange = exchange;
+        } else {
+            packed.push(',');
+        }
+        packed.push_str(name);
+    }
+    packed
+}
+
+/// `None` unless the whole thing decodes to valid symbols
+pub fn decode(packed: &str) -> Option<Vec<Symbol>> {
+    let mut entries = vec![];
+    for run in packed.split(';').filter(|run| !run.is_empty()) {
+        let (prefix, names) = match run.find(':') {
+            Some(idx) => (&run[..=idx], &run[idx + 1..]),
+            None => ("", run),
+        };
+        entries.extend(names.split(',').map(|name| format!("{}{}", prefix, name)));
+    }
+    let (symbols, invalid) = Symbol::parse_list(&entries.join(","));
+    if invalid.is_empty() && !symbols.is_empty() {
+        Some(symbols)
+    } else {
+        None
+    }
+}
+
+/// The symbols in a location hash, if it's a shared watchlist
+pub fn from_hash(hash: &str) -> Option<Vec<Symbol>> {
+    hash.strip_prefix(HASH_PREFIX)
+        .and_then(percent_decode)
+        .as_deref()
+        .and_then(decode)
+}
+
+/// Undoes whatever escaping the browser did to the fragment
+fn percent_decode(escaped: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(escaped.len());
+    let mut rest = escaped.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbols(list: &[&str]) -> Vec<Symbol> {
+        list.iter().map(|s| Symbol(s.to_string())).collect()
+    }
+
+    #[test]
+    fn round_trips_watchlists() {
+        let watchlist = symbols(&[
+            "BINANCE:BTCUSDT",
+            "BINANCE:ETHUSDT",
+            "AAPL",
+            "BRK.B",
+            "OANDA:EUR_USD",
+            "BINANCE:XRPUSDT",
+        ]);
+        let encoded = encode(&watchlist);
+        let plain: Vec<&str> = watchlist.iter().map(|s| s.0.as_str()).collect();
+        assert!(encoded.len() <= plain.join(",").len());
+        assert_eq!(decode(&encoded), Some(watchlist.clone()));
+        assert_eq!(
+            from_hash(&format!("{}{}", HASH_PREFIX, encoded)),
+            Some(watchlist.clone())
+        );
+        let escaped = encoded.replace(':', "%3A");
+        assert_eq!(
+            from_hash(&format!("{}{}", HASH_PREFIX, escaped)),
+            Some(watchlist)
+        );
+    }
+
+    #[test]
+    fn shares_exchange_prefixes() {
+        let packed = encode(&symbols(&[
+            "BINANCE:BTCUSDT",
+            "BINANCE:ETHUSDT",
+            "AAPL",
+            "MSFT",
+        ]));
+        assert_eq!(packed, "BINANCE:BTCUSDT,ETHUSDT;AAPL,MSFT");
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert_eq!(decode("!!!"), None);
+        assert_eq!(decode("AAPL,<script>"), None);
+        assert_eq!(from_hash(&format!("{}AAPL%", HASH_PREFIX)), None);
+        assert_eq!(decode(""), None);
+        assert_eq!(from_hash("#/watchlist"), None);
+    }
+}
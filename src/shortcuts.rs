@@ -0,0 +1,87 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{Document, Element, KeyboardEvent};
+use yew::Callback;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Shortcut {
+    FocusSymbolInput,
+    ToggleConnection,
+    /// Zero-based
+    JumpToCard(usize),
+    UntrackFocusedCard,
+    ToggleHelp,
+    CloseHelp,
+}
+
+/// Keys and descriptions, for the help overlay
+pub const BINDINGS: &[(&str, &str)] = &[
+    ("/", "Focus the ticker symbol input"),
+    ("c", "Connect or disconnect"),
+    ("1 – 9", "Jump to the nth card"),
+    ("Delete", "Untrack the focused card"),
+    ("?", "Show or hide this help"),
+    ("Esc", "Close this help, or go back from a maximized card"),
+];
+
+fn is_typing(event: &KeyboardEvent) -> bool {
+    event
+        .target()
+        .and_then(|t| t.dyn_into::<Element>().ok())
+        .map(|e| matches!(e.tag_name().as_str(), "INPUT" | "SELECT" | "TEXTAREA"))
+        .unwrap_or(false)
+}
+
+fn shortcut_for(event: &KeyboardEvent) -> Option<Shortcut> {
+    if event.ctrl_key() || event.meta_key() || event.alt_key() {
+        return None;
+    }
+    let key = event.key();
+    if key == "Escape" {
+        return Some(Shortcut::CloseHelp);
+    }
+    if is_typing(event) {
+        return None;
+    }
+    match key.as_str() {
+        "/" => Some(Shortcut::FocusSymbolInput),
+        "c" | "C" => Some(Shortcut::ToggleConnection),
+        "?" => Some(Shortcut::ToggleHelp),
+        "Delete" => Some(Shortcut::UntrackFocusedCard),
+        _ => key
+            .parse::<usize>()
+            .ok()
+            .filter(|n| (1..=9).contains(n))
+            .map(|n| Shortcut::JumpToCard(n - 1)),
+    }
+}
+
+/// Listens for shortcuts on the whole document for as long as it's alive
+pub struct KeyboardShortcuts {
+    document: Document,
+    _closure: Closure<dyn FnMut(KeyboardEvent)>,
+}
+
+impl KeyboardShortcuts {
+    pub fn listen(callback: Callback<Shortcut>) -> Option<KeyboardShortcuts> {
+        let document = web_sys::window()?.document()?;
+        let closure = Closure::wrap(Box::new(move |event: KeyboardEvent| {
+            if let Some(shortcut) = shortcut_for(&event) {
+                // e.g. stops "/" from triggering quick find
+                event.prevent_default();
+                callback.emit(shortcut);
+            }
+        }) as Box<dyn FnMut(KeyboardEvent)>);
+        document.set_onkeydown(Some(closure.as_ref().unchecked_ref()));
+        Some(KeyboardShortcuts {
+            document,
+            _closure: closure,
+        })
+    }
+}
+
+impl Drop for KeyboardShortcuts {
+    fn drop(&mut self) {
+        self.document.set_onkeydown(None);
+    }
+}
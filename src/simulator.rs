@@ -0,0 +1,61 @@
+use crate::decimal::Decimal;
+use crate::{Price, Symbol, TickerInfo, Volume, WsMessage};
+use chrono::{DateTime, Duration, Utc};
+use js_sys::Math;
+use std::collections::HashMap;
+
+/// Exchange prefix of the made up symbols, so they can't be confused with real ones
+pub const EXCHANGE: &str = "DEMO";
+
+pub const SYMBOLS: &[&str] = &["DEMO:ACME", "DEMO:GLOBEX", "DEMO:INITECH", "DEMO:UMBRELLA"];
+
+/// Makes up a trade stream for the demo symbols: each price takes a small random
+/// walk, with a few trades per symbol now and then
+pub struct Simulator {
+    prices: HashMap<Symbol, f64>,
+}
+
+impl Simulator {
+    /// Chance of a symbol having any trades in a given frame
+    const TRADE_CHANCE: f64 = 0.6;
+    const MAX_TRADES_PER_FRAME: u32 = 3;
+    /// Largest relative price move per trade
+    const MAX_MOVE: f64 = 0.002;
+
+    pub fn new() -> Simulator {
+        Simulator {
+            prices: HashMap::new(),
+        }
+    }
+
+    /// A frame of trades in the same JSON that Finnhub sends, so it can go down the
+    /// same path as real frames
+    pub fn frame<'a>(
+        &mut self,
+        symbols: impl IntoIterator<Item = &'a Symbol>,
+        now: DateTime<Utc>,
+    ) -> String {
+        let mut data = Vec::new();
+        for symbol in symbols {
+            if Math::random() > Self::TRADE_CHANCE {
+                continue;
+            }
+            let price = self
+                .prices
+                .entry(symbol.clone())
+                .or_insert_with(|| 20.0 + Math::random() * 480.0);
+            let trades = 1 + (Math::random() * f64::from(Self::MAX_TRADES_PER_FRAME)) as u32;
+            for offset in (0..trades).rev() {
+                *price *= 1.0 + (Math::random() * 2.0 - 1.0) * Self::MAX_MOVE;
+                data.push(TickerInfo {
+                    symbol: symbol.clone(),
+                    price: Price(Decimal::new((*price * 100.0).round() as i64, 2)),
+                    volume: Volume(Decimal::new(1 + (Math::random() * 500.0) as i64, 0)),
+                    time: now - Duration::milliseconds(i64::from(offset) * 10),
+                    delayed: false,
+                });
+            }
+        }
+        serde_json::to_string(&WsMessage::Trade { data }).unwrap_or_default()
+    }
+}
@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+
+/// Spikes aren't looked for until there's this much to compare against
+const MIN_SPIKE_SAMPLE: u64 = 20;
+
+/// Mean, variance and VWAP of a window of trades, kept up to date one trade at a
+/// time as trades come in and get evicted rather than recomputed from scratch
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct RunningStats {
+    count: u64,
+    /// Welford's running mean and sum of squared differences from it
+    mean: f64,
+    m2: f64,
+    volume: f64,
+    /// Sum of price × volume
+    notional: f64,
+}
+
+impl RunningStats {
+    pub fn of(trades: impl IntoIterator<Item = (f64, f64)>) -> RunningStats {
+        let mut stats = RunningStats::default();
+        for (price, volume) in trades {
+            stats.add(price, volume);
+        }
+        stats
+    }
+
+    pub fn add(&mut self, price: f64, volume: f64) {
+        self.count += 1;
+        let delta = price - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (price - self.mean);
+        self.volume += volume;
+        self.notional += price * volume;
+    }
+
+    /// Undoes an earlier `add` of the same trade
+    pub fn remove(&mut self, price: f64, volume: f64) {
+        if self.count <= 1 {
+            *self = RunningStats::default();
+            return;
+        }
+        let delta = price - self.mean;
+        self.count -= 1;
+        self.mean -= delta / self.count as f64;
+        // rounding can leave it a hair below zero
+        self.m2 = (self.m2 - delta * (price - self.mean)).max(0.0);
+        self.volume -= volume;
+        self.notional -= price * volume;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        if self.count > 0 {
+            Some(self.mean)
+        } else {
+            None
+        }
+    }
+
+    /// Sample variance, which needs at least two trades
+    pub fn variance(&self) -> Option<f64> {
+        if self.count > 1 {
+            Some(self.m2 / (self.count - 1) as f64)
+        } else {
+            None
+        }
+    }
+
+    pub fn std_dev(&self) -> Option<f64> {
+        self.variance().map(f64::sqrt)
+    }
+
+    pub fn vwap(&self) -> Option<f64> {
+        if self.volume > 0.0 {
+            Some(self.notional / self.volume)
+        } else {
+            None
+        }
+    }
+}
+
+/// How far a trade has to be from the mean of those before it to count as a spike,
+/// which tends to be a fat-finger print or a halt
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum SpikeThreshold {
+    StdDevs(f64),
+    Percent(f64),
+}
+
+impl SpikeThreshold {
+    pub fn value(self) -> f64 {
+        match self {
+            SpikeThreshold::StdDevs(n) | SpikeThreshold::Percent(n) => n,
+        }
+    }
+
+    pub fn unit(self) -> &'static str {
+        match self {
+            SpikeThreshold::StdDevs(_) => "σ",
+            SpikeThreshold::Percent(_) => "%",
+        }
+    }
+
+    /// The same threshold in the other unit, or this one, keeping the number
+    pub fn with_unit(self, unit: &str) -> Option<SpikeThreshold> {
+        match unit {
+            "σ" => Some(SpikeThreshold::StdDevs(self.value())),
+            "%" => Some(SpikeThreshold::Percent(self.value())),
+            _ => None,
+        }
+    }
+
+    pub fn with_value(self, value: f64) -> SpikeThreshold {
+        match self {
+            SpikeThreshold::StdDevs(_) => SpikeThreshold::StdDevs(value),
+            SpikeThreshold::Percent(_) => SpikeThreshold::Percent(value),
+        }
+    }
+
+    /// `stats` are of the trades before this one
+    pub fn is_spike(self, stats: &RunningStats, price: f64) -> bool {
+        if stats.count() < MIN_SPIKE_SAMPLE {
+            return false;
+        }
+        let mean = match stats.mean() {
+            Some(mean) if mean != 0.0 => mean,
+            _ => return false,
+        };
+        let deviation = (price - mean).abs();
+        match self {
+            SpikeThreshold::StdDevs(n) => stats
+                .std_dev()
+                .is_some_and(|sd| sd > 0.0 && deviation > n * sd),
+            SpikeThreshold::Percent(pct) => deviation / mean.abs() * 100.0 > pct,
+        }
+    }
+}
+
+impl Default for SpikeThreshold {
+    fn default() -> SpikeThreshold {
+        SpikeThreshold::StdDevs(4.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Option<f64>, b: f64) -> bool {
+        a.is_some_and(|a| (a - b).abs() < 1e-9)
+    }
+
+    #[test]
+    fn matches_recomputing_from_scratch() {
+        let trades = [(10.0, 1.0), (12.0, 3.0), (11.0, 2.0), (15.0, 4.0)];
+        let mut stats = RunningStats::default();
+        for (price, volume) in trades {
+            stats.add(price, volume);
+        }
+        assert!(close(stats.mean(), 12.0));
+        assert!(close(stats.variance(), 14.0 / 3.0));
+        assert!(close(stats.vwap(), 128.0 / 10.0));
+
+        // evicting the oldest leaves what the rest would have given
+        stats.remove(10.0, 1.0);
+        let rest = RunningStats::of(trades[1..].iter().copied());
+        assert_eq!(stats.count(), rest.count());
+        assert!(close(stats.mean(), rest.mean().unwrap()));
+        assert!(close(stats.variance(), rest.variance().unwrap()));
+        assert!(close(stats.vwap(), rest.vwap().unwrap()));
+
+        for (price, volume) in &trades[1..] {
+            stats.remove(*price, *volume);
+        }
+        assert_eq!(stats, RunningStats::default());
+        assert_eq!(stats.std_dev(), None);
+    }
+
+    #[test]
+    fn flags_prices_far_from_the_mean() {
+        let prices = (0..MIN_SPIKE_SAMPLE).map(|n| (100.0 + (n % 2) as f64, 1.0));
+        let stats = RunningStats::of(prices);
+        let by_sd = SpikeThreshold::StdDevs(4.0);
+        assert!(!by_sd.is_spike(&stats, 101.5));
+        assert!(by_sd.is_spike(&stats, 103.0));
+        let by_pct = SpikeThreshold::Percent(5.0);
+        assert!(!by_pct.is_spike(&stats, 104.0));
+        assert!(by_pct.is_spike(&stats, 94.0));
+        // not until there's enough to go on
+        let few = RunningStats::of(vec![(100.0, 1.0), (101.0, 1.0)]);
+        assert!(!by_pct.is_spike(&few, 200.0));
+    }
+}
@@ -0,0 +1,42 @@
+use yew::prelude::*;
+
+#[derive(Properties, Clone)]
+pub struct Props {
+    /// `None` when nothing on the card has changed since it was last given one
+    pub card: Option<Html>,
+}
+
+/// A symbol's card, which is only diffed again when it's given a new rendering. Trades
+/// for one symbol then don't touch the cards of all the others
+pub struct SymbolCard {
+    card: Html,
+}
+
+impl Component for SymbolCard {
+    type Message = ();
+    type Properties = Props;
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        SymbolCard {
+            card: props.card.unwrap_or_default(),
+        }
+    }
+
+    fn update(&mut self, _: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        match props.card {
+            Some(card) => {
+                self.card = card;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn view(&self) -> Html {
+        self.card.clone()
+    }
+}
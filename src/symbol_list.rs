@@ -0,0 +1,57 @@
+use crate::{Symbol, SymbolKind};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// The exchange whose listings are fetched; it's where nearly every stock symbol
+/// without a suffix trades
+pub const EXCHANGE: &str = "US";
+
+/// One row of finnhub's `/stock/symbol` REST endpoint, of which we only need the symbol
+#[derive(Deserialize, Debug)]
+pub struct ListedSymbol {
+    symbol: String,
+}
+
+/// Every stock symbol listed on `EXCHANGE`, for catching typos before subscribing
+#[derive(Deserialize, Serialize, Default, Debug)]
+pub struct SymbolList {
+    listed: HashSet<String>,
+}
+
+impl SymbolList {
+    pub fn new(listed: Vec<ListedSymbol>) -> SymbolList {
+        SymbolList {
+            listed: listed.into_iter().map(|l| l.symbol).collect(),
+        }
+    }
+
+    /// Only says so when sure: forex, crypto and suffixed stocks like `VOD.L` trade
+    /// elsewhere, so those are left for finnhub to judge
+    pub fn is_unknown(&self, symbol: &Symbol) -> bool {
+        symbol.kind() == SymbolKind::Stock
+            && symbol.exchange().is_none()
+            && !self.listed.is_empty()
+            && !self.listed.contains(&symbol.0)
+            && !symbol.0.contains('.')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_flags_what_it_can_be_sure_of() {
+        let listed: Vec<ListedSymbol> =
+            serde_json::from_str(r#"[{"symbol":"AAPL","type":"Common Stock"},{"symbol":"BRK.B"}]"#)
+                .unwrap();
+        let list = SymbolList::new(listed);
+        let unknown = |s: &str| list.is_unknown(&Symbol(s.to_string()));
+        assert!(!unknown("AAPL"));
+        assert!(!unknown("BRK.B"));
+        assert!(unknown("APPL"));
+        assert!(!unknown("VOD.L"));
+        assert!(!unknown("BINANCE:BTCUSDT"));
+        assert!(!SymbolList::default().is_unknown(&Symbol("APPL".to_string())));
+    }
+}
@@ -0,0 +1,174 @@
+use crate::finnhub::Symbol;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{BroadcastChannel, MessageEvent};
+use yew::Callback;
+
+const CHANNEL_NAME: &str = "finnhub-ws-rs";
+/// How often the leader says it's still there
+pub const HEARTBEAT_SECS: u64 = 2;
+/// A leader that's been quiet this long is assumed gone
+const LEADER_TIMEOUT_SECS: i64 = 5;
+/// How long a new tab waits to hear from a leader before leading itself
+const HELLO_WAIT_SECS: i64 = 1;
+
+/// What tabs of the app say to each other
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub enum TabMessage {
+    /// From a tab that's just opened, so that the leader makes itself known
+    Hello,
+    /// The leader's heartbeat, also sent in answer to `Hello`
+    Leader { tab: u64 },
+    /// From a leader that's closing, so another tab can take over straight away
+    Resigned { tab: u64 },
+    /// A websocket frame, as the leader received it
+    Frame(String),
+    /// The leader's watchlist, whenever it changes
+    Tracked(Vec<Symbol>),
+}
+
+/// Decides which one of the open tabs holds the websocket. Whichever tab has heard
+/// from no leader for a while leads, and if two end up leading at once, the one with
+/// the lower id stays on
+#[derive(Debug)]
+pub struct Election {
+    tab: u64,
+    /// The leading tab, and when it was last heard from
+    leader: Option<(u64, DateTime<Utc>)>,
+    started_at: DateTime<Utc>,
+}
+
+impl Election {
+    pub fn new(tab: u64, now: DateTime<Utc>) -> Election {
+        Election {
+            tab,
+            leader: None,
+            started_at: now,
+        }
+    }
+
+    pub fn tab(&self) -> u64 {
+        self.tab
+    }
+
+    pub fn is_leader(&self) -> bool {
+        matches!(self.leader, Some((tab, _)) if tab == self.tab)
+    }
+
+    /// Returns whether this tab started or stopped leading
+    pub fn on_leader(&mut self, tab: u64, now: DateTime<Utc>) -> bool {
+        let was_leader = self.is_leader();
+        if !was_leader || tab < self.tab {
+            self.leader = Some((tab, now));
+        }
+        was_leader != self.is_leader()
+    }
+
+    /// Returns whether this tab took over
+    pub fn on_resigned(&mut self, tab: u64) -> bool {
+        match self.leader {
+            Some((leader, _)) if leader == tab => {
+                self.leader = Some((self.tab, self.started_at));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Takes over if the leader's gone quiet, or there never was one. Returns whether
+    /// this tab took over
+    pub fn tick(&mut self, now: DateTime<Utc>) -> bool {
+        let leaderless = match self.leader {
+            Some((tab, _)) if tab == self.tab => return false,
+            Some((_, heard_at)) => now - heard_at > Duration::seconds(LEADER_TIMEOUT_SECS),
+            None => now - self.started_at >= Duration::seconds(HELLO_WAIT_SECS),
+        };
+        if leaderless {
+            self.leader = Some((self.tab, now));
+        }
+        leaderless
+    }
+}
+
+/// A random id for this tab, which is all that's needed to tell tabs apart
+pub fn new_tab_id() -> u64 {
+    (js_sys::Math::random() * (1u64 << 53) as f64) as u64
+}
+
+/// The channel that tabs of the app on the same origin talk over. Closed when dropped
+pub struct TabSync {
+    channel: BroadcastChannel,
+    _listener: Closure<dyn FnMut(MessageEvent)>,
+}
+
+impl TabSync {
+    /// Fails where `BroadcastChannel` isn't supported
+    pub fn new(callback: Callback<TabMessage>) -> Result<TabSync, JsValue> {
+        let channel = BroadcastChannel::new(CHANNEL_NAME)?;
+        let listener = Closure::wrap(Box::new(move |e: MessageEvent| {
+            // anything unreadable is from a different version of the app
+            if let Some(message) = e
+                .data()
+                .as_string()
+                .and_then(|json| serde_json::from_str(&json).ok())
+            {
+                callback.emit(message);
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        channel.set_onmessage(Some(listener.as_ref().unchecked_ref()));
+        Ok(TabSync {
+            channel,
+            _listener: listener,
+        })
+    }
+
+    pub fn post(&self, message: &TabMessage) {
+        if let Ok(json) = serde_json::to_string(message) {
+            // only fails once closed
+            let _ = self.channel.post_message(&JsValue::from_str(&json));
+        }
+    }
+}
+
+impl Drop for TabSync {
+    fn drop(&mut self) {
+        self.channel.set_onmessage(None);
+        self.channel.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp(secs, 0)
+    }
+
+    #[test]
+    fn leads_only_without_another_leader() {
+        let mut election = Election::new(5, at(0));
+        assert!(!election.is_leader());
+        assert!(!election.on_leader(9, at(0)));
+        assert!(!election.tick(at(4)));
+        // the leader went quiet
+        assert!(election.tick(at(6)));
+        assert!(election.is_leader());
+
+        // a tab with a higher id has to step down instead
+        assert!(!election.on_leader(9, at(7)));
+        assert!(election.is_leader());
+        assert!(election.on_leader(2, at(7)));
+        assert!(!election.is_leader());
+        assert!(!election.on_resigned(9));
+        assert!(election.on_resigned(2));
+        assert!(election.is_leader());
+
+        let mut alone = Election::new(1, at(0));
+        assert!(!alone.tick(at(0)));
+        assert!(alone.tick(at(1)));
+    }
+}
@@ -0,0 +1,121 @@
+use chrono::{DateTime, Duration, Utc};
+
+/// How a chart spreads its points across the x axis
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AxisSpacing {
+    /// In proportion to the time between them, so an hour's lull takes up an hour
+    RealTime,
+    /// One step per distinct trade time, which compresses gaps down to nothing
+    Sequence,
+}
+
+impl AxisSpacing {
+    pub const ALL: [AxisSpacing; 2] = [AxisSpacing::RealTime, AxisSpacing::Sequence];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AxisSpacing::RealTime => "Time",
+            AxisSpacing::Sequence => "Trades",
+        }
+    }
+
+    pub fn icon(self) -> &'static str {
+        match self {
+            AxisSpacing::RealTime => "fas fa-clock",
+            AxisSpacing::Sequence => "fas fa-ellipsis-h",
+        }
+    }
+}
+
+/// Maps times onto an x axis `width` wide
+pub struct TimeAxis {
+    spacing: AxisSpacing,
+    /// Oldest first, without duplicates
+    times: Vec<DateTime<Utc>>,
+    width: f64,
+}
+
+impl TimeAxis {
+    pub fn new(
+        times: impl IntoIterator<Item = DateTime<Utc>>,
+        spacing: AxisSpacing,
+        width: f64,
+    ) -> TimeAxis {
+        let mut times: Vec<DateTime<Utc>> = times.into_iter().collect();
+        times.sort();
+        times.dedup();
+        TimeAxis {
+            spacing,
+            times,
+            width,
+        }
+    }
+
+    /// Times outside of the ones the axis was made with are clamped to its ends
+    pub fn x(&self, time: DateTime<Utc>) -> f64 {
+        let (first, last) = match (self.times.first(), self.times.last()) {
+            (Some(first), Some(last)) if self.times.len() > 1 => (*first, *last),
+            _ => return 0.0,
+        };
+        let fraction = match self.spacing {
+            AxisSpacing::RealTime => {
+                (time - first).num_milliseconds() as f64
+                    / (last - first).num_milliseconds().max(1) as f64
+            }
+            AxisSpacing::Sequence => {
+                let idx = match self.times.binary_search(&time) {
+                    Ok(idx) => idx as f64,
+                    // between two known times, so halfway between their steps
+                    Err(idx) => idx as f64 - 0.5,
+                };
+                idx / (self.times.len() - 1) as f64
+            }
+        };
+        fraction.clamp(0.0, 1.0) * self.width
+    }
+
+    /// Where gaps longer than `threshold` were squeezed out, so they can be marked.
+    /// There are none with real-time spacing, which shows gaps as they are
+    pub fn compressed_gaps(&self, threshold: Duration) -> Vec<f64> {
+        if self.spacing == AxisSpacing::RealTime {
+            return vec![];
+        }
+        self.times
+            .windows(2)
+            .filter(|pair| pair[1] - pair[0] > threshold)
+            .map(|pair| (self.x(pair[0]) + self.x(pair[1])) / 2.0)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp(secs, 0)
+    }
+
+    #[test]
+    fn spaces_by_time_or_by_sequence() {
+        let times = vec![at(0), at(10), at(3_610), at(10)];
+        let real = TimeAxis::new(times.clone(), AxisSpacing::RealTime, 361.0);
+        assert_eq!(real.x(at(10)), 1.0);
+        assert_eq!(real.x(at(3_610)), 361.0);
+        assert!(real.compressed_gaps(Duration::minutes(5)).is_empty());
+
+        let sequence = TimeAxis::new(times, AxisSpacing::Sequence, 100.0);
+        assert_eq!(sequence.x(at(0)), 0.0);
+        assert_eq!(sequence.x(at(10)), 50.0);
+        assert_eq!(sequence.x(at(3_610)), 100.0);
+        assert_eq!(sequence.x(at(5)), 25.0);
+        assert_eq!(sequence.x(at(9_999)), 100.0);
+        assert_eq!(sequence.compressed_gaps(Duration::minutes(5)), vec![75.0]);
+
+        assert_eq!(
+            TimeAxis::new(vec![at(5)], AxisSpacing::Sequence, 100.0).x(at(5)),
+            0.0
+        );
+    }
+}
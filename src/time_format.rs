@@ -0,0 +1,148 @@
+use crate::i18n::{self, Language, Phrase, TimeUnit};
+use chrono::{DateTime, Local, Utc};
+use js_sys::{Array, Date, Function, Intl::DateTimeFormat, Object, Reflect};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{JsCast, JsValue};
+
+/// How trade timestamps are displayed
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug, Default)]
+pub enum TimeZoneSetting {
+    /// Whatever time zone the browser is in
+    #[default]
+    Local,
+    Utc,
+    /// An IANA zone like `America/New_York`; resolved by the browser's `Intl` support
+    /// rather than chrono-tz, which would build the whole zone database into the wasm
+    Named(String),
+    /// "x seconds ago"
+    Relative,
+}
+
+/// The browser's own IANA time zone, if it's willing to tell us
+pub fn local_zone_name() -> Option<String> {
+    let options = DateTimeFormat::new(&Array::new(), &Object::new()).resolved_options();
+    Reflect::get(&options, &"timeZone".into())
+        .ok()
+        .and_then(|zone| zone.as_string())
+}
+
+/// Formats timestamps according to a `TimeZoneSetting`
+pub struct TimeFormatter {
+    setting: TimeZoneSetting,
+    language: Language,
+    // only built for `Named`, since constructing one isn't cheap
+    named: Option<DateTimeFormat>,
+}
+
+impl TimeFormatter {
+    /// Fails if the setting names a zone the browser doesn't know about
+    pub fn new(setting: &TimeZoneSetting, language: Language) -> Result<TimeFormatter, JsValue> {
+        let named = match setting {
+            TimeZoneSetting::Named(zone) => Some(Self::zone_format(zone, language)?),
+            _ => None,
+        };
+        Ok(TimeFormatter {
+            setting: setting.clone(),
+            language,
+            named,
+        })
+    }
+
+    pub fn is_relative(&self) -> bool {
+        self.setting == TimeZoneSetting::Relative
+    }
+
+    pub fn format(&self, time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+        match (&self.setting, &self.named) {
+            (TimeZoneSetting::Named(_), Some(format)) => {
+                let date = Date::new(&JsValue::from_f64(time.timestamp_millis() as f64));
+                format
+                    .format()
+                    .call1(&JsValue::UNDEFINED, &date)
+                    .ok()
+                    .and_then(|s| s.as_string())
+                    .unwrap_or_else(|| time.to_string())
+            }
+            (TimeZoneSetting::Relative, _) => Self::relative(now - time, self.language),
+            (TimeZoneSetting::Utc, _) => time.format("%Y-%m-%d %H:%M:%S%.3f UTC").to_string(),
+            _ => time
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S%.3f %:z")
+                .to_string(),
+        }
+    }
+
+    fn relative(elapsed: chrono::Duration, language: Language) -> String {
+        let secs = elapsed.num_seconds();
+        let (amount, unit) = if secs < 1 {
+            return i18n::t(language, Phrase::JustNow).to_string();
+        } else if secs < 60 {
+            (secs, TimeUnit::Second)
+        } else if secs < 60 * 60 {
+            (secs / 60, TimeUnit::Minute)
+        } else if secs < 24 * 60 * 60 {
+            (secs / (60 * 60), TimeUnit::Hour)
+        } else {
+            (secs / (24 * 60 * 60), TimeUnit::Day)
+        };
+        i18n::ago(language, amount, unit)
+    }
+
+    /// `Intl.DateTimeFormat` throws a `RangeError` for unknown zones, which the js-sys
+    /// constructor binding doesn't let us catch, so it's invoked via `Reflect` instead
+    fn zone_format(zone: &str, language: Language) -> Result<DateTimeFormat, JsValue> {
+        let options = Object::new();
+        for (key, value) in &[
+            ("year", "numeric"),
+            ("month", "2-digit"),
+            ("day", "2-digit"),
+            ("hour", "2-digit"),
+            ("minute", "2-digit"),
+            ("second", "2-digit"),
+            ("timeZoneName", "short"),
+            ("timeZone", zone),
+        ] {
+            Reflect::set(&options, &(*key).into(), &(*value).into())?;
+        }
+        Reflect::set(&options, &"hour12".into(), &JsValue::FALSE)?;
+        let intl = Reflect::get(&js_sys::global(), &"Intl".into())?;
+        let constructor: Function = Reflect::get(&intl, &"DateTimeFormat".into())?.dyn_into()?;
+        let locales = Array::of1(&language.code().into());
+        let format = Reflect::construct(&constructor, &Array::of2(&locales, &options))?;
+        Ok(format.unchecked_into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn says_how_long_ago_in_the_biggest_whole_unit() {
+        let ago = |secs: i64| TimeFormatter::relative(Duration::seconds(secs), Language::English);
+        assert_eq!(ago(0), "just now");
+        // a trade stamped a little after our clock
+        assert_eq!(ago(-2), "just now");
+        assert_eq!(ago(1), "1 second ago");
+        assert_eq!(ago(59), "59 seconds ago");
+        assert_eq!(ago(60), "1 minute ago");
+        assert_eq!(ago(60 * 60 - 1), "59 minutes ago");
+        assert_eq!(ago(60 * 60), "1 hour ago");
+        assert_eq!(ago(24 * 60 * 60 - 1), "23 hours ago");
+        assert_eq!(ago(3 * 24 * 60 * 60), "3 days ago");
+    }
+
+    #[test]
+    fn formats_without_the_browser_unless_a_zone_is_named() {
+        let time = Utc.timestamp(1_590_000_000, 123_000_000);
+        let utc = TimeFormatter::new(&TimeZoneSetting::Utc, Language::English).unwrap();
+        assert_eq!(utc.format(time, time), "2020-05-20 18:40:00.123 UTC");
+        let relative = TimeFormatter::new(&TimeZoneSetting::Relative, Language::English).unwrap();
+        assert!(relative.is_relative());
+        assert_eq!(
+            relative.format(time, time + Duration::minutes(5)),
+            "5 minutes ago"
+        );
+    }
+}
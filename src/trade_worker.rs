@@ -0,0 +1,388 @@
+use crate::{Price, Symbol, TickerHistory, TickerInfo, Volume, WsMessage};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use yew::worker::{Agent, AgentLink, HandlerId, Public};
+
+/// The parts of `State` that decide which trades are kept, so the worker can drop the
+/// rest before they ever reach the UI
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default, Debug)]
+pub struct TradeFilter {
+    pub paused: HashSet<Symbol>,
+    pub min_volume: Option<Volume>,
+    pub min_volumes: HashMap<Symbol, Volume>,
+    pub volume_alerts: HashMap<Symbol, Volume>,
+    pub history_retention_mins: Option<u32>,
+}
+
+impl TradeFilter {
+    /// Trades may still be in flight right after pausing, and small ones are dropped
+    /// per the minimum volume filters
+    pub fn accepts(&self, ticker_info: &TickerInfo) -> bool {
+        !self.paused.contains(&ticker_info.symbol)
+            && self
+                .min_volumes
+                .get(&ticker_info.symbol)
+                .or(self.min_volume.as_ref())
+                .map(|min_volume| ticker_info.volume >= *min_volume)
+                .unwrap_or(true)
+    }
+
+    pub fn is_volume_alert(&self, ticker_info: &TickerInfo) -> bool {
+        self.volume_alerts
+            .get(&ticker_info.symbol)
+            .map(|threshold| ticker_info.volume >= *threshold)
+            .unwrap_or(false)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum WorkerInput {
+    Filter(TradeFilter),
+    /// A raw websocket text frame
+    Frame(String),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum WorkerOutput {
+    Ping,
+    Error(String),
+    Trades(TradeDelta),
+    Unknown { kind: String, payload: String },
+    Unparseable(String),
+}
+
+/// Running totals of accepted trades, for a volume-weighted average price
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+pub struct TradeTotals {
+    pub trades: u64,
+    pub volume: f64,
+    /// Sum of price × volume
+    pub notional: f64,
+}
+
+impl TradeTotals {
+    pub fn add(&mut self, other: &TradeTotals) {
+        self.trades += other.trades;
+        self.volume += other.volume;
+        self.notional += other.notional;
+    }
+
+    pub fn vwap(&self) -> Option<f64> {
+        if self.volume > 0.0 {
+            Some(self.notional / self.volume)
+        } else {
+            None
+        }
+    }
+}
+
+/// The trades received lately per symbol, newest first, so that those finnhub sends
+/// again after a reconnect aren't counted twice
+#[derive(Default)]
+struct RecentTrades(HashMap<Symbol, VecDeque<(DateTime<Utc>, Price, Volume)>>);
+
+impl RecentTrades {
+    /// Remembers the trade, unless it's already been received. Only as many are
+    /// remembered as the history keeps, which is as far back as a resend reaches
+    fn insert(&mut self, ticker_info: &TickerInfo, max_len: usize) -> bool {
+        let recent = self.0.entry(ticker_info.symbol.clone()).or_default();
+        let trade = (ticker_info.time, ticker_info.price, ticker_info.volume);
+        if recent.contains(&trade) {
+            return false;
+        }
+        recent.push_front(trade);
+        recent.truncate(max_len);
+        true
+    }
+}
+
+/// What a frame of trades boils down to
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TradeDelta {
+    /// Accepted trades in the order they arrived, but no more per symbol than the
+    /// history would keep anyway
+    pub trades: Vec<TickerInfo>,
+    /// Everything received per symbol, accepted or not
+    pub counts: Vec<(Symbol, u32)>,
+    /// Every accepted trade per symbol, including those beyond what the history keeps
+    pub totals: Vec<(Symbol, TradeTotals)>,
+    /// Symbols with a trade at or above their volume alert, and the biggest such trade
+    pub volume_alerts: Vec<(Symbol, Volume)>,
+}
+
+/// Parses and aggregates websocket frames off the main thread, so heavy trade flow
+/// doesn't hold up rendering.
+///
+/// The worker is this same crate built with `--target no-modules --out-name worker`;
+/// `run_app` registers it when there's no window around.
+pub struct TradeWorker {
+    link: AgentLink<TradeWorker>,
+    filter: TradeFilter,
+    recent: RecentTrades,
+}
+
+/// Boils a raw websocket frame down to what the UI needs from it
+fn process(frame: &str, filter: &TradeFilter, recent: &mut RecentTrades) -> WorkerOutput {
+    match WsMessage::parse(frame) {
+        Ok(WsMessage::Ping) => WorkerOutput::Ping,
+        Ok(WsMessage::Error { message }) => WorkerOutput::Error(message),
+        Ok(WsMessage::Trade { data }) => WorkerOutput::Trades(aggregate(data, filter, recent)),
+        Ok(WsMessage::Unknown { kind, payload }) => WorkerOutput::Unknown { kind, payload },
+        Err(e) => WorkerOutput::Unparseable(e.to_string()),
+    }
+}
+
+fn aggregate(
+    trades: Vec<TickerInfo>,
+    filter: &TradeFilter,
+    recent: &mut RecentTrades,
+) -> TradeDelta {
+    let mut counts: HashMap<Symbol, u32> = HashMap::new();
+    let mut kept: HashMap<Symbol, usize> = HashMap::new();
+    let mut totals: HashMap<Symbol, TradeTotals> = HashMap::new();
+    let mut volume_alerts: HashMap<Symbol, Volume> = HashMap::new();
+    let mut accepted = Vec::new();
+    let max_history = TickerHistory::max_len(filter.history_retention_mins);
+    // received before, so already in the counts, totals and alerts
+    let trades: Vec<TickerInfo> = trades
+        .into_iter()
+        .filter(|ticker_info| recent.insert(ticker_info, max_history))
+        .collect();
+    // newest last, so walk backwards to keep the newest of each symbol
+    for ticker_info in trades.into_iter().rev() {
+        *counts.entry(ticker_info.symbol.clone()).or_default() += 1;
+        if !filter.accepts(&ticker_info) {
+            continue;
+        }
+        let volume = ticker_info.volume.0.to_f64();
+        let symbol_totals = totals.entry(ticker_info.symbol.clone()).or_default();
+        symbol_totals.add(&TradeTotals {
+            trades: 1,
+            volume,
+            notional: ticker_info.price.0.to_f64() * volume,
+        });
+        if filter.is_volume_alert(&ticker_info) {
+            let biggest = volume_alerts
+                .entry(ticker_info.symbol.clone())
+                .or_insert(ticker_info.volume);
+            if ticker_info.volume > *biggest {
+                *biggest = ticker_info.volume;
+            }
+        }
+        let kept_for_symbol = kept.entry(ticker_info.symbol.clone()).or_default();
+        if *kept_for_symbol < max_history {
+            *kept_for_symbol += 1;
+            accepted.push(ticker_info);
+        }
+    }
+    accepted.reverse();
+    TradeDelta {
+        trades: accepted,
+        counts: counts.into_iter().collect(),
+        totals: totals.into_iter().collect(),
+        volume_alerts: volume_alerts.into_iter().collect(),
+    }
+}
+
+impl Agent for TradeWorker {
+    type Reach = Public;
+    type Message = ();
+    type Input = WorkerInput;
+    type Output = WorkerOutput;
+
+    fn create(link: AgentLink<TradeWorker>) -> TradeWorker {
+        TradeWorker {
+            link,
+            filter: TradeFilter::default(),
+            recent: RecentTrades::default(),
+        }
+    }
+
+    fn update(&mut self, _: ()) {}
+
+    fn handle_input(&mut self, input: WorkerInput, who: HandlerId) {
+        match input {
+            WorkerInput::Filter(filter) => self.filter = filter,
+            WorkerInput::Frame(frame) => {
+                let output = process(&frame, &self.filter, &mut self.recent);
+                self.link.respond(who, output);
+            }
+        }
+    }
+
+    /// Yew resolves this against the origin, so it has to include whatever path the
+    /// app is served under. Only ever called once, when the bridge is made
+    fn name_of_resource() -> &'static str {
+        let directory = web_sys::window()
+            .and_then(|w| w.location().pathname().ok())
+            .map(|path| {
+                let end = path.rfind('/').map_or(0, |idx| idx + 1);
+                path[..end].trim_start_matches('/').to_string()
+            })
+            .unwrap_or_default();
+        Box::leak(format!("{}worker.js", directory).into_boxed_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decimal::Decimal;
+
+    fn symbol(s: &str) -> Symbol {
+        Symbol(s.to_string())
+    }
+
+    fn volume(v: i64) -> Volume {
+        Volume(Decimal::new(v, 0))
+    }
+
+    fn trades(output: WorkerOutput) -> TradeDelta {
+        match output {
+            WorkerOutput::Trades(delta) => delta,
+            other => panic!("Expected trades, got {:?}", other),
+        }
+    }
+
+    const FRAME: &str = r#"{"type":"trade","data":[
+        {"s":"AAPL","p":123.45,"v":10,"t":1590000000000},
+        {"s":"MSFT","p":180.1,"v":500,"t":1590000000001},
+        {"s":"AAPL","p":123.5,"v":200,"t":1590000000002}
+    ]}"#;
+
+    #[test]
+    fn handles_pings_and_errors() {
+        let filter = TradeFilter::default();
+        assert!(matches!(
+            process(r#"{"type":"ping"}"#, &filter, &mut RecentTrades::default()),
+            WorkerOutput::Ping
+        ));
+        match process(
+            r#"{"type":"error","msg":"Invalid symbol"}"#,
+            &filter,
+            &mut RecentTrades::default(),
+        ) {
+            WorkerOutput::Error(message) => assert_eq!(message, "Invalid symbol"),
+            other => panic!("Expected an error, got {:?}", other),
+        }
+        assert!(matches!(
+            process("not json", &filter, &mut RecentTrades::default()),
+            WorkerOutput::Unparseable(_)
+        ));
+    }
+
+    #[test]
+    fn passes_unknown_types_through() {
+        let filter = TradeFilter::default();
+        let frame = r#"{"type":"news","data":[{"headline":"Hi"}]}"#;
+        match process(frame, &filter, &mut RecentTrades::default()) {
+            WorkerOutput::Unknown { kind, payload } => {
+                assert_eq!(kind, "news");
+                assert_eq!(payload, frame);
+            }
+            other => panic!("Expected an unknown message, got {:?}", other),
+        }
+        // known types that don't parse are still errors
+        assert!(matches!(
+            process(
+                r#"{"type":"trade","data":"oops"}"#,
+                &filter,
+                &mut RecentTrades::default()
+            ),
+            WorkerOutput::Unparseable(_)
+        ));
+    }
+
+    #[test]
+    fn keeps_trades_in_order() {
+        let delta = trades(process(
+            FRAME,
+            &TradeFilter::default(),
+            &mut RecentTrades::default(),
+        ));
+        let aapl = delta.totals.iter().find(|(s, _)| *s == symbol("AAPL"));
+        assert_eq!(aapl.map(|(_, t)| t.trades), Some(2));
+        // (123.45 × 10 + 123.5 × 200) / 210
+        assert_eq!(
+            aapl.and_then(|(_, t)| t.vwap())
+                .map(|v| format!("{:.4}", v)),
+            Some("123.4976".to_string())
+        );
+        let symbols: Vec<&str> = delta.trades.iter().map(|t| t.symbol.0.as_str()).collect();
+        assert_eq!(symbols, vec!["AAPL", "MSFT", "AAPL"]);
+        assert_eq!(delta.trades[0].price.0.to_string(), "123.45");
+        assert!(delta.volume_alerts.is_empty());
+    }
+
+    #[test]
+    fn counts_filtered_trades_too() {
+        let filter = TradeFilter {
+            paused: vec![symbol("MSFT")].into_iter().collect(),
+            min_volume: Some(volume(100)),
+            ..TradeFilter::default()
+        };
+        let delta = trades(process(FRAME, &filter, &mut RecentTrades::default()));
+        let kept: Vec<String> = delta
+            .trades
+            .iter()
+            .map(|t| t.volume.0.to_string())
+            .collect();
+        assert_eq!(kept, vec!["200"]);
+        let mut counts = delta.counts;
+        counts.sort_by(|a, b| a.0 .0.cmp(&b.0 .0));
+        assert_eq!(counts, vec![(symbol("AAPL"), 2), (symbol("MSFT"), 1)]);
+        assert_eq!(
+            delta.totals,
+            vec![(
+                symbol("AAPL"),
+                TradeTotals {
+                    trades: 1,
+                    volume: 200.0,
+                    notional: 24700.0
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn flags_volume_alerts() {
+        let filter = TradeFilter {
+            volume_alerts: vec![(symbol("MSFT"), volume(500))].into_iter().collect(),
+            ..TradeFilter::default()
+        };
+        assert_eq!(
+            trades(process(FRAME, &filter, &mut RecentTrades::default())).volume_alerts,
+            vec![(symbol("MSFT"), volume(500))]
+        );
+    }
+
+    #[test]
+    fn counts_resent_trades_once() {
+        let filter = TradeFilter {
+            volume_alerts: vec![(symbol("MSFT"), volume(500))].into_iter().collect(),
+            ..TradeFilter::default()
+        };
+        let mut recent = RecentTrades::default();
+        let first = trades(process(FRAME, &filter, &mut recent));
+        let mut session_volume = TradeTotals::default();
+        for (_, totals) in &first.totals {
+            session_volume.add(totals);
+        }
+        // as after a reconnect, with one new trade on the end
+        let resent = r#"{"type":"trade","data":[
+            {"s":"MSFT","p":180.1,"v":500,"t":1590000000001},
+            {"s":"AAPL","p":123.5,"v":200,"t":1590000000002},
+            {"s":"AAPL","p":123.6,"v":5,"t":1590000000003}
+        ]}"#;
+        let second = trades(process(resent, &filter, &mut recent));
+        for (_, totals) in &second.totals {
+            session_volume.add(totals);
+        }
+        assert_eq!(session_volume.trades, 4);
+        assert_eq!(session_volume.volume, 715.0);
+        assert_eq!(second.trades.len(), 1);
+        assert_eq!(second.counts, vec![(symbol("AAPL"), 1)]);
+        assert!(second.volume_alerts.is_empty());
+    }
+}
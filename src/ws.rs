@@ -0,0 +1,739 @@
+use crate::frame_codec::Frame;
+use crate::{Request, Symbol};
+use anyhow::anyhow;
+use chrono::{DateTime, Duration, Utc};
+use js_sys::{ArrayBuffer, Uint8Array};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::cell::RefCell;
+use std::collections::{HashSet, VecDeque};
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{BinaryType, MessageEvent, WebSocket};
+use yew::format::{Json, Text};
+use yew::services::websocket::{WebSocketService, WebSocketStatus, WebSocketTask};
+use yew::Callback;
+
+/// Finnhub only answers subscriptions that fail, so one that has gone this long without
+/// an error (or that has received a trade) is assumed to be fine
+const SUBSCRIPTION_CONFIRM_SECS: i64 = 10;
+
+pub const DEFAULT_BASE_URL: &str = "wss://ws.finnhub.io";
+
+/// The URL to connect to, with the token tacked on. `base` may already have a query
+pub fn url(base: &str, token: &str) -> String {
+    let separator = if base.contains('?') { '&' } else { '?' };
+    format!("{}{}token={}", base, separator, token)
+}
+
+/// How the token gets to the server. Browsers won't set headers on a websocket, so
+/// proxies wanting it in one have to make do with the subprotocol header
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum WsAuth {
+    /// As the `token` query parameter, which is what finnhub itself wants
+    #[default]
+    QueryParam,
+    /// In an `auth` message sent ahead of anything else
+    FirstMessage,
+    /// As the `Sec-WebSocket-Protocol` header
+    Subprotocol,
+}
+
+impl WsAuth {
+    pub const ALL: [WsAuth; 3] = [
+        WsAuth::QueryParam,
+        WsAuth::FirstMessage,
+        WsAuth::Subprotocol,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            WsAuth::QueryParam => "Query parameter",
+            WsAuth::FirstMessage => "First message",
+            WsAuth::Subprotocol => "Subprotocol",
+        }
+    }
+}
+
+/// Where to connect, and whatever else the token has to go in
+#[derive(PartialEq, Debug)]
+pub struct WsTarget {
+    pub url: String,
+    pub protocol: Option<String>,
+    /// Sent before any subscriptions
+    pub first_message: Option<String>,
+}
+
+impl WsTarget {
+    pub fn new(base: &str, token: &str, auth: WsAuth) -> WsTarget {
+        let (url, protocol, first_message) = match auth {
+            WsAuth::QueryParam => (url(base, token), None, None),
+            WsAuth::FirstMessage => (
+                base.to_string(),
+                None,
+                Some(json!({ "type": "auth", "token": token }).to_string()),
+            ),
+            WsAuth::Subprotocol => (base.to_string(), Some(token.to_string()), None),
+        };
+        WsTarget {
+            url,
+            protocol,
+            first_message,
+        }
+    }
+}
+
+/// Anything a `WebSocket` could connect to
+pub fn is_valid_base_url(base: &str) -> bool {
+    let rest = base
+        .strip_prefix("wss://")
+        .or_else(|| base.strip_prefix("ws://"))
+        .unwrap_or("");
+    !rest.is_empty() && !rest.starts_with('/') && !rest.contains(char::is_whitespace)
+}
+
+/// Opens websocket connections. `Model` only talks to the websocket through this, so
+/// that the connection handling can be exercised without a server
+pub trait WsConnector {
+    fn connect(
+        &mut self,
+        url: &str,
+        protocol: Option<&str>,
+        on_frame: Callback<Frame>,
+        on_status: Callback<WebSocketStatus>,
+    ) -> Result<Box<dyn WsConnection>, String>;
+}
+
+/// A connector that can be handed in from outside, e.g. a fake one in tests
+pub type SharedConnector = Rc<RefCell<dyn WsConnector>>;
+
+/// An open (or opening) websocket; dropping it closes the connection
+pub trait WsConnection {
+    fn send(&mut self, data: Text);
+}
+
+impl WsConnector for WebSocketService {
+    fn connect(
+        &mut self,
+        url: &str,
+        protocol: Option<&str>,
+        on_frame: Callback<Frame>,
+        on_status: Callback<WebSocketStatus>,
+    ) -> Result<Box<dyn WsConnection>, String> {
+        if let Some(protocol) = protocol {
+            return ProtocolSocket::connect(url, protocol, on_frame, on_status)
+                .map(|socket| Box::new(socket) as Box<dyn WsConnection>)
+                .map_err(|e| format!("Could not connect [{:?}]", e));
+        }
+        // binary frames too, in case they're ever sent
+        WebSocketService::connect(self, url, on_frame, on_status)
+            .map(|task| Box::new(task) as Box<dyn WsConnection>)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// A websocket opened through web-sys directly, since `WebSocketService` has no way of
+/// asking for a subprotocol
+struct ProtocolSocket {
+    ws: WebSocket,
+    // kept for as long as the socket may call them
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_open: Closure<dyn FnMut(JsValue)>,
+    _on_close: Closure<dyn FnMut(JsValue)>,
+    _on_error: Closure<dyn FnMut(JsValue)>,
+}
+
+impl ProtocolSocket {
+    fn connect(
+        url: &str,
+        protocol: &str,
+        on_frame: Callback<Frame>,
+        on_status: Callback<WebSocketStatus>,
+    ) -> Result<ProtocolSocket, JsValue> {
+        let ws = WebSocket::new_with_str(url, protocol)?;
+        ws.set_binary_type(BinaryType::Arraybuffer);
+        let on_message = Closure::wrap(Box::new(move |event: MessageEvent| {
+            let data = event.data();
+            let frame = match data.as_string() {
+                Some(text) => Frame::Text(Ok(text)),
+                None => Frame::Binary(
+                    data.dyn_into::<ArrayBuffer>()
+                        .map(|buffer| Uint8Array::new(&buffer).to_vec())
+                        .map_err(|_| anyhow!("Got a websocket message that isn't text or binary")),
+                ),
+            };
+            on_frame.emit(frame);
+        }) as Box<dyn FnMut(MessageEvent)>);
+        let on = |status: WebSocketStatus| {
+            let on_status = on_status.clone();
+            Closure::wrap(Box::new(move |_: JsValue| on_status.emit(status.clone()))
+                as Box<dyn FnMut(JsValue)>)
+        };
+        let (on_open, on_close, on_error) = (
+            on(WebSocketStatus::Opened),
+            on(WebSocketStatus::Closed),
+            on(WebSocketStatus::Error),
+        );
+        ws.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        ws.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        ws.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+        ws.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+        Ok(ProtocolSocket {
+            ws,
+            _on_message: on_message,
+            _on_open: on_open,
+            _on_close: on_close,
+            _on_error: on_error,
+        })
+    }
+}
+
+impl WsConnection for ProtocolSocket {
+    fn send(&mut self, data: Text) {
+        if let Ok(text) = data {
+            let _ = self.ws.send_with_str(&text);
+        }
+    }
+}
+
+impl Drop for ProtocolSocket {
+    /// Like `WebSocketTask`, closing quietly: the handlers go before the closures do
+    fn drop(&mut self) {
+        self.ws.set_onmessage(None);
+        self.ws.set_onopen(None);
+        self.ws.set_onclose(None);
+        self.ws.set_onerror(None);
+        let _ = self.ws.close();
+    }
+}
+
+impl WsConnection for WebSocketTask {
+    fn send(&mut self, data: Text) {
+        WebSocketTask::send(self, data);
+    }
+}
+
+/// A websocket connection along with what it has been asked to subscribe to
+pub struct Session {
+    connection: Box<dyn WsConnection>,
+    // what this connection is subscribed to
+    subscribed: HashSet<Symbol>,
+    // subscriptions sent but not yet known to be good, oldest first. Finnhub never
+    // confirms a good one, only trades arriving do, so a quiet symbol stays in here
+    // until its window runs out
+    pending: VecDeque<(Symbol, DateTime<Utc>)>,
+    // requests made before the socket opened, which would otherwise be lost
+    queued: Vec<Request>,
+    // sent as soon as the socket opens, ahead of the queued requests
+    first_message: Option<String>,
+    open: bool,
+    // most symbols finnhub lets us subscribe to at once; beyond that they wait here,
+    // in the order asked for, until a slot frees up
+    limit: usize,
+    waiting: VecDeque<Symbol>,
+}
+
+impl Session {
+    pub fn new(connection: Box<dyn WsConnection>, limit: usize) -> Session {
+        Session {
+            connection,
+            subscribed: HashSet::new(),
+            pending: VecDeque::new(),
+            queued: vec![],
+            first_message: None,
+            open: false,
+            limit,
+            waiting: VecDeque::new(),
+        }
+    }
+
+    /// For servers that want to be told the token before anything else
+    pub fn send_first(&mut self, message: String) {
+        self.first_message = Some(message);
+    }
+
+    pub fn subscribed_count(&self) -> usize {
+        self.subscribed.len()
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Asked to be subscribed to, but held back by the limit
+    pub fn is_waiting(&self, symbol: &Symbol) -> bool {
+        self.waiting.contains(symbol)
+    }
+
+    pub fn waiting_count(&self) -> usize {
+        self.waiting.len()
+    }
+
+    /// Lowering it leaves existing subscriptions be; raising it lets waiting symbols in
+    pub fn set_limit(&mut self, limit: usize, now: DateTime<Utc>) {
+        self.limit = limit;
+        self.fill_slots(now);
+    }
+
+    fn fill_slots(&mut self, now: DateTime<Utc>) {
+        while self.subscribed.len() < self.limit {
+            match self.waiting.pop_front() {
+                Some(symbol) => self.send_request(&Request::Subscribe { symbol }, now),
+                None => break,
+            }
+        }
+    }
+
+    /// Sends whatever was queued while connecting. Pending subscriptions only really
+    /// go out now, so that's when they start waiting for an answer
+    pub fn opened(&mut self, now: DateTime<Utc>) {
+        self.open = true;
+        if let Some(message) = self.first_message.take() {
+            self.connection.send(Ok(message));
+        }
+        for request in std::mem::take(&mut self.queued) {
+            self.connection.send(Json(&request).into());
+        }
+        for (_, sent_at) in self.pending.iter_mut() {
+            *sent_at = now;
+        }
+    }
+
+    /// Requests that wouldn't change what we're subscribed to are skipped, and those
+    /// made before the socket opens are held until it does. Subscriptions over the
+    /// limit wait for an unsubscription to make room
+    pub fn send_request(&mut self, request: &Request, now: DateTime<Utc>) {
+        match request {
+            Request::Subscribe { symbol } => {
+                if self.subscribed.contains(symbol) || self.waiting.contains(symbol) {
+                    return;
+                }
+                if self.subscribed.len() >= self.limit {
+                    self.waiting.push_back(symbol.clone());
+                    return;
+                }
+                self.subscribed.insert(symbol.clone());
+                self.pending.push_back((symbol.clone(), now));
+            }
+            Request::Unsubscribe { symbol } => {
+                if !self.subscribed.remove(symbol) {
+                    self.waiting.retain(|s| s != symbol);
+                    return;
+                }
+            }
+        }
+        if self.open {
+            self.connection.send(Json(request).into());
+        } else {
+            self.queued.push(request.clone());
+        }
+        if let Request::Unsubscribe { .. } = request {
+            self.fill_slots(now);
+        }
+    }
+
+    /// Brings the subscriptions in line with `wanted`, since the watchlist may have
+    /// been edited while we were disconnected
+    pub fn reconcile<'a>(
+        &mut self,
+        wanted: impl IntoIterator<Item = &'a Symbol>,
+        now: DateTime<Utc>,
+    ) {
+        let wanted: Vec<&Symbol> = wanted.into_iter().collect();
+        // before unsubscribing frees up slots, so none go to symbols no longer wanted
+        self.waiting.retain(|s| wanted.contains(&s));
+        let orphans: Vec<Symbol> = self
+            .subscribed
+            .iter()
+            .filter(|s| !wanted.contains(s))
+            .cloned()
+            .collect();
+        for symbol in orphans {
+            self.send_request(&Request::Unsubscribe { symbol }, now);
+        }
+        for symbol in wanted {
+            self.send_request(
+                &Request::Subscribe {
+                    symbol: symbol.clone(),
+                },
+                now,
+            );
+        }
+    }
+
+    /// Trades for a symbol prove its subscription is good
+    pub fn confirm(&mut self, symbols: &HashSet<Symbol>) {
+        self.pending.retain(|(symbol, _)| !symbols.contains(symbol));
+    }
+
+    /// The subscription a rejection refers to, but only when that's certain: it's named
+    /// in `message`, or it's the only one still in question. Otherwise `None`, since
+    /// pinning it on the wrong symbol would drop a good subscription
+    pub fn take_rejected_subscription(
+        &mut self,
+        message: &str,
+        now: DateTime<Utc>,
+    ) -> Option<Symbol> {
+        let cutoff = now - Duration::seconds(SUBSCRIPTION_CONFIRM_SECS);
+        self.pending.retain(|(_, sent_at)| *sent_at > cutoff);
+        let named = message
+            .split(|c: char| !(c.is_ascii_alphanumeric() || ":._-".contains(c)))
+            .find_map(|word| self.pending.iter().position(|(symbol, _)| symbol.0 == word));
+        let idx = match named {
+            Some(idx) => idx,
+            None if self.pending.len() == 1 => 0,
+            None => return None,
+        };
+        self.pending.remove(idx).map(|(symbol, _)| symbol)
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Records every connection made and everything sent on them
+    #[derive(Clone, Default)]
+    pub struct MockConnector {
+        pub urls: Rc<RefCell<Vec<String>>>,
+        pub protocol: Rc<RefCell<Option<String>>>,
+        pub sent: Rc<RefCell<Vec<String>>>,
+        pub on_frame: Rc<RefCell<Option<Callback<Frame>>>>,
+        pub on_status: Rc<RefCell<Option<Callback<WebSocketStatus>>>>,
+    }
+
+    impl MockConnector {
+        /// Connects without going through a `Callback`, for tests that only care
+        /// about what gets sent
+        pub fn connection(&self) -> Box<dyn WsConnection> {
+            Box::new(MockConnection {
+                sent: self.sent.clone(),
+            })
+        }
+
+        pub fn sent_requests(&self) -> Vec<Request> {
+            self.sent
+                .borrow()
+                .iter()
+                .map(|frame| serde_json::from_str(frame).unwrap())
+                .collect()
+        }
+    }
+
+    impl WsConnector for MockConnector {
+        fn connect(
+            &mut self,
+            url: &str,
+            protocol: Option<&str>,
+            on_frame: Callback<Frame>,
+            on_status: Callback<WebSocketStatus>,
+        ) -> Result<Box<dyn WsConnection>, String> {
+            self.urls.borrow_mut().push(url.to_string());
+            *self.protocol.borrow_mut() = protocol.map(str::to_string);
+            *self.on_frame.borrow_mut() = Some(on_frame);
+            *self.on_status.borrow_mut() = Some(on_status);
+            Ok(self.connection())
+        }
+    }
+
+    pub struct MockConnection {
+        sent: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl WsConnection for MockConnection {
+        fn send(&mut self, data: Text) {
+            if let Ok(frame) = data {
+                self.sent.borrow_mut().push(frame);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::MockConnector;
+    use super::*;
+
+    fn symbol(s: &str) -> Symbol {
+        Symbol(s.to_string())
+    }
+
+    fn subscribe(s: &str) -> Request {
+        Request::Subscribe { symbol: symbol(s) }
+    }
+
+    fn unsubscribe(s: &str) -> Request {
+        Request::Unsubscribe { symbol: symbol(s) }
+    }
+
+    #[test]
+    fn builds_urls() {
+        assert_eq!(
+            url(DEFAULT_BASE_URL, "abc"),
+            "wss://ws.finnhub.io?token=abc"
+        );
+        assert_eq!(
+            url("ws://localhost:8080/feed?debug=1", "abc"),
+            "ws://localhost:8080/feed?debug=1&token=abc"
+        );
+        assert!(is_valid_base_url("ws://localhost:8080"));
+        assert!(!is_valid_base_url("https://ws.finnhub.io"));
+        assert!(!is_valid_base_url("wss://"));
+        assert!(!is_valid_base_url("wss://ws finnhub"));
+    }
+
+    #[test]
+    fn puts_the_token_where_the_server_wants_it() {
+        let base = "ws://localhost:8080/feed";
+        assert_eq!(
+            WsTarget::new(base, "abc", WsAuth::QueryParam).url,
+            "ws://localhost:8080/feed?token=abc"
+        );
+        let first = WsTarget::new(base, "abc", WsAuth::FirstMessage);
+        assert_eq!(first.url, base);
+        assert_eq!(
+            first.first_message,
+            Some(r#"{"token":"abc","type":"auth"}"#.to_string())
+        );
+        assert_eq!(
+            WsTarget::new(base, "abc", WsAuth::Subprotocol),
+            WsTarget {
+                url: base.to_string(),
+                protocol: Some("abc".to_string()),
+                first_message: None,
+            }
+        );
+
+        let connector = MockConnector::default();
+        let mut session = Session::new(connector.connection(), 50);
+        session.send_request(&subscribe("AAPL"), Utc::now());
+        session.send_first(first.first_message.unwrap());
+        session.opened(Utc::now());
+        let sent = connector.sent.borrow();
+        assert_eq!(sent[0], r#"{"token":"abc","type":"auth"}"#);
+        assert_eq!(
+            serde_json::from_str::<Request>(&sent[1]).unwrap(),
+            subscribe("AAPL")
+        );
+    }
+
+    #[test]
+    fn connects_through_the_connector() {
+        let mut connector = MockConnector::default();
+        let connection = connector.connect(
+            "wss://ws.finnhub.io?token=abc",
+            None,
+            Callback::from(|_| ()),
+            Callback::from(|_| ()),
+        );
+        assert!(connection.is_ok());
+        assert_eq!(
+            *connector.urls.borrow(),
+            vec!["wss://ws.finnhub.io?token=abc".to_string()]
+        );
+        assert!(connector.on_frame.borrow().is_some());
+    }
+
+    #[test]
+    fn runs_a_session_through_a_shared_connector() {
+        let mock = MockConnector::default();
+        let connector: SharedConnector = Rc::new(RefCell::new(mock.clone()));
+        let frames = Rc::new(RefCell::new(vec![]));
+        let statuses = Rc::new(RefCell::new(vec![]));
+        let on_frame = {
+            let frames = frames.clone();
+            Callback::from(move |frame: Frame| {
+                if let Frame::Text(Ok(text)) = frame {
+                    frames.borrow_mut().push(text);
+                }
+            })
+        };
+        let on_status = {
+            let statuses = statuses.clone();
+            Callback::from(move |status| statuses.borrow_mut().push(status))
+        };
+        let connection = connector
+            .borrow_mut()
+            .connect("wss://ws.finnhub.io?token=abc", None, on_frame, on_status)
+            .unwrap();
+        let mut session = Session::new(connection, 50);
+        let now = Utc::now();
+        session.send_request(&subscribe("AAPL"), now);
+        assert!(mock.sent_requests().is_empty());
+
+        let emit_status = |status| mock.on_status.borrow().as_ref().unwrap().emit(status);
+        emit_status(WebSocketStatus::Opened);
+        session.opened(now);
+        assert_eq!(mock.sent_requests(), vec![subscribe("AAPL")]);
+
+        let trade = r#"{"type":"trade","data":[{"s":"AAPL","p":1.0,"t":0,"v":1.0}]}"#;
+        let on_frame = mock.on_frame.borrow().clone().unwrap();
+        on_frame.emit(Frame::Text(Ok(trade.to_string())));
+        assert_eq!(*frames.borrow(), vec![trade.to_string()]);
+
+        emit_status(WebSocketStatus::Closed);
+        assert_eq!(
+            *statuses.borrow(),
+            vec![WebSocketStatus::Opened, WebSocketStatus::Closed]
+        );
+    }
+
+    #[test]
+    fn subscribes_once_per_symbol() {
+        let connector = MockConnector::default();
+        let mut session = Session::new(connector.connection(), 50);
+        let now = Utc::now();
+        session.opened(now);
+        session.send_request(&subscribe("AAPL"), now);
+        session.send_request(&subscribe("AAPL"), now);
+        session.send_request(&subscribe("MSFT"), now);
+        assert_eq!(
+            connector.sent_requests(),
+            vec![subscribe("AAPL"), subscribe("MSFT")]
+        );
+    }
+
+    #[test]
+    fn only_unsubscribes_from_subscribed_symbols() {
+        let connector = MockConnector::default();
+        let mut session = Session::new(connector.connection(), 50);
+        let now = Utc::now();
+        session.opened(now);
+        session.send_request(&unsubscribe("AAPL"), now);
+        session.send_request(&subscribe("AAPL"), now);
+        session.send_request(&unsubscribe("AAPL"), now);
+        session.send_request(&unsubscribe("AAPL"), now);
+        assert_eq!(
+            connector.sent_requests(),
+            vec![subscribe("AAPL"), unsubscribe("AAPL")]
+        );
+    }
+
+    #[test]
+    fn reconciles_with_the_watchlist() {
+        let connector = MockConnector::default();
+        let mut session = Session::new(connector.connection(), 50);
+        let now = Utc::now();
+        session.opened(now);
+        session.send_request(&subscribe("AAPL"), now);
+        session.send_request(&subscribe("TSLA"), now);
+        connector.sent.borrow_mut().clear();
+
+        session.reconcile(&[symbol("MSFT"), symbol("AAPL")], now);
+        assert_eq!(
+            connector.sent_requests(),
+            vec![unsubscribe("TSLA"), subscribe("MSFT")]
+        );
+    }
+
+    #[test]
+    fn holds_requests_until_open() {
+        let connector = MockConnector::default();
+        let mut session = Session::new(connector.connection(), 50);
+        let now = Utc::now();
+        session.send_request(&subscribe("AAPL"), now);
+        session.send_request(&subscribe("MSFT"), now);
+        session.send_request(&unsubscribe("AAPL"), now);
+        assert!(connector.sent.borrow().is_empty());
+
+        session.opened(now);
+        assert_eq!(
+            connector.sent_requests(),
+            vec![subscribe("AAPL"), subscribe("MSFT"), unsubscribe("AAPL")]
+        );
+        session.send_request(&subscribe("TSLA"), now);
+        assert_eq!(connector.sent_requests().len(), 4);
+    }
+
+    #[test]
+    fn pending_subscriptions_wait_from_when_they_go_out() {
+        let connector = MockConnector::default();
+        let mut session = Session::new(connector.connection(), 50);
+        let now = Utc::now();
+        session.send_request(&subscribe("NOPE"), now - Duration::seconds(60));
+        session.opened(now);
+        assert_eq!(
+            session.take_rejected_subscription("Invalid symbol", now),
+            Some(symbol("NOPE"))
+        );
+    }
+
+    #[test]
+    fn invalid_symbol_errors_belong_to_the_only_pending_subscription() {
+        let connector = MockConnector::default();
+        let mut session = Session::new(connector.connection(), 50);
+        let now = Utc::now();
+        session.opened(now);
+        session.send_request(&subscribe("OLD"), now - Duration::seconds(60));
+        session.send_request(&subscribe("AAPL"), now);
+        session.send_request(&subscribe("NOPE"), now);
+        session.confirm(&[symbol("AAPL")].iter().cloned().collect());
+
+        // OLD was sent too long ago to still be in question
+        assert_eq!(
+            session.take_rejected_subscription("Invalid symbol", now),
+            Some(symbol("NOPE"))
+        );
+        assert_eq!(
+            session.take_rejected_subscription("Invalid symbol", now),
+            None
+        );
+    }
+
+    #[test]
+    fn leaves_rejections_unattributed_behind_a_quiet_symbol() {
+        let connector = MockConnector::default();
+        let mut session = Session::new(connector.connection(), 50);
+        let now = Utc::now();
+        session.opened(now);
+        // valid, but nothing has traded yet
+        session.send_request(&subscribe("QUIET"), now);
+        session.send_request(&subscribe("NOPE"), now);
+        assert_eq!(
+            session.take_rejected_subscription("Invalid symbol", now),
+            None
+        );
+        // unless the error says which it was
+        assert_eq!(
+            session.take_rejected_subscription("Invalid symbol: NOPE", now),
+            Some(symbol("NOPE"))
+        );
+        assert_eq!(
+            session.take_rejected_subscription("Invalid symbol", now),
+            Some(symbol("QUIET"))
+        );
+    }
+
+    #[test]
+    fn queues_subscriptions_over_the_limit() {
+        let connector = MockConnector::default();
+        let mut session = Session::new(connector.connection(), 2);
+        let now = Utc::now();
+        session.opened(now);
+        session.reconcile(&[symbol("AAPL"), symbol("MSFT"), symbol("TSLA")], now);
+        assert_eq!(
+            connector.sent_requests(),
+            vec![subscribe("AAPL"), subscribe("MSFT")]
+        );
+        assert!(session.is_waiting(&symbol("TSLA")));
+
+        // a slot freeing up goes to whoever's been waiting longest
+        session.send_request(&unsubscribe("AAPL"), now);
+        assert!(!session.is_waiting(&symbol("TSLA")));
+        assert_eq!(
+            connector.sent_requests()[2..],
+            [unsubscribe("AAPL"), subscribe("TSLA")]
+        );
+
+        session.send_request(&subscribe("NFLX"), now);
+        session.send_request(&unsubscribe("NFLX"), now);
+        assert_eq!(session.waiting_count(), 0);
+        session.send_request(&subscribe("NFLX"), now);
+        session.set_limit(3, now);
+        assert_eq!(session.subscribed_count(), 3);
+        assert_eq!(connector.sent_requests().len(), 5);
+    }
+}
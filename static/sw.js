@@ -0,0 +1,58 @@
+// Caches the app shell so it loads (and shows saved history) without a network.
+// Bump the version whenever the list below changes
+const CACHE = "finnhub-ws-shell-v1";
+
+const SHELL = [
+    "./",
+    "index.html",
+    "manifest.webmanifest",
+    "icon.svg",
+    "css/bootstrap.min.css",
+    "css/all.min.css",
+    "css/app.css",
+    "wasm.js",
+    "wasm_bg.wasm",
+    "worker.js",
+    "worker_bg.wasm",
+    "webfonts/fa-solid-900.woff2",
+    "webfonts/fa-regular-400.woff2",
+    "webfonts/fa-brands-400.woff2",
+];
+
+self.addEventListener("install", (event) => {
+    event.waitUntil(caches.open(CACHE).then((cache) => cache.addAll(SHELL)));
+    self.skipWaiting();
+});
+
+self.addEventListener("activate", (event) => {
+    event.waitUntil(
+        caches.keys().then((keys) =>
+            Promise.all(keys.filter((key) => key !== CACHE).map((key) => caches.delete(key)))
+        )
+    );
+    self.clients.claim();
+});
+
+// Shell files come from the network when it's there, so deploys show up straight
+// away, and from the cache when it isn't. finnhub's API is never cached
+self.addEventListener("fetch", (event) => {
+    const url = new URL(event.request.url);
+    if (event.request.method !== "GET" || url.origin !== self.location.origin) {
+        return;
+    }
+    event.respondWith(
+        fetch(event.request)
+            .then((response) => {
+                if (response.ok) {
+                    const copy = response.clone();
+                    caches.open(CACHE).then((cache) => cache.put(event.request, copy));
+                }
+                return response;
+            })
+            .catch(() =>
+                caches.match(event.request, { ignoreSearch: true }).then(
+                    (cached) => cached || caches.match("index.html")
+                )
+            )
+    );
+});